@@ -0,0 +1,22 @@
+CREATE TYPE custom_field_type AS ENUM (
+    'text', 'number', 'date', 'single_select', 'multi_select', 'user', 'checkbox'
+);
+
+CREATE TABLE custom_fields (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    field_type custom_field_type NOT NULL,
+    options JSONB DEFAULT '[]' NOT NULL,
+    required BOOLEAN DEFAULT false NOT NULL,
+    position INTEGER DEFAULT 0 NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE UNIQUE INDEX idx_custom_fields_project_name ON custom_fields(project_id, LOWER(name));
+
+-- Values are keyed by custom field id.
+ALTER TABLE tasks ADD COLUMN custom_fields JSONB DEFAULT '{}' NOT NULL;
+
+CREATE INDEX idx_tasks_custom_fields ON tasks USING GIN (custom_fields);
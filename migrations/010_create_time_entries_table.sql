@@ -0,0 +1,20 @@
+ALTER TABLE tasks ADD COLUMN original_estimate_minutes INTEGER CHECK (original_estimate_minutes >= 0);
+ALTER TABLE tasks ADD COLUMN remaining_estimate_minutes INTEGER CHECK (remaining_estimate_minutes >= 0);
+
+CREATE TABLE time_entries (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    task_id UUID NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    entry_date DATE NOT NULL,
+    -- NULL while a timer is running.
+    duration_minutes INTEGER CHECK (duration_minutes > 0),
+    started_at TIMESTAMP WITH TIME ZONE,
+    ended_at TIMESTAMP WITH TIME ZONE,
+    note TEXT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_time_entries_task_id ON time_entries(task_id);
+CREATE INDEX idx_time_entries_user_date ON time_entries(user_id, entry_date);
+CREATE UNIQUE INDEX idx_time_entries_running_timer ON time_entries(user_id) WHERE duration_minutes IS NULL;
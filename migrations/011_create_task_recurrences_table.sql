@@ -0,0 +1,29 @@
+CREATE TABLE task_recurrences (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    rrule TEXT NOT NULL,
+    timezone VARCHAR(64) DEFAULT 'UTC' NOT NULL,
+    -- Wall-clock time of the first occurrence, interpreted in `timezone`.
+    dtstart TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+    -- Template copied onto every new occurrence.
+    title VARCHAR(255) NOT NULL,
+    description TEXT,
+    priority task_priority DEFAULT 'medium' NOT NULL,
+    assignee_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    custom_fields JSONB DEFAULT '{}' NOT NULL,
+    label_ids UUID[] DEFAULT '{}' NOT NULL,
+    original_estimate_minutes INTEGER CHECK (original_estimate_minutes >= 0),
+    -- First occurrence not yet created as a task; NULL once the rule is exhausted.
+    next_occurrence_at TIMESTAMP WITH TIME ZONE,
+    active BOOLEAN DEFAULT TRUE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_task_recurrences_project_id ON task_recurrences(project_id);
+CREATE INDEX idx_task_recurrences_due ON task_recurrences(next_occurrence_at) WHERE active;
+
+ALTER TABLE tasks ADD COLUMN recurrence_id UUID REFERENCES task_recurrences(id) ON DELETE SET NULL;
+ALTER TABLE tasks ADD COLUMN occurrence_at TIMESTAMP WITH TIME ZONE;
+
+CREATE UNIQUE INDEX idx_tasks_recurrence_occurrence ON tasks(recurrence_id, occurrence_at);
@@ -0,0 +1,66 @@
+CREATE TABLE sprints (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    goal TEXT,
+    start_date DATE NOT NULL,
+    end_date DATE NOT NULL,
+    status VARCHAR(20) DEFAULT 'planned' NOT NULL CHECK (status IN ('planned', 'active', 'completed')),
+    started_at TIMESTAMP WITH TIME ZONE,
+    completed_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    CHECK (end_date >= start_date)
+);
+
+CREATE INDEX idx_sprints_project_id ON sprints(project_id);
+CREATE UNIQUE INDEX idx_sprints_one_active ON sprints(project_id) WHERE status = 'active';
+
+ALTER TABLE tasks ADD COLUMN sprint_id UUID REFERENCES sprints(id) ON DELETE SET NULL;
+CREATE INDEX idx_tasks_sprint_id ON tasks(sprint_id);
+
+-- Unfinished tasks moved out of a sprint when it was completed, so the
+-- sprint's burndown still covers them.
+CREATE TABLE sprint_rollovers (
+    sprint_id UUID NOT NULL REFERENCES sprints(id) ON DELETE CASCADE,
+    task_id UUID NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    to_sprint_id UUID REFERENCES sprints(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (sprint_id, task_id)
+);
+
+-- Changes to projects and tasks. For now only task statuses are recorded,
+-- as 'created' entries holding the initial status and 'updated' entries for
+-- the status field; burndowns read them through task_status_changes.
+CREATE TABLE activity_log (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    entity_type VARCHAR(20) NOT NULL CHECK (entity_type IN ('project', 'task')),
+    -- No foreign key: entries outlive deleted tasks.
+    entity_id UUID NOT NULL,
+    actor_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    action VARCHAR(30) NOT NULL,
+    -- Set for 'updated' entries, one row per changed field.
+    field VARCHAR(100),
+    old_value JSONB,
+    new_value JSONB,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_activity_log_entity ON activity_log(entity_type, entity_id, created_at);
+CREATE INDEX idx_activity_log_project ON activity_log(project_id, created_at);
+
+-- Existing tasks start their history in their current status.
+INSERT INTO activity_log (project_id, entity_type, entity_id, action, new_value, created_at)
+SELECT t.project_id, 'task', t.id, 'created', to_jsonb(t), t.created_at
+FROM tasks t;
+
+-- Every status a task has been in and when it entered it.
+CREATE VIEW task_status_changes AS
+SELECT entity_id AS task_id,
+       project_id,
+       actor_id,
+       created_at AS changed_at,
+       CASE WHEN action = 'created' THEN new_value ->> 'status' ELSE new_value #>> '{}' END AS status
+FROM activity_log
+WHERE entity_type = 'task' AND (action = 'created' OR field = 'status');
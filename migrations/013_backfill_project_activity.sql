@@ -0,0 +1,5 @@
+-- Task activity has been recorded since sprints were added; start each
+-- existing project's timeline at its creation.
+INSERT INTO activity_log (project_id, entity_type, entity_id, actor_id, action, new_value, created_at)
+SELECT p.id, 'project', p.id, p.owner_id, 'created', to_jsonb(p), p.created_at
+FROM projects p;
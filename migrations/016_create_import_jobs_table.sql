@@ -0,0 +1,19 @@
+CREATE TABLE import_jobs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    source VARCHAR(20) NOT NULL CHECK (source IN ('trello', 'jira')),
+    status VARCHAR(20) DEFAULT 'pending' NOT NULL
+        CHECK (status IN ('pending', 'running', 'completed', 'failed')),
+    requested_by UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- Set once the job has created its project.
+    project_id UUID REFERENCES projects(id) ON DELETE SET NULL,
+    project_name VARCHAR(255) NOT NULL,
+    total_items INTEGER DEFAULT 0 NOT NULL,
+    processed_items INTEGER DEFAULT 0 NOT NULL,
+    warnings JSONB DEFAULT '[]' NOT NULL,
+    error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    started_at TIMESTAMP WITH TIME ZONE,
+    finished_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_import_jobs_requested_by ON import_jobs(requested_by, created_at);
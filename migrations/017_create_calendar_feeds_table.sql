@@ -0,0 +1,14 @@
+-- Secret feed URLs for subscribing to deadlines from calendar apps. A feed
+-- without a project lists the user's assigned tasks across projects.
+CREATE TABLE calendar_feeds (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    project_id UUID REFERENCES projects(id) ON DELETE CASCADE,
+    token VARCHAR(64) NOT NULL UNIQUE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE UNIQUE INDEX idx_calendar_feeds_user ON calendar_feeds(user_id) WHERE project_id IS NULL;
+CREATE UNIQUE INDEX idx_calendar_feeds_user_project ON calendar_feeds(user_id, project_id)
+    WHERE project_id IS NOT NULL;
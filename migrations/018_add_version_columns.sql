@@ -0,0 +1,16 @@
+ALTER TABLE projects ADD COLUMN version INTEGER DEFAULT 1 NOT NULL;
+ALTER TABLE tasks ADD COLUMN version INTEGER DEFAULT 1 NOT NULL;
+
+-- Bumped by the database so every write path changes the ETag, not only the
+-- PATCH handlers.
+CREATE FUNCTION bump_row_version() RETURNS trigger AS $$
+BEGIN
+    NEW.version := OLD.version + 1;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER projects_bump_version BEFORE UPDATE ON projects
+    FOR EACH ROW EXECUTE FUNCTION bump_row_version();
+CREATE TRIGGER tasks_bump_version BEFORE UPDATE ON tasks
+    FOR EACH ROW EXECUTE FUNCTION bump_row_version();
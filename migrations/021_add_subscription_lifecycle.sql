@@ -0,0 +1,21 @@
+-- A subscription cancelled at period end stays active until expires_at.
+ALTER TABLE subscriptions ADD COLUMN cancel_at_period_end BOOLEAN DEFAULT false NOT NULL;
+ALTER TABLE subscriptions ADD COLUMN cancelled_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX idx_subscriptions_expires_at ON subscriptions(expires_at)
+    WHERE status = 'active' AND expires_at IS NOT NULL;
+
+-- Every user gets a free subscription, whichever service creates the account.
+CREATE FUNCTION create_free_subscription() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO subscriptions (user_id) VALUES (NEW.id) ON CONFLICT (user_id) DO NOTHING;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER users_create_free_subscription AFTER INSERT ON users
+    FOR EACH ROW EXECUTE FUNCTION create_free_subscription();
+
+INSERT INTO subscriptions (user_id)
+SELECT id FROM users
+ON CONFLICT (user_id) DO NOTHING;
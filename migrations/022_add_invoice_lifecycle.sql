@@ -0,0 +1,27 @@
+-- Numbers are allocated when an invoice is issued, in the issuing
+-- transaction, so they run without gaps within each year. Drafts have none.
+ALTER TABLE invoices ADD COLUMN number VARCHAR(32) UNIQUE;
+ALTER TABLE invoices ADD COLUMN period_start TIMESTAMP WITH TIME ZONE;
+ALTER TABLE invoices ADD COLUMN period_end TIMESTAMP WITH TIME ZONE;
+ALTER TABLE invoices ALTER COLUMN status SET DEFAULT 'draft';
+
+-- One invoice per subscription period.
+CREATE UNIQUE INDEX idx_invoices_subscription_period ON invoices(subscription_id, period_start)
+    WHERE subscription_id IS NOT NULL AND period_start IS NOT NULL;
+
+CREATE TABLE invoice_sequences (
+    year INTEGER PRIMARY KEY,
+    last_number INTEGER NOT NULL
+);
+
+CREATE TABLE invoice_line_items (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    invoice_id UUID NOT NULL REFERENCES invoices(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    description TEXT NOT NULL,
+    quantity INTEGER DEFAULT 1 NOT NULL CHECK (quantity > 0),
+    unit_amount DECIMAL(10, 2) NOT NULL,
+    amount DECIMAL(10, 2) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    UNIQUE (invoice_id, position)
+);
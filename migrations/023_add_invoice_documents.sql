@@ -0,0 +1,22 @@
+-- Invoices carry the tax rate they were created with and the tax it came to.
+-- `amount` stays the total to pay, tax included.
+ALTER TABLE invoices ADD COLUMN tax_rate_bps INTEGER DEFAULT 0 NOT NULL
+    CHECK (tax_rate_bps BETWEEN 0 AND 10000);
+ALTER TABLE invoices ADD COLUMN tax_amount DECIMAL(10, 2) DEFAULT 0 NOT NULL;
+
+-- The PDF of an issued invoice, rendered once and then served as stored.
+CREATE TABLE invoice_documents (
+    invoice_id UUID PRIMARY KEY REFERENCES invoices(id) ON DELETE CASCADE,
+    content BYTEA NOT NULL,
+    checksum_sha256 CHAR(64) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE FUNCTION reject_invoice_document_update() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'invoice documents cannot be changed';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER invoice_documents_immutable BEFORE UPDATE ON invoice_documents
+    FOR EACH ROW EXECUTE FUNCTION reject_invoice_document_update();
@@ -0,0 +1,24 @@
+-- Users' customer records at the payment provider.
+CREATE TABLE payment_customers (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    provider VARCHAR(32) NOT NULL,
+    customer_id VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (user_id, provider),
+    UNIQUE (provider, customer_id)
+);
+
+-- Webhook events already handled, so redeliveries are not applied twice.
+-- Failed payments are kept here as the invoice's payment history.
+CREATE TABLE payment_events (
+    provider VARCHAR(32) NOT NULL,
+    event_id VARCHAR(255) NOT NULL,
+    event_type VARCHAR(100) NOT NULL,
+    invoice_id UUID REFERENCES invoices(id) ON DELETE SET NULL,
+    failure_reason TEXT,
+    received_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (provider, event_id)
+);
+
+CREATE INDEX idx_payment_events_invoice_id ON payment_events(invoice_id)
+    WHERE invoice_id IS NOT NULL;
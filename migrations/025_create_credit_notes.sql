@@ -0,0 +1,28 @@
+-- Account credit spent on an invoice, and credit notes that reduced it
+-- while it was unpaid; what is left of `amount` is due.
+ALTER TABLE invoices ADD COLUMN credit_applied DECIMAL(10, 2) DEFAULT 0 NOT NULL
+    CHECK (credit_applied >= 0 AND credit_applied <= amount);
+
+CREATE TABLE credit_note_sequences (
+    year INTEGER PRIMARY KEY,
+    last_number INTEGER NOT NULL
+);
+
+-- Credit given back against an invoice, tax included. Against a paid
+-- invoice it adds to the user's account credit in the same currency; against
+-- an unpaid one it is applied to the invoice and reduces what is owed.
+CREATE TABLE credit_notes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    number VARCHAR(32) NOT NULL UNIQUE,
+    invoice_id UUID NOT NULL REFERENCES invoices(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    amount DECIMAL(10, 2) NOT NULL CHECK (amount > 0),
+    tax_amount DECIMAL(10, 2) DEFAULT 0 NOT NULL,
+    currency VARCHAR(3) NOT NULL,
+    reason TEXT NOT NULL,
+    applied BOOLEAN DEFAULT false NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_credit_notes_user_id ON credit_notes(user_id, created_at DESC);
+CREATE INDEX idx_credit_notes_invoice_id ON credit_notes(invoice_id);
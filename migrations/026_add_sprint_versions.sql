@@ -0,0 +1,5 @@
+-- Feeds the SEQUENCE of sprint events in the calendar feeds.
+ALTER TABLE sprints ADD COLUMN version INTEGER DEFAULT 1 NOT NULL;
+
+CREATE TRIGGER sprints_bump_version BEFORE UPDATE ON sprints
+    FOR EACH ROW EXECUTE FUNCTION bump_row_version();
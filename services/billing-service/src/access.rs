@@ -0,0 +1,21 @@
+use shared::{
+    errors::{AppError, AppResult},
+    models::Claims,
+};
+use uuid::Uuid;
+
+pub fn user_id(claims: &Claims) -> AppResult<Uuid> {
+    Uuid::parse_str(&claims.sub)
+        .map_err(|_| AppError::InvalidToken("Invalid subject in token".to_string()))
+}
+
+pub fn is_admin(claims: &Claims) -> bool {
+    claims.role == "admin"
+}
+
+pub fn require_admin(claims: &Claims) -> AppResult<()> {
+    if !is_admin(claims) {
+        return Err(AppError::Forbidden("Admin access required".to_string()));
+    }
+    Ok(())
+}
@@ -0,0 +1,289 @@
+//! Credit notes and the account credit they add up to.
+//!
+//! A credit note gives back part of what was invoiced and is linked to the
+//! invoice it corrects. Only money actually paid is given back as account
+//! credit, in the invoice's currency, which is spent on the user's next
+//! invoices as they are issued. A note against an invoice that is still
+//! unpaid is applied to that invoice instead and reduces what is owed on it.
+//! The balance is what credit notes granted less what invoices used, so
+//! applied notes never add to it.
+
+use chrono::{DateTime, Datelike, Utc};
+use rust_decimal::Decimal;
+use shared::errors::AppResult;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{
+    models::{CreditNote, Subscription},
+    money,
+};
+
+pub const CREDIT_NOTE_COLUMNS: &str = "id, number, invoice_id, user_id,
+    (amount * 100)::bigint AS amount_cents, (tax_amount * 100)::bigint AS tax_cents,
+    currency, reason, applied, created_at";
+
+/// An invoice of the period being credited, and how much credit it can take.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Creditable {
+    pub invoice_id: Uuid,
+    pub tax_rate_bps: i32,
+    pub paid: bool,
+    /// For an unpaid invoice, what is still owed on it. For a paid one, what
+    /// was paid for it in money and not yet given back.
+    pub room: Decimal,
+}
+
+/// One credit note to issue, tax included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Allocation {
+    pub invoice_id: Uuid,
+    pub amount: Decimal,
+    pub tax: Decimal,
+    /// Reduces what is owed on the invoice instead of adding account credit.
+    pub applied: bool,
+}
+
+/// Spreads `gross` over the period's invoices: first off what is still owed
+/// on unpaid ones, then back from paid ones as account credit, each in the
+/// given order and never beyond its room. Whatever does not fit is dropped.
+/// Each note's tax is split out at its invoice's rate.
+pub fn allocate(invoices: &[Creditable], gross: Decimal) -> Vec<Allocation> {
+    let mut left = gross;
+    let mut allocations = Vec::new();
+    let ordered = invoices
+        .iter()
+        .filter(|invoice| !invoice.paid)
+        .chain(invoices.iter().filter(|invoice| invoice.paid));
+    for invoice in ordered {
+        let amount = left.min(invoice.room);
+        if amount <= Decimal::ZERO {
+            continue;
+        }
+        allocations.push(Allocation {
+            invoice_id: invoice.invoice_id,
+            amount,
+            tax: money::split_gross(amount, invoice.tax_rate_bps).1,
+            applied: !invoice.paid,
+        });
+        left -= amount;
+    }
+    allocations
+}
+
+pub fn format_number(year: i32, sequence: i32) -> String {
+    format!("CN-{}-{:06}", year, sequence)
+}
+
+/// Takes the next credit note number of `year`, without gaps, like invoice
+/// numbers.
+async fn next_number(conn: &mut PgConnection, year: i32) -> AppResult<String> {
+    let sequence: i32 = sqlx::query_scalar(
+        "INSERT INTO credit_note_sequences (year, last_number) VALUES ($1, 1)
+         ON CONFLICT (year) DO UPDATE SET last_number = credit_note_sequences.last_number + 1
+         RETURNING last_number",
+    )
+    .bind(year)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(format_number(year, sequence))
+}
+
+/// Serializes changes to the user's account credit for the rest of the
+/// transaction.
+pub async fn lock(conn: &mut PgConnection, user_id: Uuid) -> AppResult<()> {
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended('credit:' || $1::text, 0))")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// The user's unspent credit in `currency`.
+pub async fn balance(conn: &mut PgConnection, user_id: Uuid, currency: &str) -> AppResult<Decimal> {
+    let cents: i64 = sqlx::query_scalar(
+        "SELECT (COALESCE((SELECT SUM(amount) FROM credit_notes
+                           WHERE user_id = $1 AND currency = $2), 0)
+               - COALESCE((SELECT SUM(credit_applied) FROM invoices
+                           WHERE user_id = $1 AND currency = $2), 0)) * 100)::bigint",
+    )
+    .bind(user_id)
+    .bind(currency)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(money::from_cents(cents))
+}
+
+/// Credits `net`, before tax, of the subscription's period running at `at`,
+/// taxed at the rate of the period's latest invoice. Unpaid invoices of the
+/// period are reduced first; the rest comes back as account credit, but
+/// never more than was paid for the period. Returns the notes issued, none
+/// if nothing is left to credit, e.g. because the period was never invoiced.
+pub async fn credit_period(
+    conn: &mut PgConnection,
+    subscription: &Subscription,
+    net: Decimal,
+    reason: &str,
+    at: DateTime<Utc>,
+) -> AppResult<Vec<CreditNote>> {
+    lock(conn, subscription.user_id).await?;
+
+    let invoices: Vec<(Uuid, i32, bool, i64, String)> = sqlx::query_as(
+        "SELECT i.id, i.tax_rate_bps, i.status = 'paid', ((i.amount - i.credit_applied
+                    - CASE WHEN i.status = 'paid'
+                           THEN COALESCE((SELECT SUM(c.amount) FROM credit_notes c
+                                          WHERE c.invoice_id = i.id AND NOT c.applied), 0)
+                           ELSE 0 END) * 100)::bigint,
+                i.currency
+         FROM invoices i
+         WHERE i.subscription_id = $1 AND i.status IN ('issued', 'overdue', 'paid')
+           AND i.period_start <= $2 AND i.period_end > $2
+         ORDER BY i.period_start DESC, i.issued_at DESC
+         FOR UPDATE OF i",
+    )
+    .bind(subscription.id)
+    .bind(at)
+    .fetch_all(&mut *conn)
+    .await?;
+    let Some((_, tax_rate_bps, _, _, currency)) = invoices.first().cloned() else {
+        return Ok(Vec::new());
+    };
+
+    let creditable: Vec<Creditable> = invoices
+        .into_iter()
+        .filter(|(.., c)| *c == currency)
+        .map(
+            |(invoice_id, tax_rate_bps, paid, room_cents, _)| Creditable {
+                invoice_id,
+                tax_rate_bps,
+                paid,
+                room: money::from_cents(room_cents),
+            },
+        )
+        .collect();
+    let gross = net + money::tax(net, tax_rate_bps);
+
+    let mut notes = Vec::new();
+    for allocation in allocate(&creditable, gross) {
+        let number = next_number(conn, at.year()).await?;
+        let amount_cents = money::to_cents(allocation.amount)?;
+        let note = sqlx::query_as::<_, CreditNote>(&format!(
+            "INSERT INTO credit_notes
+                 (number, invoice_id, user_id, amount, tax_amount, currency, reason, applied)
+             VALUES ($1, $2, $3, $4::numeric / 100, $5::numeric / 100, $6, $7, $8)
+             RETURNING {}",
+            CREDIT_NOTE_COLUMNS
+        ))
+        .bind(number)
+        .bind(allocation.invoice_id)
+        .bind(subscription.user_id)
+        .bind(amount_cents)
+        .bind(money::to_cents(allocation.tax)?)
+        .bind(&currency)
+        .bind(reason)
+        .bind(allocation.applied)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        if allocation.applied {
+            // Settled once nothing is left to pay, like an invoice covered by
+            // account credit.
+            sqlx::query(
+                "UPDATE invoices
+                 SET credit_applied = credit_applied + $2::numeric / 100,
+                     status = CASE WHEN credit_applied + $2::numeric / 100 = amount
+                                   THEN 'paid'::invoice_status ELSE status END,
+                     paid_at = CASE WHEN credit_applied + $2::numeric / 100 = amount
+                                    THEN NOW() ELSE paid_at END,
+                     updated_at = NOW()
+                 WHERE id = $1",
+            )
+            .bind(allocation.invoice_id)
+            .bind(amount_cents)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        tracing::info!(
+            "Issued credit note {} of {} {} to user {}{}",
+            note.number,
+            allocation.amount,
+            note.currency,
+            note.user_id,
+            if note.applied {
+                " against what is owed"
+            } else {
+                ""
+            }
+        );
+        notes.push(note);
+    }
+    Ok(notes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn d(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    fn invoice(paid: bool, room: &str) -> Creditable {
+        Creditable {
+            invoice_id: Uuid::new_v4(),
+            tax_rate_bps: 2_000,
+            paid,
+            room: d(room),
+        }
+    }
+
+    #[test]
+    fn reduces_unpaid_invoices_before_giving_credit() {
+        let paid = invoice(true, "60.00");
+        let unpaid = invoice(false, "24.00");
+        let allocations = allocate(&[paid.clone(), unpaid.clone()], d("36.00"));
+        assert_eq!(
+            allocations,
+            vec![
+                Allocation {
+                    invoice_id: unpaid.invoice_id,
+                    amount: d("24.00"),
+                    tax: d("4.00"),
+                    applied: true,
+                },
+                Allocation {
+                    invoice_id: paid.invoice_id,
+                    amount: d("12.00"),
+                    tax: d("2.00"),
+                    applied: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn never_credits_beyond_the_room_left() {
+        let allocations = allocate(&[invoice(false, "10.00")], d("99.00"));
+        assert_eq!(allocations.len(), 1);
+        assert_eq!(allocations[0].amount, d("10.00"));
+        assert!(allocations[0].applied);
+
+        assert!(allocate(&[invoice(true, "0.00")], d("5.00")).is_empty());
+        assert!(allocate(&[], d("5.00")).is_empty());
+        assert!(allocate(&[invoice(true, "5.00")], d("0.00")).is_empty());
+    }
+
+    #[test]
+    fn splits_tax_out_at_each_invoices_rate() {
+        let mut untaxed = invoice(true, "50.00");
+        untaxed.tax_rate_bps = 0;
+        let taxed = invoice(true, "50.00");
+        let allocations = allocate(&[taxed, untaxed], d("61.00"));
+        assert_eq!(allocations[0].tax, d("8.33")); // 50.00 at 20%
+        assert_eq!(allocations[1].tax, d("0.00"));
+        let total: Decimal = allocations.iter().map(|a| a.amount).sum();
+        assert_eq!(total, d("61.00"));
+    }
+}
@@ -1 +1,6 @@
 // Billing service handlers
+pub mod credit_note;
+pub mod invoice;
+pub mod payment;
+pub mod plan;
+pub mod subscription;
@@ -0,0 +1,69 @@
+use axum::{
+    extract::{Query, State},
+    Extension, Json,
+};
+use shared::{
+    errors::AppResult,
+    models::{Claims, PaginatedResponse, PaginationParams},
+};
+
+use crate::{
+    access,
+    credit_notes::CREDIT_NOTE_COLUMNS,
+    models::{CreditBalance, CreditNote},
+    AppState,
+};
+
+/// The caller's credit notes, newest first.
+pub async fn list_credit_notes(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(pagination): Query<PaginationParams>,
+) -> AppResult<Json<PaginatedResponse<CreditNote>>> {
+    let user_id = access::user_id(&claims)?;
+
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM credit_notes WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(&state.db)
+        .await?;
+    let data = sqlx::query_as::<_, CreditNote>(&format!(
+        "SELECT {} FROM credit_notes WHERE user_id = $1
+         ORDER BY created_at DESC, id LIMIT $2 OFFSET $3",
+        CREDIT_NOTE_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(pagination.limit())
+    .bind(pagination.offset())
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(PaginatedResponse {
+        data,
+        page: pagination.page(),
+        limit: pagination.limit(),
+        total,
+    }))
+}
+
+/// The caller's unspent account credit, per currency they were ever
+/// credited in.
+pub async fn get_credit_balance(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> AppResult<Json<Vec<CreditBalance>>> {
+    let user_id = access::user_id(&claims)?;
+
+    let balances = sqlx::query_as::<_, CreditBalance>(
+        "SELECT c.currency,
+                ((c.granted - COALESCE((SELECT SUM(i.credit_applied) FROM invoices i
+                                        WHERE i.user_id = $1 AND i.currency = c.currency), 0))
+                 * 100)::bigint AS balance_cents
+         FROM (SELECT currency, SUM(amount) AS granted FROM credit_notes
+               WHERE user_id = $1 GROUP BY currency) c
+         ORDER BY c.currency",
+    )
+    .bind(user_id)
+    .fetch_all(&state.db)
+    .await?;
+    Ok(Json(balances))
+}
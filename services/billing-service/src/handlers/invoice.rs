@@ -0,0 +1,234 @@
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::Response,
+    Extension, Json,
+};
+use serde_json::json;
+use shared::{
+    errors::{AppError, AppResult},
+    models::{Claims, PaginatedResponse},
+};
+use sqlx::{Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::{
+    access,
+    invoices::{self, INVOICE_COLUMNS},
+    models::{
+        CreateInvoiceRequest, Invoice, InvoiceListParams, InvoiceResponse,
+        UpdateInvoiceStatusRequest,
+    },
+    AppState,
+};
+
+fn push_filters(
+    qb: &mut QueryBuilder<'_, Postgres>,
+    user_id: Option<Uuid>,
+    status: Option<&str>,
+    include_drafts: bool,
+) {
+    qb.push(" WHERE true");
+    if let Some(user_id) = user_id {
+        qb.push(" AND i.user_id = ").push_bind(user_id);
+    }
+    if let Some(status) = status {
+        qb.push(" AND i.status = ")
+            .push_bind(status.to_string())
+            .push("::invoice_status");
+    }
+    if !include_drafts {
+        qb.push(" AND i.status <> 'draft'");
+    }
+}
+
+async fn list(
+    state: &AppState,
+    params: &InvoiceListParams,
+    user_id: Option<Uuid>,
+    include_drafts: bool,
+) -> AppResult<Json<PaginatedResponse<Invoice>>> {
+    let status = params.status.as_deref();
+    if let Some(status) = status {
+        invoices::validate_status(status)?;
+    }
+    let pagination = params.pagination();
+
+    let mut count_qb = QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM invoices i");
+    push_filters(&mut count_qb, user_id, status, include_drafts);
+    let total: i64 = count_qb.build_query_scalar().fetch_one(&state.db).await?;
+
+    let mut qb =
+        QueryBuilder::<Postgres>::new(format!("SELECT {} FROM invoices i", INVOICE_COLUMNS));
+    push_filters(&mut qb, user_id, status, include_drafts);
+    qb.push(" ORDER BY i.issued_at DESC, i.id LIMIT ")
+        .push_bind(pagination.limit())
+        .push(" OFFSET ")
+        .push_bind(pagination.offset());
+    let data: Vec<Invoice> = qb.build_query_as().fetch_all(&state.db).await?;
+
+    Ok(Json(PaginatedResponse {
+        data,
+        page: pagination.page(),
+        limit: pagination.limit(),
+        total,
+    }))
+}
+
+/// The caller's invoices, newest first. Drafts are not shown.
+pub async fn list_invoices(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(params): Query<InvoiceListParams>,
+) -> AppResult<Json<PaginatedResponse<Invoice>>> {
+    let user_id = access::user_id(&claims)?;
+    list(&state, &params, Some(user_id), false).await
+}
+
+/// Loads an invoice the caller may see: their own unless it is a draft, or
+/// any for admins.
+async fn load_visible(
+    state: &AppState,
+    claims: &Claims,
+    invoice_id: Uuid,
+) -> AppResult<InvoiceResponse> {
+    let invoice = invoices::load(&state.db, invoice_id).await?;
+    if !access::is_admin(claims)
+        && (invoice.invoice.user_id != access::user_id(claims)?
+            || invoice.invoice.status == "draft")
+    {
+        return Err(AppError::NotFound("Invoice not found".to_string()));
+    }
+    Ok(invoice)
+}
+
+/// One of the caller's invoices with its line items; admins can see any.
+pub async fn get_invoice(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(invoice_id): Path<Uuid>,
+) -> AppResult<Json<InvoiceResponse>> {
+    Ok(Json(load_visible(&state, &claims, invoice_id).await?))
+}
+
+/// Downloads an issued invoice as a PDF. The file is generated once and the
+/// same bytes are served every time after.
+pub async fn download_invoice_pdf(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(invoice_id): Path<Uuid>,
+) -> AppResult<Response> {
+    let invoice = load_visible(&state, &claims, invoice_id).await?;
+    let pdf = invoices::pdf(&state.db, &state.invoicing, &invoice).await?;
+    let number = invoice.invoice.number.as_deref().unwrap_or("invoice");
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/pdf")
+        .header(header::CONTENT_LENGTH, pdf.content.len())
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}.pdf\"", number),
+        )
+        .header(header::ETAG, format!("\"{}\"", pdf.checksum_sha256))
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header(header::CACHE_CONTROL, "private, max-age=0")
+        .body(Body::from(pdf.content))
+        .map_err(|e| AppError::InternalError(format!("Failed to build response: {}", e)))
+}
+
+/// All invoices, drafts included, optionally of one user or status.
+pub async fn admin_list_invoices(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(params): Query<InvoiceListParams>,
+) -> AppResult<Json<PaginatedResponse<Invoice>>> {
+    access::require_admin(&claims)?;
+    list(&state, &params, params.user_id, true).await
+}
+
+/// Creates a draft invoice for a one-off charge. It gets a number and a due
+/// date when it is issued.
+pub async fn create_invoice(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<CreateInvoiceRequest>,
+) -> AppResult<(StatusCode, Json<InvoiceResponse>)> {
+    access::require_admin(&claims)?;
+    let currency = invoices::validate_currency(req.currency.as_deref().unwrap_or("USD"))?;
+
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM users WHERE id = $1)")
+        .bind(req.user_id)
+        .fetch_one(&state.db)
+        .await?;
+    if !exists {
+        return Err(AppError::NotFound("User not found".to_string()));
+    }
+
+    let mut tx = state.db.begin().await?;
+    let invoice_id = invoices::create_draft(
+        &mut tx,
+        req.user_id,
+        None,
+        &currency,
+        &req.line_items,
+        state.invoicing.tax_rate_bps,
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(invoices::load(&state.db, invoice_id).await?),
+    ))
+}
+
+/// Issues a draft or records a payment, following the allowed transitions
+/// (draft → issued → paid, issued → overdue → paid).
+pub async fn update_invoice_status(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(invoice_id): Path<Uuid>,
+    Json(req): Json<UpdateInvoiceStatusRequest>,
+) -> AppResult<Json<InvoiceResponse>> {
+    access::require_admin(&claims)?;
+
+    let mut tx = state.db.begin().await?;
+    invoices::transition(&mut tx, invoice_id, req.status.trim()).await?;
+    tx.commit().await?;
+
+    Ok(Json(invoices::load(&state.db, invoice_id).await?))
+}
+
+/// Deletes a draft. Issued invoices are kept for the record.
+pub async fn delete_invoice(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(invoice_id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    access::require_admin(&claims)?;
+
+    let status: Option<String> =
+        sqlx::query_scalar("SELECT status::text FROM invoices WHERE id = $1")
+            .bind(invoice_id)
+            .fetch_optional(&state.db)
+            .await?;
+    match status.as_deref() {
+        None => return Err(AppError::NotFound("Invoice not found".to_string())),
+        Some("draft") => {}
+        Some(_) => {
+            return Err(AppError::Conflict(
+                "Only draft invoices can be deleted".to_string(),
+            ))
+        }
+    }
+
+    sqlx::query("DELETE FROM invoices WHERE id = $1 AND status = 'draft'")
+        .bind(invoice_id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(Json(json!({"message": "deleted"})))
+}
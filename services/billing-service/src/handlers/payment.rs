@@ -0,0 +1,80 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::HeaderMap,
+    Extension, Json,
+};
+use serde_json::json;
+use shared::{
+    errors::{AppError, AppResult},
+    models::Claims,
+};
+use uuid::Uuid;
+
+use crate::{
+    access, invoices,
+    models::CheckoutResponse,
+    payments::{self, CheckoutRequest},
+    AppState,
+};
+
+/// Starts the provider's hosted checkout for one of the caller's unpaid
+/// invoices.
+pub async fn create_checkout(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(invoice_id): Path<Uuid>,
+) -> AppResult<Json<CheckoutResponse>> {
+    let user_id = access::user_id(&claims)?;
+    let invoice = invoices::load(&state.db, invoice_id).await?.invoice;
+    if invoice.user_id != user_id || invoice.status == "draft" {
+        return Err(AppError::NotFound("Invoice not found".to_string()));
+    }
+    if !invoices::can_transition(&invoice.status, "paid") {
+        return Err(AppError::Conflict(format!(
+            "Invoice is {} and cannot be paid",
+            invoice.status
+        )));
+    }
+
+    let provider = state.payments.as_ref();
+    let customer_id = payments::customer_id(&state.db, provider, user_id).await?;
+    let session = provider
+        .create_checkout_session(&CheckoutRequest {
+            customer_id,
+            invoice_id,
+            description: format!("Invoice {}", invoice.number.unwrap_or_default()),
+            amount_cents: invoice.amount_cents - invoice.credit_applied_cents,
+            currency: invoice.currency,
+        })
+        .await?;
+
+    Ok(Json(CheckoutResponse {
+        provider: provider.name().to_string(),
+        session_id: session.id,
+        url: session.url,
+    }))
+}
+
+/// Receives the payment provider's webhooks. Authorization comes from the
+/// signature, so this route sits outside the JWT middleware. Events seen
+/// before are acknowledged without being applied again.
+pub async fn payment_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> AppResult<Json<serde_json::Value>> {
+    let event = state.payments.verify_webhook(&headers, &body)?;
+
+    let mut tx = state.db.begin().await?;
+    let applied = payments::handle_event(
+        &mut tx,
+        state.payments.name(),
+        &event,
+        state.invoicing.tax_rate_bps,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(Json(json!({"received": true, "duplicate": !applied})))
+}
@@ -0,0 +1,7 @@
+use axum::Json;
+
+use crate::plans::{Plan, PLANS};
+
+pub async fn list_plans() -> Json<Vec<Plan>> {
+    Json(PLANS.to_vec())
+}
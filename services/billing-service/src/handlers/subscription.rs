@@ -0,0 +1,230 @@
+use axum::{
+    extract::{Path, State},
+    http::HeaderMap,
+    Extension, Json,
+};
+use chrono::Utc;
+use shared::{
+    auth::{verify_service_secret, SERVICE_SECRET_HEADER},
+    errors::{AppError, AppResult},
+    models::Claims,
+};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{
+    access::user_id,
+    invoices,
+    models::{
+        CancelSubscriptionRequest, ChangePlanRequest, PlanLimits, Subscription,
+        SubscriptionResponse, UpdateSubscriptionRequest,
+    },
+    plans::{self, Plan},
+    proration,
+    subscriptions::{self, SUBSCRIPTION_COLUMNS},
+    AppState,
+};
+
+fn response(subscription: Subscription) -> AppResult<Json<SubscriptionResponse>> {
+    let plan_details = *plans::find(&subscription.plan)?;
+    Ok(Json(SubscriptionResponse {
+        subscription,
+        plan_details,
+    }))
+}
+
+fn require_active(subscription: &Subscription) -> AppResult<()> {
+    if subscription.status != "active" {
+        return Err(AppError::BadRequest(format!(
+            "Subscription is {}",
+            subscription.status
+        )));
+    }
+    Ok(())
+}
+
+/// Moves the subscription to `plan`. Leaving the free plan, or coming back
+/// after a cancellation, starts a new billing period and invoices it; changes
+/// from a paid plan keep the current one and prorate the rest of it.
+async fn set_plan(
+    conn: &mut PgConnection,
+    current: &Subscription,
+    plan: &Plan,
+    tax_rate_bps: i32,
+) -> AppResult<Subscription> {
+    let now = Utc::now();
+    let new_period = !plan.is_free() && (current.status != "active" || current.plan == plans::FREE);
+    let (started_at, expires_at) = if plan.is_free() {
+        (now, None)
+    } else if new_period {
+        (now, Some(subscriptions::period_end(now)))
+    } else {
+        (current.started_at, current.expires_at)
+    };
+
+    let subscription = sqlx::query_as::<_, Subscription>(&format!(
+        "UPDATE subscriptions
+         SET plan = $2::subscription_plan, status = 'active', started_at = $3, expires_at = $4,
+             auto_renew = true, cancel_at_period_end = false, cancelled_at = NULL,
+             max_projects = $5, max_tasks = $6, updated_at = NOW()
+         WHERE id = $1
+         RETURNING {}",
+        SUBSCRIPTION_COLUMNS
+    ))
+    .bind(current.id)
+    .bind(plan.id)
+    .bind(started_at)
+    .bind(expires_at)
+    .bind(plan.max_projects)
+    .bind(plan.max_tasks)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if let (true, Some(end)) = (new_period, expires_at) {
+        invoices::invoice_period(
+            conn,
+            subscription.id,
+            subscription.user_id,
+            plan,
+            tax_rate_bps,
+            started_at,
+            end,
+        )
+        .await?;
+    } else if !new_period {
+        let from = plans::find(&current.plan)?;
+        proration::settle_plan_change(conn, current, from, plan, now, tax_rate_bps).await?;
+    }
+
+    Ok(subscription)
+}
+
+pub async fn get_subscription(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> AppResult<Json<SubscriptionResponse>> {
+    let user_id = user_id(&claims)?;
+
+    let mut tx = state.db.begin().await?;
+    let subscription =
+        subscriptions::load_for_update(&mut tx, user_id, state.invoicing.tax_rate_bps).await?;
+    tx.commit().await?;
+
+    response(subscription)
+}
+
+/// Limits of a user's current plan, for the other services, which must
+/// present the shared service secret. Not exposed through the gateway. Only
+/// reads, so it never renews or ends a subscription.
+pub async fn get_user_limits(
+    State(state): State<AppState>,
+    Path(user_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> AppResult<Json<PlanLimits>> {
+    verify_service_secret(
+        &state.service_secret,
+        headers
+            .get(SERVICE_SECRET_HEADER)
+            .and_then(|value| value.to_str().ok()),
+    )?;
+
+    Ok(Json(
+        subscriptions::current_limits(&state.db, user_id).await?,
+    ))
+}
+
+/// Upgrades or downgrades to another plan, taking effect immediately.
+pub async fn change_plan(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<ChangePlanRequest>,
+) -> AppResult<Json<SubscriptionResponse>> {
+    let user_id = user_id(&claims)?;
+    let plan = plans::find(req.plan.trim())?;
+
+    let mut tx = state.db.begin().await?;
+    let current =
+        subscriptions::load_for_update(&mut tx, user_id, state.invoicing.tax_rate_bps).await?;
+
+    if current.plan == plan.id && current.status == "active" {
+        return Err(AppError::Conflict(format!(
+            "Already subscribed to the {} plan",
+            plan.name
+        )));
+    }
+
+    let subscription = set_plan(&mut tx, &current, plan, state.invoicing.tax_rate_bps).await?;
+    tx.commit().await?;
+
+    let from = plans::find(&current.plan)?;
+    tracing::info!(
+        "User {} {} from {} to {}",
+        user_id,
+        if plan.is_upgrade_from(from) {
+            "upgraded"
+        } else {
+            "downgraded"
+        },
+        from.id,
+        plan.id
+    );
+
+    response(subscription)
+}
+
+/// Cancels a paid subscription, by default at the end of the current period.
+/// Cancelling immediately drops to the free plan right away.
+pub async fn cancel_subscription(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    req: Option<Json<CancelSubscriptionRequest>>,
+) -> AppResult<Json<SubscriptionResponse>> {
+    let user_id = user_id(&claims)?;
+    let req = req.map(|Json(req)| req).unwrap_or_default();
+
+    let mut tx = state.db.begin().await?;
+    let current =
+        subscriptions::load_for_update(&mut tx, user_id, state.invoicing.tax_rate_bps).await?;
+    require_active(&current)?;
+    if current.plan == plans::FREE {
+        return Err(AppError::BadRequest(
+            "The free plan cannot be cancelled".to_string(),
+        ));
+    }
+
+    let subscription = if req.at_period_end {
+        subscriptions::cancel_at_period_end(&mut tx, current.id).await?
+    } else {
+        subscriptions::cancel_now(&mut tx, current.id).await?
+    };
+
+    tx.commit().await?;
+
+    response(subscription)
+}
+
+/// Toggles `auto_renew`. A subscription that does not renew expires at the
+/// end of its period.
+pub async fn update_subscription(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<UpdateSubscriptionRequest>,
+) -> AppResult<Json<SubscriptionResponse>> {
+    let user_id = user_id(&claims)?;
+
+    let mut tx = state.db.begin().await?;
+    let current =
+        subscriptions::load_for_update(&mut tx, user_id, state.invoicing.tax_rate_bps).await?;
+
+    let subscription = match req.auto_renew {
+        Some(auto_renew) => {
+            require_active(&current)?;
+            subscriptions::set_auto_renew(&mut tx, current.id, auto_renew).await?
+        }
+        None => current,
+    };
+
+    tx.commit().await?;
+
+    response(subscription)
+}
@@ -0,0 +1,641 @@
+use chrono::{DateTime, Datelike, Utc};
+use rust_decimal::Decimal;
+use sha2::{Digest, Sha256};
+use shared::errors::{AppError, AppResult};
+use sqlx::{PgConnection, PgPool};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::{
+    credit_notes,
+    models::{Invoice, InvoiceLineItem, InvoiceResponse, LineItemInput},
+    money,
+    pdf::invoice::{self as document, InvoiceDocument, Party},
+    plans::Plan,
+};
+
+pub const INVOICE_COLUMNS: &str = "i.id, i.user_id, i.subscription_id, i.number,
+    (i.amount * 100)::bigint AS amount_cents, i.tax_rate_bps,
+    (i.tax_amount * 100)::bigint AS tax_cents,
+    (i.credit_applied * 100)::bigint AS credit_applied_cents, i.currency, i.status::text AS status,
+    i.issued_at, i.due_date, i.paid_at, i.period_start, i.period_end, i.created_at, i.updated_at";
+
+const LINE_ITEM_COLUMNS: &str = "id, invoice_id, position, description, quantity,
+    (unit_amount * 100)::bigint AS unit_amount_cents, (amount * 100)::bigint AS amount_cents";
+
+pub const INVOICE_STATUSES: [&str; 4] = ["draft", "issued", "paid", "overdue"];
+
+/// Days from issuing an invoice to its due date.
+pub const PAYMENT_TERMS_DAYS: i32 = 14;
+
+/// Largest amount a `DECIMAL(10, 2)` column holds, in cents.
+const MAX_AMOUNT_CENTS: i64 = 9_999_999_999;
+
+const MAX_LINE_ITEMS: usize = 100;
+
+/// Who invoices are from and the tax added to them.
+#[derive(Debug, Clone)]
+pub struct Invoicing {
+    pub seller: Party,
+    /// Added to every new invoice, in basis points.
+    pub tax_rate_bps: i32,
+}
+
+/// Parses a percentage such as `20` or `8.25` into basis points.
+pub fn parse_tax_rate(rate: &str) -> AppResult<i32> {
+    let invalid = || {
+        AppError::ValidationError(format!(
+            "Invalid tax rate '{}', expected a percentage between 0 and 100 with at most two decimals",
+            rate
+        ))
+    };
+    let rate = rate.trim().trim_end_matches('%');
+    let (whole, fraction) = rate.split_once('.').unwrap_or((rate, ""));
+    if whole.is_empty()
+        || fraction.len() > 2
+        || !whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+    let whole: i32 = whole.parse().map_err(|_| invalid())?;
+    let fraction: i32 = format!("{:0<2}", fraction).parse().map_err(|_| invalid())?;
+    let bps = whole
+        .checked_mul(100)
+        .and_then(|bps| bps.checked_add(fraction))
+        .filter(|bps| *bps <= 10_000)
+        .ok_or_else(invalid)?;
+    Ok(bps)
+}
+
+/// Allowed status changes. Issuing assigns the invoice number; `overdue` is
+/// normally set by the scheduled job.
+pub fn can_transition(from: &str, to: &str) -> bool {
+    matches!(
+        (from, to),
+        ("draft", "issued") | ("issued", "paid") | ("issued", "overdue") | ("overdue", "paid")
+    )
+}
+
+pub fn validate_status(status: &str) -> AppResult<()> {
+    if !INVOICE_STATUSES.contains(&status) {
+        return Err(AppError::ValidationError(format!(
+            "Invalid status '{}', expected one of: {}",
+            status,
+            INVOICE_STATUSES.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+pub fn format_number(year: i32, sequence: i32) -> String {
+    format!("INV-{}-{:06}", year, sequence)
+}
+
+/// Takes the next number of `year`. The sequence row stays locked until the
+/// transaction ends, and a rollback gives the number back, so issued numbers
+/// have no gaps.
+async fn next_number(conn: &mut PgConnection, year: i32) -> AppResult<String> {
+    let sequence: i32 = sqlx::query_scalar(
+        "INSERT INTO invoice_sequences (year, last_number) VALUES ($1, 1)
+         ON CONFLICT (year) DO UPDATE SET last_number = invoice_sequences.last_number + 1
+         RETURNING last_number",
+    )
+    .bind(year)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(format_number(year, sequence))
+}
+
+fn validate_line_items(items: &[LineItemInput]) -> AppResult<i64> {
+    if items.is_empty() || items.len() > MAX_LINE_ITEMS {
+        return Err(AppError::ValidationError(format!(
+            "An invoice needs between 1 and {} line items",
+            MAX_LINE_ITEMS
+        )));
+    }
+    let mut total: i64 = 0;
+    for item in items {
+        let description = item.description.trim();
+        if description.is_empty() || description.chars().count() > 500 {
+            return Err(AppError::ValidationError(
+                "Line item description must be between 1 and 500 characters".to_string(),
+            ));
+        }
+        if !(1..=10_000).contains(&item.quantity) {
+            return Err(AppError::ValidationError(
+                "Line item quantity must be between 1 and 10000".to_string(),
+            ));
+        }
+        let amount = item
+            .unit_amount_cents
+            .checked_mul(i64::from(item.quantity))
+            .filter(|amount| item.unit_amount_cents >= 0 && *amount <= MAX_AMOUNT_CENTS);
+        total = amount
+            .and_then(|amount| total.checked_add(amount))
+            .filter(|total| *total <= MAX_AMOUNT_CENTS)
+            .ok_or_else(|| {
+                AppError::ValidationError("Line item amounts are out of range".to_string())
+            })?;
+    }
+    Ok(total)
+}
+
+pub fn validate_currency(currency: &str) -> AppResult<String> {
+    let currency = currency.trim().to_uppercase();
+    if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(AppError::ValidationError(
+            "Currency must be a three-letter ISO 4217 code".to_string(),
+        ));
+    }
+    Ok(currency)
+}
+
+/// Creates a draft invoice with its line items, adding tax at
+/// `tax_rate_bps` on top of them.
+pub async fn create_draft(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    subscription_id: Option<Uuid>,
+    currency: &str,
+    items: &[LineItemInput],
+    tax_rate_bps: i32,
+    period: Option<(DateTime<Utc>, DateTime<Utc>)>,
+) -> AppResult<Uuid> {
+    let subtotal = validate_line_items(items)?;
+    let tax = money::to_cents(money::tax(money::from_cents(subtotal), tax_rate_bps))?;
+    if subtotal + tax > MAX_AMOUNT_CENTS {
+        return Err(AppError::ValidationError(
+            "Line item amounts are out of range".to_string(),
+        ));
+    }
+
+    let invoice_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO invoices (user_id, subscription_id, amount, tax_rate_bps, tax_amount,
+                               currency, status, period_start, period_end)
+         VALUES ($1, $2, $3::numeric / 100, $4, $5::numeric / 100, $6, 'draft', $7, $8)
+         RETURNING id",
+    )
+    .bind(user_id)
+    .bind(subscription_id)
+    .bind(subtotal + tax)
+    .bind(tax_rate_bps)
+    .bind(tax)
+    .bind(currency)
+    .bind(period.map(|(start, _)| start))
+    .bind(period.map(|(_, end)| end))
+    .fetch_one(&mut *conn)
+    .await?;
+
+    for (position, item) in items.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO invoice_line_items
+                 (invoice_id, position, description, quantity, unit_amount, amount)
+             VALUES ($1, $2, $3, $4, $5::numeric / 100, $6::numeric / 100)",
+        )
+        .bind(invoice_id)
+        .bind(position as i32)
+        .bind(item.description.trim())
+        .bind(item.quantity)
+        .bind(item.unit_amount_cents)
+        .bind(item.unit_amount_cents * i64::from(item.quantity))
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(invoice_id)
+}
+
+async fn lock_status(conn: &mut PgConnection, invoice_id: Uuid) -> AppResult<String> {
+    sqlx::query_scalar("SELECT status::text FROM invoices WHERE id = $1 FOR UPDATE")
+        .bind(invoice_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Invoice not found".to_string()))
+}
+
+/// Spends the user's account credit in the invoice's currency on it. An
+/// invoice the credit covers in full is paid there and then.
+async fn apply_account_credit(conn: &mut PgConnection, invoice: Invoice) -> AppResult<Invoice> {
+    credit_notes::lock(conn, invoice.user_id).await?;
+    let balance = credit_notes::balance(conn, invoice.user_id, &invoice.currency).await?;
+    let applied = money::apply_credit(balance, money::from_cents(invoice.amount_cents));
+    if applied <= Decimal::ZERO {
+        return Ok(invoice);
+    }
+    let applied = money::to_cents(applied)?;
+
+    let invoice = sqlx::query_as::<_, Invoice>(&format!(
+        "UPDATE invoices i
+         SET credit_applied = $2::numeric / 100,
+             status = CASE WHEN $3 THEN 'paid'::invoice_status ELSE i.status END,
+             paid_at = CASE WHEN $3 THEN NOW() ELSE i.paid_at END,
+             updated_at = NOW()
+         WHERE i.id = $1
+         RETURNING {}",
+        INVOICE_COLUMNS
+    ))
+    .bind(invoice.id)
+    .bind(applied)
+    .bind(applied == invoice.amount_cents)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(invoice)
+}
+
+/// Moves an invoice to `to` if the transition is allowed. Issuing assigns the
+/// next number, sets the due date and spends account credit on it; paying
+/// records when.
+pub async fn transition(conn: &mut PgConnection, invoice_id: Uuid, to: &str) -> AppResult<Invoice> {
+    validate_status(to)?;
+    let from = lock_status(conn, invoice_id).await?;
+    if !can_transition(&from, to) {
+        return Err(AppError::Conflict(format!(
+            "Cannot change an invoice from {} to {}",
+            from, to
+        )));
+    }
+
+    let number = if to == "issued" {
+        Some(next_number(conn, Utc::now().year()).await?)
+    } else {
+        None
+    };
+
+    let invoice = sqlx::query_as::<_, Invoice>(&format!(
+        "UPDATE invoices i
+         SET status = $2::invoice_status,
+             number = COALESCE($3, i.number),
+             issued_at = CASE WHEN $2 = 'issued' THEN NOW() ELSE i.issued_at END,
+             due_date = CASE WHEN $2 = 'issued' THEN NOW() + make_interval(days => $4)
+                             ELSE i.due_date END,
+             paid_at = CASE WHEN $2 = 'paid' THEN NOW() ELSE i.paid_at END,
+             updated_at = NOW()
+         WHERE i.id = $1
+         RETURNING {}",
+        INVOICE_COLUMNS
+    ))
+    .bind(invoice_id)
+    .bind(to)
+    .bind(number)
+    .bind(PAYMENT_TERMS_DAYS)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if to == "issued" {
+        return apply_account_credit(conn, invoice).await;
+    }
+    Ok(invoice)
+}
+
+/// Issues the invoice for one period of a paid subscription, unless it
+/// already exists. Free plans are not invoiced.
+pub async fn invoice_period(
+    conn: &mut PgConnection,
+    subscription_id: Uuid,
+    user_id: Uuid,
+    plan: &Plan,
+    tax_rate_bps: i32,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> AppResult<Option<Invoice>> {
+    if plan.price_cents == 0 {
+        return Ok(None);
+    }
+
+    let existing: Option<Uuid> = sqlx::query_scalar(
+        "SELECT id FROM invoices WHERE subscription_id = $1 AND period_start = $2",
+    )
+    .bind(subscription_id)
+    .bind(start)
+    .fetch_optional(&mut *conn)
+    .await?;
+    if existing.is_some() {
+        return Ok(None);
+    }
+
+    let items = [LineItemInput {
+        description: format!(
+            "{} plan, {} to {}",
+            plan.name,
+            start.format("%Y-%m-%d"),
+            end.format("%Y-%m-%d")
+        ),
+        quantity: 1,
+        unit_amount_cents: plan.price_cents,
+    }];
+    let invoice_id = create_draft(
+        conn,
+        user_id,
+        Some(subscription_id),
+        plan.currency,
+        &items,
+        tax_rate_bps,
+        Some((start, end)),
+    )
+    .await?;
+
+    transition(conn, invoice_id, "issued").await.map(Some)
+}
+
+pub async fn load(db: &PgPool, invoice_id: Uuid) -> AppResult<InvoiceResponse> {
+    let invoice = sqlx::query_as::<_, Invoice>(&format!(
+        "SELECT {} FROM invoices i WHERE i.id = $1",
+        INVOICE_COLUMNS
+    ))
+    .bind(invoice_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Invoice not found".to_string()))?;
+
+    let line_items = sqlx::query_as::<_, InvoiceLineItem>(&format!(
+        "SELECT {} FROM invoice_line_items WHERE invoice_id = $1 ORDER BY position",
+        LINE_ITEM_COLUMNS
+    ))
+    .bind(invoice_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(InvoiceResponse {
+        invoice,
+        line_items,
+    })
+}
+
+/// The invoice as printed: seller, the user it is billed to, and amounts.
+async fn document(
+    db: &PgPool,
+    invoicing: &Invoicing,
+    invoice: &InvoiceResponse,
+) -> AppResult<InvoiceDocument> {
+    let number = invoice
+        .invoice
+        .number
+        .clone()
+        .ok_or_else(|| AppError::Conflict("Draft invoices have no PDF".to_string()))?;
+    let (name, email): (String, String) =
+        sqlx::query_as("SELECT name, email FROM users WHERE id = $1")
+            .bind(invoice.invoice.user_id)
+            .fetch_optional(db)
+            .await?
+            .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    let lines: Vec<document::Line> = invoice
+        .line_items
+        .iter()
+        .map(|item| document::Line {
+            description: item.description.clone(),
+            quantity: item.quantity,
+            unit_amount_cents: item.unit_amount_cents,
+            amount_cents: item.amount_cents,
+        })
+        .collect();
+    let invoice = &invoice.invoice;
+
+    Ok(InvoiceDocument {
+        number,
+        issued_on: invoice.issued_at.date_naive(),
+        due_on: invoice.due_date.map(|due| due.date_naive()),
+        period: invoice
+            .period_start
+            .zip(invoice.period_end)
+            .map(|(start, end)| (start.date_naive(), end.date_naive())),
+        currency: invoice.currency.clone(),
+        seller: invoicing.seller.clone(),
+        buyer: Party {
+            name,
+            lines: vec![email],
+        },
+        subtotal_cents: invoice.amount_cents - invoice.tax_cents,
+        tax_rate_bps: invoice.tax_rate_bps,
+        tax_cents: invoice.tax_cents,
+        total_cents: invoice.amount_cents,
+        credit_applied_cents: invoice.credit_applied_cents,
+        lines,
+    })
+}
+
+/// An invoice's PDF and the SHA-256 of its bytes.
+pub struct StoredPdf {
+    pub content: Vec<u8>,
+    pub checksum_sha256: String,
+}
+
+async fn stored_pdf(db: &PgPool, invoice_id: Uuid) -> AppResult<Option<StoredPdf>> {
+    let row: Option<(Vec<u8>, String)> = sqlx::query_as(
+        "SELECT content, checksum_sha256 FROM invoice_documents WHERE invoice_id = $1",
+    )
+    .bind(invoice_id)
+    .fetch_optional(db)
+    .await?;
+    Ok(row.map(|(content, checksum_sha256)| StoredPdf {
+        content,
+        checksum_sha256,
+    }))
+}
+
+/// The PDF of an issued invoice. It is rendered the first time it is asked
+/// for and stored; from then on the stored file is served, so the document
+/// stays the same even if the seller's or user's details change.
+pub async fn pdf(
+    db: &PgPool,
+    invoicing: &Invoicing,
+    invoice: &InvoiceResponse,
+) -> AppResult<StoredPdf> {
+    let invoice_id = invoice.invoice.id;
+    if let Some(stored) = stored_pdf(db, invoice_id).await? {
+        return Ok(stored);
+    }
+
+    let content = document::render(&document(db, invoicing, invoice).await?);
+    let checksum = hex::encode(Sha256::digest(&content));
+    sqlx::query(
+        "INSERT INTO invoice_documents (invoice_id, content, checksum_sha256)
+         VALUES ($1, $2, $3)
+         ON CONFLICT (invoice_id) DO NOTHING",
+    )
+    .bind(invoice_id)
+    .bind(&content)
+    .bind(&checksum)
+    .execute(db)
+    .await?;
+
+    // Another request may have stored its copy first; that one is kept.
+    stored_pdf(db, invoice_id)
+        .await?
+        .ok_or_else(|| AppError::InternalError("Invoice PDF was not stored".to_string()))
+}
+
+/// Marks issued invoices past their due date as overdue.
+pub async fn mark_overdue(db: &PgPool) -> AppResult<u64> {
+    let rows = sqlx::query(
+        "UPDATE invoices SET status = 'overdue', updated_at = NOW()
+         WHERE status = 'issued' AND due_date < NOW()",
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+    Ok(rows)
+}
+
+/// Runs `mark_overdue` every `every` in the background.
+pub fn spawn_overdue_marker(db: PgPool, every: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(every);
+        loop {
+            ticker.tick().await;
+            match mark_overdue(&db).await {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("Marked {} invoice(s) as overdue", n),
+                Err(e) => tracing::warn!("Marking overdue invoices failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(description: &str, quantity: i32, unit_amount_cents: i64) -> LineItemInput {
+        LineItemInput {
+            description: description.to_string(),
+            quantity,
+            unit_amount_cents,
+        }
+    }
+
+    #[test]
+    fn parses_tax_rates_into_basis_points() {
+        let cases = [
+            ("0", 0),
+            ("20", 2_000),
+            ("8.25", 825),
+            ("8.5", 850),
+            ("0.01", 1),
+            ("5.", 500),
+            ("020", 2_000),
+            (" 7.5% ", 750),
+            ("100", 10_000),
+            ("100.00", 10_000),
+        ];
+        for (rate, bps) in cases {
+            assert_eq!(parse_tax_rate(rate).unwrap(), bps, "{:?}", rate);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_tax_rates() {
+        for rate in [
+            "",
+            "%",
+            ".5",
+            "-1",
+            "+5",
+            "100.01",
+            "101",
+            "8.255",
+            "1e2",
+            "abc",
+            "1.2.3",
+            "7 %",
+            "99999999999",
+        ] {
+            assert!(
+                matches!(parse_tax_rate(rate), Err(AppError::ValidationError(_))),
+                "{:?}",
+                rate
+            );
+        }
+    }
+
+    #[test]
+    fn allows_only_forward_status_changes() {
+        let allowed = [
+            ("draft", "issued"),
+            ("issued", "paid"),
+            ("issued", "overdue"),
+            ("overdue", "paid"),
+        ];
+        for from in INVOICE_STATUSES {
+            for to in INVOICE_STATUSES {
+                assert_eq!(
+                    can_transition(from, to),
+                    allowed.contains(&(from, to)),
+                    "{} -> {}",
+                    from,
+                    to
+                );
+            }
+        }
+        assert!(!can_transition("draft", "void"));
+        assert!(!can_transition("Draft", "issued"));
+    }
+
+    #[test]
+    fn pads_invoice_numbers() {
+        assert_eq!(format_number(2024, 1), "INV-2024-000001");
+        assert_eq!(format_number(2024, 42), "INV-2024-000042");
+        assert_eq!(format_number(2025, 999_999), "INV-2025-999999");
+        assert_eq!(format_number(2025, 1_000_000), "INV-2025-1000000");
+    }
+
+    #[test]
+    fn totals_valid_line_items() {
+        assert_eq!(
+            validate_line_items(&[item("Pro plan", 1, 2_900)]).unwrap(),
+            2_900
+        );
+        assert_eq!(
+            validate_line_items(&[item("Seats", 3, 500), item("Setup", 1, 0)]).unwrap(),
+            1_500
+        );
+        assert_eq!(
+            validate_line_items(&[item("Everything", 1, MAX_AMOUNT_CENTS)]).unwrap(),
+            MAX_AMOUNT_CENTS
+        );
+        assert_eq!(
+            validate_line_items(&[item("Bulk", 10_000, 1)]).unwrap(),
+            10_000
+        );
+        assert!(validate_line_items(&[item(&"é".repeat(500), 1, 1)]).is_ok());
+        assert!(validate_line_items(&vec![item("Line", 1, 1); MAX_LINE_ITEMS]).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_line_items() {
+        let too_many = vec![item("Line", 1, 1); MAX_LINE_ITEMS + 1];
+        let long = "a".repeat(501);
+        let cases: [(&str, &[LineItemInput]); 11] = [
+            ("no items", &[]),
+            ("too many items", &too_many),
+            ("blank description", &[item("  ", 1, 100)]),
+            ("long description", &[item(&long, 1, 100)]),
+            ("zero quantity", &[item("Seats", 0, 100)]),
+            ("negative quantity", &[item("Seats", -1, 100)]),
+            ("huge quantity", &[item("Seats", 10_001, 100)]),
+            ("negative amount", &[item("Refund", 1, -100)]),
+            ("multiplication overflow", &[item("Seats", 2, i64::MAX)]),
+            (
+                "item over the maximum",
+                &[item("Seats", 1, MAX_AMOUNT_CENTS + 1)],
+            ),
+            (
+                "total over the maximum",
+                &[item("First", 1, MAX_AMOUNT_CENTS), item("Second", 1, 1)],
+            ),
+        ];
+        for (name, items) in cases {
+            assert!(
+                matches!(
+                    validate_line_items(items),
+                    Err(AppError::ValidationError(_))
+                ),
+                "{}",
+                name
+            );
+        }
+    }
+}
@@ -1,5 +1,32 @@
+// Billing service
+pub mod access;
+pub mod credit_notes;
 pub mod handlers;
+pub mod invoices;
+pub mod middleware;
 pub mod models;
+pub mod money;
+pub mod payments;
+pub mod pdf;
+pub mod plans;
+pub mod proration;
+pub mod subscriptions;
+
+use invoices::Invoicing;
+use payments::PaymentProvider;
+use shared::auth::AuthService;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub db: PgPool,
+    pub auth: Arc<AuthService>,
+    pub invoicing: Arc<Invoicing>,
+    pub payments: Arc<dyn PaymentProvider>,
+    /// Shared with the services that read plan limits.
+    pub service_secret: String,
+}
 
 pub fn init() {
     tracing::info!("Billing service initialized");
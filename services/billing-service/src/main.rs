@@ -1,12 +1,20 @@
-use axum::Router;
+use axum::{
+    middleware::from_fn_with_state,
+    routing::{delete, get, patch, post, put},
+    Json, Router,
+};
+use billing_service::{
+    handlers::{credit_note, invoice, payment, plan, subscription},
+    invoices::{parse_tax_rate, spawn_overdue_marker, Invoicing},
+    middleware::auth_middleware,
+    payments,
+    pdf::invoice::Party,
+    subscriptions::spawn_renewer,
+    AppState,
+};
 use serde_json::json;
-use shared::database::init_pool;
-use sqlx::PgPool;
-
-#[derive(Clone)]
-pub struct AppState {
-    pub db: PgPool,
-}
+use shared::{auth::AuthService, database::init_pool};
+use std::{sync::Arc, time::Duration};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -15,23 +23,104 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv::dotenv().ok();
     let database_url = std::env::var("DATABASE_URL")
         .expect("DATABASE_URL must be set");
+    let jwt_secret = std::env::var("JWT_SECRET")
+        .unwrap_or_else(|_| "super-secret-key-must-be-32-chars-long-!!".to_string());
+    let jwt_expiration: i64 = std::env::var("JWT_EXPIRATION")
+        .unwrap_or_else(|_| "3600".to_string())
+        .parse()
+        .unwrap_or(3600);
+    let service_secret = std::env::var("SERVICE_SECRET")
+        .ok()
+        .filter(|secret| !secret.is_empty())
+        .expect("SERVICE_SECRET must be set");
+    let subscription_poll_secs: u64 = std::env::var("SUBSCRIPTION_POLL_SECS")
+        .unwrap_or_else(|_| "300".to_string())
+        .parse()
+        .unwrap_or(300);
+    let invoice_overdue_poll_secs: u64 = std::env::var("INVOICE_OVERDUE_POLL_SECS")
+        .unwrap_or_else(|_| "3600".to_string())
+        .parse()
+        .unwrap_or(3600);
+    let tax_rate_bps = parse_tax_rate(
+        &std::env::var("INVOICE_TAX_RATE").unwrap_or_else(|_| "0".to_string()),
+    )
+    .expect("INVOICE_TAX_RATE must be a percentage between 0 and 100");
+    let seller = Party {
+        name: std::env::var("SELLER_NAME").unwrap_or_else(|_| "Mini SaaS".to_string()),
+        lines: std::env::var("SELLER_ADDRESS")
+            .unwrap_or_default()
+            .split('|')
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .chain(std::env::var("SELLER_EMAIL").ok().filter(|email| !email.is_empty()))
+            .chain(
+                std::env::var("SELLER_TAX_ID")
+                    .ok()
+                    .filter(|tax_id| !tax_id.is_empty())
+                    .map(|tax_id| format!("Tax ID: {}", tax_id)),
+            )
+            .collect(),
+    };
 
     let db = init_pool(&database_url, 5)
         .await
         .expect("Failed to initialize database pool");
 
-    let state = AppState { db };
+    spawn_renewer(
+        db.clone(),
+        Duration::from_secs(subscription_poll_secs.max(1)),
+        tax_rate_bps,
+    );
+    spawn_overdue_marker(db.clone(), Duration::from_secs(invoice_overdue_poll_secs.max(1)));
+
+    let auth = Arc::new(AuthService::new(jwt_secret, jwt_expiration));
+    let invoicing = Arc::new(Invoicing {
+        seller,
+        tax_rate_bps,
+    });
+    let state = AppState {
+        db,
+        auth,
+        invoicing,
+        payments: payments::from_env(),
+        service_secret,
+    };
+
+    let api = Router::new()
+        .route("/subscription", get(subscription::get_subscription))
+        .route("/subscription", patch(subscription::update_subscription))
+        .route("/subscription/plan", put(subscription::change_plan))
+        .route("/subscription/cancel", post(subscription::cancel_subscription))
+        .route("/invoices", get(invoice::list_invoices))
+        .route("/invoices/:id", get(invoice::get_invoice))
+        .route("/invoices/:id/pdf", get(invoice::download_invoice_pdf))
+        .route("/invoices/:id/checkout", post(payment::create_checkout))
+        .route("/credit-notes", get(credit_note::list_credit_notes))
+        .route("/credit", get(credit_note::get_credit_balance))
+        .route("/admin/invoices", get(invoice::admin_list_invoices))
+        .route("/admin/invoices", post(invoice::create_invoice))
+        .route("/admin/invoices/:id", delete(invoice::delete_invoice))
+        .route("/admin/invoices/:id/status", post(invoice::update_invoice_status))
+        .route_layer(from_fn_with_state(state.clone(), auth_middleware));
 
     let router = Router::new()
         .route(
             "/health",
-            axum::routing::get(|| async {
-                axum::Json(json!({
+            get(|| async {
+                Json(json!({
                     "status": "healthy",
                     "service": "billing-service"
                 }))
             }),
         )
+        .route("/plans", get(plan::list_plans))
+        .route(
+            "/internal/users/:id/limits",
+            get(subscription::get_user_limits),
+        )
+        .route("/webhooks/payments", post(payment::payment_webhook))
+        .merge(api)
         .with_state(state);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3003")
@@ -1 +1,208 @@
 // Billing service models
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use shared::models::PaginationParams;
+
+use crate::plans::Plan;
+
+// ============= SUBSCRIPTION =============
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Subscription {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub plan: String,
+    /// `active`, `cancelled` or `expired`; ended subscriptions fall back to
+    /// the free plan.
+    pub status: String,
+    /// Start of the current billing period.
+    pub started_at: DateTime<Utc>,
+    /// End of the current billing period; `None` on the free plan.
+    pub expires_at: Option<DateTime<Utc>>,
+    pub auto_renew: bool,
+    pub cancel_at_period_end: bool,
+    pub cancelled_at: Option<DateTime<Utc>>,
+    pub max_projects: Option<i32>,
+    pub max_tasks: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubscriptionResponse {
+    #[serde(flatten)]
+    pub subscription: Subscription,
+    pub plan_details: Plan,
+}
+
+/// What other services need to enforce a user's plan.
+#[derive(Debug, Serialize)]
+pub struct PlanLimits {
+    pub user_id: Uuid,
+    pub plan: String,
+    pub status: String,
+    pub max_projects: Option<i32>,
+    pub max_tasks: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangePlanRequest {
+    pub plan: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CancelSubscriptionRequest {
+    /// Keep the plan until the end of the paid period (the default) instead
+    /// of dropping to the free plan right away.
+    #[serde(default = "default_true")]
+    pub at_period_end: bool,
+}
+
+impl Default for CancelSubscriptionRequest {
+    fn default() -> Self {
+        Self {
+            at_period_end: true,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSubscriptionRequest {
+    /// Turning renewal back on also undoes a cancellation at period end.
+    pub auto_renew: Option<bool>,
+}
+
+// ============= INVOICE =============
+
+/// Amounts are in cents of `currency`.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Invoice {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub subscription_id: Option<Uuid>,
+    /// `INV-<year>-<sequence>`, assigned when the invoice is issued.
+    pub number: Option<String>,
+    /// Total to pay, tax included.
+    pub amount_cents: i64,
+    /// Tax rate in basis points, e.g. 2000 for 20%.
+    pub tax_rate_bps: i32,
+    pub tax_cents: i64,
+    /// Account credit and credit notes applied to the invoice; the rest of
+    /// the amount is due.
+    pub credit_applied_cents: i64,
+    pub currency: String,
+    /// `draft`, `issued`, `paid` or `overdue`.
+    pub status: String,
+    pub issued_at: DateTime<Utc>,
+    pub due_date: Option<DateTime<Utc>>,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub period_start: Option<DateTime<Utc>>,
+    pub period_end: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct InvoiceLineItem {
+    pub id: Uuid,
+    pub invoice_id: Uuid,
+    pub position: i32,
+    pub description: String,
+    pub quantity: i32,
+    pub unit_amount_cents: i64,
+    pub amount_cents: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InvoiceResponse {
+    #[serde(flatten)]
+    pub invoice: Invoice,
+    pub line_items: Vec<InvoiceLineItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InvoiceListParams {
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+    pub status: Option<String>,
+    /// Admins only; other users always see their own invoices.
+    pub user_id: Option<Uuid>,
+}
+
+impl InvoiceListParams {
+    pub fn pagination(&self) -> PaginationParams {
+        PaginationParams {
+            page: self.page,
+            limit: self.limit,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LineItemInput {
+    pub description: String,
+    #[serde(default = "default_quantity")]
+    pub quantity: i32,
+    pub unit_amount_cents: i64,
+}
+
+fn default_quantity() -> i32 {
+    1
+}
+
+/// A draft invoice, e.g. for a one-off charge.
+#[derive(Debug, Deserialize)]
+pub struct CreateInvoiceRequest {
+    pub user_id: Uuid,
+    pub currency: Option<String>,
+    pub line_items: Vec<LineItemInput>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateInvoiceStatusRequest {
+    pub status: String,
+}
+
+// ============= CREDIT NOTE =============
+
+/// Credit given back against an invoice, tax included, in cents of
+/// `currency`.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CreditNote {
+    pub id: Uuid,
+    /// `CN-<year>-<sequence>`.
+    pub number: String,
+    pub invoice_id: Uuid,
+    pub user_id: Uuid,
+    pub amount_cents: i64,
+    pub tax_cents: i64,
+    pub currency: String,
+    pub reason: String,
+    /// Applied to its unpaid invoice rather than added to account credit.
+    pub applied: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Unspent account credit in one currency.
+#[derive(Debug, Serialize, FromRow)]
+pub struct CreditBalance {
+    pub currency: String,
+    pub balance_cents: i64,
+}
+
+// ============= PAYMENT =============
+
+/// A hosted checkout to send the user to.
+#[derive(Debug, Serialize)]
+pub struct CheckoutResponse {
+    pub provider: String,
+    pub session_id: String,
+    pub url: String,
+}
@@ -0,0 +1,255 @@
+//! Money arithmetic on exact decimals.
+//!
+//! Amounts are stored as `DECIMAL(10, 2)` and passed around as cents. Any
+//! calculation that can produce fractions of a cent, such as tax or
+//! proration, is done on `Decimal` and rounded once to the cent, half away
+//! from zero, the way Postgres rounds `numeric`.
+
+use rust_decimal::{prelude::ToPrimitive, Decimal, RoundingStrategy};
+use shared::errors::{AppError, AppResult};
+
+/// Largest amount a `DECIMAL(10, 2)` column holds, 99,999,999.99.
+pub const MAX_AMOUNT: Decimal = Decimal::from_parts(1_410_065_407, 2, 0, false, 2);
+
+const BASIS_POINTS: i64 = 10_000;
+
+pub fn from_cents(cents: i64) -> Decimal {
+    Decimal::new(cents, 2)
+}
+
+/// Rounds to the cent, half away from zero, and keeps two decimal places
+/// like a `DECIMAL(10, 2)` column does.
+pub fn round(amount: Decimal) -> Decimal {
+    let mut rounded = amount.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero);
+    rounded.rescale(2);
+    rounded
+}
+
+/// `amount` rounded to the cent, in cents. Fails if a `DECIMAL(10, 2)`
+/// column cannot hold it.
+pub fn to_cents(amount: Decimal) -> AppResult<i64> {
+    let rounded = round(amount);
+    if rounded.abs() > MAX_AMOUNT {
+        return Err(AppError::ValidationError(format!(
+            "Amount {} is out of range",
+            rounded
+        )));
+    }
+    (rounded * Decimal::ONE_HUNDRED)
+        .to_i64()
+        .ok_or_else(|| AppError::ValidationError(format!("Amount {} is out of range", rounded)))
+}
+
+/// A rate in basis points as a fraction, e.g. 825 as 0.0825.
+pub fn rate(bps: i32) -> Decimal {
+    Decimal::new(i64::from(bps), 4)
+}
+
+/// Tax on a net amount at `rate_bps`, rounded to the cent.
+pub fn tax(net: Decimal, rate_bps: i32) -> Decimal {
+    round(net * rate(rate_bps))
+}
+
+/// Splits an amount that includes tax at `rate_bps` into its net amount and
+/// tax, which add up to `gross` exactly.
+pub fn split_gross(gross: Decimal, rate_bps: i32) -> (Decimal, Decimal) {
+    let net = round(
+        gross * Decimal::from(BASIS_POINTS) / Decimal::from(BASIS_POINTS + i64::from(rate_bps)),
+    );
+    (net, gross - net)
+}
+
+/// The share of `price` for `used` out of `total` seconds, rounded to the
+/// cent. `used` is clamped to the period, and an empty period is worth
+/// nothing.
+pub fn prorate(price: Decimal, used: i64, total: i64) -> Decimal {
+    if total <= 0 {
+        return Decimal::ZERO;
+    }
+    let used = used.clamp(0, total);
+    // Multiplying first keeps the division to a single rounding.
+    round(price * Decimal::from(used) / Decimal::from(total))
+}
+
+/// How much of `balance` goes towards `due`: all of it if `due` is larger,
+/// never more than `due`, and nothing from a balance that is not positive.
+pub fn apply_credit(balance: Decimal, due: Decimal) -> Decimal {
+    balance.min(due).max(Decimal::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn d(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn max_amount_is_the_largest_decimal_10_2() {
+        assert_eq!(MAX_AMOUNT, d("99999999.99"));
+        assert_eq!(MAX_AMOUNT.scale(), 2);
+    }
+
+    #[test]
+    fn converts_cents_exactly() {
+        assert_eq!(from_cents(0), d("0.00"));
+        assert_eq!(from_cents(1), d("0.01"));
+        assert_eq!(from_cents(2_900), d("29.00"));
+        assert_eq!(from_cents(-1_050), d("-10.50"));
+        assert_eq!(from_cents(9_999_999_999), MAX_AMOUNT);
+
+        for cents in [
+            0,
+            1,
+            9,
+            10,
+            99,
+            100,
+            2_900,
+            123_456,
+            -5,
+            -9_999_999_999,
+            9_999_999_999,
+        ] {
+            assert_eq!(to_cents(from_cents(cents)).unwrap(), cents);
+        }
+    }
+
+    #[test]
+    fn rounds_half_away_from_zero() {
+        assert_eq!(round(d("1.004")), d("1.00"));
+        assert_eq!(round(d("1.005")), d("1.01"));
+        assert_eq!(round(d("1.0049999")), d("1.00"));
+        assert_eq!(round(d("2.675")), d("2.68"));
+        assert_eq!(round(d("-1.005")), d("-1.01"));
+        assert_eq!(round(d("-1.004")), d("-1.00"));
+        assert_eq!(round(d("0.005")), d("0.01"));
+        assert_eq!(round(d("0.0049")), d("0.00"));
+        assert_eq!(round(d("7")), d("7"));
+        assert_eq!(round(d("7")).scale(), 2);
+        assert_eq!(round(d("0.0001")).scale(), 2);
+    }
+
+    #[test]
+    fn rounds_when_converting_to_cents() {
+        assert_eq!(to_cents(d("0.005")).unwrap(), 1);
+        assert_eq!(to_cents(d("0.0049")).unwrap(), 0);
+        assert_eq!(to_cents(d("-0.005")).unwrap(), -1);
+        assert_eq!(to_cents(d("12.345")).unwrap(), 1_235);
+        assert_eq!(to_cents(d("99999999.994")).unwrap(), 9_999_999_999);
+    }
+
+    #[test]
+    fn refuses_amounts_a_decimal_10_2_cannot_hold() {
+        assert!(to_cents(d("99999999.995")).is_err());
+        assert!(to_cents(d("100000000")).is_err());
+        assert!(to_cents(d("-100000000")).is_err());
+        assert!(to_cents(Decimal::MAX).is_err());
+    }
+
+    #[test]
+    fn converts_rates() {
+        assert_eq!(rate(0), d("0"));
+        assert_eq!(rate(1), d("0.0001"));
+        assert_eq!(rate(825), d("0.0825"));
+        assert_eq!(rate(2_000), d("0.2"));
+        assert_eq!(rate(10_000), d("1"));
+    }
+
+    #[test]
+    fn computes_tax() {
+        assert_eq!(tax(d("0"), 2_000), d("0"));
+        assert_eq!(tax(d("100.00"), 0), d("0"));
+        assert_eq!(tax(d("100.00"), 2_000), d("20.00"));
+        assert_eq!(tax(d("29.00"), 825), d("2.39")); // 2.3925
+        assert_eq!(tax(d("174.00"), 825), d("14.36")); // 14.355
+        assert_eq!(tax(d("0.06"), 825), d("0.00")); // 0.00495
+        assert_eq!(tax(d("0.07"), 825), d("0.01")); // 0.005775
+        assert_eq!(tax(d("-174.00"), 825), d("-14.36"));
+        assert_eq!(tax(d("1.00"), 10_000), d("1.00"));
+        assert_eq!(tax(MAX_AMOUNT, 10_000), MAX_AMOUNT);
+    }
+
+    #[test]
+    fn splits_gross_amounts_exactly() {
+        for (gross, bps, net, tax) in [
+            ("120.00", 2_000, "100.00", "20.00"),
+            ("0.00", 2_000, "0.00", "0.00"),
+            ("29.00", 0, "29.00", "0.00"),
+            ("10.00", 825, "9.24", "0.76"), // 9.2378...
+            ("0.01", 2_000, "0.01", "0.00"),
+            ("-120.00", 2_000, "-100.00", "-20.00"),
+            ("2.00", 10_000, "1.00", "1.00"),
+        ] {
+            assert_eq!(
+                split_gross(d(gross), bps),
+                (d(net), d(tax)),
+                "{} at {}",
+                gross,
+                bps
+            );
+        }
+
+        // The parts always add back up, whatever the rounding.
+        for cents in 0..=2_000 {
+            for bps in [0, 1, 500, 825, 1_900, 2_000, 2_500, 10_000] {
+                let gross = from_cents(cents);
+                let (net, tax) = split_gross(gross, bps);
+                assert_eq!(net + tax, gross);
+                assert_eq!(net.scale(), 2);
+            }
+        }
+    }
+
+    #[test]
+    fn prorates_by_time_used() {
+        let month = 30 * 86_400;
+        assert_eq!(prorate(d("29.00"), 0, month), d("0.00"));
+        assert_eq!(prorate(d("29.00"), month, month), d("29.00"));
+        assert_eq!(prorate(d("29.00"), month / 2, month), d("14.50"));
+        assert_eq!(prorate(d("9.00"), 10 * 86_400, month), d("3.00"));
+        assert_eq!(prorate(d("10.00"), 1, 3), d("3.33"));
+        assert_eq!(prorate(d("10.00"), 2, 3), d("6.67"));
+        assert_eq!(prorate(d("0.01"), 1, 2), d("0.01")); // 0.005
+        assert_eq!(prorate(d("0.01"), 1, 3), d("0.00"));
+        assert_eq!(prorate(d("99.00"), 7 * 86_400, 31 * 86_400), d("22.35")); // 22.3548...
+        assert_eq!(prorate(MAX_AMOUNT, 1, 1), MAX_AMOUNT);
+    }
+
+    #[test]
+    fn clamps_proration_to_the_period() {
+        assert_eq!(prorate(d("29.00"), -5, 100), d("0.00"));
+        assert_eq!(prorate(d("29.00"), 500, 100), d("29.00"));
+        assert_eq!(prorate(d("29.00"), 10, 0), d("0"));
+        assert_eq!(prorate(d("29.00"), 10, -1), d("0"));
+    }
+
+    #[test]
+    fn prorated_shares_never_exceed_the_price() {
+        let total = 31 * 86_400;
+        for price_cents in [1, 99, 900, 2_900, 9_900, 123_457] {
+            let price = from_cents(price_cents);
+            let mut previous = Decimal::ZERO;
+            for used in (0..=total).step_by(3_607) {
+                let share = prorate(price, used, total);
+                assert!(share >= previous && share <= price);
+                // What is used and what is left add up to the price within a cent.
+                let left = prorate(price, total - used, total);
+                assert!((share + left - price).abs() <= d("0.01"));
+                previous = share;
+            }
+        }
+    }
+
+    #[test]
+    fn applies_credit_up_to_the_amount_due() {
+        assert_eq!(apply_credit(d("10.00"), d("29.00")), d("10.00"));
+        assert_eq!(apply_credit(d("50.00"), d("29.00")), d("29.00"));
+        assert_eq!(apply_credit(d("29.00"), d("29.00")), d("29.00"));
+        assert_eq!(apply_credit(d("0.00"), d("29.00")), d("0.00"));
+        assert_eq!(apply_credit(d("-5.00"), d("29.00")), d("0"));
+        assert_eq!(apply_credit(d("10.00"), d("0.00")), d("0.00"));
+    }
+}
@@ -0,0 +1,375 @@
+//! Collecting payments for invoices through an external provider.
+//!
+//! Invoices and plans stay ours; the provider only takes the money. A user
+//! pays an issued invoice through the provider's hosted checkout, and the
+//! provider reports the outcome through a signed webhook. Each webhook event
+//! is recorded in the same transaction as its effect, so redelivered events
+//! are acknowledged without being applied twice.
+
+pub mod fake;
+pub mod stripe;
+
+use async_trait::async_trait;
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use shared::errors::{AppError, AppResult};
+use sqlx::{PgConnection, PgPool};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{invoices, plans, subscriptions};
+
+pub use fake::FakeProvider;
+pub use stripe::{StripeConfig, StripeProvider};
+
+/// The user a provider-side customer is created for.
+#[derive(Debug, Clone)]
+pub struct NewCustomer {
+    pub user_id: Uuid,
+    pub email: String,
+    pub name: String,
+}
+
+/// A hosted checkout for paying one invoice. Amounts are in cents.
+#[derive(Debug, Clone)]
+pub struct CheckoutRequest {
+    pub customer_id: String,
+    pub invoice_id: Uuid,
+    pub description: String,
+    pub amount_cents: i64,
+    pub currency: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckoutSession {
+    pub id: String,
+    /// Where to send the user to pay.
+    pub url: String,
+}
+
+/// A verified webhook delivery, reduced to what billing-service acts on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookEvent {
+    /// The provider's event ID, unique per event and repeated on redelivery.
+    pub id: String,
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    PaymentSucceeded {
+        invoice_id: Uuid,
+        payment_id: String,
+        amount_cents: i64,
+        currency: String,
+    },
+    PaymentFailed {
+        invoice_id: Uuid,
+        payment_id: String,
+        reason: Option<String>,
+    },
+    /// The customer's subscription was changed at the provider, e.g. from
+    /// its billing portal.
+    SubscriptionUpdated {
+        customer_id: String,
+        /// The provider's status, such as `active` or `canceled`.
+        status: String,
+        cancel_at_period_end: bool,
+    },
+    /// Any other event type, acknowledged without effect.
+    Ignored { event_type: String },
+}
+
+impl EventKind {
+    fn name(&self) -> &str {
+        match self {
+            EventKind::PaymentSucceeded { .. } => "payment_succeeded",
+            EventKind::PaymentFailed { .. } => "payment_failed",
+            EventKind::SubscriptionUpdated { .. } => "subscription_updated",
+            EventKind::Ignored { event_type } => event_type,
+        }
+    }
+
+    fn invoice_id(&self) -> Option<Uuid> {
+        match self {
+            EventKind::PaymentSucceeded { invoice_id, .. }
+            | EventKind::PaymentFailed { invoice_id, .. } => Some(*invoice_id),
+            _ => None,
+        }
+    }
+}
+
+/// A payment provider: creates customers and checkouts, and verifies the
+/// webhooks it sends.
+#[async_trait]
+pub trait PaymentProvider: Send + Sync {
+    /// Stored with customer IDs and events, e.g. `stripe`.
+    fn name(&self) -> &'static str;
+
+    /// Creates the provider's customer for a user and returns its ID.
+    async fn create_customer(&self, customer: &NewCustomer) -> AppResult<String>;
+
+    async fn create_checkout_session(
+        &self,
+        request: &CheckoutRequest,
+    ) -> AppResult<CheckoutSession>;
+
+    /// Checks the delivery's signature and parses the event. Fails with
+    /// `Unauthorized` when the signature is missing, wrong or too old.
+    fn verify_webhook(&self, headers: &HeaderMap, payload: &[u8]) -> AppResult<WebhookEvent>;
+}
+
+/// Reads a setting that has no safe default, failing if it is unset or empty.
+fn required_env(name: &str) -> String {
+    std::env::var(name)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| panic!("{} must be set", name))
+}
+
+/// Builds the provider selected by `PAYMENT_PROVIDER` (`stripe` or `fake`).
+/// There is no default: webhooks are unauthenticated apart from their
+/// signature, so a deployment must name its provider and secret explicitly,
+/// and startup fails otherwise.
+pub fn from_env() -> Arc<dyn PaymentProvider> {
+    let provider = required_env("PAYMENT_PROVIDER");
+    let success_url = std::env::var("PAYMENT_SUCCESS_URL")
+        .unwrap_or_else(|_| "http://localhost:3000/billing/invoices?paid=1".to_string());
+    let cancel_url = std::env::var("PAYMENT_CANCEL_URL")
+        .unwrap_or_else(|_| "http://localhost:3000/billing/invoices".to_string());
+
+    match provider.as_str() {
+        "stripe" => Arc::new(StripeProvider::new(StripeConfig {
+            api_base: std::env::var("STRIPE_API_BASE")
+                .unwrap_or_else(|_| "https://api.stripe.com".to_string()),
+            secret_key: required_env("STRIPE_SECRET_KEY"),
+            webhook_secret: required_env("STRIPE_WEBHOOK_SECRET"),
+            success_url,
+            cancel_url,
+        })),
+        "fake" => Arc::new(FakeProvider::new(required_env(
+            "FAKE_PAYMENT_WEBHOOK_SECRET",
+        ))),
+        other => panic!(
+            "Unknown PAYMENT_PROVIDER '{}', expected stripe or fake",
+            other
+        ),
+    }
+}
+
+/// The user's customer ID at the provider, created on first use.
+pub async fn customer_id(
+    db: &PgPool,
+    provider: &dyn PaymentProvider,
+    user_id: Uuid,
+) -> AppResult<String> {
+    let existing: Option<String> = sqlx::query_scalar(
+        "SELECT customer_id FROM payment_customers WHERE user_id = $1 AND provider = $2",
+    )
+    .bind(user_id)
+    .bind(provider.name())
+    .fetch_optional(db)
+    .await?;
+    if let Some(customer_id) = existing {
+        return Ok(customer_id);
+    }
+
+    let (email, name): (String, String) =
+        sqlx::query_as("SELECT email, name FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(db)
+            .await?
+            .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    let created = provider
+        .create_customer(&NewCustomer {
+            user_id,
+            email,
+            name,
+        })
+        .await?;
+
+    // A concurrent checkout may have mapped a customer first; that one wins.
+    sqlx::query(
+        "INSERT INTO payment_customers (user_id, provider, customer_id) VALUES ($1, $2, $3)
+         ON CONFLICT (user_id, provider) DO NOTHING",
+    )
+    .bind(user_id)
+    .bind(provider.name())
+    .bind(&created)
+    .execute(db)
+    .await?;
+    let customer_id = sqlx::query_scalar(
+        "SELECT customer_id FROM payment_customers WHERE user_id = $1 AND provider = $2",
+    )
+    .bind(user_id)
+    .bind(provider.name())
+    .fetch_one(db)
+    .await?;
+    Ok(customer_id)
+}
+
+/// Records `event` and applies it, both or neither. Returns false if the
+/// event was already handled.
+pub async fn handle_event(
+    conn: &mut PgConnection,
+    provider: &str,
+    event: &WebhookEvent,
+    tax_rate_bps: i32,
+) -> AppResult<bool> {
+    let invoice_id = match event.kind.invoice_id() {
+        Some(invoice_id) => {
+            sqlx::query_scalar::<_, Uuid>("SELECT id FROM invoices WHERE id = $1")
+                .bind(invoice_id)
+                .fetch_optional(&mut *conn)
+                .await?
+        }
+        None => None,
+    };
+    let failure_reason = match &event.kind {
+        EventKind::PaymentFailed { reason, .. } => reason.clone(),
+        _ => None,
+    };
+    let recorded = sqlx::query(
+        "INSERT INTO payment_events (provider, event_id, event_type, invoice_id, failure_reason)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (provider, event_id) DO NOTHING",
+    )
+    .bind(provider)
+    .bind(&event.id)
+    .bind(event.kind.name())
+    .bind(invoice_id)
+    .bind(&failure_reason)
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+    if recorded == 0 {
+        return Ok(false);
+    }
+
+    match &event.kind {
+        EventKind::PaymentSucceeded {
+            payment_id,
+            amount_cents,
+            currency,
+            ..
+        } => {
+            let Some(invoice_id) = invoice_id else {
+                tracing::warn!(event_id = %event.id, "Payment for an unknown invoice");
+                return Ok(true);
+            };
+            record_payment(conn, invoice_id, payment_id, *amount_cents, currency).await?;
+        }
+        EventKind::PaymentFailed {
+            payment_id, reason, ..
+        } => {
+            tracing::info!(
+                event_id = %event.id,
+                ?invoice_id,
+                %payment_id,
+                reason = reason.as_deref().unwrap_or("unknown"),
+                "Payment failed"
+            );
+        }
+        EventKind::SubscriptionUpdated {
+            customer_id,
+            status,
+            cancel_at_period_end,
+        } => {
+            sync_subscription(
+                conn,
+                provider,
+                customer_id,
+                status,
+                *cancel_at_period_end,
+                tax_rate_bps,
+            )
+            .await?;
+        }
+        EventKind::Ignored { .. } => {}
+    }
+    Ok(true)
+}
+
+/// Marks an invoice paid, unless it already is or the payment does not
+/// cover what was due after account credit.
+async fn record_payment(
+    conn: &mut PgConnection,
+    invoice_id: Uuid,
+    payment_id: &str,
+    amount_cents: i64,
+    currency: &str,
+) -> AppResult<()> {
+    let (status, expected_cents, expected_currency): (String, i64, String) = sqlx::query_as(
+        "SELECT status::text, ((amount - credit_applied) * 100)::bigint, currency
+         FROM invoices WHERE id = $1 FOR UPDATE",
+    )
+    .bind(invoice_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if status == "paid" {
+        return Ok(());
+    }
+    if amount_cents != expected_cents || !currency.eq_ignore_ascii_case(&expected_currency) {
+        tracing::warn!(
+            %invoice_id,
+            %payment_id,
+            "Payment of {} {} does not match the invoice's {} {}; not marking it paid",
+            amount_cents,
+            currency,
+            expected_cents,
+            expected_currency
+        );
+        return Ok(());
+    }
+    if !invoices::can_transition(&status, "paid") {
+        tracing::warn!(%invoice_id, %payment_id, "Payment received for a {} invoice", status);
+        return Ok(());
+    }
+
+    invoices::transition(conn, invoice_id, "paid").await?;
+    sqlx::query("UPDATE invoices SET stripe_id = $2 WHERE id = $1")
+        .bind(invoice_id)
+        .bind(payment_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Mirrors a cancellation made at the provider: an ended subscription drops
+/// to the free plan now, and `cancel_at_period_end` schedules or withdraws
+/// the cancellation.
+async fn sync_subscription(
+    conn: &mut PgConnection,
+    provider: &str,
+    customer_id: &str,
+    status: &str,
+    cancel_at_period_end: bool,
+    tax_rate_bps: i32,
+) -> AppResult<()> {
+    let user_id: Option<Uuid> = sqlx::query_scalar(
+        "SELECT user_id FROM payment_customers WHERE provider = $1 AND customer_id = $2",
+    )
+    .bind(provider)
+    .bind(customer_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(user_id) = user_id else {
+        tracing::warn!(%customer_id, "Subscription update for an unknown customer");
+        return Ok(());
+    };
+
+    let current = subscriptions::load_for_update(conn, user_id, tax_rate_bps).await?;
+    if current.status != "active" || current.plan == plans::FREE {
+        return Ok(());
+    }
+    if matches!(status, "canceled" | "unpaid" | "incomplete_expired") {
+        subscriptions::cancel_now(conn, current.id).await?;
+    } else if cancel_at_period_end && !current.cancel_at_period_end {
+        subscriptions::cancel_at_period_end(conn, current.id).await?;
+    } else if !cancel_at_period_end && current.cancel_at_period_end {
+        subscriptions::set_auto_renew(conn, current.id, true).await?;
+    }
+    Ok(())
+}
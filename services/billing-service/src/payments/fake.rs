@@ -0,0 +1,181 @@
+use async_trait::async_trait;
+use axum::http::HeaderMap;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use shared::errors::{AppError, AppResult};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use super::{CheckoutRequest, CheckoutSession, NewCustomer, PaymentProvider, WebhookEvent};
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const SIGNATURE_HEADER: &str = "X-Fake-Signature";
+
+/// An in-memory provider for tests and local development. It remembers the
+/// customers and checkouts it was asked for, and accepts webhooks whose body
+/// is a JSON `WebhookEvent` signed with `sign`. IDs are random, so the ones
+/// stored by an earlier run are never handed out again.
+pub struct FakeProvider {
+    webhook_secret: String,
+    customers: Mutex<Vec<NewCustomer>>,
+    checkouts: Mutex<Vec<CheckoutRequest>>,
+}
+
+impl FakeProvider {
+    pub fn new(webhook_secret: impl Into<String>) -> Self {
+        Self {
+            webhook_secret: webhook_secret.into(),
+            customers: Mutex::new(Vec::new()),
+            checkouts: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn mac(&self, payload: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(self.webhook_secret.as_bytes())
+            .expect("HMAC accepts any key length");
+        mac.update(payload);
+        mac
+    }
+
+    /// The `X-Fake-Signature` value for `payload`.
+    pub fn sign(&self, payload: &[u8]) -> String {
+        hex::encode(self.mac(payload).finalize().into_bytes())
+    }
+
+    pub fn customers(&self) -> Vec<NewCustomer> {
+        self.customers.lock().unwrap().clone()
+    }
+
+    pub fn checkouts(&self) -> Vec<CheckoutRequest> {
+        self.checkouts.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl PaymentProvider for FakeProvider {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    async fn create_customer(&self, customer: &NewCustomer) -> AppResult<String> {
+        self.customers.lock().unwrap().push(customer.clone());
+        Ok(format!("cus_fake_{}", Uuid::new_v4().simple()))
+    }
+
+    async fn create_checkout_session(
+        &self,
+        request: &CheckoutRequest,
+    ) -> AppResult<CheckoutSession> {
+        if request.amount_cents <= 0 {
+            return Err(AppError::BadRequest(
+                "Checkout amount must be positive".to_string(),
+            ));
+        }
+        self.checkouts.lock().unwrap().push(request.clone());
+        let id = format!("cs_fake_{}", Uuid::new_v4().simple());
+        Ok(CheckoutSession {
+            url: format!("https://payments.invalid/checkout/{}", id),
+            id,
+        })
+    }
+
+    fn verify_webhook(&self, headers: &HeaderMap, payload: &[u8]) -> AppResult<WebhookEvent> {
+        let signature = headers
+            .get(SIGNATURE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| hex::decode(value).ok())
+            .ok_or_else(|| AppError::Unauthorized("Invalid webhook signature".to_string()))?;
+        self.mac(payload)
+            .verify_slice(&signature)
+            .map_err(|_| AppError::Unauthorized("Invalid webhook signature".to_string()))?;
+        serde_json::from_slice(payload)
+            .map_err(|e| AppError::BadRequest(format!("Invalid webhook payload: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payments::EventKind;
+
+    fn headers(signature: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(SIGNATURE_HEADER, signature.parse().unwrap());
+        headers
+    }
+
+    #[tokio::test]
+    async fn records_customers_and_checkouts() {
+        let provider = FakeProvider::new("secret");
+        let user_id = Uuid::new_v4();
+        let customer_id = provider
+            .create_customer(&NewCustomer {
+                user_id,
+                email: "ada@example.com".to_string(),
+                name: "Ada".to_string(),
+            })
+            .await
+            .unwrap();
+        assert!(customer_id.starts_with("cus_fake_"));
+        let other = provider
+            .create_customer(&NewCustomer {
+                user_id: Uuid::new_v4(),
+                email: "grace@example.com".to_string(),
+                name: "Grace".to_string(),
+            })
+            .await
+            .unwrap();
+        assert_ne!(other, customer_id);
+        // A restarted provider starts from scratch but never repeats an ID.
+        let restarted = FakeProvider::new("secret")
+            .create_customer(&provider.customers()[0])
+            .await
+            .unwrap();
+        assert_ne!(restarted, customer_id);
+
+        let invoice_id = Uuid::new_v4();
+        let session = provider
+            .create_checkout_session(&CheckoutRequest {
+                customer_id,
+                invoice_id,
+                description: "Invoice INV-2024-000001".to_string(),
+                amount_cents: 2900,
+                currency: "USD".to_string(),
+            })
+            .await
+            .unwrap();
+        assert!(session.id.starts_with("cs_fake_"));
+        assert!(session.url.ends_with(&format!("/{}", session.id)));
+        assert_eq!(provider.customers()[0].user_id, user_id);
+        assert_eq!(provider.checkouts()[0].invoice_id, invoice_id);
+    }
+
+    #[test]
+    fn verifies_signed_events() {
+        let provider = FakeProvider::new("secret");
+        let event = WebhookEvent {
+            id: "evt_1".to_string(),
+            kind: EventKind::PaymentFailed {
+                invoice_id: Uuid::new_v4(),
+                payment_id: "pay_1".to_string(),
+                reason: Some("declined".to_string()),
+            },
+        };
+        let payload = serde_json::to_vec(&event).unwrap();
+
+        let parsed = provider
+            .verify_webhook(&headers(&provider.sign(&payload)), &payload)
+            .unwrap();
+        assert_eq!(parsed, event);
+
+        let other = FakeProvider::new("other");
+        assert!(matches!(
+            provider.verify_webhook(&headers(&other.sign(&payload)), &payload),
+            Err(AppError::Unauthorized(_))
+        ));
+        assert!(provider
+            .verify_webhook(&HeaderMap::new(), &payload)
+            .is_err());
+    }
+}
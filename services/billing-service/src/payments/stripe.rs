@@ -0,0 +1,480 @@
+use async_trait::async_trait;
+use axum::http::HeaderMap;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::Sha256;
+use shared::errors::{AppError, AppResult};
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::{
+    CheckoutRequest, CheckoutSession, EventKind, NewCustomer, PaymentProvider, WebhookEvent,
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// How old a webhook signature may be, against replayed deliveries.
+const SIGNATURE_TOLERANCE_SECS: u64 = 300;
+
+pub struct StripeConfig {
+    pub api_base: String,
+    pub secret_key: String,
+    pub webhook_secret: String,
+    /// Where Checkout sends the user after paying, and after giving up.
+    pub success_url: String,
+    pub cancel_url: String,
+}
+
+/// Stripe through its REST API: customers, Checkout in payment mode, and
+/// webhooks signed with the endpoint's secret.
+pub struct StripeProvider {
+    config: StripeConfig,
+    client: Client,
+}
+
+#[derive(Deserialize)]
+struct Created {
+    id: String,
+    url: Option<String>,
+}
+
+impl StripeProvider {
+    pub fn new(config: StripeConfig) -> Self {
+        Self {
+            config,
+            client: Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    async fn post(
+        &self,
+        path: &str,
+        idempotency_key: &str,
+        form: &[(String, String)],
+    ) -> AppResult<Created> {
+        let response = self
+            .client
+            .post(format!("{}{}", self.config.api_base, path))
+            .bearer_auth(&self.config.secret_key)
+            .header("Idempotency-Key", idempotency_key)
+            .form(form)
+            .send()
+            .await
+            .map_err(|e| AppError::InternalError(format!("Stripe request failed: {}", e)))?;
+
+        let status = response.status();
+        let body: Value = response
+            .json()
+            .await
+            .map_err(|e| AppError::InternalError(format!("Invalid Stripe response: {}", e)))?;
+        if !status.is_success() {
+            let message = body["error"]["message"].as_str().unwrap_or("unknown error");
+            return Err(AppError::InternalError(format!(
+                "Stripe returned {}: {}",
+                status, message
+            )));
+        }
+        serde_json::from_value(body)
+            .map_err(|e| AppError::InternalError(format!("Invalid Stripe response: {}", e)))
+    }
+}
+
+fn field(key: &str, value: impl ToString) -> (String, String) {
+    (key.to_string(), value.to_string())
+}
+
+#[async_trait]
+impl PaymentProvider for StripeProvider {
+    fn name(&self) -> &'static str {
+        "stripe"
+    }
+
+    async fn create_customer(&self, customer: &NewCustomer) -> AppResult<String> {
+        let form = [
+            field("email", &customer.email),
+            field("name", &customer.name),
+            field("metadata[user_id]", customer.user_id),
+        ];
+        let created = self
+            .post(
+                "/v1/customers",
+                &format!("customer-{}", customer.user_id),
+                &form,
+            )
+            .await?;
+        Ok(created.id)
+    }
+
+    async fn create_checkout_session(
+        &self,
+        request: &CheckoutRequest,
+    ) -> AppResult<CheckoutSession> {
+        let form = [
+            field("mode", "payment"),
+            field("customer", &request.customer_id),
+            field("client_reference_id", request.invoice_id),
+            field("success_url", &self.config.success_url),
+            field("cancel_url", &self.config.cancel_url),
+            field("metadata[invoice_id]", request.invoice_id),
+            field(
+                "payment_intent_data[metadata][invoice_id]",
+                request.invoice_id,
+            ),
+            field("line_items[0][quantity]", 1),
+            field(
+                "line_items[0][price_data][currency]",
+                request.currency.to_lowercase(),
+            ),
+            field(
+                "line_items[0][price_data][unit_amount]",
+                request.amount_cents,
+            ),
+            field(
+                "line_items[0][price_data][product_data][name]",
+                &request.description,
+            ),
+        ];
+        // A fresh key per request: an expired session must not be handed back.
+        let created = self
+            .post(
+                "/v1/checkout/sessions",
+                &format!("checkout-{}-{}", request.invoice_id, Uuid::new_v4()),
+                &form,
+            )
+            .await?;
+        let url = created.url.ok_or_else(|| {
+            AppError::InternalError("Stripe returned a session without a URL".to_string())
+        })?;
+        Ok(CheckoutSession {
+            id: created.id,
+            url,
+        })
+    }
+
+    fn verify_webhook(&self, headers: &HeaderMap, payload: &[u8]) -> AppResult<WebhookEvent> {
+        let signature = headers
+            .get("Stripe-Signature")
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| AppError::Unauthorized("Missing Stripe-Signature header".to_string()))?;
+        verify_signature(
+            &self.config.webhook_secret,
+            signature,
+            payload,
+            Utc::now().timestamp(),
+        )?;
+        parse_event(payload)
+    }
+}
+
+/// Checks a `Stripe-Signature` header (`t=<timestamp>,v1=<hex>,...`): one of
+/// the `v1` signatures must be the HMAC-SHA256 of `<timestamp>.<payload>`,
+/// made no more than five minutes before `now`.
+pub fn verify_signature(secret: &str, header: &str, payload: &[u8], now: i64) -> AppResult<()> {
+    let invalid = || AppError::Unauthorized("Invalid webhook signature".to_string());
+
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", value)) => timestamp = value.parse::<i64>().ok(),
+            Some(("v1", value)) => signatures.extend(hex::decode(value).ok()),
+            _ => {}
+        }
+    }
+    let timestamp = timestamp.ok_or_else(invalid)?;
+    if now.abs_diff(timestamp) > SIGNATURE_TOLERANCE_SECS {
+        return Err(AppError::Unauthorized(
+            "Webhook signature has expired".to_string(),
+        ));
+    }
+
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(payload);
+    if signatures
+        .iter()
+        .any(|signature| mac.clone().verify_slice(signature).is_ok())
+    {
+        Ok(())
+    } else {
+        Err(invalid())
+    }
+}
+
+fn invoice_id(object: &Value) -> Option<Uuid> {
+    object["metadata"]["invoice_id"]
+        .as_str()
+        .or_else(|| object["client_reference_id"].as_str())
+        .and_then(|id| Uuid::parse_str(id).ok())
+}
+
+fn text(value: &Value) -> Option<String> {
+    value.as_str().map(str::to_string)
+}
+
+/// Maps a Stripe event to what billing-service acts on. Payments without an
+/// invoice in their metadata did not come from our checkouts and are ignored.
+pub fn parse_event(payload: &[u8]) -> AppResult<WebhookEvent> {
+    let event: Value = serde_json::from_slice(payload)
+        .map_err(|e| AppError::BadRequest(format!("Invalid webhook payload: {}", e)))?;
+    let id = text(&event["id"])
+        .ok_or_else(|| AppError::BadRequest("Webhook event has no id".to_string()))?;
+    let event_type = event["type"].as_str().unwrap_or_default().to_string();
+    let object = &event["data"]["object"];
+
+    let ignored = || EventKind::Ignored {
+        event_type: event_type.clone(),
+    };
+    let kind = match event_type.as_str() {
+        "checkout.session.completed" | "checkout.session.async_payment_succeeded" => {
+            match (invoice_id(object), object["payment_status"].as_str()) {
+                (Some(invoice_id), Some("paid")) => EventKind::PaymentSucceeded {
+                    invoice_id,
+                    payment_id: text(&object["payment_intent"])
+                        .or_else(|| text(&object["id"]))
+                        .unwrap_or_default(),
+                    amount_cents: object["amount_total"].as_i64().unwrap_or_default(),
+                    currency: object["currency"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_uppercase(),
+                },
+                _ => ignored(),
+            }
+        }
+        "checkout.session.async_payment_failed" => match invoice_id(object) {
+            Some(invoice_id) => EventKind::PaymentFailed {
+                invoice_id,
+                payment_id: text(&object["payment_intent"])
+                    .or_else(|| text(&object["id"]))
+                    .unwrap_or_default(),
+                reason: None,
+            },
+            None => ignored(),
+        },
+        "payment_intent.succeeded" => match invoice_id(object) {
+            Some(invoice_id) => EventKind::PaymentSucceeded {
+                invoice_id,
+                payment_id: text(&object["id"]).unwrap_or_default(),
+                amount_cents: object["amount_received"].as_i64().unwrap_or_default(),
+                currency: object["currency"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_uppercase(),
+            },
+            None => ignored(),
+        },
+        "payment_intent.payment_failed" => match invoice_id(object) {
+            Some(invoice_id) => EventKind::PaymentFailed {
+                invoice_id,
+                payment_id: text(&object["id"]).unwrap_or_default(),
+                reason: text(&object["last_payment_error"]["message"]),
+            },
+            None => ignored(),
+        },
+        "customer.subscription.updated" | "customer.subscription.deleted" => {
+            match text(&object["customer"]) {
+                Some(customer_id) => EventKind::SubscriptionUpdated {
+                    customer_id,
+                    status: object["status"].as_str().unwrap_or_default().to_string(),
+                    cancel_at_period_end: object["cancel_at_period_end"]
+                        .as_bool()
+                        .unwrap_or_default(),
+                },
+                None => ignored(),
+            }
+        }
+        _ => ignored(),
+    };
+
+    Ok(WebhookEvent { id, kind })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SECRET: &str = "whsec_test";
+    const NOW: i64 = 1_700_000_000;
+
+    fn sign(payload: &[u8], timestamp: i64) -> String {
+        let mut mac = HmacSha256::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(format!("{}.", timestamp).as_bytes());
+        mac.update(payload);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn event(event_type: &str, object: Value) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "id": "evt_1",
+            "type": event_type,
+            "data": { "object": object },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn accepts_a_valid_signature() {
+        let payload = br#"{"id":"evt_1"}"#;
+        let header = format!("t={},v1={}", NOW, sign(payload, NOW));
+        assert!(verify_signature(SECRET, &header, payload, NOW + 10).is_ok());
+
+        // Any of several signatures may match, e.g. while the secret rolls.
+        let header = format!("t={},v1={},v1={}", NOW, "00".repeat(32), sign(payload, NOW));
+        assert!(verify_signature(SECRET, &header, payload, NOW).is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_signatures() {
+        let payload = br#"{"id":"evt_1"}"#;
+        let valid = sign(payload, NOW);
+        for header in [
+            format!("t={},v1={}", NOW, sign(b"{}", NOW)),
+            format!("t={},v1={}", NOW + 1, valid),
+            format!("t={},v0={}", NOW, valid),
+            format!("v1={}", valid),
+            format!("t={},v1=not-hex", NOW),
+            String::new(),
+        ] {
+            assert!(
+                matches!(
+                    verify_signature(SECRET, &header, payload, NOW),
+                    Err(AppError::Unauthorized(_))
+                ),
+                "accepted {:?}",
+                header
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_stale_signatures() {
+        let payload = br#"{"id":"evt_1"}"#;
+        let header = format!("t={},v1={}", NOW, sign(payload, NOW));
+        assert!(verify_signature(SECRET, &header, payload, NOW + 301).is_err());
+        assert!(verify_signature(SECRET, &header, payload, NOW - 301).is_err());
+
+        // Timestamps far enough out to overflow a subtraction are stale too.
+        for timestamp in [i64::MIN, i64::MAX] {
+            let header = format!("t={},v1={}", timestamp, sign(payload, timestamp));
+            for now in [NOW, i64::MIN, i64::MAX, -NOW] {
+                if now != timestamp {
+                    assert!(verify_signature(SECRET, &header, payload, now).is_err());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parses_completed_checkouts() {
+        let invoice_id = Uuid::new_v4();
+        let parsed = parse_event(&event(
+            "checkout.session.completed",
+            json!({
+                "id": "cs_1",
+                "payment_status": "paid",
+                "payment_intent": "pi_1",
+                "amount_total": 2900,
+                "currency": "usd",
+                "metadata": { "invoice_id": invoice_id.to_string() },
+            }),
+        ))
+        .unwrap();
+        assert_eq!(
+            parsed,
+            WebhookEvent {
+                id: "evt_1".to_string(),
+                kind: EventKind::PaymentSucceeded {
+                    invoice_id,
+                    payment_id: "pi_1".to_string(),
+                    amount_cents: 2900,
+                    currency: "USD".to_string(),
+                },
+            }
+        );
+
+        // Delayed payment methods complete the session before the money arrives.
+        let pending = parse_event(&event(
+            "checkout.session.completed",
+            json!({
+                "id": "cs_1",
+                "payment_status": "unpaid",
+                "metadata": { "invoice_id": invoice_id.to_string() },
+            }),
+        ))
+        .unwrap();
+        assert!(matches!(pending.kind, EventKind::Ignored { .. }));
+    }
+
+    #[test]
+    fn parses_payment_intents() {
+        let invoice_id = Uuid::new_v4();
+        let failed = parse_event(&event(
+            "payment_intent.payment_failed",
+            json!({
+                "id": "pi_1",
+                "metadata": { "invoice_id": invoice_id.to_string() },
+                "last_payment_error": { "message": "Your card was declined." },
+            }),
+        ))
+        .unwrap();
+        assert_eq!(
+            failed.kind,
+            EventKind::PaymentFailed {
+                invoice_id,
+                payment_id: "pi_1".to_string(),
+                reason: Some("Your card was declined.".to_string()),
+            }
+        );
+
+        let foreign = parse_event(&event(
+            "payment_intent.succeeded",
+            json!({ "id": "pi_2", "amount_received": 100, "currency": "usd", "metadata": {} }),
+        ))
+        .unwrap();
+        assert_eq!(
+            foreign.kind,
+            EventKind::Ignored {
+                event_type: "payment_intent.succeeded".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn parses_subscription_updates() {
+        let parsed = parse_event(&event(
+            "customer.subscription.deleted",
+            json!({ "id": "sub_1", "customer": "cus_1", "status": "canceled" }),
+        ))
+        .unwrap();
+        assert_eq!(
+            parsed.kind,
+            EventKind::SubscriptionUpdated {
+                customer_id: "cus_1".to_string(),
+                status: "canceled".to_string(),
+                cancel_at_period_end: false,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_payloads() {
+        assert!(matches!(
+            parse_event(b"not json"),
+            Err(AppError::BadRequest(_))
+        ));
+        assert!(matches!(
+            parse_event(br#"{"type":"invoice.paid"}"#),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+}
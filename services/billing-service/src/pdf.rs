@@ -0,0 +1,319 @@
+//! A small PDF writer: text, lines and shaded boxes on A4 pages, in the
+//! standard Helvetica fonts that every reader provides, so nothing is
+//! embedded. The output depends only on what is drawn. There are no
+//! timestamps or random IDs and the file is plain ASCII, so the same document
+//! always gives the same bytes and snapshots diff cleanly.
+
+mod helvetica;
+pub mod invoice;
+
+use std::fmt::Write;
+
+/// A4 in points.
+pub const PAGE_WIDTH: f32 = 595.0;
+pub const PAGE_HEIGHT: f32 = 842.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+        }
+    }
+
+    fn base_font(self) -> &'static str {
+        match self {
+            Font::Regular => "Helvetica",
+            Font::Bold => "Helvetica-Bold",
+        }
+    }
+
+    /// Width of `text` set at `size`, in points.
+    pub fn width(self, text: &str, size: f32) -> f32 {
+        let units: u32 = encode(text)
+            .into_iter()
+            .map(|byte| {
+                let width = match (self, byte) {
+                    (Font::Regular, 32..=126) => helvetica::REGULAR[usize::from(byte - 32)],
+                    (Font::Bold, 32..=126) => helvetica::BOLD[usize::from(byte - 32)],
+                    (font, _) => helvetica::extended(byte, font == Font::Bold),
+                };
+                u32::from(width)
+            })
+            .sum();
+        units as f32 * size / 1000.0
+    }
+
+    /// `text` cut short with an ellipsis so it fits in `max_width`.
+    pub fn truncate(self, text: &str, size: f32, max_width: f32) -> String {
+        if self.width(text, size) <= max_width {
+            return text.to_string();
+        }
+        let mut chars: Vec<char> = text.chars().collect();
+        while !chars.is_empty() {
+            chars.pop();
+            let candidate = format!("{}...", chars.iter().collect::<String>().trim_end());
+            if self.width(&candidate, size) <= max_width {
+                return candidate;
+            }
+        }
+        String::new()
+    }
+}
+
+/// Maps `text` to WinAnsiEncoding, the encoding of the standard fonts.
+/// Characters it lacks become `?`.
+fn encode(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            ' '..='~' => c as u8,
+            '\u{a0}'..='\u{ff}' => c as u32 as u8,
+            '€' => 0x80,
+            '…' => 0x85,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            '™' => 0x99,
+            '\t' | '\n' | '\r' => b' ',
+            _ => b'?',
+        })
+        .collect()
+}
+
+/// `text` as a PDF string literal. Bytes outside printable ASCII are written
+/// as octal escapes.
+fn string_literal(text: &str) -> String {
+    let mut out = String::from("(");
+    for byte in encode(text) {
+        match byte {
+            b'(' | b')' | b'\\' => {
+                out.push('\\');
+                out.push(byte as char);
+            }
+            32..=126 => out.push(byte as char),
+            _ => {
+                let _ = write!(out, "\\{:03o}", byte);
+            }
+        }
+    }
+    out.push(')');
+    out
+}
+
+/// A coordinate or size with at most two decimals and no trailing zeros.
+fn number(value: f32) -> String {
+    let formatted = format!("{:.2}", value);
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "" | "-0" => "0".to_string(),
+        _ => trimmed.to_string(),
+    }
+}
+
+/// The drawing operations of one page. The origin is the bottom left corner.
+#[derive(Debug, Default, Clone)]
+pub struct Page {
+    content: String,
+}
+
+impl Page {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Text with its baseline starting at (`x`, `y`), in `gray` from 0
+    /// (black) to 1 (white).
+    pub fn text(&mut self, x: f32, y: f32, font: Font, size: f32, gray: f32, text: &str) {
+        let _ = writeln!(
+            self.content,
+            "BT {} g /{} {} Tf {} {} Td {} Tj ET",
+            number(gray),
+            font.resource(),
+            number(size),
+            number(x),
+            number(y),
+            string_literal(text)
+        );
+    }
+
+    /// Text ending at `right`.
+    pub fn text_right(&mut self, right: f32, y: f32, font: Font, size: f32, gray: f32, text: &str) {
+        self.text(right - font.width(text, size), y, font, size, gray, text);
+    }
+
+    pub fn line(&mut self, from: (f32, f32), to: (f32, f32), width: f32, gray: f32) {
+        let _ = writeln!(
+            self.content,
+            "{} G {} w {} {} m {} {} l S",
+            number(gray),
+            number(width),
+            number(from.0),
+            number(from.1),
+            number(to.0),
+            number(to.1)
+        );
+    }
+
+    pub fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32, gray: f32) {
+        let _ = writeln!(
+            self.content,
+            "{} g {} {} {} {} re f",
+            number(gray),
+            number(x),
+            number(y),
+            number(width),
+            number(height)
+        );
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Document {
+    title: String,
+    pages: Vec<Page>,
+}
+
+impl Document {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            pages: Vec::new(),
+        }
+    }
+
+    pub fn push_page(&mut self, page: Page) {
+        self.pages.push(page);
+    }
+
+    /// The finished file. Objects are numbered in a fixed order: catalog,
+    /// page tree, the two fonts, then each page followed by its content
+    /// stream, and the document information last.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let page_id = |index: usize| 5 + 2 * index;
+        let info_id = page_id(self.pages.len());
+
+        let mut objects: Vec<String> = Vec::with_capacity(info_id);
+        objects.push("<< /Type /Catalog /Pages 2 0 R >>".to_string());
+        objects.push(format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..self.pages.len())
+                .map(|index| format!("{} 0 R", page_id(index)))
+                .collect::<Vec<_>>()
+                .join(" "),
+            self.pages.len()
+        ));
+        for font in [Font::Regular, Font::Bold] {
+            objects.push(format!(
+                "<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+                font.base_font()
+            ));
+        }
+        for (index, page) in self.pages.iter().enumerate() {
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                 /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                number(PAGE_WIDTH),
+                number(PAGE_HEIGHT),
+                page_id(index) + 1
+            ));
+            objects.push(format!(
+                "<< /Length {} >>\nstream\n{}endstream",
+                page.content.len(),
+                page.content
+            ));
+        }
+        objects.push(format!(
+            "<< /Title {} /Producer (billing-service) >>",
+            string_literal(&self.title)
+        ));
+
+        let mut out = String::from("%PDF-1.4\n");
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            let _ = write!(out, "{} 0 obj\n{}\nendobj\n", index + 1, object);
+        }
+
+        let xref = out.len();
+        let _ = write!(out, "xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(out, "{:010} 00000 n ", offset);
+        }
+        let _ = write!(
+            out,
+            "trailer\n<< /Size {} /Root 1 0 R /Info {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            info_id,
+            xref
+        );
+        out.into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_string_literals() {
+        assert_eq!(string_literal(r"a (b) \c"), r"(a \(b\) \\c)");
+        assert_eq!(string_literal("Café €5"), r"(Caf\351 \2005)");
+        assert_eq!(string_literal("日本"), "(??)");
+    }
+
+    #[test]
+    fn formats_numbers_without_trailing_zeros() {
+        assert_eq!(number(50.0), "50");
+        assert_eq!(number(12.5), "12.5");
+        assert_eq!(number(0.333), "0.33");
+        assert_eq!(number(-0.001), "0");
+    }
+
+    #[test]
+    fn measures_and_truncates_text() {
+        assert_eq!(Font::Regular.width("0000", 10.0), 22.24);
+        assert!(Font::Bold.width("Total", 10.0) > Font::Regular.width("Total", 10.0));
+
+        let long = "A description far too long for its column";
+        let cut = Font::Regular.truncate(long, 10.0, 100.0);
+        assert!(cut.ends_with("...") && Font::Regular.width(&cut, 10.0) <= 100.0);
+        assert_eq!(Font::Regular.truncate("Short", 10.0, 100.0), "Short");
+    }
+
+    #[test]
+    fn cross_reference_offsets_point_at_objects() {
+        let mut document = Document::new("Test");
+        let mut page = Page::new();
+        page.text(50.0, 800.0, Font::Regular, 12.0, 0.0, "Hello");
+        document.push_page(page);
+        document.push_page(Page::new());
+        let bytes = document.to_bytes();
+        let text = String::from_utf8(bytes.clone()).unwrap();
+
+        let startxref: usize = text
+            .rsplit("startxref\n")
+            .next()
+            .and_then(|rest| rest.lines().next())
+            .and_then(|line| line.parse().ok())
+            .unwrap();
+        assert!(text[startxref..].starts_with("xref\n0 10\n"));
+
+        let entries = text[startxref..].lines().skip(3).take(9);
+        for (index, entry) in entries.enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(text[offset..].starts_with(&format!("{} 0 obj\n", index + 1)));
+        }
+        assert!(text.contains("/Kids [5 0 R 7 0 R] /Count 2"));
+        assert!(text.ends_with("%%EOF\n"));
+    }
+}
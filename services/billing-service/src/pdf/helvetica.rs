@@ -0,0 +1,54 @@
+//! Glyph widths of the standard Helvetica fonts, in thousandths of the font
+//! size, from the Adobe font metrics. Only used to measure text for alignment
+//! and truncation.
+
+/// Widths of the printable ASCII characters, space (32) to tilde (126).
+pub const REGULAR: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, // space to '
+    333, 333, 389, 584, 278, 333, 278, 278, // ( to /
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, // 0 to 9
+    278, 278, 584, 584, 584, 556, 1015, // : to @
+    667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, // A to M
+    722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, // N to Z
+    278, 278, 278, 469, 556, 333, // [ to `
+    556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, // a to m
+    556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, // n to z
+    334, 260, 334, 584, // { to ~
+];
+
+pub const BOLD: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, // space to '
+    333, 333, 389, 584, 278, 333, 278, 278, // ( to /
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, // 0 to 9
+    333, 333, 584, 584, 584, 611, 975, // : to @
+    722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, // A to M
+    722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, // N to Z
+    333, 278, 333, 584, 556, 333, // [ to `
+    556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, // a to m
+    611, 611, 611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, // n to z
+    389, 280, 389, 584, // { to ~
+];
+
+/// Width of a WinAnsi byte outside printable ASCII. Accented letters are
+/// close enough to the average letter width.
+pub fn extended(byte: u8, bold: bool) -> u16 {
+    match byte {
+        0x85 | 0x97 | 0x99 => 1000,
+        0x91 | 0x92 => {
+            if bold {
+                278
+            } else {
+                222
+            }
+        }
+        0x93 | 0x94 => {
+            if bold {
+                500
+            } else {
+                333
+            }
+        }
+        0x95 => 350,
+        _ => 556,
+    }
+}
@@ -0,0 +1,461 @@
+//! The printed invoice: seller and buyer, dates, line items, tax and totals.
+//! Line items run over as many pages as they need, with the totals after the
+//! last one.
+
+use chrono::NaiveDate;
+
+use super::{Document, Font, Page, PAGE_HEIGHT};
+
+const LEFT: f32 = 50.0;
+const RIGHT: f32 = 545.0;
+const BUYER_X: f32 = 310.0;
+const LABEL_X: f32 = 360.0;
+const QUANTITY_RIGHT: f32 = 350.0;
+const UNIT_PRICE_RIGHT: f32 = 450.0;
+const DESCRIPTION_WIDTH: f32 = 255.0;
+const ROW_HEIGHT: f32 = 18.0;
+/// Rows stop here to leave room for the footer.
+const BOTTOM: f32 = 70.0;
+/// Room the totals block needs below the last row.
+const TOTALS_HEIGHT: f32 = 110.0;
+const MUTED: f32 = 0.4;
+
+#[derive(Debug, Clone)]
+pub struct Party {
+    pub name: String,
+    /// Address, email, tax ID and the like, one per line.
+    pub lines: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub description: String,
+    pub quantity: i32,
+    pub unit_amount_cents: i64,
+    pub amount_cents: i64,
+}
+
+/// Everything printed on an invoice. Amounts are in cents of `currency`.
+#[derive(Debug, Clone)]
+pub struct InvoiceDocument {
+    pub number: String,
+    pub issued_on: NaiveDate,
+    pub due_on: Option<NaiveDate>,
+    pub period: Option<(NaiveDate, NaiveDate)>,
+    pub currency: String,
+    pub seller: Party,
+    pub buyer: Party,
+    pub lines: Vec<Line>,
+    pub subtotal_cents: i64,
+    pub tax_rate_bps: i32,
+    pub tax_cents: i64,
+    pub total_cents: i64,
+    /// Account credit spent on the invoice, printed only when there is some.
+    pub credit_applied_cents: i64,
+}
+
+impl InvoiceDocument {
+    /// What is left to pay after account credit.
+    pub fn amount_due_cents(&self) -> i64 {
+        self.total_cents - self.credit_applied_cents
+    }
+
+    /// Room the totals block needs, with the credit rows if there are any.
+    fn totals_height(&self) -> f32 {
+        if self.credit_applied_cents == 0 {
+            TOTALS_HEIGHT
+        } else {
+            TOTALS_HEIGHT + 2.0 * ROW_HEIGHT
+        }
+    }
+}
+
+/// `cents` with thousands separators and the currency code, e.g.
+/// `1,234.50 EUR`.
+pub fn format_money(cents: i64, currency: &str) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    let cents = cents.unsigned_abs();
+    let units = (cents / 100).to_string();
+    let mut grouped = String::with_capacity(units.len() + units.len() / 3);
+    for (index, digit) in units.chars().enumerate() {
+        if index > 0 && (units.len() - index).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    format!("{}{}.{:02} {}", sign, grouped, cents % 100, currency)
+}
+
+/// A rate in basis points as a percentage, e.g. `8.25%` or `20%`.
+pub fn format_rate(bps: i32) -> String {
+    let fraction = bps % 100;
+    if fraction == 0 {
+        format!("{}%", bps / 100)
+    } else if fraction % 10 == 0 {
+        format!("{}.{}%", bps / 100, fraction / 10)
+    } else {
+        format!("{}.{:02}%", bps / 100, fraction)
+    }
+}
+
+fn date(date: NaiveDate) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+/// Prints a party under `heading`, returning where the block ends.
+fn party(page: &mut Page, x: f32, y: f32, heading: &str, party: &Party) -> f32 {
+    let width = RIGHT - BUYER_X - 10.0;
+    page.text(x, y, Font::Bold, 9.0, MUTED, heading);
+    let mut y = y - 16.0;
+    page.text(
+        x,
+        y,
+        Font::Bold,
+        11.0,
+        0.0,
+        &Font::Bold.truncate(&party.name, 11.0, width),
+    );
+    for line in &party.lines {
+        y -= 14.0;
+        page.text(
+            x,
+            y,
+            Font::Regular,
+            10.0,
+            0.0,
+            &Font::Regular.truncate(line, 10.0, width),
+        );
+    }
+    y
+}
+
+fn table_header(page: &mut Page, y: f32) {
+    page.fill_rect(LEFT, y - 6.0, RIGHT - LEFT, 20.0, 0.93);
+    page.text(LEFT + 6.0, y, Font::Bold, 9.0, 0.0, "Description");
+    page.text_right(QUANTITY_RIGHT, y, Font::Bold, 9.0, 0.0, "Qty");
+    page.text_right(UNIT_PRICE_RIGHT, y, Font::Bold, 9.0, 0.0, "Unit price");
+    page.text_right(RIGHT - 6.0, y, Font::Bold, 9.0, 0.0, "Amount");
+}
+
+/// The first page's heading, returning where the line items start.
+fn first_page(page: &mut Page, invoice: &InvoiceDocument) -> f32 {
+    let top = PAGE_HEIGHT - 62.0;
+    page.text(LEFT, top - 8.0, Font::Bold, 24.0, 0.0, "INVOICE");
+
+    let mut details = vec![
+        ("Invoice number", invoice.number.clone()),
+        ("Issue date", date(invoice.issued_on)),
+    ];
+    if let Some(due_on) = invoice.due_on {
+        details.push(("Due date", date(due_on)));
+    }
+    if let Some((start, end)) = invoice.period {
+        details.push((
+            "Service period",
+            format!("{} to {}", date(start), date(end)),
+        ));
+    }
+    details.push(("Currency", invoice.currency.clone()));
+    let mut y = top;
+    for (label, value) in &details {
+        page.text(LABEL_X, y, Font::Regular, 9.0, MUTED, label);
+        page.text_right(RIGHT, y, Font::Bold, 9.0, 0.0, value);
+        y -= 14.0;
+    }
+
+    let parties_top = y.min(top - 40.0) - 30.0;
+    let seller_end = party(page, LEFT, parties_top, "FROM", &invoice.seller);
+    let buyer_end = party(page, BUYER_X, parties_top, "BILL TO", &invoice.buyer);
+    seller_end.min(buyer_end) - 40.0
+}
+
+fn continuation_page(page: &mut Page, invoice: &InvoiceDocument) -> f32 {
+    let top = PAGE_HEIGHT - 62.0;
+    page.text(LEFT, top, Font::Bold, 12.0, 0.0, "INVOICE");
+    page.text_right(RIGHT, top, Font::Regular, 10.0, MUTED, &invoice.number);
+    top - 40.0
+}
+
+fn totals(page: &mut Page, y: f32, invoice: &InvoiceDocument) -> f32 {
+    let mut y = y - 8.0;
+    let rows = [
+        ("Subtotal".to_string(), invoice.subtotal_cents),
+        (
+            format!("Tax ({})", format_rate(invoice.tax_rate_bps)),
+            invoice.tax_cents,
+        ),
+    ];
+    for (label, cents) in &rows {
+        page.text(LABEL_X, y, Font::Regular, 10.0, 0.0, label);
+        page.text_right(
+            RIGHT - 6.0,
+            y,
+            Font::Regular,
+            10.0,
+            0.0,
+            &format_money(*cents, &invoice.currency),
+        );
+        y -= ROW_HEIGHT;
+    }
+    page.line((LABEL_X, y + 12.0), (RIGHT, y + 12.0), 1.0, 0.0);
+    y -= 4.0;
+    page.text(LABEL_X, y, Font::Bold, 12.0, 0.0, "Total");
+    page.text_right(
+        RIGHT - 6.0,
+        y,
+        Font::Bold,
+        12.0,
+        0.0,
+        &format_money(invoice.total_cents, &invoice.currency),
+    );
+    if invoice.credit_applied_cents != 0 {
+        y -= ROW_HEIGHT;
+        page.text(LABEL_X, y, Font::Regular, 10.0, 0.0, "Credit applied");
+        page.text_right(
+            RIGHT - 6.0,
+            y,
+            Font::Regular,
+            10.0,
+            0.0,
+            &format_money(-invoice.credit_applied_cents, &invoice.currency),
+        );
+        y -= ROW_HEIGHT;
+        page.text(LABEL_X, y, Font::Bold, 12.0, 0.0, "Amount due");
+        page.text_right(
+            RIGHT - 6.0,
+            y,
+            Font::Bold,
+            12.0,
+            0.0,
+            &format_money(invoice.amount_due_cents(), &invoice.currency),
+        );
+    }
+    y
+}
+
+/// Renders the invoice. The same invoice always gives the same bytes.
+pub fn render(invoice: &InvoiceDocument) -> Vec<u8> {
+    let mut pages = Vec::new();
+    let mut page = Page::new();
+    let mut y = first_page(&mut page, invoice);
+    table_header(&mut page, y);
+    y -= ROW_HEIGHT + 6.0;
+
+    for (index, line) in invoice.lines.iter().enumerate() {
+        let last = index + 1 == invoice.lines.len();
+        let needed = if last { invoice.totals_height() } else { 0.0 };
+        if y - needed < BOTTOM && index > 0 {
+            pages.push(std::mem::take(&mut page));
+            y = continuation_page(&mut page, invoice);
+            table_header(&mut page, y);
+            y -= ROW_HEIGHT + 6.0;
+        }
+
+        let description = Font::Regular.truncate(&line.description, 10.0, DESCRIPTION_WIDTH);
+        page.text(LEFT + 6.0, y, Font::Regular, 10.0, 0.0, &description);
+        page.text_right(
+            QUANTITY_RIGHT,
+            y,
+            Font::Regular,
+            10.0,
+            0.0,
+            &line.quantity.to_string(),
+        );
+        page.text_right(
+            UNIT_PRICE_RIGHT,
+            y,
+            Font::Regular,
+            10.0,
+            0.0,
+            &format_money(line.unit_amount_cents, &invoice.currency),
+        );
+        page.text_right(
+            RIGHT - 6.0,
+            y,
+            Font::Regular,
+            10.0,
+            0.0,
+            &format_money(line.amount_cents, &invoice.currency),
+        );
+        page.line((LEFT, y - 6.0), (RIGHT, y - 6.0), 0.5, 0.85);
+        y -= ROW_HEIGHT;
+    }
+
+    if y - invoice.totals_height() < BOTTOM {
+        pages.push(std::mem::take(&mut page));
+        y = continuation_page(&mut page, invoice);
+    }
+    y = totals(&mut page, y, invoice);
+    if let Some(due_on) = invoice.due_on.filter(|_| invoice.amount_due_cents() > 0) {
+        page.text(
+            LEFT,
+            y - 36.0,
+            Font::Regular,
+            9.0,
+            MUTED,
+            &format!(
+                "Please pay {} by {}, quoting {}.",
+                format_money(invoice.amount_due_cents(), &invoice.currency),
+                date(due_on),
+                invoice.number
+            ),
+        );
+    }
+    pages.push(page);
+
+    let count = pages.len();
+    let mut document = Document::new(format!("Invoice {}", invoice.number));
+    for (index, mut page) in pages.into_iter().enumerate() {
+        page.line((LEFT, 48.0), (RIGHT, 48.0), 0.5, 0.85);
+        page.text(LEFT, 34.0, Font::Regular, 8.0, MUTED, &invoice.seller.name);
+        page.text_right(
+            RIGHT,
+            34.0,
+            Font::Regular,
+            8.0,
+            MUTED,
+            &format!("{} - page {} of {}", invoice.number, index + 1, count),
+        );
+        document.push_page(page);
+    }
+    document.to_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SNAPSHOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/pdf/snapshots/invoice.pdf");
+
+    fn day(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn invoice(lines: usize) -> InvoiceDocument {
+        let lines: Vec<Line> = (0..lines)
+            .map(|i| Line {
+                description: format!("Pro plan seat {} (Zürich office)", i + 1),
+                quantity: i as i32 % 3 + 1,
+                unit_amount_cents: 2_900,
+                amount_cents: 2_900 * (i as i64 % 3 + 1),
+            })
+            .collect();
+        let subtotal_cents = lines.iter().map(|line| line.amount_cents).sum();
+        let tax_cents = subtotal_cents * 825 / 10_000;
+        InvoiceDocument {
+            number: "INV-2024-000042".to_string(),
+            issued_on: day("2024-05-01"),
+            due_on: Some(day("2024-05-15")),
+            period: Some((day("2024-05-01"), day("2024-06-01"))),
+            currency: "EUR".to_string(),
+            seller: Party {
+                name: "Mini SaaS Inc.".to_string(),
+                lines: vec![
+                    "1 Market Street".to_string(),
+                    "San Francisco, CA 94105".to_string(),
+                    "Tax ID: US-12-3456789".to_string(),
+                ],
+            },
+            buyer: Party {
+                name: "Ada (Analytical) Lovelace".to_string(),
+                lines: vec!["ada@example.com".to_string()],
+            },
+            lines,
+            subtotal_cents,
+            tax_rate_bps: 825,
+            tax_cents,
+            total_cents: subtotal_cents + tax_cents,
+            credit_applied_cents: 0,
+        }
+    }
+
+    fn text(bytes: &[u8]) -> &str {
+        std::str::from_utf8(bytes).unwrap()
+    }
+
+    /// Set `UPDATE_SNAPSHOTS=1` to rewrite the snapshot after an intended
+    /// layout change.
+    #[test]
+    fn matches_snapshot() {
+        let rendered = render(&invoice(3));
+        if std::env::var("UPDATE_SNAPSHOTS").is_ok() {
+            std::fs::write(SNAPSHOT, &rendered).unwrap();
+        }
+        let snapshot = std::fs::read(SNAPSHOT).unwrap();
+        assert_eq!(text(&rendered), text(&snapshot));
+    }
+
+    #[test]
+    fn renders_deterministically() {
+        assert_eq!(render(&invoice(40)), render(&invoice(40)));
+    }
+
+    #[test]
+    fn prints_parties_items_and_totals() {
+        let rendered = render(&invoice(3));
+        let pdf = text(&rendered);
+        assert!(pdf.starts_with("%PDF-1.4\n"));
+        for expected in [
+            "(INV-2024-000042)",
+            "(Mini SaaS Inc.)",
+            "(Tax ID: US-12-3456789)",
+            r"(Ada \(Analytical\) Lovelace)",
+            r"(Pro plan seat 1 \(Z\374rich office\))",
+            "(58.00 EUR)",
+            "(Subtotal)",
+            "(Tax \\(8.25%\\))",
+            "(14.35 EUR)",
+            "(188.35 EUR)",
+            "(2024-05-01 to 2024-06-01)",
+            "/Count 1",
+        ] {
+            assert!(pdf.contains(expected), "missing {}", expected);
+        }
+    }
+
+    #[test]
+    fn prints_account_credit_and_what_is_left_to_pay() {
+        let pdf = text(&render(&invoice(3))).to_string();
+        assert!(!pdf.contains("(Credit applied)"));
+
+        let mut credited = invoice(3);
+        credited.credit_applied_cents = 4_835;
+        let pdf = text(&render(&credited)).to_string();
+        for expected in [
+            "(Credit applied)",
+            "(-48.35 EUR)",
+            "(Amount due)",
+            "(140.00 EUR)",
+            "(Please pay 140.00 EUR by 2024-05-15, quoting INV-2024-000042.)",
+        ] {
+            assert!(pdf.contains(expected), "missing {}", expected);
+        }
+
+        credited.credit_applied_cents = credited.total_cents;
+        let pdf = text(&render(&credited)).to_string();
+        assert!(pdf.contains("(0.00 EUR)"));
+        assert!(!pdf.contains("Please pay"));
+    }
+
+    #[test]
+    fn spreads_many_items_over_pages() {
+        let rendered = render(&invoice(100));
+        let pdf = text(&rendered);
+        assert!(pdf.contains("/Count 4"));
+        assert!(pdf.contains("(INV-2024-000042 - page 4 of 4)"));
+        assert!(pdf.contains("(Pro plan seat 100 \\(Z\\374rich office\\))"));
+        assert_eq!(pdf.matches("(Total)").count(), 1);
+    }
+
+    #[test]
+    fn formats_money_and_rates() {
+        assert_eq!(format_money(0, "USD"), "0.00 USD");
+        assert_eq!(format_money(5, "USD"), "0.05 USD");
+        assert_eq!(format_money(123_456_789, "EUR"), "1,234,567.89 EUR");
+        assert_eq!(format_money(-100_000, "GBP"), "-1,000.00 GBP");
+        assert_eq!(format_rate(0), "0%");
+        assert_eq!(format_rate(2_000), "20%");
+        assert_eq!(format_rate(750), "7.5%");
+        assert_eq!(format_rate(825), "8.25%");
+    }
+}
@@ -0,0 +1,80 @@
+use serde::Serialize;
+use shared::errors::{AppError, AppResult};
+
+/// A plan users can subscribe to. Limits of `None` are unlimited.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Plan {
+    pub id: &'static str,
+    pub name: &'static str,
+    /// Monthly price in cents.
+    pub price_cents: i64,
+    pub currency: &'static str,
+    pub max_projects: Option<i32>,
+    pub max_tasks: Option<i32>,
+}
+
+pub const FREE: &str = "free";
+
+/// Ordered from the cheapest to the most expensive plan.
+pub const PLANS: [Plan; 4] = [
+    Plan {
+        id: FREE,
+        name: "Free",
+        price_cents: 0,
+        currency: "USD",
+        max_projects: Some(3),
+        max_tasks: Some(100),
+    },
+    Plan {
+        id: "starter",
+        name: "Starter",
+        price_cents: 900,
+        currency: "USD",
+        max_projects: Some(10),
+        max_tasks: Some(1_000),
+    },
+    Plan {
+        id: "pro",
+        name: "Pro",
+        price_cents: 2_900,
+        currency: "USD",
+        max_projects: Some(50),
+        max_tasks: Some(10_000),
+    },
+    Plan {
+        id: "enterprise",
+        name: "Enterprise",
+        price_cents: 9_900,
+        currency: "USD",
+        max_projects: None,
+        max_tasks: None,
+    },
+];
+
+pub fn find(id: &str) -> AppResult<&'static Plan> {
+    PLANS.iter().find(|plan| plan.id == id).ok_or_else(|| {
+        AppError::ValidationError(format!(
+            "Unknown plan '{}', expected one of: {}",
+            id,
+            PLANS.iter().map(|p| p.id).collect::<Vec<_>>().join(", ")
+        ))
+    })
+}
+
+pub fn free() -> &'static Plan {
+    &PLANS[0]
+}
+
+impl Plan {
+    pub fn is_free(&self) -> bool {
+        self.id == FREE
+    }
+
+    fn rank(&self) -> usize {
+        PLANS.iter().position(|p| p.id == self.id).unwrap_or(0)
+    }
+
+    pub fn is_upgrade_from(&self, other: &Plan) -> bool {
+        self.rank() > other.rank()
+    }
+}
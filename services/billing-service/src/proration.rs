@@ -0,0 +1,410 @@
+//! Prorated plan changes.
+//!
+//! Changing between paid plans mid-period keeps the period and settles the
+//! difference for the time that is left. The unused part of the old plan is
+//! credited, the rest of the period on the new plan is charged, and the two
+//! are netted. An upgrade is invoiced for the difference straight away. A
+//! downgrade, including one to the free plan, gets credit notes against the
+//! period's invoices for the part no longer used: what is still owed on them
+//! is reduced, and only what was already paid comes back as account credit,
+//! which is spent on the user's next invoices. Cancelling does not prorate.
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use shared::errors::AppResult;
+use sqlx::PgConnection;
+
+use crate::{
+    credit_notes, invoices,
+    models::{LineItemInput, Subscription},
+    money,
+    plans::Plan,
+};
+
+/// What changing plans at some point of a period is worth, at full-period
+/// prices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlanChange {
+    /// The unused rest of the period on the old plan.
+    pub credit: Decimal,
+    /// The rest of the period on the new plan.
+    pub charge: Decimal,
+}
+
+impl PlanChange {
+    /// Positive when the user owes more, negative when they are owed.
+    pub fn net(&self) -> Decimal {
+        self.charge - self.credit
+    }
+}
+
+/// Prorates a change from `old_price` to `new_price` at `at`, in the period
+/// from `start` to `end`.
+pub fn plan_change(
+    old_price: Decimal,
+    new_price: Decimal,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    at: DateTime<Utc>,
+) -> PlanChange {
+    let total = (end - start).num_seconds();
+    let left = (end - at).num_seconds();
+    PlanChange {
+        credit: money::prorate(old_price, left, total),
+        charge: money::prorate(new_price, left, total),
+    }
+}
+
+/// Settles a change of `subscription` from `from` to `to` at `at`: invoices
+/// an upgrade's difference or credits a downgrade's. Subscriptions without a
+/// running paid period have nothing to settle.
+pub async fn settle_plan_change(
+    conn: &mut PgConnection,
+    subscription: &Subscription,
+    from: &Plan,
+    to: &Plan,
+    at: DateTime<Utc>,
+    tax_rate_bps: i32,
+) -> AppResult<()> {
+    let Some(end) = subscription.expires_at else {
+        return Ok(());
+    };
+    if from.is_free() || subscription.status != "active" || at >= end {
+        return Ok(());
+    }
+
+    let change = plan_change(
+        money::from_cents(from.price_cents),
+        money::from_cents(to.price_cents),
+        subscription.started_at,
+        end,
+        at,
+    );
+    let net = change.net();
+
+    if net > Decimal::ZERO {
+        let items = [LineItemInput {
+            description: format!(
+                "Change from {} to {} plan, {} to {}",
+                from.name,
+                to.name,
+                at.format("%Y-%m-%d"),
+                end.format("%Y-%m-%d")
+            ),
+            quantity: 1,
+            unit_amount_cents: money::to_cents(net)?,
+        }];
+        let invoice_id = invoices::create_draft(
+            conn,
+            subscription.user_id,
+            Some(subscription.id),
+            to.currency,
+            &items,
+            tax_rate_bps,
+            Some((at, end)),
+        )
+        .await?;
+        invoices::transition(conn, invoice_id, "issued").await?;
+    } else if net < Decimal::ZERO {
+        credit_notes::credit_period(
+            conn,
+            subscription,
+            -net,
+            &format!(
+                "Unused time after changing from {} to {} plan, {} to {}",
+                from.name,
+                to.name,
+                at.format("%Y-%m-%d"),
+                end.format("%Y-%m-%d")
+            ),
+            at,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use uuid::Uuid;
+
+    fn d(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    /// May 2024 has 31 days.
+    fn change(old: &str, new: &str, when: &str) -> PlanChange {
+        plan_change(
+            d(old),
+            d(new),
+            at("2024-05-01T00:00:00Z"),
+            at("2024-06-01T00:00:00Z"),
+            at(when),
+        )
+    }
+
+    #[test]
+    fn upgrades_charge_the_difference_for_the_rest_of_the_period() {
+        // 21 of 31 days left.
+        let upgrade = change("9.00", "29.00", "2024-05-11T00:00:00Z");
+        assert_eq!(upgrade.credit, d("6.10")); // 6.0967...
+        assert_eq!(upgrade.charge, d("19.65")); // 19.6451...
+        assert_eq!(upgrade.net(), d("13.55"));
+    }
+
+    #[test]
+    fn downgrades_credit_the_difference() {
+        let downgrade = change("99.00", "29.00", "2024-05-16T12:00:00Z");
+        // 15.5 of 31 days left, exactly half.
+        assert_eq!(downgrade.credit, d("49.50"));
+        assert_eq!(downgrade.charge, d("14.50"));
+        assert_eq!(downgrade.net(), d("-35.00"));
+    }
+
+    #[test]
+    fn downgrading_to_free_credits_all_unused_time() {
+        let to_free = change("29.00", "0.00", "2024-05-24T06:00:00Z");
+        // 7.75 of 31 days left, a quarter.
+        assert_eq!(to_free.credit, d("7.25"));
+        assert_eq!(to_free.charge, d("0.00"));
+        assert_eq!(to_free.net(), d("-7.25"));
+    }
+
+    #[test]
+    fn changes_at_the_edges_of_the_period() {
+        let at_start = change("9.00", "29.00", "2024-05-01T00:00:00Z");
+        assert_eq!((at_start.credit, at_start.charge), (d("9.00"), d("29.00")));
+        assert_eq!(at_start.net(), d("20.00"));
+
+        let at_end = change("9.00", "29.00", "2024-06-01T00:00:00Z");
+        assert_eq!(at_end.net(), d("0.00"));
+
+        // A change after the period is over, or before it began, is clamped.
+        assert_eq!(
+            change("9.00", "29.00", "2024-07-01T00:00:00Z").net(),
+            d("0.00")
+        );
+        assert_eq!(
+            change("9.00", "29.00", "2024-04-01T00:00:00Z").net(),
+            d("20.00")
+        );
+    }
+
+    #[test]
+    fn same_price_changes_cost_nothing() {
+        for when in [
+            "2024-05-01T00:00:00Z",
+            "2024-05-09T13:17:29Z",
+            "2024-05-31T23:59:59Z",
+        ] {
+            assert_eq!(change("29.00", "29.00", when).net(), d("0.00"));
+        }
+    }
+
+    #[test]
+    fn rounds_each_side_to_the_cent() {
+        // One second into the period: nearly everything is left.
+        let change = change("9.00", "29.00", "2024-05-01T00:00:01Z");
+        assert_eq!(change.credit, d("9.00"));
+        assert_eq!(change.charge, d("29.00"));
+        assert_eq!(change.credit.scale(), 2);
+        assert_eq!(change.charge.scale(), 2);
+    }
+
+    #[test]
+    fn up_and_back_down_at_the_same_moment_nets_to_zero() {
+        for when in [
+            "2024-05-03T08:00:00Z",
+            "2024-05-17T19:45:10Z",
+            "2024-05-30T01:02:03Z",
+        ] {
+            let up = change("9.00", "99.00", when);
+            let down = change("99.00", "9.00", when);
+            assert_eq!(up.net() + down.net(), d("0.00"));
+        }
+    }
+
+    /// The billing side of a subscription period, kept the way the database
+    /// keeps it: invoices with the credit applied to them, and credit notes.
+    #[derive(Default)]
+    struct Ledger {
+        /// (id, amount, credit applied, paid), oldest first.
+        invoices: Vec<(Uuid, Decimal, Decimal, bool)>,
+        notes: Vec<credit_notes::Allocation>,
+    }
+
+    impl Ledger {
+        fn balance(&self) -> Decimal {
+            let granted: Decimal = self.notes.iter().map(|note| note.amount).sum();
+            let used: Decimal = self
+                .invoices
+                .iter()
+                .map(|(_, _, applied, _)| *applied)
+                .sum();
+            granted - used
+        }
+
+        /// Issues an invoice and spends account credit on it.
+        fn invoice(&mut self, amount: Decimal) {
+            let applied = money::apply_credit(self.balance(), amount);
+            self.invoices
+                .push((Uuid::new_v4(), amount, applied, applied == amount));
+        }
+
+        fn pay_all(&mut self) {
+            for invoice in &mut self.invoices {
+                invoice.3 = true;
+            }
+        }
+
+        /// Credits `gross` the way `credit_notes::credit_period` does.
+        fn credit(&mut self, gross: Decimal) {
+            let creditable: Vec<credit_notes::Creditable> = self
+                .invoices
+                .iter()
+                .rev()
+                .map(|&(invoice_id, amount, applied, paid)| {
+                    let given_back: Decimal = self
+                        .notes
+                        .iter()
+                        .filter(|note| note.invoice_id == invoice_id && !note.applied)
+                        .map(|note| note.amount)
+                        .sum();
+                    credit_notes::Creditable {
+                        invoice_id,
+                        tax_rate_bps: 0,
+                        paid,
+                        room: amount - applied - if paid { given_back } else { Decimal::ZERO },
+                    }
+                })
+                .collect();
+            for allocation in credit_notes::allocate(&creditable, gross) {
+                if allocation.applied {
+                    let invoice = self
+                        .invoices
+                        .iter_mut()
+                        .find(|invoice| invoice.0 == allocation.invoice_id)
+                        .unwrap();
+                    invoice.2 += allocation.amount;
+                    invoice.3 = invoice.2 == invoice.1;
+                }
+                self.notes.push(allocation);
+            }
+        }
+
+        /// Money still owed on the invoices.
+        fn owed(&self) -> Decimal {
+            self.invoices
+                .iter()
+                .filter(|(.., paid)| !paid)
+                .map(|(_, amount, applied, _)| amount - applied)
+                .sum()
+        }
+
+        /// Changes plans at `when`, invoicing an upgrade and crediting a
+        /// downgrade.
+        fn change(&mut self, old: &str, new: &str, when: &str) {
+            let net = change(old, new, when).net();
+            if net > Decimal::ZERO {
+                self.invoice(net);
+            } else if net < Decimal::ZERO {
+                self.credit(-net);
+            }
+        }
+    }
+
+    #[test]
+    fn unpaid_upgrades_give_no_account_credit_when_undone() {
+        // Free to enterprise starts a period, invoiced and left unpaid.
+        let mut ledger = Ledger::default();
+        ledger.invoice(d("99.00"));
+
+        ledger.change("99.00", "29.00", "2024-05-01T00:00:00Z");
+        assert_eq!(ledger.balance(), d("0.00"));
+        assert_eq!(ledger.owed(), d("29.00"));
+        assert!(ledger.notes.iter().all(|note| note.applied));
+
+        ledger.change("29.00", "99.00", "2024-05-01T00:00:00Z");
+        assert_eq!(ledger.balance(), d("0.00"));
+        assert_eq!(ledger.owed(), d("99.00"));
+
+        // Down to nothing settles the first invoice without money or credit.
+        ledger.change("99.00", "0.00", "2024-05-01T00:00:00Z");
+        assert_eq!(ledger.balance(), d("0.00"));
+        assert_eq!(ledger.owed(), d("0.00"));
+    }
+
+    #[test]
+    fn paid_upgrades_give_back_what_was_paid() {
+        let mut ledger = Ledger::default();
+        ledger.invoice(d("99.00"));
+        ledger.pay_all();
+
+        ledger.change("99.00", "29.00", "2024-05-01T00:00:00Z");
+        assert_eq!(ledger.balance(), d("70.00"));
+
+        // Coming back is paid for with the credit.
+        ledger.change("29.00", "99.00", "2024-05-01T00:00:00Z");
+        assert_eq!(ledger.balance(), d("0.00"));
+        assert_eq!(ledger.owed(), d("0.00"));
+    }
+
+    #[test]
+    fn going_up_and_down_never_makes_credit_out_of_nothing() {
+        for paid_first in [false, true] {
+            let mut ledger = Ledger::default();
+            ledger.invoice(d("29.00"));
+            if paid_first {
+                ledger.pay_all();
+            }
+            let paid = if paid_first { d("29.00") } else { d("0.00") };
+            for when in [
+                "2024-05-03T08:00:00Z",
+                "2024-05-10T12:00:00Z",
+                "2024-05-17T19:45:10Z",
+                "2024-05-30T01:02:03Z",
+            ] {
+                ledger.change("29.00", "99.00", when);
+                ledger.change("99.00", "9.00", when);
+                ledger.change("9.00", "29.00", when);
+                assert!(ledger.balance() >= Decimal::ZERO);
+                assert!(
+                    ledger.balance() <= paid,
+                    "{} of {} paid",
+                    ledger.balance(),
+                    paid
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn net_stays_within_the_price_difference() {
+        let start = at("2024-02-01T00:00:00Z");
+        let end = at("2024-03-01T00:00:00Z");
+        let prices = ["0.00", "9.00", "29.00", "99.00"];
+        for old in prices {
+            for new in prices {
+                let difference = d(new) - d(old);
+                for hours in (0..=29 * 24).step_by(7) {
+                    let when = start + chrono::Duration::hours(hours);
+                    let net = plan_change(d(old), d(new), start, end, when).net();
+                    assert!(
+                        net.abs() <= difference.abs(),
+                        "{} -> {} at {}",
+                        old,
+                        new,
+                        when
+                    );
+                    assert!(net * difference >= Decimal::ZERO);
+                }
+            }
+        }
+    }
+}
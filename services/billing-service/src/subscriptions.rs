@@ -0,0 +1,387 @@
+use chrono::{DateTime, Months, Utc};
+use shared::errors::{AppError, AppResult};
+use sqlx::{Connection, PgConnection, PgPool};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::{
+    invoices,
+    models::{PlanLimits, Subscription},
+    plans,
+};
+
+pub const SUBSCRIPTION_COLUMNS: &str = "id, user_id, plan::text AS plan, status::text AS status,
+    started_at, expires_at, auto_renew, cancel_at_period_end, cancelled_at,
+    max_projects, max_tasks, created_at, updated_at";
+
+/// Most periods a lapsed subscription is renewed by in one pass.
+const MAX_RENEWALS: usize = 24;
+
+/// End of a billing period starting at `start`.
+pub fn period_end(start: DateTime<Utc>) -> DateTime<Utc> {
+    start
+        .checked_add_months(Months::new(1))
+        .unwrap_or(start + chrono::Duration::days(30))
+}
+
+/// Moves a subscription into its next period and issues the invoice for it,
+/// both or neither. Does nothing if the period was already renewed.
+async fn renew(
+    conn: &mut PgConnection,
+    subscription: &Subscription,
+    tax_rate_bps: i32,
+) -> AppResult<bool> {
+    let Some(start) = subscription.expires_at else {
+        return Ok(false);
+    };
+    let end = period_end(start);
+    let plan = plans::find(&subscription.plan)?;
+
+    let mut tx = conn.begin().await?;
+    let updated = sqlx::query(
+        "UPDATE subscriptions SET started_at = $2, expires_at = $3, updated_at = NOW()
+         WHERE id = $1 AND expires_at = $2",
+    )
+    .bind(subscription.id)
+    .bind(start)
+    .bind(end)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if updated == 0 {
+        return Ok(false);
+    }
+    invoices::invoice_period(
+        &mut tx,
+        subscription.id,
+        subscription.user_id,
+        plan,
+        tax_rate_bps,
+        start,
+        end,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+/// Renews paid subscriptions whose period is over, invoicing each new period,
+/// and ends those that are cancelled or not renewing, for one user or for
+/// everyone. Returns how many periods were renewed and subscriptions ended.
+pub async fn settle_due(
+    conn: &mut PgConnection,
+    user_id: Option<Uuid>,
+    tax_rate_bps: i32,
+) -> AppResult<(u64, u64)> {
+    let mut renewed = 0;
+    let mut failed: Vec<Uuid> = Vec::new();
+    for _ in 0..MAX_RENEWALS {
+        let due = sqlx::query_as::<_, Subscription>(&format!(
+            "SELECT {} FROM subscriptions
+             WHERE status = 'active' AND plan <> 'free' AND auto_renew
+               AND NOT cancel_at_period_end AND expires_at <= NOW()
+               AND ($1::uuid IS NULL OR user_id = $1) AND NOT (id = ANY($2))
+             ORDER BY expires_at
+             LIMIT 100",
+            SUBSCRIPTION_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(&failed)
+        .fetch_all(&mut *conn)
+        .await?;
+        if due.is_empty() {
+            break;
+        }
+
+        for subscription in &due {
+            match renew(conn, subscription, tax_rate_bps).await {
+                Ok(true) => renewed += 1,
+                Ok(false) => {}
+                Err(e) if user_id.is_some() => return Err(e),
+                Err(e) => {
+                    tracing::warn!(subscription_id = %subscription.id, error = %e, "Renewal failed");
+                    failed.push(subscription.id);
+                }
+            }
+        }
+    }
+
+    let free = plans::free();
+    let ended = sqlx::query(
+        "UPDATE subscriptions
+         SET status = (CASE WHEN cancel_at_period_end THEN 'cancelled' ELSE 'expired' END)::subscription_status,
+             plan = 'free', max_projects = $2, max_tasks = $3,
+             auto_renew = false, cancel_at_period_end = false, updated_at = NOW()
+         WHERE status = 'active' AND plan <> 'free' AND expires_at <= NOW()
+           AND (cancel_at_period_end OR NOT auto_renew)
+           AND ($1::uuid IS NULL OR user_id = $1)",
+    )
+    .bind(user_id)
+    .bind(free.max_projects)
+    .bind(free.max_tasks)
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    Ok((renewed, ended))
+}
+
+/// Loads the user's subscription, settled up to now and locked for the rest
+/// of the transaction. Users without one get the free plan.
+pub async fn load_for_update(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    tax_rate_bps: i32,
+) -> AppResult<Subscription> {
+    sqlx::query(
+        "INSERT INTO subscriptions (user_id) SELECT id FROM users WHERE id = $1
+         ON CONFLICT (user_id) DO NOTHING",
+    )
+    .bind(user_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query("SELECT 1 FROM subscriptions WHERE user_id = $1 FOR UPDATE")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+    settle_due(conn, Some(user_id), tax_rate_bps).await?;
+
+    sqlx::query_as::<_, Subscription>(&format!(
+        "SELECT {} FROM subscriptions WHERE user_id = $1",
+        SUBSCRIPTION_COLUMNS
+    ))
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Subscription not found".to_string()))
+}
+
+/// Whether `settle_due` would end the subscription at `now` instead of
+/// renewing it.
+fn ends_at(subscription: &Subscription, now: DateTime<Utc>) -> bool {
+    subscription.status == "active"
+        && subscription.plan != plans::FREE
+        && subscription.expires_at.is_some_and(|end| end <= now)
+        && (subscription.cancel_at_period_end || !subscription.auto_renew)
+}
+
+/// Limits of the plan the user is on at `now`, as `settle_due` would leave
+/// them: a period that is over and not renewing counts as ended, one that
+/// renews keeps its plan. Users without a subscription are on the free plan.
+fn limits_at(user_id: Uuid, subscription: Option<&Subscription>, now: DateTime<Utc>) -> PlanLimits {
+    let free = plans::free();
+    match subscription {
+        Some(s) if ends_at(s, now) => PlanLimits {
+            user_id,
+            plan: free.id.to_string(),
+            status: if s.cancel_at_period_end {
+                "cancelled"
+            } else {
+                "expired"
+            }
+            .to_string(),
+            max_projects: free.max_projects,
+            max_tasks: free.max_tasks,
+        },
+        Some(s) => PlanLimits {
+            user_id,
+            plan: s.plan.clone(),
+            status: s.status.clone(),
+            max_projects: s.max_projects,
+            max_tasks: s.max_tasks,
+        },
+        None => PlanLimits {
+            user_id,
+            plan: free.id.to_string(),
+            status: "active".to_string(),
+            max_projects: free.max_projects,
+            max_tasks: free.max_tasks,
+        },
+    }
+}
+
+/// The user's current plan limits. Only reads: renewals and endings are left
+/// to `settle_due`.
+pub async fn current_limits(db: &PgPool, user_id: Uuid) -> AppResult<PlanLimits> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM users WHERE id = $1)")
+        .bind(user_id)
+        .fetch_one(db)
+        .await?;
+    if !exists {
+        return Err(AppError::NotFound("User not found".to_string()));
+    }
+
+    let subscription = sqlx::query_as::<_, Subscription>(&format!(
+        "SELECT {} FROM subscriptions WHERE user_id = $1",
+        SUBSCRIPTION_COLUMNS
+    ))
+    .bind(user_id)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(limits_at(user_id, subscription.as_ref(), Utc::now()))
+}
+
+/// Cancels a subscription at the end of its current period.
+pub async fn cancel_at_period_end(
+    conn: &mut PgConnection,
+    subscription_id: Uuid,
+) -> AppResult<Subscription> {
+    let subscription = sqlx::query_as::<_, Subscription>(&format!(
+        "UPDATE subscriptions
+         SET cancel_at_period_end = true, auto_renew = false,
+             cancelled_at = COALESCE(cancelled_at, NOW()), updated_at = NOW()
+         WHERE id = $1
+         RETURNING {}",
+        SUBSCRIPTION_COLUMNS
+    ))
+    .bind(subscription_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(subscription)
+}
+
+/// Cancels a subscription right away, dropping it to the free plan.
+pub async fn cancel_now(conn: &mut PgConnection, subscription_id: Uuid) -> AppResult<Subscription> {
+    let free = plans::free();
+    let subscription = sqlx::query_as::<_, Subscription>(&format!(
+        "UPDATE subscriptions
+         SET status = 'cancelled', plan = 'free', max_projects = $2, max_tasks = $3,
+             expires_at = NOW(), auto_renew = false, cancel_at_period_end = false,
+             cancelled_at = NOW(), updated_at = NOW()
+         WHERE id = $1
+         RETURNING {}",
+        SUBSCRIPTION_COLUMNS
+    ))
+    .bind(subscription_id)
+    .bind(free.max_projects)
+    .bind(free.max_tasks)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(subscription)
+}
+
+/// Sets `auto_renew`. Turning it on also withdraws a scheduled cancellation.
+pub async fn set_auto_renew(
+    conn: &mut PgConnection,
+    subscription_id: Uuid,
+    auto_renew: bool,
+) -> AppResult<Subscription> {
+    let subscription = sqlx::query_as::<_, Subscription>(&format!(
+        "UPDATE subscriptions
+         SET auto_renew = $2,
+             cancel_at_period_end = cancel_at_period_end AND NOT $2,
+             cancelled_at = CASE WHEN $2 THEN NULL ELSE cancelled_at END,
+             updated_at = NOW()
+         WHERE id = $1
+         RETURNING {}",
+        SUBSCRIPTION_COLUMNS
+    ))
+    .bind(subscription_id)
+    .bind(auto_renew)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(subscription)
+}
+
+/// Runs `settle_due` every `every` in the background.
+pub fn spawn_renewer(db: PgPool, every: Duration, tax_rate_bps: i32) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(every);
+        loop {
+            ticker.tick().await;
+            let result = match db.acquire().await {
+                Ok(mut conn) => settle_due(&mut conn, None, tax_rate_bps).await,
+                Err(e) => Err(e.into()),
+            };
+            match result {
+                Ok((0, 0)) => {}
+                Ok((renewed, ended)) => {
+                    tracing::info!("Renewed {} and ended {} subscription(s)", renewed, ended)
+                }
+                Err(e) => tracing::warn!("Subscription renewal failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subscription(plan: &str, expires_in_days: Option<i64>) -> Subscription {
+        let now = Utc::now();
+        Subscription {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            plan: plan.to_string(),
+            status: "active".to_string(),
+            started_at: now - chrono::Duration::days(30),
+            expires_at: expires_in_days.map(|days| now + chrono::Duration::days(days)),
+            auto_renew: true,
+            cancel_at_period_end: false,
+            cancelled_at: None,
+            max_projects: Some(50),
+            max_tasks: Some(10_000),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn limits_follow_the_plan_until_a_lapsed_period_ends_it() {
+        let now = Utc::now();
+        let user_id = Uuid::new_v4();
+
+        let none = limits_at(user_id, None, now);
+        assert_eq!((none.plan.as_str(), none.max_projects), ("free", Some(3)));
+
+        let current = subscription("pro", Some(10));
+        let limits = limits_at(user_id, Some(&current), now);
+        assert_eq!(
+            (limits.plan.as_str(), limits.max_tasks),
+            ("pro", Some(10_000))
+        );
+
+        let renewing = subscription("pro", Some(-1));
+        let limits = limits_at(user_id, Some(&renewing), now);
+        assert_eq!(
+            (limits.plan.as_str(), limits.status.as_str()),
+            ("pro", "active")
+        );
+
+        let not_renewing = Subscription {
+            auto_renew: false,
+            ..subscription("pro", Some(-1))
+        };
+        let limits = limits_at(user_id, Some(&not_renewing), now);
+        assert_eq!(
+            (
+                limits.plan.as_str(),
+                limits.status.as_str(),
+                limits.max_tasks
+            ),
+            ("free", "expired", Some(100))
+        );
+
+        let cancelled = Subscription {
+            auto_renew: false,
+            cancel_at_period_end: true,
+            ..subscription("pro", Some(-1))
+        };
+        let limits = limits_at(user_id, Some(&cancelled), now);
+        assert_eq!(
+            (limits.plan.as_str(), limits.status.as_str()),
+            ("free", "cancelled")
+        );
+
+        let cancelling = Subscription {
+            auto_renew: false,
+            cancel_at_period_end: true,
+            ..subscription("pro", Some(3))
+        };
+        assert_eq!(limits_at(user_id, Some(&cancelling), now).plan, "pro");
+    }
+}
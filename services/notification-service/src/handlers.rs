@@ -1 +1,121 @@
 // Notification service handlers
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde_json::json;
+use shared::{
+    errors::{AppError, AppResult},
+    auth::{verify_service_secret, SERVICE_SECRET_HEADER},
+    models::{NotificationEvent, PaginatedResponse, PaginationParams},
+};
+use uuid::Uuid;
+
+use crate::{models::Notification, AppState};
+
+fn caller_id(state: &AppState, headers: &HeaderMap) -> AppResult<Uuid> {
+    let token = headers
+        .get("Authorization")
+        .and_then(|header| header.to_str().ok())
+        .ok_or_else(|| AppError::Unauthorized("Missing authorization header".to_string()))?
+        .strip_prefix("Bearer ")
+        .ok_or_else(|| AppError::Unauthorized("Invalid token format".to_string()))?;
+
+    let claims = state.auth.validate_token(token)?;
+    Uuid::parse_str(&claims.sub)
+        .map_err(|_| AppError::InvalidToken("Invalid subject in token".to_string()))
+}
+
+/// Internal endpoint used by other services to hand over events, which must
+/// present the shared service secret. One notification row is stored per
+/// recipient.
+pub async fn ingest_event(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(event): Json<NotificationEvent>,
+) -> AppResult<(StatusCode, Json<serde_json::Value>)> {
+    verify_service_secret(
+        &state.service_secret,
+        headers
+            .get(SERVICE_SECRET_HEADER)
+            .and_then(|value| value.to_str().ok()),
+    )?;
+    if event.kind.trim().is_empty() {
+        return Err(AppError::ValidationError("Event kind is required".to_string()));
+    }
+
+    let result = sqlx::query(
+        "INSERT INTO notifications (user_id, actor_id, kind, payload)
+         SELECT recipient, $2, $3, $4
+         FROM UNNEST($1::uuid[]) AS recipient
+         WHERE recipient IN (SELECT id FROM users)",
+    )
+    .bind(&event.recipient_ids)
+    .bind(event.actor_id)
+    .bind(&event.kind)
+    .bind(&event.payload)
+    .execute(&state.db)
+    .await?;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({"delivered": result.rows_affected()})),
+    ))
+}
+
+pub async fn list_notifications(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<PaginationParams>,
+) -> AppResult<Json<PaginatedResponse<Notification>>> {
+    let user_id = caller_id(&state, &headers)?;
+
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM notifications WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(&state.db)
+        .await?;
+
+    let data = sqlx::query_as::<_, Notification>(
+        "SELECT id, user_id, actor_id, kind, payload, read_at, created_at
+         FROM notifications
+         WHERE user_id = $1
+         ORDER BY created_at DESC
+         LIMIT $2 OFFSET $3",
+    )
+    .bind(user_id)
+    .bind(params.limit())
+    .bind(params.offset())
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(PaginatedResponse {
+        data,
+        page: params.page(),
+        limit: params.limit(),
+        total,
+    }))
+}
+
+pub async fn mark_read(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    let user_id = caller_id(&state, &headers)?;
+
+    let result = sqlx::query(
+        "UPDATE notifications SET read_at = COALESCE(read_at, NOW())
+         WHERE id = $1 AND user_id = $2",
+    )
+    .bind(id)
+    .bind(user_id)
+    .execute(&state.db)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Notification not found".to_string()));
+    }
+
+    Ok(Json(json!({"message": "marked as read"})))
+}
@@ -1,6 +1,19 @@
+// Notification service
 pub mod handlers;
 pub mod models;
 
+use shared::auth::AuthService;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub db: PgPool,
+    pub auth: Arc<AuthService>,
+    /// Shared with the services that hand over events.
+    pub service_secret: String,
+}
+
 pub fn init() {
     tracing::info!("Notification service initialized");
 }
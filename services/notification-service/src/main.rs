@@ -1,12 +1,11 @@
-use axum::Router;
+use axum::{
+    routing::{get, patch, post},
+    Router,
+};
+use notification_service::{handlers, AppState};
 use serde_json::json;
-use shared::database::init_pool;
-use sqlx::PgPool;
-
-#[derive(Clone)]
-pub struct AppState {
-    pub db: PgPool,
-}
+use shared::{auth::AuthService, database::init_pool};
+use std::sync::Arc;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -15,23 +14,41 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv::dotenv().ok();
     let database_url = std::env::var("DATABASE_URL")
         .expect("DATABASE_URL must be set");
+    let jwt_secret = std::env::var("JWT_SECRET")
+        .unwrap_or_else(|_| "super-secret-key-must-be-32-chars-long-!!".to_string());
+    let jwt_expiration: i64 = std::env::var("JWT_EXPIRATION")
+        .unwrap_or_else(|_| "3600".to_string())
+        .parse()
+        .unwrap_or(3600);
+    let service_secret = std::env::var("SERVICE_SECRET")
+        .ok()
+        .filter(|secret| !secret.is_empty())
+        .expect("SERVICE_SECRET must be set");
 
     let db = init_pool(&database_url, 5)
         .await
         .expect("Failed to initialize database pool");
 
-    let state = AppState { db };
+    let auth = Arc::new(AuthService::new(jwt_secret, jwt_expiration));
+    let state = AppState {
+        db,
+        auth,
+        service_secret,
+    };
 
     let router = Router::new()
         .route(
             "/health",
-            axum::routing::get(|| async {
+            get(|| async {
                 axum::Json(json!({
                     "status": "healthy",
                     "service": "notification-service"
                 }))
             }),
         )
+        .route("/events", post(handlers::ingest_event))
+        .route("/notifications", get(handlers::list_notifications))
+        .route("/notifications/:id/read", patch(handlers::mark_read))
         .with_state(state);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3004")
@@ -0,0 +1,204 @@
+use shared::{
+    errors::{AppError, AppResult},
+    models::Claims,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Role of a user on a project, ordered from least to most privileged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ProjectRole {
+    Viewer,
+    Editor,
+    Admin,
+    Owner,
+}
+
+impl ProjectRole {
+    fn from_db(role: &str) -> Self {
+        match role {
+            "owner" => ProjectRole::Owner,
+            "admin" => ProjectRole::Admin,
+            "editor" => ProjectRole::Editor,
+            _ => ProjectRole::Viewer,
+        }
+    }
+
+    pub fn can_edit(self) -> bool {
+        self >= ProjectRole::Editor
+    }
+
+    pub fn is_admin(self) -> bool {
+        self >= ProjectRole::Admin
+    }
+}
+
+pub fn user_id(claims: &Claims) -> AppResult<Uuid> {
+    Uuid::parse_str(&claims.sub)
+        .map_err(|_| AppError::InvalidToken("Invalid subject in token".to_string()))
+}
+
+pub fn is_global_admin(claims: &Claims) -> bool {
+    claims.role == "admin"
+}
+
+/// Resolves the caller's role on a project. Global admins are treated as
+/// project admins everywhere.
+pub async fn project_role(
+    db: &PgPool,
+    project_id: Uuid,
+    claims: &Claims,
+) -> AppResult<Option<ProjectRole>> {
+    let uid = user_id(claims)?;
+
+    let row: Option<(Uuid, Option<String>)> = sqlx::query_as(
+        "SELECT p.owner_id, pm.role::text
+         FROM projects p
+         LEFT JOIN project_members pm ON pm.project_id = p.id AND pm.user_id = $2
+         WHERE p.id = $1 AND p.deleted_at IS NULL",
+    )
+    .bind(project_id)
+    .bind(uid)
+    .fetch_optional(db)
+    .await?;
+
+    let (owner_id, member_role) =
+        row.ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+
+    let role = if owner_id == uid {
+        Some(ProjectRole::Owner)
+    } else {
+        member_role.as_deref().map(ProjectRole::from_db)
+    };
+
+    if is_global_admin(claims) {
+        return Ok(Some(role.unwrap_or(ProjectRole::Admin).max(ProjectRole::Admin)));
+    }
+
+    Ok(role)
+}
+
+pub async fn require_member(
+    db: &PgPool,
+    project_id: Uuid,
+    claims: &Claims,
+) -> AppResult<ProjectRole> {
+    project_role(db, project_id, claims)
+        .await?
+        .ok_or_else(|| AppError::Forbidden("You are not a member of this project".to_string()))
+}
+
+/// Fails when the project is archived; archived projects are read-only.
+pub async fn require_writable(db: &PgPool, project_id: Uuid) -> AppResult<()> {
+    let archived: bool =
+        sqlx::query_scalar("SELECT status = 'archived' FROM projects WHERE id = $1")
+            .bind(project_id)
+            .fetch_optional(db)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+
+    if archived {
+        return Err(AppError::Forbidden(
+            "Project is archived and read-only".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Editor role without the write check, for reads that need editor rights.
+pub async fn require_editor_role(
+    db: &PgPool,
+    project_id: Uuid,
+    claims: &Claims,
+) -> AppResult<ProjectRole> {
+    let role = require_member(db, project_id, claims).await?;
+    if !role.can_edit() {
+        return Err(AppError::Forbidden(
+            "Editor access to this project is required".to_string(),
+        ));
+    }
+    Ok(role)
+}
+
+/// Admin role without the write check, for changes allowed on archived
+/// projects such as unarchiving.
+pub async fn require_admin_role(
+    db: &PgPool,
+    project_id: Uuid,
+    claims: &Claims,
+) -> AppResult<ProjectRole> {
+    let role = require_member(db, project_id, claims).await?;
+    if !role.is_admin() {
+        return Err(AppError::Forbidden(
+            "Admin access to this project is required".to_string(),
+        ));
+    }
+    Ok(role)
+}
+
+/// Editor access to a project that can be written to.
+pub async fn require_editor(
+    db: &PgPool,
+    project_id: Uuid,
+    claims: &Claims,
+) -> AppResult<ProjectRole> {
+    let role = require_editor_role(db, project_id, claims).await?;
+    require_writable(db, project_id).await?;
+    Ok(role)
+}
+
+/// Admin access to a project that can be written to.
+pub async fn require_admin(
+    db: &PgPool,
+    project_id: Uuid,
+    claims: &Claims,
+) -> AppResult<ProjectRole> {
+    let role = require_admin_role(db, project_id, claims).await?;
+    require_writable(db, project_id).await?;
+    Ok(role)
+}
+
+/// Returns the project a task belongs to. Tasks in the trash are not found.
+pub async fn task_project_id(db: &PgPool, task_id: Uuid) -> AppResult<Uuid> {
+    sqlx::query_scalar("SELECT project_id FROM tasks WHERE id = $1 AND deleted_at IS NULL")
+        .bind(task_id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Task not found".to_string()))
+}
+
+/// Owner and members of a project with their email, used to resolve mentions
+/// and assignees.
+pub async fn project_member_emails(db: &PgPool, project_id: Uuid) -> AppResult<Vec<(Uuid, String)>> {
+    let members = sqlx::query_as(
+        "SELECT u.id, u.email
+         FROM users u
+         WHERE u.id IN (
+             SELECT owner_id FROM projects WHERE id = $1
+             UNION
+             SELECT user_id FROM project_members WHERE project_id = $1
+         )",
+    )
+    .bind(project_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(members)
+}
+
+/// Whether a user is the owner or a member of a project.
+pub async fn is_project_member(db: &PgPool, project_id: Uuid, user_id: Uuid) -> AppResult<bool> {
+    let is_member = sqlx::query_scalar(
+        "SELECT EXISTS (
+             SELECT 1 FROM projects WHERE id = $1 AND owner_id = $2
+             UNION ALL
+             SELECT 1 FROM project_members WHERE project_id = $1 AND user_id = $2
+         )",
+    )
+    .bind(project_id)
+    .bind(user_id)
+    .fetch_one(db)
+    .await?;
+
+    Ok(is_member)
+}
@@ -0,0 +1,312 @@
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use shared::errors::{AppError, AppResult};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::webhooks;
+
+/// Fields that change on every write and carry no history of their own.
+const IGNORED_FIELDS: [&str; 3] = ["created_at", "updated_at", "version"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entity {
+    Project,
+    Task,
+}
+
+impl Entity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Entity::Project => "project",
+            Entity::Task => "task",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+fn to_value<T: Serialize>(value: &T) -> AppResult<Value> {
+    serde_json::to_value(value)
+        .map_err(|e| AppError::InternalError(format!("Failed to serialize activity: {}", e)))
+}
+
+/// Top-level fields whose serialized value differs between `old` and `new`,
+/// in field name order.
+pub fn diff<T: Serialize>(old: &T, new: &T) -> AppResult<Vec<FieldChange>> {
+    let (Value::Object(old), Value::Object(new)) = (to_value(old)?, to_value(new)?) else {
+        return Ok(Vec::new());
+    };
+
+    let mut fields: Vec<&String> = old.keys().chain(new.keys()).collect();
+    fields.sort();
+    fields.dedup();
+
+    Ok(fields
+        .into_iter()
+        .filter(|f| !IGNORED_FIELDS.contains(&f.as_str()))
+        .filter_map(|f| {
+            let before = old.get(f).cloned().unwrap_or(Value::Null);
+            let after = new.get(f).cloned().unwrap_or(Value::Null);
+            (before != after).then(|| FieldChange {
+                field: f.clone(),
+                old: before,
+                new: after,
+            })
+        })
+        .collect())
+}
+
+/// Appends one entry to the activity log. Whole-entity events are also
+/// queued for the project's webhooks.
+#[allow(clippy::too_many_arguments)]
+pub async fn record_event(
+    tx: &mut Transaction<'_, Postgres>,
+    project_id: Uuid,
+    entity: Entity,
+    entity_id: Uuid,
+    actor_id: Option<Uuid>,
+    action: &str,
+    field: Option<&str>,
+    old_value: Option<Value>,
+    new_value: Option<Value>,
+) -> AppResult<()> {
+    sqlx::query(
+        "INSERT INTO activity_log (project_id, entity_type, entity_id, actor_id, action, field,
+                                   old_value, new_value)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+    )
+    .bind(project_id)
+    .bind(entity.as_str())
+    .bind(entity_id)
+    .bind(actor_id)
+    .bind(action)
+    .bind(field)
+    .bind(&old_value)
+    .bind(&new_value)
+    .execute(&mut **tx)
+    .await?;
+
+    if field.is_none() {
+        let data = json!({"before": old_value, "after": new_value});
+        webhooks::enqueue(tx, project_id, entity, entity_id, actor_id, action, data).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn record_created<T: Serialize>(
+    tx: &mut Transaction<'_, Postgres>,
+    project_id: Uuid,
+    entity: Entity,
+    entity_id: Uuid,
+    actor_id: Option<Uuid>,
+    value: &T,
+) -> AppResult<()> {
+    let value = to_value(value)?;
+    record_event(
+        tx,
+        project_id,
+        entity,
+        entity_id,
+        actor_id,
+        "created",
+        None,
+        None,
+        Some(value),
+    )
+    .await
+}
+
+fn changes_value(changes: Vec<FieldChange>) -> Value {
+    let changes: Map<String, Value> = changes
+        .into_iter()
+        .map(|c| (c.field, json!({"old": c.old, "new": c.new})))
+        .collect();
+    Value::Object(changes)
+}
+
+/// Records one `updated` entry per changed field, and a single webhook event
+/// for the whole update.
+pub async fn record_updated<T: Serialize>(
+    tx: &mut Transaction<'_, Postgres>,
+    project_id: Uuid,
+    entity: Entity,
+    entity_id: Uuid,
+    actor_id: Option<Uuid>,
+    old: &T,
+    new: &T,
+) -> AppResult<()> {
+    let changes = diff(old, new)?;
+    if changes.is_empty() {
+        return Ok(());
+    }
+    for change in &changes {
+        record_event(
+            tx,
+            project_id,
+            entity,
+            entity_id,
+            actor_id,
+            "updated",
+            Some(&change.field),
+            Some(change.old.clone()),
+            Some(change.new.clone()),
+        )
+        .await?;
+    }
+
+    let data = json!({"changes": changes_value(changes), "after": to_value(new)?});
+    webhooks::enqueue(tx, project_id, entity, entity_id, actor_id, "updated", data).await
+}
+
+pub async fn record_deleted<T: Serialize>(
+    tx: &mut Transaction<'_, Postgres>,
+    project_id: Uuid,
+    entity: Entity,
+    entity_id: Uuid,
+    actor_id: Option<Uuid>,
+    value: &T,
+) -> AppResult<()> {
+    let value = to_value(value)?;
+    record_event(
+        tx,
+        project_id,
+        entity,
+        entity_id,
+        actor_id,
+        "deleted",
+        None,
+        Some(value),
+        None,
+    )
+    .await
+}
+
+/// Records a single field change made outside a full entity update, such as
+/// moving a task between sprints.
+#[allow(clippy::too_many_arguments)]
+pub async fn record_field<T: Serialize>(
+    tx: &mut Transaction<'_, Postgres>,
+    project_id: Uuid,
+    entity: Entity,
+    entity_id: Uuid,
+    actor_id: Option<Uuid>,
+    field: &str,
+    old: &T,
+    new: &T,
+) -> AppResult<()> {
+    let (old, new) = (to_value(old)?, to_value(new)?);
+    if old == new {
+        return Ok(());
+    }
+    record_event(
+        tx,
+        project_id,
+        entity,
+        entity_id,
+        actor_id,
+        "updated",
+        Some(field),
+        Some(old.clone()),
+        Some(new.clone()),
+    )
+    .await?;
+
+    let data = json!({"changes": changes_value(vec![FieldChange {
+        field: field.to_string(),
+        old,
+        new,
+    }])});
+    webhooks::enqueue(tx, project_id, entity, entity_id, actor_id, "updated", data).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(field: &str, old: Value, new: Value) -> FieldChange {
+        FieldChange {
+            field: field.to_string(),
+            old,
+            new,
+        }
+    }
+
+    #[test]
+    fn lists_changed_fields_in_name_order() {
+        let old = json!({
+            "title": "Draft",
+            "status": "todo",
+            "priority": "low",
+            "assignee_id": null,
+            "labels": ["bug"],
+        });
+        let new = json!({
+            "title": "Draft",
+            "status": "done",
+            "priority": "low",
+            "assignee_id": "1c5e0a4e-52d2-4a8f-9a43-7b0d0a1f2c3d",
+            "labels": ["bug", "ui"],
+        });
+        assert_eq!(
+            diff(&old, &new).unwrap(),
+            [
+                change(
+                    "assignee_id",
+                    Value::Null,
+                    json!("1c5e0a4e-52d2-4a8f-9a43-7b0d0a1f2c3d")
+                ),
+                change("labels", json!(["bug"]), json!(["bug", "ui"])),
+                change("status", json!("todo"), json!("done")),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_bookkeeping_fields() {
+        let old = json!({"title": "A", "version": 1, "updated_at": "2024-05-01T00:00:00Z"});
+        let new = json!({"title": "A", "version": 2, "updated_at": "2024-05-02T00:00:00Z"});
+        assert!(diff(&old, &new).unwrap().is_empty());
+
+        let new = json!({"title": "B", "version": 2, "created_at": "2024-05-02T00:00:00Z"});
+        assert_eq!(
+            diff(&old, &new).unwrap(),
+            [change("title", json!("A"), json!("B"))]
+        );
+    }
+
+    #[test]
+    fn treats_missing_fields_as_null() {
+        let old = json!({"description": "Notes"});
+        let new = json!({"deadline": "2024-06-01T00:00:00Z"});
+        assert_eq!(
+            diff(&old, &new).unwrap(),
+            [
+                change("deadline", Value::Null, json!("2024-06-01T00:00:00Z")),
+                change("description", json!("Notes"), Value::Null),
+            ]
+        );
+        assert!(diff(&json!({"a": null}), &json!({})).unwrap().is_empty());
+    }
+
+    #[test]
+    fn compares_nested_values_whole() {
+        let old = json!({"custom_fields": {"points": 3, "kind": "bug"}});
+        let new = json!({"custom_fields": {"kind": "bug", "points": 5}});
+        assert_eq!(
+            diff(&old, &new).unwrap(),
+            [change(
+                "custom_fields",
+                json!({"points": 3, "kind": "bug"}),
+                json!({"kind": "bug", "points": 5})
+            )]
+        );
+        assert!(diff(&json!([1, 2]), &json!([1, 3])).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,327 @@
+use chrono::NaiveDate;
+use serde_json::{Map, Value};
+use shared::errors::{AppError, AppResult};
+use uuid::Uuid;
+
+use crate::models::CustomField;
+
+pub const FIELD_TYPES: [&str; 7] = [
+    "text",
+    "number",
+    "date",
+    "single_select",
+    "multi_select",
+    "user",
+    "checkbox",
+];
+
+const MAX_TEXT_LENGTH: usize = 10_000;
+
+/// Finds a field by id or case-insensitive name.
+pub fn find_field<'a>(fields: &'a [CustomField], key: &str) -> Option<&'a CustomField> {
+    match Uuid::parse_str(key) {
+        Ok(id) => fields.iter().find(|f| f.id == id),
+        Err(_) => fields.iter().find(|f| f.name.eq_ignore_ascii_case(key)),
+    }
+}
+
+pub fn field_options(field: &CustomField) -> Vec<String> {
+    field
+        .options
+        .as_array()
+        .map(|opts| {
+            opts.iter()
+                .filter_map(|o| o.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Validates a single value against its field definition and returns the
+/// normalized value to store.
+pub fn normalize_value(field: &CustomField, value: &Value, members: &[Uuid]) -> Result<Value, String> {
+    match field.field_type.as_str() {
+        "text" => match value.as_str() {
+            Some(s) if s.chars().count() <= MAX_TEXT_LENGTH => Ok(Value::String(s.to_string())),
+            Some(_) => Err(format!("must be at most {} characters", MAX_TEXT_LENGTH)),
+            None => Err("must be a string".to_string()),
+        },
+        "number" => match value.as_f64() {
+            Some(n) if n.is_finite() => Ok(value.clone()),
+            _ => Err("must be a number".to_string()),
+        },
+        "date" => value
+            .as_str()
+            .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok())
+            .map(|d| Value::String(d.format("%Y-%m-%d").to_string()))
+            .ok_or_else(|| "must be a date formatted as YYYY-MM-DD".to_string()),
+        "single_select" => {
+            let options = field_options(field);
+            match value.as_str() {
+                Some(s) if options.iter().any(|o| o == s) => Ok(Value::String(s.to_string())),
+                _ => Err(format!("must be one of: {}", options.join(", "))),
+            }
+        }
+        "multi_select" => {
+            let options = field_options(field);
+            let items = value
+                .as_array()
+                .ok_or_else(|| "must be an array of options".to_string())?;
+
+            let mut selected: Vec<String> = Vec::new();
+            for item in items {
+                match item.as_str() {
+                    Some(s) if options.iter().any(|o| o == s) => {
+                        if !selected.iter().any(|x| x == s) {
+                            selected.push(s.to_string());
+                        }
+                    }
+                    _ => return Err(format!("values must be among: {}", options.join(", "))),
+                }
+            }
+            Ok(Value::Array(selected.into_iter().map(Value::String).collect()))
+        }
+        "user" => {
+            let id = value
+                .as_str()
+                .and_then(|s| Uuid::parse_str(s).ok())
+                .ok_or_else(|| "must be a user id".to_string())?;
+            if !members.contains(&id) {
+                return Err("must reference a member of the project".to_string());
+            }
+            Ok(Value::String(id.to_string()))
+        }
+        "checkbox" => match value {
+            Value::Bool(_) => Ok(value.clone()),
+            _ => Err("must be true or false".to_string()),
+        },
+        other => Err(format!("has unsupported type '{}'", other)),
+    }
+}
+
+/// Validates submitted custom field values and merges them onto `existing`.
+/// Keys may be field ids or names; a `null` value clears the field. Required
+/// fields can never be cleared, and when `enforce_required` is set (task
+/// creation) they must all have a value once the merge is done.
+pub fn apply_values(
+    fields: &[CustomField],
+    existing: &Value,
+    input: &Map<String, Value>,
+    members: &[Uuid],
+    enforce_required: bool,
+) -> AppResult<Value> {
+    let mut values = existing.as_object().cloned().unwrap_or_default();
+
+    for (key, value) in input {
+        let field = find_field(fields, key)
+            .ok_or_else(|| AppError::ValidationError(format!("Unknown custom field '{}'", key)))?;
+        let field_key = field.id.to_string();
+
+        if value.is_null() {
+            if field.required {
+                return Err(AppError::ValidationError(format!(
+                    "Custom field '{}' is required",
+                    field.name
+                )));
+            }
+            values.remove(&field_key);
+            continue;
+        }
+
+        let normalized = normalize_value(field, value, members).map_err(|msg| {
+            AppError::ValidationError(format!("Custom field '{}' {}", field.name, msg))
+        })?;
+        values.insert(field_key, normalized);
+    }
+
+    if enforce_required {
+        if let Some(missing) = fields
+            .iter()
+            .find(|f| f.required && !values.contains_key(&f.id.to_string()))
+        {
+            return Err(AppError::ValidationError(format!(
+                "Custom field '{}' is required",
+                missing.name
+            )));
+        }
+    }
+
+    Ok(Value::Object(values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use serde_json::json;
+
+    fn field(name: &str, field_type: &str, options: &[&str], required: bool) -> CustomField {
+        CustomField {
+            id: Uuid::new_v4(),
+            project_id: Uuid::nil(),
+            name: name.to_string(),
+            field_type: field_type.to_string(),
+            options: json!(options),
+            required,
+            position: 0,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn validation_error(result: AppResult<Value>) -> String {
+        match result {
+            Err(AppError::ValidationError(message)) => message,
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn normalizes_values_per_field_type() {
+        let member = Uuid::new_v4();
+        let stranger = Uuid::new_v4();
+        let long_text = "é".repeat(MAX_TEXT_LENGTH);
+
+        // Field type, options, submitted value, and what is stored or why not.
+        type Case = (&'static str, &'static [&'static str], Value, Result<Value, &'static str>);
+        let cases: Vec<Case> = vec![
+            ("text", &[], json!("hello"), Ok(json!("hello"))),
+            ("text", &[], json!(long_text), Ok(json!(long_text))),
+            (
+                "text",
+                &[],
+                json!(format!("{}e", long_text)),
+                Err("must be at most 10000 characters"),
+            ),
+            ("text", &[], json!(5), Err("must be a string")),
+            ("number", &[], json!(3), Ok(json!(3))),
+            ("number", &[], json!(-2.5), Ok(json!(-2.5))),
+            ("number", &[], json!("3"), Err("must be a number")),
+            ("number", &[], json!(null), Err("must be a number")),
+            ("date", &[], json!("2024-02-29"), Ok(json!("2024-02-29"))),
+            ("date", &[], json!("2024-2-3"), Ok(json!("2024-02-03"))),
+            ("date", &[], json!("2023-02-29"), Err("must be a date formatted as YYYY-MM-DD")),
+            ("date", &[], json!("03/02/2024"), Err("must be a date formatted as YYYY-MM-DD")),
+            ("single_select", &["Low", "High"], json!("High"), Ok(json!("High"))),
+            ("single_select", &["Low", "High"], json!("high"), Err("must be one of: Low, High")),
+            ("single_select", &["Low", "High"], json!(["Low"]), Err("must be one of: Low, High")),
+            ("multi_select", &["a", "b"], json!(["b", "a", "b"]), Ok(json!(["b", "a"]))),
+            ("multi_select", &["a", "b"], json!([]), Ok(json!([]))),
+            ("multi_select", &["a", "b"], json!("a"), Err("must be an array of options")),
+            ("multi_select", &["a", "b"], json!(["a", "c"]), Err("values must be among: a, b")),
+            ("user", &[], json!(member.to_string()), Ok(json!(member.to_string()))),
+            (
+                "user",
+                &[],
+                json!(member.to_string().to_uppercase()),
+                Ok(json!(member.to_string())),
+            ),
+            (
+                "user",
+                &[],
+                json!(stranger.to_string()),
+                Err("must reference a member of the project"),
+            ),
+            ("user", &[], json!("bob"), Err("must be a user id")),
+            ("checkbox", &[], json!(true), Ok(json!(true))),
+            ("checkbox", &[], json!(false), Ok(json!(false))),
+            ("checkbox", &[], json!("true"), Err("must be true or false")),
+            ("colour", &[], json!("red"), Err("has unsupported type 'colour'")),
+        ];
+
+        for (field_type, options, input, expected) in cases {
+            let field = field("Field", field_type, options, false);
+            assert_eq!(
+                normalize_value(&field, &input, &[member]),
+                expected.map_err(str::to_string),
+                "{} {}",
+                field_type,
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn merges_values_by_id_or_name() {
+        let points = field("Points", "number", &[], false);
+        let notes = field("Notes", "text", &[], false);
+        let fields = vec![points.clone(), notes.clone()];
+        let existing = json!({ points.id.to_string(): 1, notes.id.to_string(): "old" });
+
+        let input = json!({ "points": 5, notes.id.to_string(): "new" });
+        let merged =
+            apply_values(&fields, &existing, input.as_object().unwrap(), &[], false).unwrap();
+        assert_eq!(
+            merged,
+            json!({ points.id.to_string(): 5, notes.id.to_string(): "new" })
+        );
+
+        let input = json!({ "NOTES": null });
+        let merged =
+            apply_values(&fields, &existing, input.as_object().unwrap(), &[], false).unwrap();
+        assert_eq!(merged, json!({ points.id.to_string(): 1 }));
+
+        // Values are only ever stored under field ids.
+        let merged = apply_values(&fields, &json!(null), &Map::new(), &[], false).unwrap();
+        assert_eq!(merged, json!({}));
+    }
+
+    #[test]
+    fn reports_invalid_and_missing_values() {
+        let points = field("Points", "number", &[], false);
+        let team = field("Team", "single_select", &["Web", "Mobile"], true);
+        let fields = vec![points.clone(), team.clone()];
+        let with_team = json!({ team.id.to_string(): "Web" });
+
+        let cases: Vec<(Value, Value, bool, &str)> = vec![
+            (json!({}), json!({ "Colour": "red" }), false, "Unknown custom field 'Colour'"),
+            (json!({}), json!({ "Points": "lots" }), false, "Custom field 'Points' must be a number"),
+            (with_team.clone(), json!({ "Team": null }), false, "Custom field 'Team' is required"),
+            (json!({}), json!({ "Points": 3 }), true, "Custom field 'Team' is required"),
+            (
+                with_team.clone(),
+                json!({ "Team": "Desktop" }),
+                true,
+                "Custom field 'Team' must be one of: Web, Mobile",
+            ),
+        ];
+        for (existing, input, enforce_required, message) in cases {
+            let result =
+                apply_values(&fields, &existing, input.as_object().unwrap(), &[], enforce_required);
+            assert_eq!(validation_error(result), message, "{}", input);
+        }
+
+        // Required fields only need a value once the merge is done.
+        let input = json!({ "Points": 3 });
+        apply_values(&fields, &with_team, input.as_object().unwrap(), &[], true).unwrap();
+        let input = json!({ "Team": "Mobile" });
+        apply_values(&fields, &json!({}), input.as_object().unwrap(), &[], true).unwrap();
+    }
+
+    #[test]
+    fn finds_fields_by_id_or_name() {
+        let fields = vec![
+            field("Points", "number", &[], false),
+            field("Est. hours", "number", &[], false),
+        ];
+        let by_id = fields[1].id.to_string();
+
+        assert_eq!(find_field(&fields, "points").map(|f| f.id), Some(fields[0].id));
+        assert_eq!(find_field(&fields, "EST. HOURS").map(|f| f.id), Some(fields[1].id));
+        assert_eq!(find_field(&fields, &by_id).map(|f| f.id), Some(fields[1].id));
+        assert!(find_field(&fields, "Hours").is_none());
+        assert!(find_field(&fields, &Uuid::new_v4().to_string()).is_none());
+    }
+
+    #[test]
+    fn reads_options_defensively() {
+        let mut select = field("Team", "single_select", &["Web", "Mobile"], false);
+        assert_eq!(field_options(&select), ["Web", "Mobile"]);
+
+        select.options = json!(["Web", 3, null, "Mobile"]);
+        assert_eq!(field_options(&select), ["Web", "Mobile"]);
+
+        select.options = json!(null);
+        assert!(field_options(&select).is_empty());
+    }
+}
@@ -0,0 +1,171 @@
+//! Optimistic concurrency for projects and tasks. Responses carry the row
+//! version as an `ETag`; writes honour `If-Match` and fail with 412 when the
+//! row has changed since the client read it.
+
+use axum::http::{header, HeaderMap, HeaderName};
+use shared::errors::{AppError, AppResult};
+
+pub fn etag(version: i32) -> String {
+    format!("\"{}\"", version)
+}
+
+/// Header for a response that returns a project or task.
+pub fn header(version: i32) -> [(HeaderName, String); 1] {
+    [(header::ETAG, etag(version))]
+}
+
+/// Checks `If-Match` against the current version. Returns the version the
+/// write must still find in the database, or `None` when the request is not
+/// conditional (no header, or `*`). With `required`, a missing header is
+/// rejected with 428.
+///
+/// As RFC 9110 requires for `If-Match`, tags are compared strongly: a weak
+/// tag (`W/"3"`) never matches, and neither does an unquoted version.
+pub fn expected_version(
+    headers: &HeaderMap,
+    current: i32,
+    required: bool,
+) -> AppResult<Option<i32>> {
+    let Some(value) = headers.get(header::IF_MATCH) else {
+        if required {
+            return Err(AppError::PreconditionRequired(
+                "This request requires an If-Match header".to_string(),
+            ));
+        }
+        return Ok(None);
+    };
+    let value = value
+        .to_str()
+        .map_err(|_| AppError::BadRequest("Invalid If-Match header".to_string()))?;
+
+    if value.trim() == "*" {
+        return Ok(None);
+    }
+
+    let current_tag = etag(current);
+    let matches = value.split(',').any(|tag| tag.trim() == current_tag);
+    if !matches {
+        return Err(modified(current));
+    }
+    Ok(Some(current))
+}
+
+/// Error for a write whose expected version is no longer current.
+pub fn modified(current: i32) -> AppError {
+    AppError::PreconditionFailed(format!(
+        "Resource has been modified; the current ETag is {}",
+        etag(current)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        http::{HeaderValue, StatusCode},
+        response::IntoResponse,
+    };
+
+    fn if_match(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_MATCH, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    fn status(result: AppResult<Option<i32>>) -> StatusCode {
+        match result {
+            Ok(version) => panic!("expected an error, got {:?}", version),
+            Err(err) => err.into_response().status(),
+        }
+    }
+
+    #[test]
+    fn formats_versions_as_strong_tags() {
+        assert_eq!(etag(3), "\"3\"");
+        assert_eq!(header(3), [(header::ETAG, "\"3\"".to_string())]);
+    }
+
+    #[test]
+    fn missing_header_is_unconditional_unless_required() {
+        assert_eq!(expected_version(&HeaderMap::new(), 3, false).unwrap(), None);
+        assert_eq!(
+            status(expected_version(&HeaderMap::new(), 3, true)),
+            StatusCode::PRECONDITION_REQUIRED
+        );
+    }
+
+    #[test]
+    fn matches_the_current_version() {
+        for value in [
+            "\"3\"",
+            " \"3\" ",
+            "\"2\", \"3\"",
+            "\"3\",\"4\"",
+            "W/\"3\", \"3\"",
+        ] {
+            for required in [false, true] {
+                assert_eq!(
+                    expected_version(&if_match(value), 3, required).unwrap(),
+                    Some(3),
+                    "{}",
+                    value
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn wildcard_is_unconditional() {
+        for required in [false, true] {
+            assert_eq!(expected_version(&if_match("*"), 3, required).unwrap(), None);
+            assert_eq!(
+                expected_version(&if_match(" * "), 3, required).unwrap(),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn anything_else_fails_the_precondition() {
+        for value in [
+            "\"2\"",
+            "\"2\", \"4\"",
+            "W/\"3\"",
+            "w/\"3\"",
+            "3",
+            "\"03\"",
+            "\"3",
+            "\"\"",
+            "",
+            "\"*\"",
+        ] {
+            assert_eq!(
+                status(expected_version(&if_match(value), 3, false)),
+                StatusCode::PRECONDITION_FAILED,
+                "{}",
+                value
+            );
+        }
+
+        match expected_version(&if_match("\"2\""), 3, false) {
+            Err(AppError::PreconditionFailed(message)) => assert_eq!(
+                message,
+                "Resource has been modified; the current ETag is \"3\""
+            ),
+            other => panic!("expected 412, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_unreadable_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::IF_MATCH,
+            HeaderValue::from_bytes(b"\"3\xff\"").unwrap(),
+        );
+        assert_eq!(
+            status(expected_version(&headers, 3, false)),
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
@@ -1,3 +1,21 @@
 // Project service handlers
+pub mod activity;
+pub mod attachment;
+pub mod bulk;
+pub mod calendar;
+pub mod comment;
+pub mod custom_field;
+pub mod export;
+pub mod import;
+pub mod label;
 pub mod project;
+pub mod recurrence;
+pub mod report;
+pub mod sprint;
 pub mod task;
+pub mod template;
+pub mod time_entry;
+pub mod trash;
+pub mod usage;
+pub mod watcher;
+pub mod webhook;
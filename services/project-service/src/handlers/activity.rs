@@ -0,0 +1,264 @@
+use axum::{
+    extract::{Path, Query, State},
+    Extension, Json,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use shared::{
+    errors::{AppError, AppResult},
+    models::{Claims, PaginatedResponse},
+};
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::{
+    access,
+    handlers::time_entry::resolve_range,
+    models::{ActivityEntry, ActivityParams, DateRangeParams, FlowMetrics},
+    AppState,
+};
+
+const ACTIVITY_COLUMNS: &str = "a.id, a.project_id, a.entity_type, a.entity_id, a.actor_id,
+    a.action, a.field, a.old_value, a.new_value, a.created_at";
+
+async fn activity_page(
+    db: &PgPool,
+    project_id: Uuid,
+    task_id: Option<Uuid>,
+    params: &ActivityParams,
+) -> AppResult<PaginatedResponse<ActivityEntry>> {
+    if let Some(entity_type) = &params.entity_type {
+        if !matches!(entity_type.as_str(), "project" | "task") {
+            return Err(AppError::BadRequest(format!(
+                "Invalid entity_type '{}', expected 'project' or 'task'",
+                entity_type
+            )));
+        }
+    }
+
+    let push_filters = |qb: &mut QueryBuilder<'_, Postgres>| {
+        qb.push(" WHERE a.project_id = ").push_bind(project_id);
+        if let Some(task_id) = task_id {
+            qb.push(" AND a.entity_type = 'task' AND a.entity_id = ")
+                .push_bind(task_id);
+        }
+        if let Some(entity_type) = &params.entity_type {
+            qb.push(" AND a.entity_type = ")
+                .push_bind(entity_type.clone());
+        }
+        if let Some(actor_id) = params.actor_id {
+            qb.push(" AND a.actor_id = ").push_bind(actor_id);
+        }
+    };
+    let pagination = params.pagination();
+
+    let mut count_qb = QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM activity_log a");
+    push_filters(&mut count_qb);
+    let total: i64 = count_qb.build_query_scalar().fetch_one(db).await?;
+
+    let mut qb =
+        QueryBuilder::<Postgres>::new(format!("SELECT {} FROM activity_log a", ACTIVITY_COLUMNS));
+    push_filters(&mut qb);
+    qb.push(" ORDER BY a.created_at DESC, a.id LIMIT ")
+        .push_bind(pagination.limit())
+        .push(" OFFSET ")
+        .push_bind(pagination.offset());
+    let entries: Vec<ActivityEntry> = qb.build_query_as().fetch_all(db).await?;
+
+    Ok(PaginatedResponse {
+        data: entries,
+        page: pagination.page(),
+        limit: pagination.limit(),
+        total,
+    })
+}
+
+/// Timeline of a task, newest first. Still available after the task is
+/// deleted.
+pub async fn get_task_activity(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(task_id): Path<Uuid>,
+    Query(params): Query<ActivityParams>,
+) -> AppResult<Json<PaginatedResponse<ActivityEntry>>> {
+    let project_id: Uuid = sqlx::query_scalar(
+        "SELECT project_id FROM tasks WHERE id = $1
+         UNION ALL
+         SELECT project_id FROM activity_log WHERE entity_type = 'task' AND entity_id = $1
+         LIMIT 1",
+    )
+    .bind(task_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Task not found".to_string()))?;
+    access::require_member(&state.db, project_id, &claims).await?;
+
+    Ok(Json(
+        activity_page(&state.db, project_id, Some(task_id), &params).await?,
+    ))
+}
+
+/// Timeline of a project and all of its tasks, newest first.
+pub async fn get_project_activity(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+    Query(params): Query<ActivityParams>,
+) -> AppResult<Json<PaginatedResponse<ActivityEntry>>> {
+    access::require_member(&state.db, project_id, &claims).await?;
+
+    Ok(Json(
+        activity_page(&state.db, project_id, None, &params).await?,
+    ))
+}
+
+/// When a completed task was created, first moved to `in_progress` and last
+/// moved to `done`.
+#[derive(Debug, FromRow)]
+struct Completion {
+    created_at: DateTime<Utc>,
+    started_at: Option<DateTime<Utc>>,
+    done_at: DateTime<Utc>,
+}
+
+fn hours_between(start: DateTime<Utc>, end: DateTime<Utc>) -> f64 {
+    (end - start).num_milliseconds() as f64 / 3_600_000.0
+}
+
+fn average(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+/// The `fraction` percentile of sorted values, interpolated between the two
+/// closest ranks like PostgreSQL's `PERCENTILE_CONT`.
+fn percentile(sorted: &[f64], fraction: f64) -> Option<f64> {
+    let last = sorted.len().checked_sub(1)?;
+    let rank = fraction * last as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    Some(sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64))
+}
+
+/// Lead time runs from creation to done, cycle time from the first start to
+/// done. Tasks never started, or only started after they were done, have no
+/// cycle time.
+fn flow_metrics(from: NaiveDate, to: NaiveDate, completions: &[Completion]) -> FlowMetrics {
+    let mut lead: Vec<f64> = completions
+        .iter()
+        .map(|c| hours_between(c.created_at, c.done_at))
+        .collect();
+    let mut cycle: Vec<f64> = completions
+        .iter()
+        .filter_map(|c| {
+            let started_at = c.started_at.filter(|started_at| *started_at <= c.done_at)?;
+            Some(hours_between(started_at, c.done_at))
+        })
+        .collect();
+    lead.sort_by(f64::total_cmp);
+    cycle.sort_by(f64::total_cmp);
+
+    FlowMetrics {
+        from,
+        to,
+        completed_tasks: completions.len() as i64,
+        avg_lead_time_hours: average(&lead),
+        median_lead_time_hours: percentile(&lead, 0.5),
+        p85_lead_time_hours: percentile(&lead, 0.85),
+        avg_cycle_time_hours: average(&cycle),
+        median_cycle_time_hours: percentile(&cycle, 0.5),
+        p85_cycle_time_hours: percentile(&cycle, 0.85),
+    }
+}
+
+/// Lead and cycle time of tasks whose last move to `done` falls in the range.
+pub async fn get_flow_metrics(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+    Query(params): Query<DateRangeParams>,
+) -> AppResult<Json<FlowMetrics>> {
+    access::require_member(&state.db, project_id, &claims).await?;
+    let (from, to) = resolve_range(&params)?;
+
+    let completions = sqlx::query_as::<_, Completion>(
+        "SELECT created_at, started_at, done_at
+         FROM (
+             SELECT t.created_at,
+                    (SELECT MAX(c.changed_at) FROM task_status_changes c
+                     WHERE c.task_id = t.id AND c.status = 'done') AS done_at,
+                    (SELECT MIN(c.changed_at) FROM task_status_changes c
+                     WHERE c.task_id = t.id AND c.status = 'in_progress') AS started_at
+             FROM tasks t
+             WHERE t.project_id = $1 AND t.status = 'done' AND t.deleted_at IS NULL
+         ) completed
+         WHERE done_at >= $2::date AND done_at < $3::date + INTERVAL '1 day'",
+    )
+    .bind(project_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(flow_metrics(from, to, &completions)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn completion(lead_hours: i64, cycle_hours: Option<i64>) -> Completion {
+        let created_at = Utc.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap();
+        let done_at = created_at + Duration::hours(lead_hours);
+        Completion {
+            created_at,
+            started_at: cycle_hours.map(|hours| done_at - Duration::hours(hours)),
+            done_at,
+        }
+    }
+
+    #[test]
+    fn interpolates_percentiles_like_postgres() {
+        let values = [10.0, 20.0, 30.0, 40.0];
+        assert_eq!(percentile(&values, 0.0), Some(10.0));
+        assert_eq!(percentile(&values, 0.5), Some(25.0));
+        assert_eq!(percentile(&values, 0.85), Some(35.5));
+        assert_eq!(percentile(&values, 1.0), Some(40.0));
+        assert_eq!(percentile(&[7.0], 0.85), Some(7.0));
+        assert_eq!(percentile(&[], 0.5), None);
+    }
+
+    #[test]
+    fn measures_lead_and_cycle_time() {
+        let from = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let to = NaiveDate::from_ymd_opt(2024, 5, 31).unwrap();
+        let completions = [
+            completion(40, Some(4)),
+            completion(10, Some(2)),
+            completion(30, None),
+            completion(20, Some(-3)),
+            completion(25, Some(6)),
+        ];
+
+        let metrics = flow_metrics(from, to, &completions);
+        assert_eq!((metrics.from, metrics.to), (from, to));
+        assert_eq!(metrics.completed_tasks, 5);
+        assert_eq!(metrics.avg_lead_time_hours, Some(25.0));
+        assert_eq!(metrics.median_lead_time_hours, Some(25.0));
+        assert_eq!(metrics.p85_lead_time_hours, Some(34.0));
+        assert_eq!(metrics.avg_cycle_time_hours, Some(4.0));
+        assert_eq!(metrics.median_cycle_time_hours, Some(4.0));
+        assert_eq!(metrics.p85_cycle_time_hours, Some(5.4));
+    }
+
+    #[test]
+    fn reports_nothing_without_completions() {
+        let day = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let metrics = flow_metrics(day, day, &[]);
+        assert_eq!(metrics.completed_tasks, 0);
+        assert_eq!(metrics.avg_lead_time_hours, None);
+        assert_eq!(metrics.p85_cycle_time_hours, None);
+
+        let metrics = flow_metrics(day, day, &[completion(3, None)]);
+        assert_eq!(metrics.median_lead_time_hours, Some(3.0));
+        assert_eq!(metrics.avg_cycle_time_hours, None);
+    }
+}
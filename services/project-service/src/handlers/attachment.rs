@@ -0,0 +1,363 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{Multipart, Path, Query, State},
+    http::{header, StatusCode},
+    response::Response,
+    Extension, Json,
+};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use shared::{
+    errors::{AppError, AppResult},
+    models::Claims,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    access,
+    models::{Attachment, AttachmentResponse, DownloadParams},
+    AppState,
+};
+
+/// Hard cap on request bodies for upload routes; per-plan limits are lower.
+pub const MAX_UPLOAD_BYTES: usize = 100 * 1024 * 1024;
+
+const MB: i64 = 1024 * 1024;
+
+const ATTACHMENT_SELECT: &str = "SELECT id, project_id, task_id, uploader_id, file_name,
+        content_type, size_bytes, checksum_sha256, storage_key, created_at
+    FROM attachments";
+
+/// Excludes attachments of tasks in the trash.
+const LIVE_TASK: &str =
+    "(task_id IS NULL OR task_id IN (SELECT id FROM tasks WHERE deleted_at IS NULL))";
+
+/// Per-plan `(max file size, total storage)` in bytes.
+fn storage_limits(plan: &str) -> (i64, i64) {
+    match plan {
+        "starter" => (25 * MB, 1024 * MB),
+        "pro" => (100 * MB, 10 * 1024 * MB),
+        "enterprise" => (100 * MB, 100 * 1024 * MB),
+        _ => (5 * MB, 100 * MB),
+    }
+}
+
+struct UploadedFile {
+    file_name: String,
+    declared_type: Option<String>,
+    data: Bytes,
+}
+
+fn sanitize_file_name(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"')
+        .take(255)
+        .collect();
+
+    if cleaned.trim().is_empty() {
+        "upload".to_string()
+    } else {
+        cleaned.trim().to_string()
+    }
+}
+
+/// Sniffs the content type from the file's magic bytes, falling back to the
+/// extension and only then to what the client declared.
+fn detect_content_type(data: &[u8], file_name: &str, declared: Option<&str>) -> String {
+    if let Some(kind) = infer::get(data) {
+        return kind.mime_type().to_string();
+    }
+    if let Some(mime) = mime_guess::from_path(file_name).first() {
+        return mime.essence_str().to_string();
+    }
+    declared
+        .filter(|ct| ct.parse::<mime_guess::Mime>().is_ok())
+        .unwrap_or("application/octet-stream")
+        .to_string()
+}
+
+async fn read_file(mut multipart: Multipart) -> AppResult<UploadedFile> {
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Invalid multipart body: {}", e)))?
+    {
+        if field.name() != Some("file") {
+            continue;
+        }
+
+        let file_name = sanitize_file_name(field.file_name().unwrap_or_default());
+        let declared_type = field.content_type().map(str::to_string);
+        let data = field
+            .bytes()
+            .await
+            .map_err(|e| AppError::BadRequest(format!("Failed to read upload: {}", e)))?;
+
+        return Ok(UploadedFile {
+            file_name,
+            declared_type,
+            data,
+        });
+    }
+
+    Err(AppError::BadRequest("Missing 'file' field in upload".to_string()))
+}
+
+async fn check_quota(db: &PgPool, project_id: Uuid, size: i64) -> AppResult<()> {
+    let (plan, used): (String, i64) = sqlx::query_as(
+        "SELECT
+            COALESCE(
+                (SELECT s.plan::text FROM subscriptions s
+                 WHERE s.user_id = p.owner_id AND s.status = 'active'),
+                'free'
+            ),
+            COALESCE(
+                (SELECT SUM(a.size_bytes) FROM attachments a
+                 JOIN projects ap ON ap.id = a.project_id
+                 WHERE ap.owner_id = p.owner_id),
+                0
+            )::bigint
+         FROM projects p
+         WHERE p.id = $1",
+    )
+    .bind(project_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+
+    let (max_file, total) = storage_limits(&plan);
+
+    if size > max_file {
+        return Err(AppError::BadRequest(format!(
+            "File exceeds the {} MB limit of the {} plan",
+            max_file / MB,
+            plan
+        )));
+    }
+    if used + size > total {
+        return Err(AppError::Forbidden(format!(
+            "Storage quota of {} MB for the {} plan would be exceeded",
+            total / MB,
+            plan
+        )));
+    }
+
+    Ok(())
+}
+
+/// Attachments of trashed projects and tasks are not found.
+async fn load_attachment(db: &PgPool, attachment_id: Uuid) -> AppResult<Attachment> {
+    sqlx::query_as::<_, Attachment>(&format!(
+        "{} WHERE id = $1
+           AND project_id IN (SELECT id FROM projects WHERE deleted_at IS NULL)
+           AND {}",
+        ATTACHMENT_SELECT, LIVE_TASK
+    ))
+        .bind(attachment_id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Attachment not found".to_string()))
+}
+
+fn to_response(state: &AppState, attachment: Attachment) -> AttachmentResponse {
+    let (download_url, download_expires_at) = state.url_signer.sign(attachment.id);
+    AttachmentResponse {
+        attachment,
+        download_url,
+        download_expires_at,
+    }
+}
+
+async fn store_upload(
+    state: &AppState,
+    claims: &Claims,
+    project_id: Uuid,
+    task_id: Option<Uuid>,
+    multipart: Multipart,
+) -> AppResult<AttachmentResponse> {
+    access::require_editor(&state.db, project_id, claims).await?;
+    let uploader_id = access::user_id(claims)?;
+
+    let file = read_file(multipart).await?;
+    if file.data.is_empty() {
+        return Err(AppError::BadRequest("Uploaded file is empty".to_string()));
+    }
+
+    let size = file.data.len() as i64;
+    check_quota(&state.db, project_id, size).await?;
+
+    let id = Uuid::new_v4();
+    let storage_key = format!("{}/{}", project_id, id);
+    let content_type = detect_content_type(&file.data, &file.file_name, file.declared_type.as_deref());
+    let checksum = hex::encode(Sha256::digest(&file.data));
+
+    state.storage.put(&storage_key, file.data, &content_type).await?;
+
+    let inserted = sqlx::query_as::<_, Attachment>(
+        "INSERT INTO attachments
+            (id, project_id, task_id, uploader_id, file_name, content_type,
+             size_bytes, checksum_sha256, storage_key)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+         RETURNING id, project_id, task_id, uploader_id, file_name, content_type,
+            size_bytes, checksum_sha256, storage_key, created_at",
+    )
+    .bind(id)
+    .bind(project_id)
+    .bind(task_id)
+    .bind(uploader_id)
+    .bind(&file.file_name)
+    .bind(&content_type)
+    .bind(size)
+    .bind(&checksum)
+    .bind(&storage_key)
+    .fetch_one(&state.db)
+    .await;
+
+    match inserted {
+        Ok(attachment) => Ok(to_response(state, attachment)),
+        Err(err) => {
+            if let Err(cleanup) = state.storage.delete(&storage_key).await {
+                tracing::warn!(key = %storage_key, error = %cleanup, "Failed to remove orphaned upload");
+            }
+            Err(err.into())
+        }
+    }
+}
+
+pub async fn upload_project_attachment(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+    multipart: Multipart,
+) -> AppResult<(StatusCode, Json<AttachmentResponse>)> {
+    let attachment = store_upload(&state, &claims, project_id, None, multipart).await?;
+    Ok((StatusCode::CREATED, Json(attachment)))
+}
+
+pub async fn upload_task_attachment(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(task_id): Path<Uuid>,
+    multipart: Multipart,
+) -> AppResult<(StatusCode, Json<AttachmentResponse>)> {
+    let project_id = access::task_project_id(&state.db, task_id).await?;
+    let attachment = store_upload(&state, &claims, project_id, Some(task_id), multipart).await?;
+    Ok((StatusCode::CREATED, Json(attachment)))
+}
+
+pub async fn list_project_attachments(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+) -> AppResult<Json<Vec<AttachmentResponse>>> {
+    access::require_member(&state.db, project_id, &claims).await?;
+
+    let attachments = sqlx::query_as::<_, Attachment>(&format!(
+        "{} WHERE project_id = $1 AND {} ORDER BY created_at DESC",
+        ATTACHMENT_SELECT, LIVE_TASK
+    ))
+    .bind(project_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(
+        attachments
+            .into_iter()
+            .map(|attachment| to_response(&state, attachment))
+            .collect(),
+    ))
+}
+
+pub async fn list_task_attachments(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(task_id): Path<Uuid>,
+) -> AppResult<Json<Vec<AttachmentResponse>>> {
+    let project_id = access::task_project_id(&state.db, task_id).await?;
+    access::require_member(&state.db, project_id, &claims).await?;
+
+    let attachments = sqlx::query_as::<_, Attachment>(&format!(
+        "{} WHERE task_id = $1 ORDER BY created_at DESC",
+        ATTACHMENT_SELECT
+    ))
+    .bind(task_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(
+        attachments
+            .into_iter()
+            .map(|attachment| to_response(&state, attachment))
+            .collect(),
+    ))
+}
+
+pub async fn get_attachment(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(attachment_id): Path<Uuid>,
+) -> AppResult<Json<AttachmentResponse>> {
+    let attachment = load_attachment(&state.db, attachment_id).await?;
+    access::require_member(&state.db, attachment.project_id, &claims).await?;
+
+    Ok(Json(to_response(&state, attachment)))
+}
+
+/// Serves attachment contents. Authorization comes from the signed URL, so
+/// this route sits outside the JWT middleware.
+pub async fn download_attachment(
+    State(state): State<AppState>,
+    Path(attachment_id): Path<Uuid>,
+    Query(params): Query<DownloadParams>,
+) -> AppResult<Response> {
+    state
+        .url_signer
+        .verify(attachment_id, params.expires, &params.signature)?;
+
+    let attachment = load_attachment(&state.db, attachment_id).await?;
+    let data = state.storage.get(&attachment.storage_key).await?;
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, &attachment.content_type)
+        .header(header::CONTENT_LENGTH, data.len())
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", attachment.file_name.replace('\\', "_")),
+        )
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header(header::CACHE_CONTROL, "private, max-age=0")
+        .body(Body::from(data))
+        .map_err(|e| AppError::InternalError(format!("Failed to build response: {}", e)))
+}
+
+pub async fn delete_attachment(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(attachment_id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    let attachment = load_attachment(&state.db, attachment_id).await?;
+    let role = access::require_member(&state.db, attachment.project_id, &claims).await?;
+    access::require_writable(&state.db, attachment.project_id).await?;
+    let uid = access::user_id(&claims)?;
+
+    if attachment.uploader_id != Some(uid) && !role.is_admin() {
+        return Err(AppError::Forbidden(
+            "Only the uploader or a project admin can delete this attachment".to_string(),
+        ));
+    }
+
+    sqlx::query("DELETE FROM attachments WHERE id = $1")
+        .bind(attachment_id)
+        .execute(&state.db)
+        .await?;
+
+    if let Err(err) = state.storage.delete(&attachment.storage_key).await {
+        tracing::warn!(key = %attachment.storage_key, error = %err, "Failed to delete attachment content");
+    }
+
+    Ok(Json(json!({"message": "deleted"})))
+}
@@ -0,0 +1,445 @@
+use axum::{extract::State, Extension, Json};
+use serde_json::json;
+use shared::{
+    errors::{AppError, AppResult},
+    models::{Claims, Task},
+};
+use sqlx::{Connection, PgPool, Postgres, Transaction};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+use crate::{
+    access,
+    activity::{self, Entity},
+    handlers::{
+        label, sprint,
+        task::{self, validate_priority, validate_status, TASK_COLUMNS},
+    },
+    models::{BulkOperation, BulkTaskFailure, BulkTaskRequest, BulkTaskResult},
+    recurrence, watchers, AppState,
+};
+
+/// Most tasks a single bulk request may change.
+const MAX_BULK_TASKS: usize = 500;
+
+/// The operations of a request folded into one set of changes. A later
+/// operation of the same kind replaces an earlier one.
+#[derive(Default)]
+struct Changes {
+    status: Option<String>,
+    priority: Option<String>,
+    /// `Some(None)` unassigns.
+    assignee_id: Option<Option<Uuid>>,
+    /// `Some(None)` moves to the backlog.
+    sprint_id: Option<Option<Uuid>>,
+    add_labels: Vec<Uuid>,
+    remove_labels: Vec<Uuid>,
+    delete: bool,
+}
+
+fn collect_changes(operations: &[BulkOperation]) -> AppResult<Changes> {
+    if operations.is_empty() {
+        return Err(AppError::ValidationError(
+            "At least one operation is required".to_string(),
+        ));
+    }
+    if operations.len() > 1
+        && operations
+            .iter()
+            .any(|op| matches!(op, BulkOperation::Delete))
+    {
+        return Err(AppError::BadRequest(
+            "delete cannot be combined with other operations".to_string(),
+        ));
+    }
+
+    let mut changes = Changes::default();
+    for op in operations {
+        match op {
+            BulkOperation::SetStatus { status } => {
+                validate_status(status)?;
+                changes.status = Some(status.clone());
+            }
+            BulkOperation::SetPriority { priority } => {
+                validate_priority(priority)?;
+                changes.priority = Some(priority.clone());
+            }
+            BulkOperation::SetAssignee { assignee_id } => changes.assignee_id = Some(*assignee_id),
+            BulkOperation::SetSprint { sprint_id } => changes.sprint_id = Some(*sprint_id),
+            BulkOperation::AddLabels { label_ids } => {
+                changes.remove_labels.retain(|id| !label_ids.contains(id));
+                changes.add_labels.extend(label_ids);
+            }
+            BulkOperation::RemoveLabels { label_ids } => {
+                changes.add_labels.retain(|id| !label_ids.contains(id));
+                changes.remove_labels.extend(label_ids);
+            }
+            BulkOperation::Delete => changes.delete = true,
+        }
+    }
+
+    Ok(changes)
+}
+
+/// A task before and after its changes, for the notifications sent once the
+/// whole request is committed. `new` is `None` when the task was deleted.
+struct Applied {
+    old: Task,
+    new: Option<Task>,
+}
+
+/// Applies the changes to one task.
+async fn apply(
+    tx: &mut Transaction<'_, Postgres>,
+    db: &PgPool,
+    task_id: Uuid,
+    changes: &Changes,
+    actor_id: Uuid,
+) -> AppResult<Applied> {
+    let current = sqlx::query_as::<_, Task>(&format!(
+        "SELECT {} FROM tasks t WHERE t.id = $1 AND t.deleted_at IS NULL FOR UPDATE",
+        TASK_COLUMNS
+    ))
+    .bind(task_id)
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Task not found".to_string()))?;
+
+    if changes.delete {
+        sqlx::query("UPDATE tasks SET deleted_at = NOW(), deleted_by = $2 WHERE id = $1")
+            .bind(task_id)
+            .bind(actor_id)
+            .execute(&mut **tx)
+            .await?;
+        activity::record_deleted(
+            tx,
+            current.project_id,
+            Entity::Task,
+            task_id,
+            Some(actor_id),
+            &current,
+        )
+        .await?;
+        return Ok(Applied {
+            old: current,
+            new: None,
+        });
+    }
+
+    if let Some(Some(assignee_id)) = changes.assignee_id {
+        if !access::is_project_member(db, current.project_id, assignee_id).await? {
+            return Err(AppError::BadRequest(
+                "Assignee must be a member of the project".to_string(),
+            ));
+        }
+    }
+    if let Some(Some(sprint_id)) = changes.sprint_id {
+        let sprint = sprint::load_sprint(db, sprint_id).await?;
+        if sprint.project_id != current.project_id {
+            return Err(AppError::BadRequest(
+                "Sprint belongs to another project".to_string(),
+            ));
+        }
+        sprint::require_not_completed(&sprint)?;
+    }
+
+    let task = sqlx::query_as::<_, Task>(&format!(
+        "UPDATE tasks AS t SET
+            status = COALESCE($2::task_status, t.status),
+            priority = COALESCE($3::task_priority, t.priority),
+            assignee_id = CASE WHEN $4 THEN $5 ELSE t.assignee_id END,
+            sprint_id = CASE WHEN $6 THEN $7 ELSE t.sprint_id END,
+            updated_at = NOW()
+         WHERE t.id = $1
+         RETURNING {}",
+        TASK_COLUMNS
+    ))
+    .bind(task_id)
+    .bind(&changes.status)
+    .bind(&changes.priority)
+    .bind(changes.assignee_id.is_some())
+    .bind(changes.assignee_id.flatten())
+    .bind(changes.sprint_id.is_some())
+    .bind(changes.sprint_id.flatten())
+    .fetch_one(&mut **tx)
+    .await?;
+
+    if let Some(assignee_id) = task.assignee_id.filter(|id| current.assignee_id != Some(*id)) {
+        watchers::auto_watch(tx, task_id, &[assignee_id]).await?;
+    }
+
+    if !changes.add_labels.is_empty() || !changes.remove_labels.is_empty() {
+        let old: Vec<Uuid> = sqlx::query_scalar(
+            "SELECT label_id FROM task_labels WHERE task_id = $1 ORDER BY label_id",
+        )
+        .bind(task_id)
+        .fetch_all(&mut **tx)
+        .await?;
+
+        let mut new: Vec<Uuid> = old
+            .iter()
+            .filter(|id| !changes.remove_labels.contains(id))
+            .chain(&changes.add_labels)
+            .copied()
+            .collect();
+        new.sort();
+        new.dedup();
+
+        if new != old {
+            label::set_task_labels(tx, task.project_id, task_id, &new).await?;
+            activity::record_field(
+                tx,
+                task.project_id,
+                Entity::Task,
+                task_id,
+                Some(actor_id),
+                "label_ids",
+                &old,
+                &new,
+            )
+            .await?;
+        }
+    }
+
+    activity::record_updated(
+        tx,
+        task.project_id,
+        Entity::Task,
+        task_id,
+        Some(actor_id),
+        &current,
+        &task,
+    )
+    .await?;
+
+    Ok(Applied {
+        old: current,
+        new: Some(task),
+    })
+}
+
+/// Applies status, priority, assignee, label, sprint or delete operations to
+/// a list of tasks or to the tasks of a project matching a filter. All
+/// changes happen in one transaction; a task that cannot be changed, for lack
+/// of permission or otherwise, is reported as failed without affecting the
+/// others. Watchers and new assignees are notified as for single changes.
+pub async fn bulk_update_tasks(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<BulkTaskRequest>,
+) -> AppResult<Json<BulkTaskResult>> {
+    let actor = access::user_id(&claims)?;
+    let changes = collect_changes(&req.operations)?;
+
+    let task_ids = match (req.task_ids, req.filter) {
+        (Some(ids), None) => {
+            let mut seen = HashSet::new();
+            ids.into_iter().filter(|id| seen.insert(*id)).collect()
+        }
+        (None, Some(filter)) => {
+            access::require_member(&state.db, filter.project_id, &claims).await?;
+            let custom: HashMap<String, String> = filter
+                .custom_fields
+                .into_iter()
+                .map(|(key, value)| (format!("cf.{}", key), value))
+                .collect();
+            task::matching_task_ids(&state.db, filter.project_id, &filter.params, &custom).await?
+        }
+        _ => {
+            return Err(AppError::BadRequest(
+                "Provide either task_ids or filter".to_string(),
+            ))
+        }
+    };
+    if task_ids.len() > MAX_BULK_TASKS {
+        return Err(AppError::BadRequest(format!(
+            "At most {} tasks can be changed at once, {} selected",
+            MAX_BULK_TASKS,
+            task_ids.len()
+        )));
+    }
+
+    let projects: HashMap<Uuid, Uuid> = sqlx::query_as::<_, (Uuid, Uuid)>(
+        "SELECT id, project_id FROM tasks WHERE id = ANY($1) AND deleted_at IS NULL",
+    )
+    .bind(&task_ids)
+    .fetch_all(&state.db)
+    .await?
+    .into_iter()
+    .collect();
+
+    // Permission is checked once per project; denials keep their message.
+    let mut denied: HashMap<Uuid, Option<String>> = HashMap::new();
+    for project_id in projects.values() {
+        if !denied.contains_key(project_id) {
+            let error = access::require_editor(&state.db, *project_id, &claims)
+                .await
+                .err()
+                .map(|e| e.to_string());
+            denied.insert(*project_id, error);
+        }
+    }
+
+    let mut result = BulkTaskResult {
+        matched: task_ids.len(),
+        succeeded: Vec::new(),
+        failed: Vec::new(),
+    };
+    let mut applied = Vec::new();
+
+    let mut tx = state.db.begin().await?;
+
+    for task_id in task_ids {
+        let error = match projects.get(&task_id) {
+            None => Some(AppError::NotFound("Task not found".to_string()).to_string()),
+            Some(project_id) => denied[project_id].clone(),
+        };
+        if let Some(error) = error {
+            result.failed.push(BulkTaskFailure { task_id, error });
+            continue;
+        }
+
+        let mut item = tx.begin().await?;
+        match apply(&mut item, &state.db, task_id, &changes, actor).await {
+            Ok(outcome) => {
+                item.commit().await?;
+                result.succeeded.push(task_id);
+                applied.push(outcome);
+            }
+            Err(e) => {
+                item.rollback().await?;
+                result.failed.push(BulkTaskFailure {
+                    task_id,
+                    error: e.to_string(),
+                });
+            }
+        }
+    }
+
+    tx.commit().await?;
+
+    for Applied { old, new } in applied {
+        let Some(new) = new else {
+            watchers::notify_task(&state, "task.deleted", &old, actor, &[], json!({})).await;
+            continue;
+        };
+
+        let assigned = new.assignee_id.filter(|id| old.assignee_id != Some(*id));
+        if let Err(e) = task::notify_update(&state, &old, &new, actor, assigned).await {
+            tracing::warn!("Failed to notify watchers of task {}: {}", new.id, e);
+        }
+
+        if new.status == "done" && old.status != "done" {
+            if let Err(e) = recurrence::on_task_completed(&state.db, &new).await {
+                tracing::warn!("Failed to create next occurrence of task {}: {}", new.id, e);
+            }
+        }
+    }
+
+    Ok(Json(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(operations: serde_json::Value) -> AppResult<Changes> {
+        let operations: Vec<BulkOperation> = serde_json::from_value(operations).unwrap();
+        collect_changes(&operations)
+    }
+
+    fn error(operations: serde_json::Value) -> AppError {
+        match collect(operations) {
+            Err(err) => err,
+            Ok(_) => panic!("expected the operations to be rejected"),
+        }
+    }
+
+    #[test]
+    fn requires_an_operation() {
+        let err = error(json!([]));
+        assert!(
+            matches!(&err, AppError::ValidationError(m) if m.contains("At least one")),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn delete_stands_alone() {
+        let changes = collect(json!([{"op": "delete"}])).unwrap();
+        assert!(changes.delete);
+
+        for operations in [
+            json!([{"op": "delete"}, {"op": "set_status", "status": "done"}]),
+            json!([{"op": "set_priority", "priority": "low"}, {"op": "delete"}]),
+            json!([{"op": "delete"}, {"op": "delete"}]),
+        ] {
+            let err = error(operations);
+            assert!(
+                matches!(&err, AppError::BadRequest(m) if m.contains("cannot be combined")),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn validates_values() {
+        assert!(matches!(
+            error(json!([{"op": "set_status", "status": "finished"}])),
+            AppError::ValidationError(_)
+        ));
+        assert!(matches!(
+            error(json!([{"op": "set_priority", "priority": "urgent"}])),
+            AppError::ValidationError(_)
+        ));
+    }
+
+    #[test]
+    fn later_operations_replace_earlier_ones() {
+        let assignee = Uuid::new_v4();
+        let sprint = Uuid::new_v4();
+        let changes = collect(json!([
+            {"op": "set_status", "status": "todo"},
+            {"op": "set_priority", "priority": "low"},
+            {"op": "set_assignee", "assignee_id": assignee},
+            {"op": "set_sprint", "sprint_id": sprint},
+            {"op": "set_status", "status": "done"},
+            {"op": "set_assignee", "assignee_id": null},
+            {"op": "set_sprint", "sprint_id": null},
+        ]))
+        .unwrap();
+        assert_eq!(changes.status.as_deref(), Some("done"));
+        assert_eq!(changes.priority.as_deref(), Some("low"));
+        assert_eq!(changes.assignee_id, Some(None));
+        assert_eq!(changes.sprint_id, Some(None));
+        assert!(!changes.delete);
+
+        let changes = collect(json!([{"op": "set_priority", "priority": "high"}])).unwrap();
+        assert_eq!(changes.status, None);
+        assert_eq!(changes.assignee_id, None);
+        assert_eq!(changes.sprint_id, None);
+    }
+
+    #[test]
+    fn adding_and_removing_labels_cancel_out() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        let changes = collect(json!([
+            {"op": "add_labels", "label_ids": [a, b]},
+            {"op": "remove_labels", "label_ids": [b, c]},
+        ]))
+        .unwrap();
+        assert_eq!(changes.add_labels, [a]);
+        assert_eq!(changes.remove_labels, [b, c]);
+
+        let changes = collect(json!([
+            {"op": "remove_labels", "label_ids": [a, b]},
+            {"op": "add_labels", "label_ids": [a]},
+        ]))
+        .unwrap();
+        assert_eq!(changes.add_labels, [a]);
+        assert_eq!(changes.remove_labels, [b]);
+    }
+}
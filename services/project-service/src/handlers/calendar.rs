@@ -0,0 +1,311 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use shared::{
+    errors::{AppError, AppResult},
+    models::Claims,
+};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::{
+    access,
+    handlers::project::load_project,
+    ical::{Calendar, Event, EventTime},
+    models::{CalendarFeed, CalendarFeedResponse},
+    AppState,
+};
+
+const FEED_COLUMNS: &str = "f.id, f.user_id, f.project_id, f.token, f.created_at, f.updated_at";
+
+/// How far back deadlines are still listed.
+const HISTORY_DAYS: i64 = 365;
+
+#[derive(FromRow)]
+struct FeedTask {
+    id: Uuid,
+    title: String,
+    description: Option<String>,
+    status: String,
+    priority: String,
+    deadline: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    version: i32,
+    project_name: String,
+}
+
+#[derive(FromRow)]
+struct FeedSprint {
+    id: Uuid,
+    name: String,
+    goal: Option<String>,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    updated_at: DateTime<Utc>,
+    version: i32,
+}
+
+fn new_token() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+fn feed_response(feed: CalendarFeed) -> CalendarFeedResponse {
+    CalendarFeedResponse {
+        url: format!("/calendar/{}.ics", feed.token),
+        feed,
+    }
+}
+
+/// Returns the caller's feed, creating it on first use.
+async fn ensure_feed(
+    db: &PgPool,
+    user_id: Uuid,
+    project_id: Option<Uuid>,
+) -> AppResult<CalendarFeed> {
+    sqlx::query(
+        "INSERT INTO calendar_feeds (user_id, project_id, token) VALUES ($1, $2, $3)
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(user_id)
+    .bind(project_id)
+    .bind(new_token())
+    .execute(db)
+    .await?;
+
+    Ok(sqlx::query_as::<_, CalendarFeed>(&format!(
+        "SELECT {} FROM calendar_feeds f
+         WHERE f.user_id = $1 AND f.project_id IS NOT DISTINCT FROM $2",
+        FEED_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(project_id)
+    .fetch_one(db)
+    .await?)
+}
+
+/// Replaces the token, so the old feed URL stops working.
+async fn rotate_feed(
+    db: &PgPool,
+    user_id: Uuid,
+    project_id: Option<Uuid>,
+) -> AppResult<CalendarFeed> {
+    ensure_feed(db, user_id, project_id).await?;
+
+    Ok(sqlx::query_as::<_, CalendarFeed>(&format!(
+        "UPDATE calendar_feeds AS f SET token = $3, updated_at = NOW()
+         WHERE f.user_id = $1 AND f.project_id IS NOT DISTINCT FROM $2
+         RETURNING {}",
+        FEED_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(project_id)
+    .bind(new_token())
+    .fetch_one(db)
+    .await?)
+}
+
+pub async fn get_my_feed(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> AppResult<Json<CalendarFeedResponse>> {
+    let user_id = access::user_id(&claims)?;
+    Ok(Json(feed_response(
+        ensure_feed(&state.db, user_id, None).await?,
+    )))
+}
+
+pub async fn regenerate_my_feed(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> AppResult<Json<CalendarFeedResponse>> {
+    let user_id = access::user_id(&claims)?;
+    Ok(Json(feed_response(
+        rotate_feed(&state.db, user_id, None).await?,
+    )))
+}
+
+pub async fn get_project_feed(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+) -> AppResult<Json<CalendarFeedResponse>> {
+    access::require_member(&state.db, project_id, &claims).await?;
+    let user_id = access::user_id(&claims)?;
+    Ok(Json(feed_response(
+        ensure_feed(&state.db, user_id, Some(project_id)).await?,
+    )))
+}
+
+pub async fn regenerate_project_feed(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+) -> AppResult<Json<CalendarFeedResponse>> {
+    access::require_member(&state.db, project_id, &claims).await?;
+    let user_id = access::user_id(&claims)?;
+    Ok(Json(feed_response(
+        rotate_feed(&state.db, user_id, Some(project_id)).await?,
+    )))
+}
+
+/// The feed's owner must still be active and, for a project feed, still able
+/// to see the project; otherwise the feed reads as missing.
+async fn feed_visible(db: &PgPool, feed: &CalendarFeed) -> AppResult<bool> {
+    Ok(sqlx::query_scalar(
+        "SELECT EXISTS (
+             SELECT 1 FROM users u
+             WHERE u.id = $1 AND u.is_active
+               AND ($2::uuid IS NULL OR EXISTS (
+                   SELECT 1 FROM projects p
+                   WHERE p.id = $2 AND p.deleted_at IS NULL
+                     AND (u.role = 'admin' OR p.owner_id = u.id
+                          OR EXISTS (SELECT 1 FROM project_members pm
+                                     WHERE pm.project_id = p.id AND pm.user_id = u.id))))
+         )",
+    )
+    .bind(feed.user_id)
+    .bind(feed.project_id)
+    .fetch_one(db)
+    .await?)
+}
+
+fn task_event(task: FeedTask) -> Event {
+    let mut description = format!(
+        "Project: {}\nStatus: {}\nPriority: {}",
+        task.project_name,
+        task.status.replace('_', " "),
+        task.priority
+    );
+    if let Some(text) = task.description.as_deref().filter(|d| !d.trim().is_empty()) {
+        description.push_str("\n\n");
+        description.push_str(text.trim());
+    }
+
+    Event {
+        uid: format!("task-{}@project-service", task.id),
+        summary: if task.status == "done" {
+            format!("✓ {}", task.title)
+        } else {
+            task.title
+        },
+        description: Some(description),
+        time: EventTime::At(task.deadline),
+        last_modified: task.updated_at,
+        sequence: task.version - 1,
+    }
+}
+
+fn sprint_event(sprint: FeedSprint) -> Event {
+    Event {
+        uid: format!("sprint-{}@project-service", sprint.id),
+        summary: format!("Sprint: {}", sprint.name),
+        description: sprint.goal,
+        time: EventTime::Days {
+            start: sprint.start_date,
+            end: sprint.end_date.succ_opt().unwrap_or(sprint.end_date),
+        },
+        last_modified: sprint.updated_at,
+        sequence: sprint.version - 1,
+    }
+}
+
+const FEED_TASK_SELECT: &str = "SELECT t.id, t.title, t.description, t.status::text AS status,
+        t.priority::text AS priority, t.deadline, t.updated_at, t.version,
+        p.name AS project_name
+     FROM tasks t
+     JOIN projects p ON p.id = t.project_id
+     WHERE t.deadline IS NOT NULL AND t.deadline >= $2
+       AND t.deleted_at IS NULL AND p.deleted_at IS NULL";
+
+async fn build_calendar(db: &PgPool, feed: &CalendarFeed) -> AppResult<Calendar> {
+    let since = Utc::now() - Duration::days(HISTORY_DAYS);
+
+    let Some(project_id) = feed.project_id else {
+        // Assigned tasks, in projects the user can still see.
+        let tasks = sqlx::query_as::<_, FeedTask>(&format!(
+            "{} AND t.assignee_id = $1
+               AND (p.owner_id = $1 OR EXISTS (SELECT 1 FROM project_members pm
+                                               WHERE pm.project_id = p.id AND pm.user_id = $1))
+             ORDER BY t.deadline, t.id",
+            FEED_TASK_SELECT
+        ))
+        .bind(feed.user_id)
+        .bind(since)
+        .fetch_all(db)
+        .await?;
+
+        let mut calendar = Calendar::new("My task deadlines");
+        for task in tasks {
+            calendar.push(task_event(task));
+        }
+        return Ok(calendar);
+    };
+
+    let project = load_project(db, project_id).await?;
+    let tasks = sqlx::query_as::<_, FeedTask>(&format!(
+        "{} AND t.project_id = $1 ORDER BY t.deadline, t.id",
+        FEED_TASK_SELECT
+    ))
+    .bind(project_id)
+    .bind(since)
+    .fetch_all(db)
+    .await?;
+
+    // Sprints stand in for milestones.
+    let sprints = sqlx::query_as::<_, FeedSprint>(
+        "SELECT id, name, goal, start_date, end_date, updated_at, version FROM sprints
+         WHERE project_id = $1 AND end_date >= $2::date
+         ORDER BY start_date, id",
+    )
+    .bind(project_id)
+    .bind(since)
+    .fetch_all(db)
+    .await?;
+
+    let mut calendar = Calendar::new(project.name);
+    for sprint in sprints {
+        calendar.push(sprint_event(sprint));
+    }
+    for task in tasks {
+        calendar.push(task_event(task));
+    }
+    Ok(calendar)
+}
+
+/// Serves a feed as `/calendar/<token>.ics`. The token is the only
+/// credential, so this route sits outside the JWT middleware.
+pub async fn get_calendar(
+    State(state): State<AppState>,
+    Path(file): Path<String>,
+) -> AppResult<Response> {
+    let token = file.strip_suffix(".ics").unwrap_or(&file);
+
+    let feed = sqlx::query_as::<_, CalendarFeed>(&format!(
+        "SELECT {} FROM calendar_feeds f WHERE f.token = $1",
+        FEED_COLUMNS
+    ))
+    .bind(token)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Calendar feed not found".to_string()))?;
+
+    if !feed_visible(&state.db, &feed).await? {
+        return Err(AppError::NotFound("Calendar feed not found".to_string()));
+    }
+
+    let calendar = build_calendar(&state.db, &feed).await?;
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "text/calendar; charset=utf-8"),
+            (header::CACHE_CONTROL, "private, max-age=0"),
+        ],
+        calendar.render(),
+    )
+        .into_response())
+}
@@ -0,0 +1,358 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde_json::json;
+use shared::{
+    errors::{AppError, AppResult},
+    models::{Claims, NotificationEvent},
+};
+use sqlx::{Postgres, Transaction};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::{
+    access,
+    handlers::task,
+    markdown,
+    models::{Comment, CommentEdit, CommentResponse, CreateCommentRequest, UpdateCommentRequest},
+    watchers, AppState,
+};
+
+const MAX_COMMENT_LENGTH: usize = 10_000;
+
+const COMMENT_SELECT: &str = "SELECT c.id, c.task_id, c.author_id, c.parent_id, c.body,
+        ARRAY(SELECT m.user_id FROM task_comment_mentions m
+              WHERE m.comment_id = c.id ORDER BY m.created_at) AS mentions,
+        c.edited_at, c.deleted_at, c.created_at, c.updated_at
+    FROM task_comments c";
+
+fn validate_body(body: &str) -> AppResult<()> {
+    if body.trim().is_empty() {
+        return Err(AppError::ValidationError("Comment body cannot be empty".to_string()));
+    }
+    if body.chars().count() > MAX_COMMENT_LENGTH {
+        return Err(AppError::ValidationError(format!(
+            "Comment body cannot exceed {} characters",
+            MAX_COMMENT_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+async fn load_comment(db: &sqlx::PgPool, comment_id: Uuid) -> AppResult<Comment> {
+    sqlx::query_as::<_, Comment>(&format!("{} WHERE c.id = $1", COMMENT_SELECT))
+        .bind(comment_id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Comment not found".to_string()))
+}
+
+/// Resolves the mentions in `body` against the project's members, excluding
+/// the author, and stores them for the comment. Returns the newly added users.
+async fn sync_mentions(
+    tx: &mut Transaction<'_, Postgres>,
+    db: &sqlx::PgPool,
+    project_id: Uuid,
+    comment_id: Uuid,
+    author_id: Uuid,
+    body: &str,
+) -> AppResult<Vec<Uuid>> {
+    let handles = markdown::extract_mentions(body);
+    let members = if handles.is_empty() {
+        Vec::new()
+    } else {
+        access::project_member_emails(db, project_id).await?
+    };
+    let mentioned: Vec<Uuid> = markdown::resolve_mentions(&handles, &members)
+        .into_iter()
+        .filter(|id| *id != author_id)
+        .collect();
+
+    sqlx::query("DELETE FROM task_comment_mentions WHERE comment_id = $1 AND NOT (user_id = ANY($2))")
+        .bind(comment_id)
+        .bind(&mentioned)
+        .execute(&mut **tx)
+        .await?;
+
+    let added: Vec<Uuid> = sqlx::query_scalar(
+        "INSERT INTO task_comment_mentions (comment_id, user_id)
+         SELECT $1, UNNEST($2::uuid[])
+         ON CONFLICT DO NOTHING
+         RETURNING user_id",
+    )
+    .bind(comment_id)
+    .bind(&mentioned)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    Ok(added)
+}
+
+fn notify_mentions(state: &AppState, comment: &Comment, project_id: Uuid, recipients: Vec<Uuid>) {
+    state.notifier.send(NotificationEvent {
+        kind: "comment.mentioned".to_string(),
+        recipient_ids: recipients,
+        actor_id: comment.author_id,
+        payload: json!({
+            "project_id": project_id,
+            "task_id": comment.task_id,
+            "comment_id": comment.id,
+            "body": comment.body,
+        }),
+    });
+}
+
+/// Nests comments under their parents. Deleted comments are kept only when
+/// they still have replies, so threads stay intact.
+fn build_threads(comments: Vec<Comment>) -> Vec<CommentResponse> {
+    fn attach(
+        parent: Option<Uuid>,
+        children: &mut HashMap<Option<Uuid>, Vec<Comment>>,
+    ) -> Vec<CommentResponse> {
+        children
+            .remove(&parent)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|comment| {
+                let id = comment.id;
+                let mut node = CommentResponse::from(comment);
+                node.replies = attach(Some(id), children);
+                if node.deleted && node.replies.is_empty() {
+                    None
+                } else {
+                    Some(node)
+                }
+            })
+            .collect()
+    }
+
+    let mut children: HashMap<Option<Uuid>, Vec<Comment>> = HashMap::new();
+    for comment in comments {
+        children.entry(comment.parent_id).or_default().push(comment);
+    }
+
+    attach(None, &mut children)
+}
+
+pub async fn list_comments(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(task_id): Path<Uuid>,
+) -> AppResult<Json<Vec<CommentResponse>>> {
+    let project_id = access::task_project_id(&state.db, task_id).await?;
+    access::require_member(&state.db, project_id, &claims).await?;
+
+    let comments = sqlx::query_as::<_, Comment>(&format!(
+        "{} WHERE c.task_id = $1 ORDER BY c.created_at, c.id",
+        COMMENT_SELECT
+    ))
+    .bind(task_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(build_threads(comments)))
+}
+
+pub async fn create_comment(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(task_id): Path<Uuid>,
+    Json(req): Json<CreateCommentRequest>,
+) -> AppResult<(StatusCode, Json<CommentResponse>)> {
+    validate_body(&req.body)?;
+
+    let project_id = access::task_project_id(&state.db, task_id).await?;
+    access::require_member(&state.db, project_id, &claims).await?;
+    access::require_writable(&state.db, project_id).await?;
+    let author_id = access::user_id(&claims)?;
+
+    if let Some(parent_id) = req.parent_id {
+        let parent = load_comment(&state.db, parent_id).await?;
+        if parent.task_id != task_id {
+            return Err(AppError::BadRequest(
+                "Parent comment belongs to another task".to_string(),
+            ));
+        }
+        if parent.deleted_at.is_some() {
+            return Err(AppError::BadRequest(
+                "Cannot reply to a deleted comment".to_string(),
+            ));
+        }
+    }
+
+    let mut tx = state.db.begin().await?;
+
+    let comment_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO task_comments (task_id, author_id, parent_id, body)
+         VALUES ($1, $2, $3, $4)
+         RETURNING id",
+    )
+    .bind(task_id)
+    .bind(author_id)
+    .bind(req.parent_id)
+    .bind(&req.body)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let mentioned =
+        sync_mentions(&mut tx, &state.db, project_id, comment_id, author_id, &req.body).await?;
+    watchers::auto_watch(&mut tx, task_id, &[author_id]).await?;
+
+    tx.commit().await?;
+
+    let comment = load_comment(&state.db, comment_id).await?;
+    let task = task::load_task(&state.db, task_id).await?;
+    watchers::notify_task(
+        &state,
+        "comment.created",
+        &task,
+        author_id,
+        &mentioned,
+        json!({"comment_id": comment.id, "body": comment.body}),
+    )
+    .await;
+    notify_mentions(&state, &comment, project_id, mentioned);
+
+    Ok((StatusCode::CREATED, Json(comment.into())))
+}
+
+pub async fn update_comment(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(comment_id): Path<Uuid>,
+    Json(req): Json<UpdateCommentRequest>,
+) -> AppResult<Json<CommentResponse>> {
+    validate_body(&req.body)?;
+
+    let comment = load_comment(&state.db, comment_id).await?;
+    if comment.deleted_at.is_some() {
+        return Err(AppError::NotFound("Comment not found".to_string()));
+    }
+
+    let project_id = access::task_project_id(&state.db, comment.task_id).await?;
+    let role = access::require_member(&state.db, project_id, &claims).await?;
+    access::require_writable(&state.db, project_id).await?;
+    let editor_id = access::user_id(&claims)?;
+
+    if comment.author_id != Some(editor_id) && !role.is_admin() {
+        return Err(AppError::Forbidden(
+            "Only the author or a project admin can edit this comment".to_string(),
+        ));
+    }
+
+    if comment.body == req.body {
+        return Ok(Json(comment.into()));
+    }
+
+    let mut tx = state.db.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO task_comment_edits (comment_id, editor_id, previous_body)
+         VALUES ($1, $2, $3)",
+    )
+    .bind(comment_id)
+    .bind(editor_id)
+    .bind(&comment.body)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        "UPDATE task_comments
+         SET body = $2, edited_at = NOW(), updated_at = NOW()
+         WHERE id = $1",
+    )
+    .bind(comment_id)
+    .bind(&req.body)
+    .execute(&mut *tx)
+    .await?;
+
+    let author_id = comment.author_id.unwrap_or(editor_id);
+    let mentioned =
+        sync_mentions(&mut tx, &state.db, project_id, comment_id, author_id, &req.body).await?;
+
+    tx.commit().await?;
+
+    let comment = load_comment(&state.db, comment_id).await?;
+    notify_mentions(&state, &comment, project_id, mentioned);
+
+    Ok(Json(comment.into()))
+}
+
+pub async fn delete_comment(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(comment_id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    let comment = load_comment(&state.db, comment_id).await?;
+    if comment.deleted_at.is_some() {
+        return Err(AppError::NotFound("Comment not found".to_string()));
+    }
+
+    let project_id = access::task_project_id(&state.db, comment.task_id).await?;
+    let role = access::require_member(&state.db, project_id, &claims).await?;
+    access::require_writable(&state.db, project_id).await?;
+    let uid = access::user_id(&claims)?;
+
+    if comment.author_id != Some(uid) && !role.is_admin() {
+        return Err(AppError::Forbidden(
+            "Only the author or a project admin can delete this comment".to_string(),
+        ));
+    }
+
+    sqlx::query("UPDATE task_comments SET deleted_at = NOW(), updated_at = NOW() WHERE id = $1")
+        .bind(comment_id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(Json(json!({"message": "deleted"})))
+}
+
+pub async fn get_comment_history(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(comment_id): Path<Uuid>,
+) -> AppResult<Json<Vec<CommentEdit>>> {
+    let comment = load_comment(&state.db, comment_id).await?;
+    let project_id = access::task_project_id(&state.db, comment.task_id).await?;
+    access::require_member(&state.db, project_id, &claims).await?;
+
+    if comment.deleted_at.is_some() {
+        return Err(AppError::NotFound("Comment not found".to_string()));
+    }
+
+    let edits = sqlx::query_as::<_, CommentEdit>(
+        "SELECT id, comment_id, editor_id, previous_body, edited_at
+         FROM task_comment_edits
+         WHERE comment_id = $1
+         ORDER BY edited_at DESC",
+    )
+    .bind(comment_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(edits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_comments_by_characters() {
+        assert!(validate_body("Looks good").is_ok());
+        assert!(validate_body(&"ü".repeat(MAX_COMMENT_LENGTH)).is_ok());
+        assert!(validate_body(&"a".repeat(MAX_COMMENT_LENGTH + 1)).is_err());
+        assert!(validate_body(&"ü".repeat(MAX_COMMENT_LENGTH + 1)).is_err());
+
+        for body in ["", "  ", "\n\t"] {
+            match validate_body(body) {
+                Err(AppError::ValidationError(message)) => {
+                    assert_eq!(message, "Comment body cannot be empty")
+                }
+                other => panic!("{:?} gave {:?}", body, other),
+            }
+        }
+    }
+}
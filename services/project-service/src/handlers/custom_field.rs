@@ -0,0 +1,266 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde_json::json;
+use shared::{
+    errors::{AppError, AppResult},
+    models::Claims,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    access,
+    custom_fields::{field_options, FIELD_TYPES},
+    models::{CreateCustomFieldRequest, CustomField, UpdateCustomFieldRequest},
+    AppState,
+};
+
+const FIELD_COLUMNS: &str = "f.id, f.project_id, f.name, f.field_type::text AS field_type,
+    f.options, f.required, f.position, f.created_at, f.updated_at";
+
+fn is_select(field_type: &str) -> bool {
+    matches!(field_type, "single_select" | "multi_select")
+}
+
+fn validate_name(name: &str) -> AppResult<String> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err(AppError::ValidationError(
+            "Custom field name must be between 1 and 100 characters".to_string(),
+        ));
+    }
+    if Uuid::parse_str(name).is_ok() {
+        return Err(AppError::ValidationError(
+            "Custom field name cannot be a UUID".to_string(),
+        ));
+    }
+    Ok(name.to_string())
+}
+
+fn validate_options(options: &[String]) -> AppResult<Vec<String>> {
+    let mut cleaned: Vec<String> = Vec::new();
+    for option in options {
+        let option = option.trim();
+        if option.is_empty() || option.chars().count() > 100 {
+            return Err(AppError::ValidationError(
+                "Options must be between 1 and 100 characters".to_string(),
+            ));
+        }
+        if !cleaned.iter().any(|o| o == option) {
+            cleaned.push(option.to_string());
+        }
+    }
+    if cleaned.is_empty() {
+        return Err(AppError::ValidationError(
+            "Select fields need at least one option".to_string(),
+        ));
+    }
+    Ok(cleaned)
+}
+
+fn map_name_conflict(err: sqlx::Error, name: &str) -> AppError {
+    match &err {
+        sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("23505") => {
+            AppError::Conflict(format!(
+                "A custom field named '{}' already exists in this project",
+                name
+            ))
+        }
+        _ => err.into(),
+    }
+}
+
+/// All custom fields defined on a project, in display order.
+pub(crate) async fn project_fields(db: &PgPool, project_id: Uuid) -> AppResult<Vec<CustomField>> {
+    let fields = sqlx::query_as::<_, CustomField>(&format!(
+        "SELECT {} FROM custom_fields f WHERE f.project_id = $1 ORDER BY f.position, f.created_at",
+        FIELD_COLUMNS
+    ))
+    .bind(project_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(fields)
+}
+
+async fn load_field(db: &PgPool, field_id: Uuid) -> AppResult<CustomField> {
+    sqlx::query_as::<_, CustomField>(&format!(
+        "SELECT {} FROM custom_fields f WHERE f.id = $1",
+        FIELD_COLUMNS
+    ))
+    .bind(field_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Custom field not found".to_string()))
+}
+
+pub async fn list_custom_fields(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+) -> AppResult<Json<Vec<CustomField>>> {
+    access::require_member(&state.db, project_id, &claims).await?;
+
+    Ok(Json(project_fields(&state.db, project_id).await?))
+}
+
+pub async fn create_custom_field(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+    Json(req): Json<CreateCustomFieldRequest>,
+) -> AppResult<(StatusCode, Json<CustomField>)> {
+    access::require_admin(&state.db, project_id, &claims).await?;
+
+    let name = validate_name(&req.name)?;
+    if !FIELD_TYPES.contains(&req.field_type.as_str()) {
+        return Err(AppError::ValidationError(format!(
+            "Invalid field type '{}', expected one of: {}",
+            req.field_type,
+            FIELD_TYPES.join(", ")
+        )));
+    }
+
+    let options = match (&req.options, is_select(&req.field_type)) {
+        (Some(options), true) => validate_options(options)?,
+        (None, true) => {
+            return Err(AppError::ValidationError(
+                "Select fields need at least one option".to_string(),
+            ))
+        }
+        (Some(options), false) if !options.is_empty() => {
+            return Err(AppError::ValidationError(
+                "Only select fields can have options".to_string(),
+            ))
+        }
+        _ => Vec::new(),
+    };
+
+    let field = sqlx::query_as::<_, CustomField>(&format!(
+        "INSERT INTO custom_fields AS f (project_id, name, field_type, options, required, position)
+         VALUES (
+             $1, $2, $3::custom_field_type, $4, $5,
+             COALESCE($6, (SELECT COALESCE(MAX(position) + 1, 0)
+                           FROM custom_fields WHERE project_id = $1))
+         )
+         RETURNING {}",
+        FIELD_COLUMNS
+    ))
+    .bind(project_id)
+    .bind(&name)
+    .bind(&req.field_type)
+    .bind(json!(options))
+    .bind(req.required.unwrap_or(false))
+    .bind(req.position)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| map_name_conflict(e, &name))?;
+
+    Ok((StatusCode::CREATED, Json(field)))
+}
+
+/// Updates a field's name, options, required flag or position. The type of a
+/// field cannot change; removing a select option still used by a task is
+/// rejected.
+pub async fn update_custom_field(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(field_id): Path<Uuid>,
+    Json(req): Json<UpdateCustomFieldRequest>,
+) -> AppResult<Json<CustomField>> {
+    let field = load_field(&state.db, field_id).await?;
+    access::require_admin(&state.db, field.project_id, &claims).await?;
+
+    let name = req.name.as_deref().map(validate_name).transpose()?;
+
+    let options = match &req.options {
+        Some(_) if !is_select(&field.field_type) => {
+            return Err(AppError::ValidationError(
+                "Only select fields can have options".to_string(),
+            ))
+        }
+        Some(options) => {
+            let options = validate_options(options)?;
+            let removed: Vec<String> = field_options(&field)
+                .into_iter()
+                .filter(|o| !options.contains(o))
+                .collect();
+
+            if !removed.is_empty() {
+                let in_use: i64 = sqlx::query_scalar(
+                    "SELECT COUNT(*) FROM tasks
+                     WHERE project_id = $1 AND custom_fields -> $2 ?| $3",
+                )
+                .bind(field.project_id)
+                .bind(field.id.to_string())
+                .bind(&removed)
+                .fetch_one(&state.db)
+                .await?;
+
+                if in_use > 0 {
+                    return Err(AppError::Conflict(format!(
+                        "Options still in use by {} task(s) cannot be removed",
+                        in_use
+                    )));
+                }
+            }
+            Some(json!(options))
+        }
+        None => None,
+    };
+
+    let updated = sqlx::query_as::<_, CustomField>(&format!(
+        "UPDATE custom_fields AS f SET
+            name = COALESCE($2, f.name),
+            options = COALESCE($3, f.options),
+            required = COALESCE($4, f.required),
+            position = COALESCE($5, f.position),
+            updated_at = NOW()
+         WHERE f.id = $1
+         RETURNING {}",
+        FIELD_COLUMNS
+    ))
+    .bind(field_id)
+    .bind(&name)
+    .bind(&options)
+    .bind(req.required)
+    .bind(req.position)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| map_name_conflict(e, name.as_deref().unwrap_or(&field.name)))?;
+
+    Ok(Json(updated))
+}
+
+/// Deletes a field and strips its values from every task in the project.
+pub async fn delete_custom_field(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(field_id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    let field = load_field(&state.db, field_id).await?;
+    access::require_admin(&state.db, field.project_id, &claims).await?;
+
+    let mut tx = state.db.begin().await?;
+
+    sqlx::query(
+        "UPDATE tasks SET custom_fields = custom_fields - $2
+         WHERE project_id = $1 AND custom_fields ? $2",
+    )
+    .bind(field.project_id)
+    .bind(field.id.to_string())
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("DELETE FROM custom_fields WHERE id = $1")
+        .bind(field_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(json!({"message": "deleted"})))
+}
@@ -0,0 +1,289 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::IntoResponse,
+    Extension,
+};
+use chrono::Utc;
+use serde_json::Value;
+use shared::{
+    errors::{AppError, AppResult},
+    models::{Claims, Task},
+};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::{
+    access,
+    handlers::{
+        custom_field, label, project::load_project, sprint::SPRINT_COLUMNS, task::TASK_COLUMNS,
+    },
+    models::{
+        BundleComment, BundleMember, BundleTask, CustomField, ExportParams, Label, ProjectBundle,
+        Sprint,
+    },
+    spreadsheet, AppState,
+};
+
+pub const BUNDLE_FORMAT: &str = "project-bundle";
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Columns of the CSV export, followed by one `cf.<name>` column per custom
+/// field. The names match what the CSV import maps by default.
+pub(crate) const CSV_COLUMNS: [&str; 14] = [
+    "id",
+    "title",
+    "description",
+    "status",
+    "priority",
+    "assignee_email",
+    "deadline",
+    "labels",
+    "sprint",
+    "original_estimate_minutes",
+    "remaining_estimate_minutes",
+    "created_at",
+    "updated_at",
+    "comments",
+];
+
+async fn build_bundle(db: &PgPool, project_id: Uuid) -> AppResult<ProjectBundle> {
+    let project = load_project(db, project_id).await?;
+
+    let members = sqlx::query_as::<_, BundleMember>(
+        "SELECT u.id AS user_id, u.email, u.name, 'owner' AS role
+         FROM projects p JOIN users u ON u.id = p.owner_id
+         WHERE p.id = $1
+         UNION ALL
+         SELECT u.id, u.email, u.name, pm.role::text
+         FROM project_members pm JOIN users u ON u.id = pm.user_id
+         WHERE pm.project_id = $1
+         ORDER BY email",
+    )
+    .bind(project_id)
+    .fetch_all(db)
+    .await?;
+
+    let labels = sqlx::query_as::<_, Label>(&format!(
+        "SELECT {} FROM labels l WHERE l.project_id = $1 ORDER BY LOWER(l.name)",
+        label::LABEL_COLUMNS
+    ))
+    .bind(project_id)
+    .fetch_all(db)
+    .await?;
+
+    let custom_fields = custom_field::project_fields(db, project_id).await?;
+
+    let sprints = sqlx::query_as::<_, Sprint>(&format!(
+        "SELECT {} FROM sprints s WHERE s.project_id = $1 ORDER BY s.start_date, s.created_at",
+        SPRINT_COLUMNS
+    ))
+    .bind(project_id)
+    .fetch_all(db)
+    .await?;
+
+    let tasks = sqlx::query_as::<_, Task>(&format!(
+        "SELECT {} FROM tasks t
+         WHERE t.project_id = $1 AND t.deleted_at IS NULL
+         ORDER BY t.created_at, t.id",
+        TASK_COLUMNS
+    ))
+    .bind(project_id)
+    .fetch_all(db)
+    .await?;
+
+    let ids: Vec<Uuid> = tasks.iter().map(|t| t.id).collect();
+    let mut task_labels = label::labels_for_tasks(db, &ids).await?;
+
+    let assignees: Vec<Uuid> = tasks.iter().filter_map(|t| t.assignee_id).collect();
+    let emails: HashMap<Uuid, String> =
+        sqlx::query_as::<_, (Uuid, String)>("SELECT id, email FROM users WHERE id = ANY($1)")
+            .bind(&assignees)
+            .fetch_all(db)
+            .await?
+            .into_iter()
+            .collect();
+
+    let mut comments: HashMap<Uuid, Vec<BundleComment>> = HashMap::new();
+    for comment in sqlx::query_as::<_, BundleComment>(
+        "SELECT c.id, c.task_id, c.parent_id, u.email AS author_email, c.body, c.created_at,
+                c.edited_at
+         FROM task_comments c
+         JOIN tasks t ON t.id = c.task_id
+         LEFT JOIN users u ON u.id = c.author_id
+         WHERE t.project_id = $1 AND t.deleted_at IS NULL AND c.deleted_at IS NULL
+         ORDER BY c.created_at, c.id",
+    )
+    .bind(project_id)
+    .fetch_all(db)
+    .await?
+    {
+        comments.entry(comment.task_id).or_default().push(comment);
+    }
+
+    let tasks = tasks
+        .into_iter()
+        .map(|task| BundleTask {
+            assignee_email: task.assignee_id.and_then(|id| emails.get(&id).cloned()),
+            labels: task_labels
+                .remove(&task.id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|l| l.name)
+                .collect(),
+            comments: comments.remove(&task.id).unwrap_or_default(),
+            task,
+        })
+        .collect();
+
+    Ok(ProjectBundle {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        exported_at: Utc::now(),
+        project,
+        members,
+        labels,
+        custom_fields,
+        sprints,
+        tasks,
+    })
+}
+
+/// Renders a custom field value as text; `user` values become emails.
+fn field_text(field: &CustomField, value: &Value, emails: &HashMap<String, String>) -> String {
+    match value {
+        Value::String(s) if field.field_type == "user" => {
+            emails.get(s).cloned().unwrap_or_else(|| s.clone())
+        }
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(", "),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn bundle_csv(bundle: &ProjectBundle) -> AppResult<Vec<u8>> {
+    let csv_error = |e: csv::Error| AppError::InternalError(format!("Failed to write CSV: {}", e));
+    let mut writer = csv::Writer::from_writer(Vec::new());
+
+    let mut header: Vec<String> = CSV_COLUMNS.iter().map(|c| c.to_string()).collect();
+    header.extend(
+        bundle
+            .custom_fields
+            .iter()
+            .map(|f| format!("cf.{}", f.name)),
+    );
+    writer.write_record(&header).map_err(csv_error)?;
+
+    let sprints: HashMap<Uuid, &str> = bundle
+        .sprints
+        .iter()
+        .map(|s| (s.id, s.name.as_str()))
+        .collect();
+    let member_emails: HashMap<String, String> = bundle
+        .members
+        .iter()
+        .map(|m| (m.user_id.to_string(), m.email.clone()))
+        .collect();
+
+    for entry in &bundle.tasks {
+        let task = &entry.task;
+        let comments = entry
+            .comments
+            .iter()
+            .map(|c| {
+                format!(
+                    "{} ({}): {}",
+                    c.author_email.as_deref().unwrap_or("deleted user"),
+                    c.created_at.to_rfc3339(),
+                    c.body
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let mut record = vec![
+            task.id.to_string(),
+            task.title.clone(),
+            task.description.clone().unwrap_or_default(),
+            task.status.clone(),
+            task.priority.clone(),
+            entry.assignee_email.clone().unwrap_or_default(),
+            task.deadline.map(|d| d.to_rfc3339()).unwrap_or_default(),
+            entry.labels.join(", "),
+            task.sprint_id
+                .and_then(|id| sprints.get(&id))
+                .map(|name| name.to_string())
+                .unwrap_or_default(),
+            task.original_estimate_minutes
+                .map(|m| m.to_string())
+                .unwrap_or_default(),
+            task.remaining_estimate_minutes
+                .map(|m| m.to_string())
+                .unwrap_or_default(),
+            task.created_at.to_rfc3339(),
+            task.updated_at.to_rfc3339(),
+            comments,
+        ];
+        for field in &bundle.custom_fields {
+            record.push(
+                task.custom_fields
+                    .get(field.id.to_string())
+                    .map(|v| field_text(field, v, &member_emails))
+                    .unwrap_or_default(),
+            );
+        }
+        writer
+            .write_record(record.into_iter().map(spreadsheet::escape_cell))
+            .map_err(csv_error)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| AppError::InternalError(format!("Failed to write CSV: {}", e)))
+}
+
+/// Exports a project's tasks with their members, labels and comments, as a
+/// versioned JSON bundle (`?format=json`, the default) or as CSV.
+pub async fn export_project(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+    Query(params): Query<ExportParams>,
+) -> AppResult<impl IntoResponse> {
+    access::require_member(&state.db, project_id, &claims).await?;
+
+    let format = params.format.as_deref().unwrap_or("json");
+    if !matches!(format, "json" | "csv") {
+        return Err(AppError::BadRequest(format!(
+            "Invalid format '{}', expected 'json' or 'csv'",
+            format
+        )));
+    }
+
+    let bundle = build_bundle(&state.db, project_id).await?;
+
+    let (content_type, body) = if format == "csv" {
+        ("text/csv; charset=utf-8", bundle_csv(&bundle)?)
+    } else {
+        let body = serde_json::to_vec_pretty(&bundle)
+            .map_err(|e| AppError::InternalError(format!("Failed to write JSON: {}", e)))?;
+        ("application/json", body)
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"project-{}.{}\"", project_id, format),
+            ),
+        ],
+        body,
+    ))
+}
@@ -0,0 +1,571 @@
+use axum::{
+    extract::{Multipart, Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use shared::{
+    errors::{AppError, AppResult},
+    models::{Claims, PaginatedResponse, PaginationParams},
+};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::{
+    access,
+    import::{self, ImportContext, TaskDraft},
+    importers::{self, jira, trello, SourceProject, UserMap, JOB_COLUMNS},
+    models::{CustomField, ImportJob, ImportParams, ImportReport, ImportRowError},
+    quota::{self, Resource},
+    spreadsheet, AppState,
+};
+
+/// Hard cap on request bodies for import routes.
+pub const MAX_IMPORT_BYTES: usize = 10 * 1024 * 1024;
+
+const MAX_IMPORT_ROWS: usize = 5_000;
+
+/// Task fields a CSV column can be mapped to, with the headers each one
+/// picks up when no mapping is given. Custom fields are mapped as
+/// `cf.<field name>`.
+const TARGETS: [(&str, &[&str]); 8] = [
+    ("title", &["title", "name", "summary"]),
+    ("description", &["description"]),
+    ("status", &["status"]),
+    ("priority", &["priority"]),
+    ("assignee", &["assignee", "assignee_email"]),
+    ("deadline", &["deadline", "due", "due_date"]),
+    ("labels", &["labels", "tags"]),
+    ("estimate", &["estimate", "original_estimate_minutes"]),
+];
+
+struct CsvUpload {
+    data: Vec<u8>,
+    mapping: Option<HashMap<String, String>>,
+}
+
+async fn read_upload(mut multipart: Multipart) -> AppResult<CsvUpload> {
+    let mut data = None;
+    let mut mapping = None;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Invalid multipart body: {}", e)))?
+    {
+        match field.name() {
+            Some("file") => {
+                let bytes = field
+                    .bytes()
+                    .await
+                    .map_err(|e| AppError::BadRequest(format!("Failed to read upload: {}", e)))?;
+                data = Some(bytes.to_vec());
+            }
+            Some("mapping") => {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|e| AppError::BadRequest(format!("Failed to read mapping: {}", e)))?;
+                mapping = Some(serde_json::from_str(&text).map_err(|e| {
+                    AppError::BadRequest(format!(
+                        "Mapping must be a JSON object of field to column: {}",
+                        e
+                    ))
+                })?);
+            }
+            _ => {}
+        }
+    }
+
+    let data =
+        data.ok_or_else(|| AppError::BadRequest("Missing 'file' field in upload".to_string()))?;
+    Ok(CsvUpload { data, mapping })
+}
+
+/// Resolves which column feeds each target. Without an explicit mapping,
+/// headers are matched by name, ignoring case.
+fn resolve_columns(
+    headers: &[String],
+    fields: &[CustomField],
+    mapping: Option<&HashMap<String, String>>,
+) -> AppResult<HashMap<String, usize>> {
+    let find = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h.eq_ignore_ascii_case(name.trim()))
+    };
+    let mut columns = HashMap::new();
+
+    match mapping {
+        Some(mapping) => {
+            for (target, column) in mapping {
+                let known = TARGETS.iter().any(|(t, _)| t == target)
+                    || target.strip_prefix("cf.").is_some_and(|name| {
+                        crate::custom_fields::find_field(fields, name).is_some()
+                    });
+                if !known {
+                    return Err(AppError::BadRequest(format!(
+                        "Unknown import field '{}'",
+                        target
+                    )));
+                }
+                let index = find(column).ok_or_else(|| {
+                    AppError::BadRequest(format!(
+                        "Column '{}' mapped to '{}' is not in the file",
+                        column, target
+                    ))
+                })?;
+                columns.insert(target.clone(), index);
+            }
+        }
+        None => {
+            for (target, names) in TARGETS {
+                if let Some(index) = names.iter().find_map(|name| find(name)) {
+                    columns.insert(target.to_string(), index);
+                }
+            }
+            for field in fields {
+                let index = find(&format!("cf.{}", field.name)).or_else(|| find(&field.name));
+                if let Some(index) = index {
+                    columns.insert(format!("cf.{}", field.name), index);
+                }
+            }
+        }
+    }
+
+    if !columns.contains_key("title") {
+        return Err(AppError::BadRequest(
+            "No column is mapped to 'title'".to_string(),
+        ));
+    }
+    Ok(columns)
+}
+
+fn draft_from_record(record: &csv::StringRecord, columns: &HashMap<String, usize>) -> TaskDraft {
+    let cell = |target: &str| {
+        columns
+            .get(target)
+            .and_then(|&i| record.get(i))
+            .map(|value| spreadsheet::unescape_cell(value).to_string())
+    };
+
+    TaskDraft {
+        title: cell("title").unwrap_or_default(),
+        description: cell("description"),
+        status: cell("status"),
+        priority: cell("priority"),
+        assignee_email: cell("assignee"),
+        deadline: cell("deadline"),
+        labels: cell("labels").into_iter().collect(),
+        estimate: cell("estimate"),
+        custom_fields: columns
+            .iter()
+            .filter_map(|(target, &i)| {
+                let name = target.strip_prefix("cf.")?;
+                let value = spreadsheet::unescape_cell(record.get(i)?);
+                Some((name.to_string(), value.to_string()))
+            })
+            .collect(),
+    }
+}
+
+/// Imports tasks from a CSV upload (`file`), with an optional `mapping` part
+/// giving the column for each task field. Every row is validated first; rows
+/// are only imported when none has errors. `?dry_run=true` stops after
+/// validation. Cells the CSV export escaped with `'` are read back unescaped.
+pub async fn import_tasks_csv(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+    Query(params): Query<ImportParams>,
+    multipart: Multipart,
+) -> AppResult<(StatusCode, Json<ImportReport>)> {
+    access::require_editor(&state.db, project_id, &claims).await?;
+    let actor = access::user_id(&claims)?;
+    let upload = read_upload(multipart).await?;
+    let context = ImportContext::load(&state.db, project_id).await?;
+
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(upload.data.as_slice());
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| AppError::BadRequest(format!("Invalid CSV header: {}", e)))?
+        .iter()
+        .map(|h| h.trim_start_matches('\u{feff}').trim().to_string())
+        .collect();
+    let columns = resolve_columns(&headers, context.fields(), upload.mapping.as_ref())?;
+
+    let mut valid = Vec::new();
+    let mut errors = Vec::new();
+    let mut total_rows = 0;
+
+    for record in reader.records() {
+        total_rows += 1;
+        if total_rows > MAX_IMPORT_ROWS {
+            return Err(AppError::BadRequest(format!(
+                "At most {} rows can be imported at once",
+                MAX_IMPORT_ROWS
+            )));
+        }
+
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                errors.push(ImportRowError {
+                    row: e.position().map_or(0, |p| p.line()),
+                    errors: vec![format!("Invalid CSV row: {}", e)],
+                });
+                continue;
+            }
+        };
+        let row = record.position().map_or(0, |p| p.line());
+
+        match context.validate(&draft_from_record(&record, &columns)) {
+            Ok(task) => valid.push(task),
+            Err(row_errors) => errors.push(ImportRowError {
+                row,
+                errors: row_errors,
+            }),
+        }
+    }
+
+    let mut report = ImportReport {
+        dry_run: params.dry_run,
+        total_rows,
+        valid_rows: valid.len(),
+        imported: 0,
+        labels_created: 0,
+        errors,
+    };
+
+    if !report.errors.is_empty() {
+        let status = if params.dry_run {
+            StatusCode::OK
+        } else {
+            StatusCode::UNPROCESSABLE_ENTITY
+        };
+        return Ok((status, Json(report)));
+    }
+    if params.dry_run || valid.is_empty() {
+        return Ok((StatusCode::OK, Json(report)));
+    }
+
+    let owner_id = quota::project_owner(&state.db, project_id).await?;
+    let quota = state.billing.quota(owner_id, actor).await?;
+
+    let mut tx = state.db.begin().await?;
+    quota
+        .check(&mut tx, Resource::Tasks, valid.len() as i64)
+        .await?;
+    let (summary, _) = import::insert_tasks(&mut tx, project_id, &valid, Some(actor)).await?;
+    tx.commit().await?;
+
+    report.imported = summary.tasks_created;
+    report.labels_created = summary.labels_created;
+
+    Ok((StatusCode::CREATED, Json(report)))
+}
+
+struct ExportUpload {
+    data: Vec<u8>,
+    name: Option<String>,
+    users: UserMap,
+}
+
+/// Reads a Trello or Jira upload: the export as `file`, an optional project
+/// `name`, and an optional `user_map` JSON object giving the email for each
+/// person named in the export.
+async fn read_export(mut multipart: Multipart) -> AppResult<ExportUpload> {
+    let mut data = None;
+    let mut name = None;
+    let mut users = UserMap::default();
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Invalid multipart body: {}", e)))?
+    {
+        let part = field.name().map(str::to_string);
+        match part.as_deref() {
+            Some("file") => {
+                let bytes = field
+                    .bytes()
+                    .await
+                    .map_err(|e| AppError::BadRequest(format!("Failed to read upload: {}", e)))?;
+                data = Some(bytes.to_vec());
+            }
+            Some(part @ ("name" | "user_map")) => {
+                let text = field.text().await.map_err(|e| {
+                    AppError::BadRequest(format!("Failed to read '{}': {}", part, e))
+                })?;
+                if part == "name" {
+                    name = Some(text).filter(|n| !n.trim().is_empty());
+                } else {
+                    users = UserMap::new(serde_json::from_str(&text).map_err(|e| {
+                        AppError::BadRequest(format!(
+                            "user_map must be a JSON object of name to email: {}",
+                            e
+                        ))
+                    })?);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let data =
+        data.ok_or_else(|| AppError::BadRequest("Missing 'file' field in upload".to_string()))?;
+    Ok(ExportUpload { data, name, users })
+}
+
+async fn start_import(
+    state: &AppState,
+    claims: &Claims,
+    source: &str,
+    multipart: Multipart,
+    parse: fn(&[u8], &UserMap) -> AppResult<SourceProject>,
+) -> AppResult<(StatusCode, Json<ImportJob>)> {
+    let requested_by = access::user_id(claims)?;
+    let upload = read_export(multipart).await?;
+
+    let mut project = parse(&upload.data, &upload.users)?;
+    if let Some(name) = upload.name {
+        project.name = name;
+    }
+
+    let quota = state.billing.quota(requested_by, requested_by).await?;
+    let job = importers::start_job(&state.db, source, requested_by, quota, project).await?;
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// Creates a project from a Trello board JSON export, in the background.
+pub async fn import_trello(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    multipart: Multipart,
+) -> AppResult<(StatusCode, Json<ImportJob>)> {
+    start_import(&state, &claims, "trello", multipart, trello::parse).await
+}
+
+/// Creates a project from a Jira CSV or XML issue export, in the background.
+pub async fn import_jira(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    multipart: Multipart,
+) -> AppResult<(StatusCode, Json<ImportJob>)> {
+    start_import(&state, &claims, "jira", multipart, jira::parse).await
+}
+
+/// Jobs are visible to whoever started them and to global admins.
+pub async fn get_import_job(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(job_id): Path<Uuid>,
+) -> AppResult<Json<ImportJob>> {
+    let job = importers::load_job(&state.db, job_id).await?;
+    if job.requested_by != access::user_id(&claims)? && !access::is_global_admin(&claims) {
+        return Err(AppError::NotFound("Import job not found".to_string()));
+    }
+    Ok(Json(job))
+}
+
+/// The caller's import jobs, newest first.
+pub async fn list_import_jobs(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(pagination): Query<PaginationParams>,
+) -> AppResult<Json<PaginatedResponse<ImportJob>>> {
+    let user_id = access::user_id(&claims)?;
+
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM import_jobs WHERE requested_by = $1")
+        .bind(user_id)
+        .fetch_one(&state.db)
+        .await?;
+
+    let jobs = sqlx::query_as::<_, ImportJob>(&format!(
+        "SELECT {} FROM import_jobs j WHERE j.requested_by = $1
+         ORDER BY j.created_at DESC, j.id
+         LIMIT $2 OFFSET $3",
+        JOB_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(pagination.limit())
+    .bind(pagination.offset())
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(PaginatedResponse {
+        data: jobs,
+        page: pagination.page(),
+        limit: pagination.limit(),
+        total,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::export::CSV_COLUMNS;
+    use chrono::Utc;
+
+    fn field(name: &str) -> CustomField {
+        CustomField {
+            id: Uuid::new_v4(),
+            project_id: Uuid::nil(),
+            name: name.to_string(),
+            field_type: "text".to_string(),
+            options: serde_json::json!([]),
+            required: false,
+            position: 0,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn headers(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn bad_request(result: AppResult<HashMap<String, usize>>) -> String {
+        match result {
+            Err(AppError::BadRequest(message)) => message,
+            other => panic!("expected a bad request, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn matches_headers_by_name() {
+        let fields = [field("Points"), field("Size")];
+        let columns = resolve_columns(
+            &headers(&["Summary", "DUE", "Tags", "cf.points", "size", "Other"]),
+            &fields,
+            None,
+        )
+        .unwrap();
+        let expected: HashMap<String, usize> = [
+            ("title", 0),
+            ("deadline", 1),
+            ("labels", 2),
+            ("cf.Points", 3),
+            ("cf.Size", 4),
+        ]
+        .into_iter()
+        .map(|(target, index)| (target.to_string(), index))
+        .collect();
+        assert_eq!(columns, expected);
+
+        let columns = resolve_columns(&headers(&["Name", "Title"]), &[], None).unwrap();
+        assert_eq!(columns["title"], 1);
+    }
+
+    #[test]
+    fn uses_an_explicit_mapping() {
+        let fields = [field("Points")];
+        let mapping: HashMap<String, String> = [
+            ("title", " Task "),
+            ("assignee", "Owner"),
+            ("cf.points", "Effort"),
+        ]
+        .into_iter()
+        .map(|(target, column)| (target.to_string(), column.to_string()))
+        .collect();
+        let columns = resolve_columns(
+            &headers(&["Effort", "title", "task", "owner"]),
+            &fields,
+            Some(&mapping),
+        )
+        .unwrap();
+        assert_eq!(columns.len(), 3);
+        assert_eq!(columns["title"], 2);
+        assert_eq!(columns["assignee"], 3);
+        assert_eq!(columns["cf.points"], 0);
+    }
+
+    #[test]
+    fn rejects_bad_mappings() {
+        let map = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(t, c)| (t.to_string(), c.to_string()))
+                .collect()
+        };
+        let file = headers(&["Task", "Notes"]);
+
+        let message = bad_request(resolve_columns(
+            &file,
+            &[],
+            Some(&map(&[("title", "Task"), ("owner", "Notes")])),
+        ));
+        assert_eq!(message, "Unknown import field 'owner'");
+
+        let message = bad_request(resolve_columns(
+            &file,
+            &[],
+            Some(&map(&[("title", "Task"), ("cf.Points", "Notes")])),
+        ));
+        assert_eq!(message, "Unknown import field 'cf.Points'");
+
+        let message = bad_request(resolve_columns(
+            &file,
+            &[],
+            Some(&map(&[("title", "Name")])),
+        ));
+        assert_eq!(
+            message,
+            "Column 'Name' mapped to 'title' is not in the file"
+        );
+
+        let message = bad_request(resolve_columns(
+            &file,
+            &[],
+            Some(&map(&[("description", "Notes")])),
+        ));
+        assert_eq!(message, "No column is mapped to 'title'");
+
+        let message = bad_request(resolve_columns(&file, &[], None));
+        assert_eq!(message, "No column is mapped to 'title'");
+    }
+
+    #[test]
+    fn reads_back_cells_escaped_by_the_export() {
+        let task = [
+            ("title", "=HYPERLINK(\"http://example.com\")"),
+            ("description", "- buy milk\n- buy eggs"),
+            ("status", "todo"),
+            ("priority", "high"),
+            ("assignee_email", "@ann@example.com"),
+            ("labels", "'quoted, +1"),
+            ("original_estimate_minutes", "90"),
+        ];
+        let value = |column: &str| {
+            task.iter()
+                .find(|(c, _)| *c == column)
+                .map_or("", |(_, v)| *v)
+                .to_string()
+        };
+
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(CSV_COLUMNS).unwrap();
+        writer
+            .write_record(
+                CSV_COLUMNS
+                    .iter()
+                    .map(|column| spreadsheet::escape_cell(value(column))),
+            )
+            .unwrap();
+        let data = writer.into_inner().unwrap();
+
+        let mut reader = csv::Reader::from_reader(data.as_slice());
+        let headers: Vec<String> = reader.headers().unwrap().iter().map(String::from).collect();
+        let columns = resolve_columns(&headers, &[], None).unwrap();
+        let record = reader.records().next().unwrap().unwrap();
+        assert!(record.iter().any(|cell| cell.starts_with("'=")));
+
+        let draft = draft_from_record(&record, &columns);
+        assert_eq!(draft.title, value("title"));
+        assert_eq!(draft.description, Some(value("description")));
+        assert_eq!(draft.assignee_email, Some(value("assignee_email")));
+        assert_eq!(draft.labels, vec![value("labels")]);
+        assert_eq!(draft.estimate, Some(value("original_estimate_minutes")));
+    }
+}
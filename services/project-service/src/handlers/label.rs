@@ -0,0 +1,469 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde_json::json;
+use shared::{
+    errors::{AppError, AppResult},
+    models::Claims,
+};
+use sqlx::{FromRow, PgPool, Postgres, Transaction};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::{
+    access,
+    models::{CreateLabelRequest, Label, LabelWithCounts, MergeLabelRequest, UpdateLabelRequest},
+    AppState,
+};
+
+pub(crate) const LABEL_COLUMNS: &str = "l.id, l.project_id, l.name, l.color, l.created_at, l.updated_at";
+
+const LABEL_COUNTS_SELECT: &str = "SELECT l.id, l.project_id, l.name, l.color, l.created_at, l.updated_at,
+        COUNT(t.id) AS task_count,
+        COUNT(t.id) FILTER (WHERE t.status <> 'done') AS open_task_count
+    FROM labels l
+    LEFT JOIN task_labels tl ON tl.label_id = l.id
+    LEFT JOIN tasks t ON t.id = tl.task_id AND t.deleted_at IS NULL";
+
+pub(crate) const DEFAULT_COLOR: &str = "#6B7280";
+
+pub(crate) fn validate_name(name: &str) -> AppResult<String> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 50 {
+        return Err(AppError::ValidationError(
+            "Label name must be between 1 and 50 characters".to_string(),
+        ));
+    }
+    if name.contains(',') {
+        return Err(AppError::ValidationError(
+            "Label name cannot contain commas".to_string(),
+        ));
+    }
+    Ok(name.to_string())
+}
+
+fn validate_color(color: &str) -> AppResult<String> {
+    let hex = color.strip_prefix('#').unwrap_or_default();
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(AppError::ValidationError(
+            "Label color must be a hex value like #1E90FF".to_string(),
+        ));
+    }
+    Ok(format!("#{}", hex.to_ascii_uppercase()))
+}
+
+/// Touches the tasks carrying a label before their labels change, which bumps
+/// their `version` and so their ETag.
+async fn touch_labeled_tasks(
+    tx: &mut Transaction<'_, Postgres>,
+    label_id: Uuid,
+) -> AppResult<()> {
+    sqlx::query(
+        "UPDATE tasks SET updated_at = NOW()
+         WHERE id IN (SELECT task_id FROM task_labels WHERE label_id = $1)",
+    )
+    .bind(label_id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+async fn touch_task(tx: &mut Transaction<'_, Postgres>, task_id: Uuid) -> AppResult<()> {
+    sqlx::query("UPDATE tasks SET updated_at = NOW() WHERE id = $1")
+        .bind(task_id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+fn map_name_conflict(err: sqlx::Error, name: &str) -> AppError {
+    match &err {
+        sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("23505") => {
+            AppError::Conflict(format!("A label named '{}' already exists in this project", name))
+        }
+        _ => err.into(),
+    }
+}
+
+pub(crate) async fn load_label(db: &PgPool, label_id: Uuid) -> AppResult<Label> {
+    sqlx::query_as::<_, Label>(&format!("SELECT {} FROM labels l WHERE l.id = $1", LABEL_COLUMNS))
+        .bind(label_id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Label not found".to_string()))
+}
+
+#[derive(FromRow)]
+struct TaskLabelRow {
+    task_id: Uuid,
+    #[sqlx(flatten)]
+    label: Label,
+}
+
+/// Labels attached to each of the given tasks, keyed by task id.
+pub(crate) async fn labels_for_tasks(
+    db: &PgPool,
+    task_ids: &[Uuid],
+) -> AppResult<HashMap<Uuid, Vec<Label>>> {
+    let rows = sqlx::query_as::<_, TaskLabelRow>(&format!(
+        "SELECT tl.task_id, {}
+         FROM task_labels tl
+         JOIN labels l ON l.id = tl.label_id
+         WHERE tl.task_id = ANY($1)
+         ORDER BY LOWER(l.name)",
+        LABEL_COLUMNS
+    ))
+    .bind(task_ids)
+    .fetch_all(db)
+    .await?;
+
+    let mut by_task: HashMap<Uuid, Vec<Label>> = HashMap::new();
+    for row in rows {
+        by_task.entry(row.task_id).or_default().push(row.label);
+    }
+
+    Ok(by_task)
+}
+
+/// Replaces the labels of a task. Every label must belong to the task's project.
+pub(crate) async fn set_task_labels(
+    tx: &mut Transaction<'_, Postgres>,
+    project_id: Uuid,
+    task_id: Uuid,
+    label_ids: &[Uuid],
+) -> AppResult<()> {
+    let mut label_ids = label_ids.to_vec();
+    label_ids.sort();
+    label_ids.dedup();
+
+    let found: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM labels WHERE project_id = $1 AND id = ANY($2)")
+            .bind(project_id)
+            .bind(&label_ids)
+            .fetch_one(&mut **tx)
+            .await?;
+
+    if found != label_ids.len() as i64 {
+        return Err(AppError::BadRequest(
+            "One or more labels do not belong to this project".to_string(),
+        ));
+    }
+
+    sqlx::query("DELETE FROM task_labels WHERE task_id = $1")
+        .bind(task_id)
+        .execute(&mut **tx)
+        .await?;
+
+    sqlx::query("INSERT INTO task_labels (task_id, label_id) SELECT $1, UNNEST($2::uuid[])")
+        .bind(task_id)
+        .bind(&label_ids)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(())
+}
+
+/// Splits a comma-separated label filter into ids and lowercased names.
+fn split_label_filter(raw: &str) -> (Vec<Uuid>, Vec<String>) {
+    let mut ids = Vec::new();
+    let mut names = Vec::new();
+
+    for token in raw.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+        match Uuid::parse_str(token) {
+            Ok(id) => ids.push(id),
+            Err(_) => names.push(token.to_lowercase()),
+        }
+    }
+    (ids, names)
+}
+
+/// Resolves a comma-separated list of label ids or names within a project.
+pub(crate) async fn resolve_label_filter(
+    db: &PgPool,
+    project_id: Uuid,
+    raw: &str,
+) -> AppResult<Vec<Uuid>> {
+    let (mut ids, names) = split_label_filter(raw);
+
+    if !names.is_empty() {
+        let found: Vec<(Uuid, String)> = sqlx::query_as(
+            "SELECT id, LOWER(name) FROM labels WHERE project_id = $1 AND LOWER(name) = ANY($2)",
+        )
+        .bind(project_id)
+        .bind(&names)
+        .fetch_all(db)
+        .await?;
+
+        if let Some(missing) = names.iter().find(|n| !found.iter().any(|(_, f)| f == *n)) {
+            return Err(AppError::BadRequest(format!("Unknown label '{}'", missing)));
+        }
+        ids.extend(found.into_iter().map(|(id, _)| id));
+    }
+
+    ids.sort();
+    ids.dedup();
+    Ok(ids)
+}
+
+async fn load_label_with_counts(db: &PgPool, label_id: Uuid) -> AppResult<LabelWithCounts> {
+    sqlx::query_as::<_, LabelWithCounts>(&format!(
+        "{} WHERE l.id = $1 GROUP BY l.id",
+        LABEL_COUNTS_SELECT
+    ))
+    .bind(label_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Label not found".to_string()))
+}
+
+/// Lists a project's labels with total and open task counts for dashboards.
+pub async fn list_labels(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+) -> AppResult<Json<Vec<LabelWithCounts>>> {
+    access::require_member(&state.db, project_id, &claims).await?;
+
+    let labels = sqlx::query_as::<_, LabelWithCounts>(&format!(
+        "{} WHERE l.project_id = $1 GROUP BY l.id ORDER BY LOWER(l.name)",
+        LABEL_COUNTS_SELECT
+    ))
+    .bind(project_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(labels))
+}
+
+pub async fn create_label(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+    Json(req): Json<CreateLabelRequest>,
+) -> AppResult<(StatusCode, Json<Label>)> {
+    access::require_editor(&state.db, project_id, &claims).await?;
+
+    let name = validate_name(&req.name)?;
+    let color = validate_color(req.color.as_deref().unwrap_or(DEFAULT_COLOR))?;
+
+    let label = sqlx::query_as::<_, Label>(&format!(
+        "INSERT INTO labels AS l (project_id, name, color)
+         VALUES ($1, $2, $3)
+         RETURNING {}",
+        LABEL_COLUMNS
+    ))
+    .bind(project_id)
+    .bind(&name)
+    .bind(&color)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| map_name_conflict(e, &name))?;
+
+    Ok((StatusCode::CREATED, Json(label)))
+}
+
+/// Renames or recolours a label.
+pub async fn update_label(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(label_id): Path<Uuid>,
+    Json(req): Json<UpdateLabelRequest>,
+) -> AppResult<Json<Label>> {
+    let label = load_label(&state.db, label_id).await?;
+    access::require_editor(&state.db, label.project_id, &claims).await?;
+
+    let name = req.name.as_deref().map(validate_name).transpose()?;
+    let color = req.color.as_deref().map(validate_color).transpose()?;
+
+    let label = sqlx::query_as::<_, Label>(&format!(
+        "UPDATE labels AS l
+         SET name = COALESCE($2, l.name), color = COALESCE($3, l.color), updated_at = NOW()
+         WHERE l.id = $1
+         RETURNING {}",
+        LABEL_COLUMNS
+    ))
+    .bind(label_id)
+    .bind(&name)
+    .bind(&color)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| map_name_conflict(e, name.as_deref().unwrap_or(&label.name)))?;
+
+    Ok(Json(label))
+}
+
+pub async fn delete_label(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(label_id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    let label = load_label(&state.db, label_id).await?;
+    access::require_editor(&state.db, label.project_id, &claims).await?;
+
+    let mut tx = state.db.begin().await?;
+    touch_labeled_tasks(&mut tx, label_id).await?;
+    sqlx::query("DELETE FROM labels WHERE id = $1")
+        .bind(label_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(Json(json!({"message": "deleted"})))
+}
+
+/// Moves every task from one label onto another, then removes the source label.
+pub async fn merge_label(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(label_id): Path<Uuid>,
+    Json(req): Json<MergeLabelRequest>,
+) -> AppResult<Json<LabelWithCounts>> {
+    if req.into_label_id == label_id {
+        return Err(AppError::BadRequest("Cannot merge a label into itself".to_string()));
+    }
+
+    let source = load_label(&state.db, label_id).await?;
+    let target = load_label(&state.db, req.into_label_id).await?;
+    if source.project_id != target.project_id {
+        return Err(AppError::BadRequest(
+            "Labels must belong to the same project".to_string(),
+        ));
+    }
+    access::require_editor(&state.db, source.project_id, &claims).await?;
+
+    let mut tx = state.db.begin().await?;
+
+    touch_labeled_tasks(&mut tx, source.id).await?;
+    sqlx::query(
+        "INSERT INTO task_labels (task_id, label_id)
+         SELECT task_id, $2 FROM task_labels WHERE label_id = $1
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(source.id)
+    .bind(target.id)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("DELETE FROM labels WHERE id = $1")
+        .bind(source.id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(load_label_with_counts(&state.db, target.id).await?))
+}
+
+pub async fn attach_label(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((task_id, label_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<Vec<Label>>> {
+    let project_id = access::task_project_id(&state.db, task_id).await?;
+    access::require_editor(&state.db, project_id, &claims).await?;
+
+    let label = load_label(&state.db, label_id).await?;
+    if label.project_id != project_id {
+        return Err(AppError::BadRequest(
+            "Label does not belong to this project".to_string(),
+        ));
+    }
+
+    let mut tx = state.db.begin().await?;
+    let attached = sqlx::query(
+        "INSERT INTO task_labels (task_id, label_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+    )
+    .bind(task_id)
+    .bind(label_id)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if attached > 0 {
+        touch_task(&mut tx, task_id).await?;
+    }
+    tx.commit().await?;
+
+    let mut labels = labels_for_tasks(&state.db, &[task_id]).await?;
+    Ok(Json(labels.remove(&task_id).unwrap_or_default()))
+}
+
+pub async fn detach_label(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((task_id, label_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<Vec<Label>>> {
+    let project_id = access::task_project_id(&state.db, task_id).await?;
+    access::require_editor(&state.db, project_id, &claims).await?;
+
+    let mut tx = state.db.begin().await?;
+    let detached = sqlx::query("DELETE FROM task_labels WHERE task_id = $1 AND label_id = $2")
+        .bind(task_id)
+        .bind(label_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if detached > 0 {
+        touch_task(&mut tx, task_id).await?;
+    }
+    tx.commit().await?;
+
+    let mut labels = labels_for_tasks(&state.db, &[task_id]).await?;
+    Ok(Json(labels.remove(&task_id).unwrap_or_default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_names() {
+        let longest = "é".repeat(50);
+        let too_long = "x".repeat(51);
+        let cases = [
+            ("bug", Ok("bug")),
+            ("  needs review ", Ok("needs review")),
+            (longest.as_str(), Ok(longest.as_str())),
+            ("", Err("between 1 and 50")),
+            ("   ", Err("between 1 and 50")),
+            (too_long.as_str(), Err("between 1 and 50")),
+            ("bug, ui", Err("cannot contain commas")),
+        ];
+        for (input, expected) in cases {
+            match (validate_name(input), expected) {
+                (Ok(name), Ok(expected)) => assert_eq!(name, expected),
+                (Err(AppError::ValidationError(message)), Err(expected)) => {
+                    assert!(message.contains(expected), "{:?}: {}", input, message)
+                }
+                (result, _) => panic!("{:?}: unexpected {:?}", input, result),
+            }
+        }
+    }
+
+    #[test]
+    fn validates_and_normalizes_colors() {
+        assert_eq!(validate_color("#1e90ff").unwrap(), "#1E90FF");
+        assert_eq!(validate_color("#6B7280").unwrap(), DEFAULT_COLOR);
+        for color in [
+            "", "#", "1E90FF", "#1E90F", "#1E90FF0", "#GGGGGG", "#1E 0FF", "#ＡＢＣＤＥＦ",
+        ] {
+            assert!(
+                matches!(validate_color(color), Err(AppError::ValidationError(_))),
+                "{:?}",
+                color
+            );
+        }
+    }
+
+    #[test]
+    fn splits_label_filters_into_ids_and_names() {
+        let id = Uuid::new_v4();
+        let (ids, names) = split_label_filter(&format!(" Bug ,{}, ,UI,,bug", id));
+        assert_eq!(ids, [id]);
+        assert_eq!(names, ["bug", "ui", "bug"]);
+
+        let (ids, names) = split_label_filter(" , ");
+        assert!(ids.is_empty() && names.is_empty());
+    }
+}
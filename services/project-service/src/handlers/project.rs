@@ -1,34 +1,455 @@
-use axum::Json;
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
 use serde_json::json;
+use shared::{
+    errors::{AppError, AppResult},
+    models::{
+        AddMemberRequest, Claims, CreateProjectRequest, PaginatedResponse, PaginationParams,
+        Project, ProjectMember, UpdateProjectRequest,
+    },
+};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
 
-pub async fn create_project() -> Json<serde_json::Value> {
-    Json(json!({}))
+use crate::{
+    access::{self, ProjectRole},
+    activity::{self, Entity},
+    etag,
+    models::CloneProjectRequest,
+    quota::Resource,
+    templates::{self, SnapshotOptions},
+    AppState,
+};
+
+pub(crate) const PROJECT_COLUMNS: &str = "p.id, p.owner_id, p.name, p.description,
+    p.status::text AS status, p.version, p.created_at, p.updated_at";
+
+const PROJECT_STATUSES: [&str; 2] = ["active", "archived"];
+const MEMBER_ROLES: [&str; 3] = ["viewer", "editor", "admin"];
+
+const MEMBER_COLUMNS: &str =
+    "pm.id, pm.project_id, pm.user_id, pm.role::text AS role, pm.joined_at";
+
+pub(crate) fn validate_name(name: &str) -> AppResult<String> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 255 {
+        return Err(AppError::ValidationError(
+            "Project name must be between 1 and 255 characters".to_string(),
+        ));
+    }
+    Ok(name.to_string())
 }
 
-pub async fn list_projects() -> Json<serde_json::Value> {
-    Json(json!([]))
+pub(crate) async fn load_project(db: &PgPool, project_id: Uuid) -> AppResult<Project> {
+    sqlx::query_as::<_, Project>(&format!(
+        "SELECT {} FROM projects p WHERE p.id = $1 AND p.deleted_at IS NULL",
+        PROJECT_COLUMNS
+    ))
+    .bind(project_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Project not found".to_string()))
 }
 
-pub async fn get_project() -> Json<serde_json::Value> {
-    Json(json!({}))
+/// Inserts a project owned by `owner_id` and records its creation.
+pub(crate) async fn insert_project(
+    tx: &mut Transaction<'_, Postgres>,
+    owner_id: Uuid,
+    name: &str,
+    description: Option<&str>,
+) -> AppResult<Project> {
+    let project = sqlx::query_as::<_, Project>(&format!(
+        "INSERT INTO projects AS p (owner_id, name, description)
+         VALUES ($1, $2, $3)
+         RETURNING {}",
+        PROJECT_COLUMNS
+    ))
+    .bind(owner_id)
+    .bind(name)
+    .bind(description)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    activity::record_created(
+        tx,
+        project.id,
+        Entity::Project,
+        project.id,
+        Some(owner_id),
+        &project,
+    )
+    .await?;
+
+    Ok(project)
 }
 
-pub async fn update_project() -> Json<serde_json::Value> {
-    Json(json!({}))
+pub async fn create_project(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<CreateProjectRequest>,
+) -> AppResult<(StatusCode, Json<Project>)> {
+    let owner_id = access::user_id(&claims)?;
+    let name = validate_name(&req.name)?;
+    let quota = state.billing.quota(owner_id, owner_id).await?;
+
+    let mut tx = state.db.begin().await?;
+    quota.check(&mut tx, Resource::Projects, 1).await?;
+    let project = insert_project(&mut tx, owner_id, &name, req.description.as_deref()).await?;
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(project)))
 }
 
-pub async fn delete_project() -> Json<serde_json::Value> {
-    Json(json!({"message": "deleted"}))
+/// Projects the caller owns or is a member of; global admins see all.
+pub async fn list_projects(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(pagination): Query<PaginationParams>,
+) -> AppResult<Json<PaginatedResponse<Project>>> {
+    let user_id = access::user_id(&claims)?;
+    let all = access::is_global_admin(&claims);
+
+    let visible = "p.deleted_at IS NULL AND ($2 OR p.owner_id = $1
+                    OR EXISTS (SELECT 1 FROM project_members pm
+                               WHERE pm.project_id = p.id AND pm.user_id = $1))";
+
+    let total: i64 = sqlx::query_scalar(&format!(
+        "SELECT COUNT(*) FROM projects p WHERE {}",
+        visible
+    ))
+    .bind(user_id)
+    .bind(all)
+    .fetch_one(&state.db)
+    .await?;
+
+    let projects = sqlx::query_as::<_, Project>(&format!(
+        "SELECT {} FROM projects p WHERE {}
+         ORDER BY p.created_at DESC, p.id
+         LIMIT $3 OFFSET $4",
+        PROJECT_COLUMNS, visible
+    ))
+    .bind(user_id)
+    .bind(all)
+    .bind(pagination.limit())
+    .bind(pagination.offset())
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(PaginatedResponse {
+        data: projects,
+        page: pagination.page(),
+        limit: pagination.limit(),
+        total,
+    }))
 }
 
-pub async fn get_members() -> Json<serde_json::Value> {
-    Json(json!([]))
+pub async fn get_project(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+) -> AppResult<impl IntoResponse> {
+    access::require_member(&state.db, project_id, &claims).await?;
+
+    let project = load_project(&state.db, project_id).await?;
+    Ok((etag::header(project.version), Json(project)))
 }
 
-pub async fn add_member() -> Json<serde_json::Value> {
-    Json(json!({}))
+pub async fn update_project(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+    headers: HeaderMap,
+    Json(req): Json<UpdateProjectRequest>,
+) -> AppResult<impl IntoResponse> {
+    access::require_admin_role(&state.db, project_id, &claims).await?;
+    let current = load_project(&state.db, project_id).await?;
+    let expected = etag::expected_version(&headers, current.version, state.require_if_match)?;
+
+    let name = req.name.as_deref().map(validate_name).transpose()?;
+    if current.status == "archived" && (name.is_some() || req.description.is_some()) {
+        return Err(AppError::Forbidden(
+            "Project is archived and read-only; unarchive it first".to_string(),
+        ));
+    }
+    if let Some(status) = &req.status {
+        if !PROJECT_STATUSES.contains(&status.as_str()) {
+            return Err(AppError::ValidationError(format!(
+                "Invalid status '{}', expected one of: {}",
+                status,
+                PROJECT_STATUSES.join(", ")
+            )));
+        }
+    }
+
+    let mut tx = state.db.begin().await?;
+
+    let project = sqlx::query_as::<_, Project>(&format!(
+        "UPDATE projects AS p SET
+            name = COALESCE($2, p.name),
+            description = COALESCE($3, p.description),
+            status = COALESCE($4::project_status, p.status),
+            updated_at = NOW()
+         WHERE p.id = $1 AND ($5::int IS NULL OR p.version = $5)
+         RETURNING {}",
+        PROJECT_COLUMNS
+    ))
+    .bind(project_id)
+    .bind(&name)
+    .bind(&req.description)
+    .bind(&req.status)
+    .bind(expected)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| etag::modified(current.version))?;
+
+    let actor = access::user_id(&claims)?;
+    activity::record_updated(
+        &mut tx,
+        project_id,
+        Entity::Project,
+        project_id,
+        Some(actor),
+        &current,
+        &project,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((etag::header(project.version), Json(project)))
+}
+
+/// Moves a project to the trash. Only the owner may do this; archived
+/// projects can be deleted too.
+pub async fn delete_project(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> AppResult<Json<serde_json::Value>> {
+    let role = access::require_member(&state.db, project_id, &claims).await?;
+    if role != ProjectRole::Owner && !access::is_global_admin(&claims) {
+        return Err(AppError::Forbidden(
+            "Only the project owner can delete it".to_string(),
+        ));
+    }
+    let actor = access::user_id(&claims)?;
+    let current = load_project(&state.db, project_id).await?;
+    let expected = etag::expected_version(&headers, current.version, state.require_if_match)?;
+
+    let mut tx = state.db.begin().await?;
+
+    let deleted = sqlx::query(
+        "UPDATE projects SET deleted_at = NOW(), deleted_by = $2
+         WHERE id = $1 AND ($3::int IS NULL OR version = $3)",
+    )
+    .bind(project_id)
+    .bind(actor)
+    .bind(expected)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if deleted == 0 {
+        return Err(etag::modified(current.version));
+    }
+    activity::record_event(
+        &mut tx,
+        project_id,
+        Entity::Project,
+        project_id,
+        Some(actor),
+        "deleted",
+        None,
+        None,
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(json!({"message": "moved to trash"})))
 }
 
-pub async fn remove_member() -> Json<serde_json::Value> {
-    Json(json!({"message": "removed"}))
+/// Deep-copies a project into a new one owned by the caller. Members are
+/// copied on request; otherwise assignees who are not members of the copy are
+/// cleared.
+pub async fn clone_project(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+    req: Option<Json<CloneProjectRequest>>,
+) -> AppResult<(StatusCode, Json<Project>)> {
+    let req = req.map(|Json(r)| r).unwrap_or_default();
+    access::require_editor_role(&state.db, project_id, &claims).await?;
+    let owner_id = access::user_id(&claims)?;
+    let source = load_project(&state.db, project_id).await?;
+
+    let name = match &req.name {
+        Some(name) => validate_name(name)?,
+        None => validate_name(&format!("Copy of {}", source.name))?,
+    };
+    let reference = source.created_at.date_naive();
+    let content = templates::snapshot(
+        &state.db,
+        &source,
+        reference,
+        SnapshotOptions {
+            include_tasks: req.include_tasks,
+            keep_progress: !req.reset_statuses,
+            keep_people: true,
+        },
+    )
+    .await?;
+    let quota = state.billing.quota(owner_id, owner_id).await?;
+
+    let mut tx = state.db.begin().await?;
+    quota.check(&mut tx, Resource::Projects, 1).await?;
+    quota
+        .check(&mut tx, Resource::Tasks, content.tasks.len() as i64)
+        .await?;
+    let project = insert_project(&mut tx, owner_id, &name, source.description.as_deref()).await?;
+    if req.include_members {
+        templates::copy_members(&mut tx, &source, project.id, owner_id).await?;
+    }
+    templates::instantiate(&mut tx, project.id, &content, reference, owner_id).await?;
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(project)))
+}
+
+pub async fn get_members(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+) -> AppResult<Json<Vec<ProjectMember>>> {
+    access::require_member(&state.db, project_id, &claims).await?;
+
+    let members = sqlx::query_as::<_, ProjectMember>(&format!(
+        "SELECT {} FROM project_members pm WHERE pm.project_id = $1 ORDER BY pm.joined_at",
+        MEMBER_COLUMNS
+    ))
+    .bind(project_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(members))
+}
+
+pub async fn add_member(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+    Json(req): Json<AddMemberRequest>,
+) -> AppResult<(StatusCode, Json<ProjectMember>)> {
+    access::require_admin(&state.db, project_id, &claims).await?;
+
+    if !MEMBER_ROLES.contains(&req.role.as_str()) {
+        return Err(AppError::ValidationError(format!(
+            "Invalid role '{}', expected one of: {}",
+            req.role,
+            MEMBER_ROLES.join(", ")
+        )));
+    }
+
+    let project = load_project(&state.db, project_id).await?;
+    if project.owner_id == req.user_id {
+        return Err(AppError::BadRequest(
+            "The project owner is already a member".to_string(),
+        ));
+    }
+
+    let user_exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM users WHERE id = $1)")
+        .bind(req.user_id)
+        .fetch_one(&state.db)
+        .await?;
+    if !user_exists {
+        return Err(AppError::NotFound("User not found".to_string()));
+    }
+
+    let mut tx = state.db.begin().await?;
+
+    let member = sqlx::query_as::<_, ProjectMember>(&format!(
+        "INSERT INTO project_members AS pm (project_id, user_id, role)
+         VALUES ($1, $2, $3::member_role)
+         RETURNING {}",
+        MEMBER_COLUMNS
+    ))
+    .bind(project_id)
+    .bind(req.user_id)
+    .bind(&req.role)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| match &e {
+        sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("23505") => {
+            AppError::Conflict("User is already a member of this project".to_string())
+        }
+        _ => e.into(),
+    })?;
+
+    activity::record_event(
+        &mut tx,
+        project_id,
+        Entity::Project,
+        project_id,
+        Some(access::user_id(&claims)?),
+        "member_added",
+        None,
+        None,
+        Some(json!({"user_id": member.user_id, "role": member.role})),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(member)))
+}
+
+/// Removes a member. Admins can remove anyone; members can remove themselves.
+pub async fn remove_member(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((project_id, user_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<serde_json::Value>> {
+    let actor = access::user_id(&claims)?;
+    if actor == user_id {
+        access::require_member(&state.db, project_id, &claims).await?;
+        access::require_writable(&state.db, project_id).await?;
+    } else {
+        access::require_admin(&state.db, project_id, &claims).await?;
+    }
+
+    let mut tx = state.db.begin().await?;
+
+    let member = sqlx::query_as::<_, ProjectMember>(&format!(
+        "DELETE FROM project_members AS pm
+         WHERE pm.project_id = $1 AND pm.user_id = $2
+         RETURNING {}",
+        MEMBER_COLUMNS
+    ))
+    .bind(project_id)
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Member not found".to_string()))?;
+
+    activity::record_event(
+        &mut tx,
+        project_id,
+        Entity::Project,
+        project_id,
+        Some(actor),
+        "member_removed",
+        None,
+        Some(json!({"user_id": member.user_id, "role": member.role})),
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(json!({"message": "removed"})))
 }
@@ -0,0 +1,62 @@
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use shared::{
+    errors::{AppError, AppResult},
+    models::Claims,
+};
+use uuid::Uuid;
+
+use crate::{
+    access,
+    handlers::task::load_task,
+    models::RecurrenceResponse,
+    recurrence, AppState,
+};
+
+const UPCOMING_PREVIEW: usize = 5;
+
+/// The series a task belongs to, with a preview of its next occurrences.
+pub async fn get_task_recurrence(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(task_id): Path<Uuid>,
+) -> AppResult<Json<RecurrenceResponse>> {
+    let task = load_task(&state.db, task_id).await?;
+    access::require_member(&state.db, task.project_id, &claims).await?;
+
+    let recurrence_id = task
+        .recurrence_id
+        .ok_or_else(|| AppError::NotFound("Task does not recur".to_string()))?;
+    let series = recurrence::load_series(&state.db, recurrence_id).await?;
+    let upcoming = recurrence::upcoming(&series, UPCOMING_PREVIEW)?;
+
+    Ok(Json(RecurrenceResponse {
+        recurrence: series,
+        upcoming,
+    }))
+}
+
+/// Stops the series a task belongs to. Occurrences already created are kept.
+pub async fn stop_task_recurrence(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(task_id): Path<Uuid>,
+) -> AppResult<Json<RecurrenceResponse>> {
+    let task = load_task(&state.db, task_id).await?;
+    access::require_editor(&state.db, task.project_id, &claims).await?;
+
+    let recurrence_id = task
+        .recurrence_id
+        .ok_or_else(|| AppError::NotFound("Task does not recur".to_string()))?;
+
+    let mut tx = state.db.begin().await?;
+    recurrence::stop_series(&mut tx, recurrence_id).await?;
+    tx.commit().await?;
+
+    Ok(Json(RecurrenceResponse {
+        recurrence: recurrence::load_series(&state.db, recurrence_id).await?,
+        upcoming: Vec::new(),
+    }))
+}
@@ -0,0 +1,183 @@
+use axum::{
+    extract::{Query, State},
+    Extension, Json,
+};
+use chrono::NaiveDate;
+use shared::{
+    errors::{AppError, AppResult},
+    models::Claims,
+};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::{
+    access,
+    handlers::time_entry::resolve_range,
+    models::{
+        AssigneeWorkload, PriorityCount, ReportParams, ReportTotals, WeeklyThroughput,
+        WorkloadReport,
+    },
+    AppState,
+};
+
+/// Tasks in scope, flagged for the aggregates below. Parameters: $1 caller,
+/// $2 whether every project is visible, $3 optional project, $4 and $5 the
+/// date range. A done task counts as completed at its last move to `done`.
+const SCOPE: &str = "WITH scoped AS (
+         SELECT t.id, t.assignee_id, t.status::text AS status, t.priority::text AS priority,
+                t.deadline, t.created_at,
+                COALESCE(t.remaining_estimate_minutes, t.original_estimate_minutes, 0) AS estimate,
+                CASE WHEN t.status = 'done' THEN
+                    COALESCE((SELECT MAX(c.changed_at) FROM task_status_changes c
+                              WHERE c.task_id = t.id AND c.status = 'done'), t.updated_at)
+                END AS done_at
+         FROM tasks t
+         JOIN projects p ON p.id = t.project_id
+         WHERE t.deleted_at IS NULL AND p.deleted_at IS NULL
+           AND ($3::uuid IS NULL OR p.id = $3)
+           AND ($2 OR p.owner_id = $1 OR EXISTS (SELECT 1 FROM project_members pm
+                                                 WHERE pm.project_id = p.id AND pm.user_id = $1))
+     ),
+     flags AS (
+         SELECT s.*,
+                s.status <> 'done' AS open,
+                s.status <> 'done' AND s.deadline < NOW() AS overdue,
+                COALESCE(s.done_at >= $4::date AND s.done_at < $5::date + INTERVAL '1 day', false)
+                    AS completed,
+                (EXTRACT(EPOCH FROM s.done_at - s.created_at) / 3600.0)::float8 AS completion_hours
+         FROM scoped s
+     )";
+
+/// Binds the `SCOPE` parameters, in order.
+struct Scope {
+    user_id: Uuid,
+    all_projects: bool,
+    project_id: Option<Uuid>,
+    from: NaiveDate,
+    to: NaiveDate,
+}
+
+impl Scope {
+    async fn fetch_all<T>(&self, db: &PgPool, query: &str) -> AppResult<Vec<T>>
+    where
+        T: for<'r> FromRow<'r, sqlx::postgres::PgRow> + Send + Unpin,
+    {
+        Ok(sqlx::query_as::<_, T>(&format!("{} {}", SCOPE, query))
+            .bind(self.user_id)
+            .bind(self.all_projects)
+            .bind(self.project_id)
+            .bind(self.from)
+            .bind(self.to)
+            .fetch_all(db)
+            .await?)
+    }
+
+    /// For aggregates, which always return one row.
+    async fn fetch_one<T>(&self, db: &PgPool, query: &str) -> AppResult<T>
+    where
+        T: for<'r> FromRow<'r, sqlx::postgres::PgRow> + Send + Unpin,
+    {
+        self.fetch_all(db, query)
+            .await?
+            .pop()
+            .ok_or_else(|| AppError::InternalError("Report query returned no rows".to_string()))
+    }
+}
+
+/// Open tasks per assignee, overdue counts, weekly throughput, priority
+/// distribution and completion times across the caller's projects, or one
+/// project with `project_id`.
+pub async fn get_workload_report(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(params): Query<ReportParams>,
+) -> AppResult<Json<WorkloadReport>> {
+    let user_id = access::user_id(&claims)?;
+    if let Some(project_id) = params.project_id {
+        access::require_member(&state.db, project_id, &claims).await?;
+    }
+    let (from, to) = resolve_range(&params.range())?;
+
+    let scope = Scope {
+        user_id,
+        all_projects: params.project_id.is_some() || access::is_global_admin(&claims),
+        project_id: params.project_id,
+        from,
+        to,
+    };
+
+    let totals = scope
+        .fetch_one::<ReportTotals>(
+            &state.db,
+            "SELECT COUNT(*) FILTER (WHERE open) AS open_tasks,
+                    COUNT(*) FILTER (WHERE overdue) AS overdue_tasks,
+                    COUNT(*) FILTER (WHERE completed) AS completed_tasks,
+                    AVG(completion_hours) FILTER (WHERE completed) AS avg_completion_hours,
+                    PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY completion_hours)
+                        FILTER (WHERE completed) AS median_completion_hours
+             FROM flags",
+        )
+        .await?;
+
+    let by_assignee = scope
+        .fetch_all::<AssigneeWorkload>(
+            &state.db,
+            "SELECT f.assignee_id, u.name AS assignee_name, u.email AS assignee_email,
+                    COUNT(*) FILTER (WHERE f.open) AS open_tasks,
+                    COUNT(*) FILTER (WHERE f.status = 'in_progress') AS in_progress_tasks,
+                    COUNT(*) FILTER (WHERE f.overdue) AS overdue_tasks,
+                    COUNT(*) FILTER (WHERE f.open AND f.priority = 'high')
+                        AS high_priority_open_tasks,
+                    COALESCE(SUM(f.estimate) FILTER (WHERE f.open), 0)::bigint
+                        AS open_estimate_minutes,
+                    COUNT(*) FILTER (WHERE f.completed) AS completed_tasks,
+                    AVG(f.completion_hours) FILTER (WHERE f.completed) AS avg_completion_hours
+             FROM flags f
+             LEFT JOIN users u ON u.id = f.assignee_id
+             GROUP BY f.assignee_id, u.name, u.email
+             HAVING COUNT(*) FILTER (WHERE f.open OR f.completed) > 0
+             ORDER BY open_tasks DESC, overdue_tasks DESC, u.name NULLS LAST, f.assignee_id",
+        )
+        .await?;
+
+    let by_priority = scope
+        .fetch_all::<PriorityCount>(
+            &state.db,
+            "SELECT p.priority,
+                    COUNT(f.id) FILTER (WHERE f.open) AS open_tasks,
+                    COUNT(f.id) FILTER (WHERE f.overdue) AS overdue_tasks,
+                    COUNT(f.id) FILTER (WHERE f.completed) AS completed_tasks
+             FROM unnest(ARRAY['high', 'medium', 'low']) WITH ORDINALITY AS p(priority, position)
+             LEFT JOIN flags f ON f.priority = p.priority
+             GROUP BY p.priority, p.position
+             ORDER BY p.position",
+        )
+        .await?;
+
+    let throughput = scope
+        .fetch_all::<WeeklyThroughput>(
+            &state.db,
+            "SELECT w.week_start::date AS week_start,
+                    (SELECT COUNT(*) FROM flags f
+                     WHERE f.created_at >= GREATEST(w.week_start, $4::date)
+                       AND f.created_at < LEAST(w.week_start + INTERVAL '1 week',
+                                                $5::date + INTERVAL '1 day')) AS created_tasks,
+                    (SELECT COUNT(*) FROM flags f
+                     WHERE f.completed AND f.done_at >= w.week_start
+                       AND f.done_at < w.week_start + INTERVAL '1 week') AS completed_tasks
+             FROM generate_series(date_trunc('week', $4::date::timestamp), $5::date::timestamp,
+                                  INTERVAL '1 week') AS w(week_start)
+             ORDER BY w.week_start",
+        )
+        .await?;
+
+    Ok(Json(WorkloadReport {
+        from,
+        to,
+        project_id: params.project_id,
+        totals,
+        by_assignee,
+        by_priority,
+        throughput,
+    }))
+}
@@ -0,0 +1,546 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::{NaiveDate, Utc};
+use serde_json::json;
+use shared::{
+    errors::{AppError, AppResult},
+    models::Claims,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    access,
+    activity::{self, Entity},
+    models::{
+        Burndown, BurndownPoint, CompleteSprintRequest, CreateSprintRequest, Sprint,
+        SprintCompletion, SprintTasksRequest, SprintWithCounts, UpdateSprintRequest,
+    },
+    AppState,
+};
+
+pub(crate) const SPRINT_COLUMNS: &str = "s.id, s.project_id, s.name, s.goal, s.start_date, s.end_date,
+    s.status, s.started_at, s.completed_at, s.created_at, s.updated_at";
+
+const SPRINT_COUNTS_SELECT: &str = "SELECT s.id, s.project_id, s.name, s.goal, s.start_date,
+        s.end_date, s.status, s.started_at, s.completed_at, s.created_at, s.updated_at,
+        (SELECT COUNT(*) FROM tasks t WHERE t.sprint_id = s.id AND t.deleted_at IS NULL)
+            AS task_count,
+        (SELECT COUNT(*) FROM tasks t
+         WHERE t.sprint_id = s.id AND t.status = 'done' AND t.deleted_at IS NULL)
+            AS done_task_count
+     FROM sprints s";
+
+/// Tasks a sprint's burndown covers: those in it now and those rolled over
+/// out of it when it was completed.
+const SPRINT_SCOPE: &str = "SELECT t.id, t.original_estimate_minutes FROM tasks t
+     WHERE t.deleted_at IS NULL
+       AND (t.sprint_id = $1
+            OR t.id IN (SELECT task_id FROM sprint_rollovers WHERE sprint_id = $1))";
+
+fn validate_name(name: &str) -> AppResult<String> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err(AppError::ValidationError(
+            "Sprint name must be between 1 and 100 characters".to_string(),
+        ));
+    }
+    Ok(name.to_string())
+}
+
+fn validate_dates(start_date: NaiveDate, end_date: NaiveDate) -> AppResult<()> {
+    if end_date < start_date {
+        return Err(AppError::ValidationError(
+            "Sprint end date cannot be before its start date".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) async fn load_sprint(db: &PgPool, sprint_id: Uuid) -> AppResult<Sprint> {
+    sqlx::query_as::<_, Sprint>(&format!(
+        "SELECT {} FROM sprints s WHERE s.id = $1",
+        SPRINT_COLUMNS
+    ))
+    .bind(sprint_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Sprint not found".to_string()))
+}
+
+async fn load_sprint_with_counts(db: &PgPool, sprint_id: Uuid) -> AppResult<SprintWithCounts> {
+    sqlx::query_as::<_, SprintWithCounts>(&format!("{} WHERE s.id = $1", SPRINT_COUNTS_SELECT))
+        .bind(sprint_id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Sprint not found".to_string()))
+}
+
+pub(crate) fn require_not_completed(sprint: &Sprint) -> AppResult<()> {
+    if sprint.status == "completed" {
+        return Err(AppError::Conflict(
+            "Sprint is already completed".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+pub async fn list_sprints(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+) -> AppResult<Json<Vec<SprintWithCounts>>> {
+    access::require_member(&state.db, project_id, &claims).await?;
+
+    let sprints = sqlx::query_as::<_, SprintWithCounts>(&format!(
+        "{} WHERE s.project_id = $1 ORDER BY s.start_date, s.created_at",
+        SPRINT_COUNTS_SELECT
+    ))
+    .bind(project_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(sprints))
+}
+
+pub async fn create_sprint(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+    Json(req): Json<CreateSprintRequest>,
+) -> AppResult<(StatusCode, Json<SprintWithCounts>)> {
+    access::require_editor(&state.db, project_id, &claims).await?;
+
+    let name = validate_name(&req.name)?;
+    validate_dates(req.start_date, req.end_date)?;
+
+    let sprint_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO sprints (project_id, name, goal, start_date, end_date)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING id",
+    )
+    .bind(project_id)
+    .bind(&name)
+    .bind(&req.goal)
+    .bind(req.start_date)
+    .bind(req.end_date)
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(load_sprint_with_counts(&state.db, sprint_id).await?),
+    ))
+}
+
+pub async fn get_sprint(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(sprint_id): Path<Uuid>,
+) -> AppResult<Json<SprintWithCounts>> {
+    let sprint = load_sprint_with_counts(&state.db, sprint_id).await?;
+    access::require_member(&state.db, sprint.sprint.project_id, &claims).await?;
+
+    Ok(Json(sprint))
+}
+
+pub async fn update_sprint(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(sprint_id): Path<Uuid>,
+    Json(req): Json<UpdateSprintRequest>,
+) -> AppResult<Json<SprintWithCounts>> {
+    let sprint = load_sprint(&state.db, sprint_id).await?;
+    access::require_editor(&state.db, sprint.project_id, &claims).await?;
+
+    let name = req.name.as_deref().map(validate_name).transpose()?;
+    validate_dates(
+        req.start_date.unwrap_or(sprint.start_date),
+        req.end_date.unwrap_or(sprint.end_date),
+    )?;
+
+    sqlx::query(
+        "UPDATE sprints SET
+            name = COALESCE($2, name),
+            goal = COALESCE($3, goal),
+            start_date = COALESCE($4, start_date),
+            end_date = COALESCE($5, end_date),
+            updated_at = NOW()
+         WHERE id = $1",
+    )
+    .bind(sprint_id)
+    .bind(&name)
+    .bind(&req.goal)
+    .bind(req.start_date)
+    .bind(req.end_date)
+    .execute(&state.db)
+    .await?;
+
+    Ok(Json(load_sprint_with_counts(&state.db, sprint_id).await?))
+}
+
+/// Deletes a sprint; its tasks go back to the backlog.
+pub async fn delete_sprint(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(sprint_id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    let sprint = load_sprint(&state.db, sprint_id).await?;
+    access::require_editor(&state.db, sprint.project_id, &claims).await?;
+
+    let mut tx = state.db.begin().await?;
+
+    let task_ids: Vec<Uuid> =
+        sqlx::query_scalar("SELECT id FROM tasks WHERE sprint_id = $1 FOR UPDATE")
+            .bind(sprint_id)
+            .fetch_all(&mut *tx)
+            .await?;
+
+    sqlx::query("DELETE FROM sprints WHERE id = $1")
+        .bind(sprint_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let actor = access::user_id(&claims)?;
+    for task_id in task_ids {
+        activity::record_field(
+            &mut tx,
+            sprint.project_id,
+            Entity::Task,
+            task_id,
+            Some(actor),
+            "sprint_id",
+            &Some(sprint_id),
+            &None::<Uuid>,
+        )
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(Json(json!({"message": "deleted"})))
+}
+
+/// Starts a planned sprint. A project has at most one active sprint.
+pub async fn start_sprint(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(sprint_id): Path<Uuid>,
+) -> AppResult<Json<SprintWithCounts>> {
+    let sprint = load_sprint(&state.db, sprint_id).await?;
+    access::require_editor(&state.db, sprint.project_id, &claims).await?;
+
+    if sprint.status != "planned" {
+        return Err(AppError::Conflict(format!(
+            "Only planned sprints can be started, this one is {}",
+            sprint.status
+        )));
+    }
+
+    sqlx::query(
+        "UPDATE sprints SET status = 'active', started_at = NOW(), updated_at = NOW()
+         WHERE id = $1",
+    )
+    .bind(sprint_id)
+    .execute(&state.db)
+    .await
+    .map_err(|e| match &e {
+        sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("23505") => {
+            AppError::Conflict("Another sprint is already active in this project".to_string())
+        }
+        _ => e.into(),
+    })?;
+
+    Ok(Json(load_sprint_with_counts(&state.db, sprint_id).await?))
+}
+
+/// Completes the active sprint and rolls its unfinished tasks over to another
+/// sprint or the backlog.
+pub async fn complete_sprint(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(sprint_id): Path<Uuid>,
+    req: Option<Json<CompleteSprintRequest>>,
+) -> AppResult<Json<SprintCompletion>> {
+    let req = req.map(|Json(req)| req).unwrap_or_default();
+    let sprint = load_sprint(&state.db, sprint_id).await?;
+    access::require_editor(&state.db, sprint.project_id, &claims).await?;
+
+    if sprint.status != "active" {
+        return Err(AppError::Conflict(format!(
+            "Only active sprints can be completed, this one is {}",
+            sprint.status
+        )));
+    }
+
+    let rollover_to = match (req.rollover_sprint_id, req.to_backlog) {
+        (Some(_), true) => {
+            return Err(AppError::BadRequest(
+                "Choose either rollover_sprint_id or to_backlog".to_string(),
+            ))
+        }
+        (Some(target_id), false) => {
+            let target = load_sprint(&state.db, target_id).await?;
+            if target.project_id != sprint.project_id || target.id == sprint.id {
+                return Err(AppError::BadRequest(
+                    "Tasks can only roll over to another sprint of the same project".to_string(),
+                ));
+            }
+            require_not_completed(&target)?;
+            Some(target.id)
+        }
+        (None, true) => None,
+        (None, false) => {
+            sqlx::query_scalar(
+                "SELECT id FROM sprints
+             WHERE project_id = $1 AND status = 'planned' AND id <> $2
+             ORDER BY start_date, created_at
+             LIMIT 1",
+            )
+            .bind(sprint.project_id)
+            .bind(sprint.id)
+            .fetch_optional(&state.db)
+            .await?
+        }
+    };
+
+    let mut tx = state.db.begin().await?;
+
+    let completed_tasks: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM tasks
+         WHERE sprint_id = $1 AND status = 'done' AND deleted_at IS NULL",
+    )
+    .bind(sprint.id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let unfinished: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT id FROM tasks
+         WHERE sprint_id = $1 AND status <> 'done' AND deleted_at IS NULL
+         FOR UPDATE",
+    )
+    .bind(sprint.id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO sprint_rollovers (sprint_id, task_id, to_sprint_id)
+         SELECT $1, UNNEST($2::uuid[]), $3",
+    )
+    .bind(sprint.id)
+    .bind(&unfinished)
+    .bind(rollover_to)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("UPDATE tasks SET sprint_id = $2, updated_at = NOW() WHERE id = ANY($1)")
+        .bind(&unfinished)
+        .bind(rollover_to)
+        .execute(&mut *tx)
+        .await?;
+
+    let actor = access::user_id(&claims)?;
+    for task_id in &unfinished {
+        activity::record_field(
+            &mut tx,
+            sprint.project_id,
+            Entity::Task,
+            *task_id,
+            Some(actor),
+            "sprint_id",
+            &Some(sprint.id),
+            &rollover_to,
+        )
+        .await?;
+    }
+    let rolled_over_tasks = unfinished.len() as i64;
+
+    let sprint = sqlx::query_as::<_, Sprint>(&format!(
+        "UPDATE sprints AS s SET status = 'completed', completed_at = NOW(), updated_at = NOW()
+         WHERE s.id = $1
+         RETURNING {}",
+        SPRINT_COLUMNS
+    ))
+    .bind(sprint.id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(SprintCompletion {
+        sprint,
+        completed_tasks,
+        rolled_over_tasks,
+        rolled_over_to: rollover_to,
+    }))
+}
+
+/// Moves tasks of the same project into the sprint. A task belongs to at most
+/// one sprint, so this takes it out of any other.
+pub async fn add_sprint_tasks(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(sprint_id): Path<Uuid>,
+    Json(req): Json<SprintTasksRequest>,
+) -> AppResult<Json<SprintWithCounts>> {
+    let sprint = load_sprint(&state.db, sprint_id).await?;
+    access::require_editor(&state.db, sprint.project_id, &claims).await?;
+    require_not_completed(&sprint)?;
+
+    let mut task_ids = req.task_ids.clone();
+    task_ids.sort();
+    task_ids.dedup();
+
+    let mut tx = state.db.begin().await?;
+
+    let previous: Vec<(Uuid, Option<Uuid>)> = sqlx::query_as(
+        "SELECT id, sprint_id FROM tasks
+         WHERE project_id = $1 AND id = ANY($2) AND deleted_at IS NULL
+         FOR UPDATE",
+    )
+    .bind(sprint.project_id)
+    .bind(&task_ids)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    if previous.len() != task_ids.len() {
+        return Err(AppError::BadRequest(
+            "One or more tasks do not belong to this project".to_string(),
+        ));
+    }
+
+    sqlx::query("UPDATE tasks SET sprint_id = $1, updated_at = NOW() WHERE id = ANY($2)")
+        .bind(sprint.id)
+        .bind(&task_ids)
+        .execute(&mut *tx)
+        .await?;
+
+    let actor = access::user_id(&claims)?;
+    for (task_id, old_sprint) in &previous {
+        activity::record_field(
+            &mut tx,
+            sprint.project_id,
+            Entity::Task,
+            *task_id,
+            Some(actor),
+            "sprint_id",
+            old_sprint,
+            &Some(sprint.id),
+        )
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(Json(load_sprint_with_counts(&state.db, sprint_id).await?))
+}
+
+/// Moves a task from the sprint back to the backlog.
+pub async fn remove_sprint_task(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((sprint_id, task_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<SprintWithCounts>> {
+    let sprint = load_sprint(&state.db, sprint_id).await?;
+    access::require_editor(&state.db, sprint.project_id, &claims).await?;
+    require_not_completed(&sprint)?;
+
+    let mut tx = state.db.begin().await?;
+
+    let removed = sqlx::query(
+        "UPDATE tasks SET sprint_id = NULL, updated_at = NOW() WHERE id = $1 AND sprint_id = $2",
+    )
+    .bind(task_id)
+    .bind(sprint_id)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    if removed == 0 {
+        return Err(AppError::NotFound("Task is not in this sprint".to_string()));
+    }
+
+    activity::record_field(
+        &mut tx,
+        sprint.project_id,
+        Entity::Task,
+        task_id,
+        Some(access::user_id(&claims)?),
+        "sprint_id",
+        &Some(sprint_id),
+        &None::<Uuid>,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(load_sprint_with_counts(&state.db, sprint_id).await?))
+}
+
+/// Remaining tasks and estimated minutes at the end of each sprint day (UTC),
+/// computed from the task activity log, with the ideal line for comparison.
+pub async fn get_burndown(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(sprint_id): Path<Uuid>,
+) -> AppResult<Json<Burndown>> {
+    let sprint = load_sprint(&state.db, sprint_id).await?;
+    access::require_member(&state.db, sprint.project_id, &claims).await?;
+
+    let (total_tasks, total_estimate_minutes): (i64, i64) = sqlx::query_as(&format!(
+        "WITH scope AS ({})
+         SELECT COUNT(*), COALESCE(SUM(original_estimate_minutes), 0)::BIGINT FROM scope",
+        SPRINT_SCOPE
+    ))
+    .bind(sprint.id)
+    .fetch_one(&state.db)
+    .await?;
+
+    let mut points = sqlx::query_as::<_, BurndownPoint>(&format!(
+        "WITH scope AS ({})
+         SELECT d.day::date AS date,
+                COUNT(sc.id) FILTER (WHERE st.status IS DISTINCT FROM 'done') AS remaining_tasks,
+                COALESCE(SUM(sc.original_estimate_minutes)
+                         FILTER (WHERE st.status IS DISTINCT FROM 'done'), 0)::BIGINT
+                    AS remaining_estimate_minutes
+         FROM generate_series($2::date, $3::date, INTERVAL '1 day') AS d(day)
+         LEFT JOIN scope sc ON TRUE
+         LEFT JOIN LATERAL (
+             SELECT c.status
+             FROM task_status_changes c
+             WHERE c.task_id = sc.id AND c.changed_at < d.day + INTERVAL '1 day'
+             ORDER BY c.changed_at DESC
+             LIMIT 1
+         ) st ON TRUE
+         GROUP BY d.day
+         ORDER BY d.day",
+        SPRINT_SCOPE
+    ))
+    .bind(sprint.id)
+    .bind(sprint.start_date)
+    .bind(sprint.end_date)
+    .fetch_all(&state.db)
+    .await?;
+
+    let today = Utc::now().date_naive();
+    let last = points.len().saturating_sub(1).max(1) as f64;
+    for (i, point) in points.iter_mut().enumerate() {
+        point.ideal_tasks = total_tasks as f64 * (1.0 - (i as f64 / last).min(1.0));
+        if point.date > today {
+            point.remaining_tasks = None;
+            point.remaining_estimate_minutes = None;
+        }
+    }
+
+    Ok(Json(Burndown {
+        sprint_id: sprint.id,
+        start_date: sprint.start_date,
+        end_date: sprint.end_date,
+        total_tasks,
+        total_estimate_minutes,
+        points,
+    }))
+}
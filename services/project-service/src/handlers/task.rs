@@ -1,22 +1,1132 @@
-use axum::Json;
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::NaiveDate;
 use serde_json::json;
+use shared::{
+    errors::{AppError, AppResult},
+    models::{
+        Claims, CreateTaskRequest, NotificationEvent, PaginatedResponse, Task, UpdateTaskRequest,
+    },
+};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use std::collections::HashMap;
+use uuid::Uuid;
 
-pub async fn create_task() -> Json<serde_json::Value> {
-    Json(json!({}))
+use crate::{
+    access,
+    activity::{self, Entity},
+    custom_fields, etag,
+    handlers::{custom_field, label},
+    models::{
+        CustomField, EditScope, EditScopeParams, TaskListParams, TaskResponse, TaskSearchParams,
+    },
+    quota::{self, Resource},
+    recurrence, tql, watchers, AppState,
+};
+
+pub(crate) const TASK_COLUMNS: &str = "t.id, t.project_id, t.assignee_id, t.title, t.description,
+    t.status::text AS status, t.priority::text AS priority, t.deadline,
+    t.custom_fields, t.original_estimate_minutes, t.remaining_estimate_minutes,
+    t.recurrence_id, t.occurrence_at, t.sprint_id, t.version, t.created_at, t.updated_at";
+
+pub(crate) const TASK_STATUSES: [&str; 3] = ["todo", "in_progress", "done"];
+pub(crate) const TASK_PRIORITIES: [&str; 3] = ["low", "medium", "high"];
+
+pub(crate) fn validate_title(title: &str) -> AppResult<String> {
+    let title = title.trim();
+    if title.is_empty() || title.chars().count() > 255 {
+        return Err(AppError::ValidationError(
+            "Task title must be between 1 and 255 characters".to_string(),
+        ));
+    }
+    Ok(title.to_string())
+}
+
+pub(crate) fn validate_status(status: &str) -> AppResult<()> {
+    if !TASK_STATUSES.contains(&status) {
+        return Err(AppError::ValidationError(format!(
+            "Invalid status '{}', expected one of: {}",
+            status,
+            TASK_STATUSES.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn validate_priority(priority: &str) -> AppResult<()> {
+    if !TASK_PRIORITIES.contains(&priority) {
+        return Err(AppError::ValidationError(format!(
+            "Invalid priority '{}', expected one of: {}",
+            priority,
+            TASK_PRIORITIES.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn validate_estimate(minutes: Option<i32>) -> AppResult<()> {
+    if minutes.is_some_and(|m| m < 0) {
+        return Err(AppError::ValidationError(
+            "Estimates cannot be negative".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Loads a task that is not in the trash.
+pub(crate) async fn load_task(db: &PgPool, task_id: Uuid) -> AppResult<Task> {
+    sqlx::query_as::<_, Task>(&format!(
+        "SELECT {} FROM tasks t WHERE t.id = $1 AND t.deleted_at IS NULL",
+        TASK_COLUMNS
+    ))
+    .bind(task_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Task not found".to_string()))
+}
+
+/// Attaches labels to a batch of tasks.
+pub(crate) async fn task_responses(db: &PgPool, tasks: Vec<Task>) -> AppResult<Vec<TaskResponse>> {
+    let ids: Vec<Uuid> = tasks.iter().map(|t| t.id).collect();
+    let mut labels = label::labels_for_tasks(db, &ids).await?;
+
+    Ok(tasks
+        .into_iter()
+        .map(|task| TaskResponse {
+            labels: labels.remove(&task.id).unwrap_or_default(),
+            task,
+        })
+        .collect())
+}
+
+pub(crate) async fn task_response(db: &PgPool, task: Task) -> AppResult<TaskResponse> {
+    let mut responses = task_responses(db, vec![task]).await?;
+    Ok(responses.remove(0))
 }
 
-pub async fn list_tasks() -> Json<serde_json::Value> {
-    Json(json!([]))
+/// Validates custom field values for a task, loading member ids only when a
+/// `user` field is involved.
+async fn apply_custom_fields(
+    db: &PgPool,
+    project_id: Uuid,
+    existing: &serde_json::Value,
+    input: &serde_json::Map<String, serde_json::Value>,
+    enforce_required: bool,
+) -> AppResult<serde_json::Value> {
+    let fields = custom_field::project_fields(db, project_id).await?;
+
+    let members: Vec<Uuid> = if fields.iter().any(|f| f.field_type == "user") {
+        access::project_member_emails(db, project_id)
+            .await?
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    custom_fields::apply_values(&fields, existing, input, &members, enforce_required)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FilterOp {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+impl FilterOp {
+    fn parse(op: &str) -> Option<Self> {
+        match op {
+            "eq" => Some(FilterOp::Eq),
+            "ne" => Some(FilterOp::Ne),
+            "gt" => Some(FilterOp::Gt),
+            "gte" => Some(FilterOp::Gte),
+            "lt" => Some(FilterOp::Lt),
+            "lte" => Some(FilterOp::Lte),
+            _ => None,
+        }
+    }
+
+    fn sql(self) -> &'static str {
+        match self {
+            FilterOp::Eq => " = ",
+            FilterOp::Ne => " <> ",
+            FilterOp::Gt => " > ",
+            FilterOp::Gte => " >= ",
+            FilterOp::Lt => " < ",
+            FilterOp::Lte => " <= ",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum FilterValue {
+    Number(f64),
+    Date(NaiveDate),
+    Bool(bool),
+    Text(String),
 }
 
-pub async fn get_task() -> Json<serde_json::Value> {
-    Json(json!({}))
+#[derive(Debug, PartialEq)]
+struct CustomFieldFilter {
+    field_key: String,
+    field_type: String,
+    op: FilterOp,
+    value: FilterValue,
 }
 
-pub async fn update_task() -> Json<serde_json::Value> {
-    Json(json!({}))
+/// Parses `cf.<field>=value` and `cf.<field>.<op>=value` query parameters,
+/// where `<field>` is a custom field id or name and `<op>` is one of
+/// `eq`, `ne`, `gt`, `gte`, `lt`, `lte`.
+fn parse_custom_filters(
+    fields: &[CustomField],
+    query: &HashMap<String, String>,
+) -> AppResult<Vec<CustomFieldFilter>> {
+    let mut filters = Vec::new();
+
+    for (key, raw) in query {
+        let Some(spec) = key.strip_prefix("cf.") else {
+            continue;
+        };
+
+        let (field, op) = match custom_fields::find_field(fields, spec) {
+            Some(field) => (field, FilterOp::Eq),
+            None => {
+                let (name, op) = spec.rsplit_once('.').unwrap_or((spec, ""));
+                let op = FilterOp::parse(op).ok_or_else(|| {
+                    AppError::BadRequest(format!("Unknown custom field filter '{}'", key))
+                })?;
+                let field = custom_fields::find_field(fields, name).ok_or_else(|| {
+                    AppError::BadRequest(format!("Unknown custom field '{}'", name))
+                })?;
+                (field, op)
+            }
+        };
+
+        let invalid = || {
+            AppError::BadRequest(format!(
+                "Invalid value '{}' for custom field '{}'",
+                raw, field.name
+            ))
+        };
+        let is_range = !matches!(op, FilterOp::Eq | FilterOp::Ne);
+
+        let value = match field.field_type.as_str() {
+            "number" => FilterValue::Number(raw.parse().map_err(|_| invalid())?),
+            "date" => FilterValue::Date(
+                NaiveDate::parse_from_str(raw, "%Y-%m-%d").map_err(|_| invalid())?,
+            ),
+            _ if is_range => {
+                return Err(AppError::BadRequest(format!(
+                    "Custom field '{}' only supports eq and ne filters",
+                    field.name
+                )))
+            }
+            "checkbox" => FilterValue::Bool(raw.parse().map_err(|_| invalid())?),
+            _ => FilterValue::Text(raw.clone()),
+        };
+
+        filters.push(CustomFieldFilter {
+            field_key: field.id.to_string(),
+            field_type: field.field_type.clone(),
+            op,
+            value,
+        });
+    }
+
+    Ok(filters)
 }
 
-pub async fn delete_task() -> Json<serde_json::Value> {
-    Json(json!({"message": "deleted"}))
+fn push_custom_filter(qb: &mut QueryBuilder<'_, Postgres>, filter: &CustomFieldFilter) {
+    qb.push(" AND ");
+    match (&filter.value, filter.field_type.as_str()) {
+        (FilterValue::Number(n), _) => {
+            qb.push("(t.custom_fields ->> ")
+                .push_bind(filter.field_key.clone())
+                .push(")::float8")
+                .push(filter.op.sql())
+                .push_bind(*n);
+        }
+        (FilterValue::Date(d), _) => {
+            qb.push("(t.custom_fields ->> ")
+                .push_bind(filter.field_key.clone())
+                .push(")::date")
+                .push(filter.op.sql())
+                .push_bind(*d);
+        }
+        (FilterValue::Bool(b), _) => {
+            let expected = matches!(filter.op, FilterOp::Eq) == *b;
+            qb.push("COALESCE((t.custom_fields ->> ")
+                .push_bind(filter.field_key.clone())
+                .push(")::boolean, false) = ")
+                .push_bind(expected);
+        }
+        (FilterValue::Text(v), "multi_select") => {
+            if matches!(filter.op, FilterOp::Ne) {
+                qb.push("NOT ");
+            }
+            qb.push("COALESCE(t.custom_fields -> ")
+                .push_bind(filter.field_key.clone())
+                .push(" ? ")
+                .push_bind(v.clone())
+                .push(", false)");
+        }
+        (FilterValue::Text(v), _) => {
+            qb.push("t.custom_fields ->> ")
+                .push_bind(filter.field_key.clone())
+                .push(if matches!(filter.op, FilterOp::Ne) {
+                    " IS DISTINCT FROM "
+                } else {
+                    " = "
+                })
+                .push_bind(v.clone());
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum SortKey {
+    Column(&'static str),
+    Custom {
+        field_key: String,
+        cast: &'static str,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+struct TaskSort {
+    key: SortKey,
+    descending: bool,
+}
+
+/// Parses `sort=<key>` or `sort=-<key>` (descending), where `<key>` is a task
+/// column or `cf.<field>`.
+fn parse_sort(fields: &[CustomField], raw: Option<&str>) -> AppResult<TaskSort> {
+    let Some(raw) = raw.map(str::trim).filter(|s| !s.is_empty()) else {
+        return Ok(TaskSort {
+            key: SortKey::Column("t.created_at"),
+            descending: true,
+        });
+    };
+
+    let (descending, name) = match raw.strip_prefix('-') {
+        Some(name) => (true, name),
+        None => (false, raw),
+    };
+
+    let key = match name {
+        "created_at" => SortKey::Column("t.created_at"),
+        "updated_at" => SortKey::Column("t.updated_at"),
+        "deadline" => SortKey::Column("t.deadline"),
+        "title" => SortKey::Column("LOWER(t.title)"),
+        "status" => SortKey::Column("t.status"),
+        "priority" => SortKey::Column("t.priority"),
+        _ => {
+            let field = name
+                .strip_prefix("cf.")
+                .and_then(|spec| custom_fields::find_field(fields, spec))
+                .ok_or_else(|| AppError::BadRequest(format!("Cannot sort by '{}'", name)))?;
+            let cast = match field.field_type.as_str() {
+                "number" => "::float8",
+                "date" => "::date",
+                "checkbox" => "::boolean",
+                _ => "",
+            };
+            SortKey::Custom {
+                field_key: field.id.to_string(),
+                cast,
+            }
+        }
+    };
+
+    Ok(TaskSort { key, descending })
+}
+
+fn push_sort(qb: &mut QueryBuilder<'_, Postgres>, sort: &TaskSort) {
+    qb.push(" ORDER BY ");
+    match &sort.key {
+        SortKey::Column(column) => {
+            qb.push(*column);
+        }
+        SortKey::Custom { field_key, cast } => {
+            qb.push("(t.custom_fields ->> ")
+                .push_bind(field_key.clone())
+                .push(")")
+                .push(*cast);
+        }
+    }
+    qb.push(if sort.descending {
+        " DESC NULLS LAST"
+    } else {
+        " ASC NULLS LAST"
+    });
+    qb.push(", t.id");
+}
+
+struct TaskFilters {
+    status: Option<String>,
+    priority: Option<String>,
+    assignee_id: Option<Uuid>,
+    label_ids: Option<Vec<Uuid>>,
+    match_all_labels: bool,
+    /// `Some(None)` selects the backlog.
+    sprint_id: Option<Option<Uuid>>,
+    custom: Vec<CustomFieldFilter>,
+}
+
+fn push_filters(qb: &mut QueryBuilder<'_, Postgres>, project_id: Uuid, filters: &TaskFilters) {
+    qb.push(" WHERE t.deleted_at IS NULL AND t.project_id = ")
+        .push_bind(project_id);
+
+    if let Some(status) = &filters.status {
+        qb.push(" AND t.status = ")
+            .push_bind(status.clone())
+            .push("::task_status");
+    }
+    if let Some(priority) = &filters.priority {
+        qb.push(" AND t.priority = ")
+            .push_bind(priority.clone())
+            .push("::task_priority");
+    }
+    if let Some(assignee_id) = filters.assignee_id {
+        qb.push(" AND t.assignee_id = ").push_bind(assignee_id);
+    }
+    if let Some(label_ids) = &filters.label_ids {
+        if filters.match_all_labels {
+            qb.push(
+                " AND (SELECT COUNT(DISTINCT tl.label_id) FROM task_labels tl
+                   WHERE tl.task_id = t.id AND tl.label_id = ANY(",
+            )
+            .push_bind(label_ids.clone())
+            .push(")) = ")
+            .push_bind(label_ids.len() as i64);
+        } else {
+            qb.push(
+                " AND EXISTS (SELECT 1 FROM task_labels tl
+                   WHERE tl.task_id = t.id AND tl.label_id = ANY(",
+            )
+            .push_bind(label_ids.clone())
+            .push("))");
+        }
+    }
+    match filters.sprint_id {
+        Some(Some(sprint_id)) => {
+            qb.push(" AND t.sprint_id = ").push_bind(sprint_id);
+        }
+        Some(None) => {
+            qb.push(" AND t.sprint_id IS NULL");
+        }
+        None => {}
+    }
+    for filter in &filters.custom {
+        push_custom_filter(qb, filter);
+    }
+}
+
+/// Parses the task filters shared by listing and bulk selection.
+async fn build_filters(
+    db: &PgPool,
+    project_id: Uuid,
+    fields: &[CustomField],
+    params: &TaskListParams,
+    raw_query: &HashMap<String, String>,
+) -> AppResult<TaskFilters> {
+    if let Some(status) = &params.status {
+        validate_status(status)?;
+    }
+    if let Some(priority) = &params.priority {
+        validate_priority(priority)?;
+    }
+
+    let match_all_labels = match params.label_match.as_deref() {
+        None | Some("any") => false,
+        Some("all") => true,
+        Some(other) => {
+            return Err(AppError::BadRequest(format!(
+                "Invalid label_match '{}', expected 'any' or 'all'",
+                other
+            )))
+        }
+    };
+
+    let label_ids = match params.labels.as_deref() {
+        Some(raw) if !raw.trim().is_empty() => {
+            Some(label::resolve_label_filter(db, project_id, raw).await?)
+        }
+        _ => None,
+    };
+
+    let sprint_id = match params.sprint_id.as_deref() {
+        None => None,
+        Some("none") => Some(None),
+        Some(raw) => Some(Some(Uuid::parse_str(raw).map_err(|_| {
+            AppError::BadRequest(format!(
+                "Invalid sprint_id '{}', expected an id or 'none'",
+                raw
+            ))
+        })?)),
+    };
+
+    Ok(TaskFilters {
+        status: params.status.clone(),
+        priority: params.priority.clone(),
+        assignee_id: params.assignee_id,
+        label_ids,
+        match_all_labels,
+        sprint_id,
+        custom: parse_custom_filters(fields, raw_query)?,
+    })
+}
+
+/// Ids of the live tasks of a project matching the same filters as
+/// `list_tasks`, oldest first.
+pub(crate) async fn matching_task_ids(
+    db: &PgPool,
+    project_id: Uuid,
+    params: &TaskListParams,
+    raw_query: &HashMap<String, String>,
+) -> AppResult<Vec<Uuid>> {
+    let fields = custom_field::project_fields(db, project_id).await?;
+    let filters = build_filters(db, project_id, &fields, params, raw_query).await?;
+
+    let mut qb = QueryBuilder::<Postgres>::new("SELECT t.id FROM tasks t");
+    push_filters(&mut qb, project_id, &filters);
+    qb.push(" ORDER BY t.created_at, t.id");
+    Ok(qb.build_query_scalar().fetch_all(db).await?)
+}
+
+pub async fn create_task(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+    Json(req): Json<CreateTaskRequest>,
+) -> AppResult<(StatusCode, Json<TaskResponse>)> {
+    access::require_editor(&state.db, project_id, &claims).await?;
+
+    let title = validate_title(&req.title)?;
+    let priority = req.priority.unwrap_or_else(|| "medium".to_string());
+    validate_priority(&priority)?;
+    validate_estimate(req.original_estimate_minutes)?;
+    let rule = req
+        .recurrence
+        .as_ref()
+        .map(recurrence::parse_rule)
+        .transpose()?;
+    let custom_fields = apply_custom_fields(
+        &state.db,
+        project_id,
+        &json!({}),
+        &req.custom_fields.unwrap_or_default(),
+        true,
+    )
+    .await?;
+    let actor = access::user_id(&claims)?;
+    let owner_id = quota::project_owner(&state.db, project_id).await?;
+    let quota = state.billing.quota(owner_id, actor).await?;
+
+    let mut tx = state.db.begin().await?;
+    quota.check(&mut tx, Resource::Tasks, 1).await?;
+
+    let task = sqlx::query_as::<_, Task>(&format!(
+        "INSERT INTO tasks AS t (project_id, title, description, priority, deadline, custom_fields,
+                                 original_estimate_minutes, remaining_estimate_minutes)
+         VALUES ($1, $2, $3, $4::task_priority, $5, $6, $7, $7)
+         RETURNING {}",
+        TASK_COLUMNS
+    ))
+    .bind(project_id)
+    .bind(&title)
+    .bind(&req.description)
+    .bind(&priority)
+    .bind(req.deadline)
+    .bind(&custom_fields)
+    .bind(req.original_estimate_minutes)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if let Some(label_ids) = &req.label_ids {
+        label::set_task_labels(&mut tx, project_id, task.id, label_ids).await?;
+    }
+    let task = match &rule {
+        Some((rule, tz)) => {
+            let label_ids = req.label_ids.as_deref().unwrap_or_default();
+            recurrence::create_series(&mut tx, &task, rule, *tz, label_ids).await?
+        }
+        None => task,
+    };
+
+    activity::record_created(
+        &mut tx,
+        project_id,
+        Entity::Task,
+        task.id,
+        Some(actor),
+        &task,
+    )
+    .await?;
+    watchers::auto_watch(&mut tx, task.id, &[actor]).await?;
+
+    tx.commit().await?;
+
+    watchers::notify_task(&state, "task.created", &task, actor, &[], json!({})).await;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(task_response(&state.db, task).await?),
+    ))
+}
+
+/// Lists a project's tasks, optionally filtered by status, priority, assignee
+/// labels (`?labels=bug,frontend&label_match=all`) and custom fields
+/// (`?cf.points.gte=3`), sorted with `?sort=-cf.points`.
+pub async fn list_tasks(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+    Query(params): Query<TaskListParams>,
+    Query(raw_query): Query<HashMap<String, String>>,
+) -> AppResult<Json<PaginatedResponse<TaskResponse>>> {
+    access::require_member(&state.db, project_id, &claims).await?;
+
+    let fields = custom_field::project_fields(&state.db, project_id).await?;
+    let sort = parse_sort(&fields, params.sort.as_deref())?;
+    let filters = build_filters(&state.db, project_id, &fields, &params, &raw_query).await?;
+    let pagination = params.pagination();
+
+    let mut count_qb = QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM tasks t");
+    push_filters(&mut count_qb, project_id, &filters);
+    let total: i64 = count_qb.build_query_scalar().fetch_one(&state.db).await?;
+
+    let mut qb = QueryBuilder::<Postgres>::new(format!("SELECT {} FROM tasks t", TASK_COLUMNS));
+    push_filters(&mut qb, project_id, &filters);
+    push_sort(&mut qb, &sort);
+    qb.push(" LIMIT ")
+        .push_bind(pagination.limit())
+        .push(" OFFSET ")
+        .push_bind(pagination.offset());
+    let tasks: Vec<Task> = qb.build_query_as().fetch_all(&state.db).await?;
+
+    Ok(Json(PaginatedResponse {
+        data: task_responses(&state.db, tasks).await?,
+        page: pagination.page(),
+        limit: pagination.limit(),
+        total,
+    }))
+}
+
+/// Pushes the tasks the caller may see: live tasks of live projects they own
+/// or belong to (all of them for global admins), optionally of one project.
+fn push_visible(
+    qb: &mut QueryBuilder<'_, Postgres>,
+    user_id: Uuid,
+    all: bool,
+    project_id: Option<Uuid>,
+) {
+    qb.push(
+        " JOIN projects p ON p.id = t.project_id
+          WHERE t.deleted_at IS NULL AND p.deleted_at IS NULL AND (",
+    )
+        .push_bind(all)
+        .push(" OR p.owner_id = ")
+        .push_bind(user_id)
+        .push(
+            " OR EXISTS (SELECT 1 FROM project_members pm
+               WHERE pm.project_id = p.id AND pm.user_id = ",
+        )
+        .push_bind(user_id)
+        .push("))");
+    if let Some(project_id) = project_id {
+        qb.push(" AND t.project_id = ").push_bind(project_id);
+    }
+}
+
+/// Searches tasks with a TQL query (see [`tql`]), e.g.
+/// `?q=status != done AND assignee = me AND deadline < +7d`.
+pub async fn search_tasks(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(params): Query<TaskSearchParams>,
+) -> AppResult<Json<PaginatedResponse<TaskResponse>>> {
+    let query = tql::parse(&params.q)?;
+    if let Some(project_id) = params.project_id {
+        access::require_member(&state.db, project_id, &claims).await?;
+    }
+
+    let user_id = access::user_id(&claims)?;
+    let all = access::is_global_admin(&claims);
+    let ctx = tql::Context {
+        user_id,
+        now: chrono::Utc::now(),
+    };
+    let pagination = params.pagination();
+
+    let mut count_qb = QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM tasks t");
+    push_visible(&mut count_qb, user_id, all, params.project_id);
+    query.push_filter(&mut count_qb, &ctx)?;
+    let total: i64 = count_qb.build_query_scalar().fetch_one(&state.db).await?;
+
+    let mut qb = QueryBuilder::<Postgres>::new(format!("SELECT {} FROM tasks t", TASK_COLUMNS));
+    push_visible(&mut qb, user_id, all, params.project_id);
+    query.push_filter(&mut qb, &ctx)?;
+    query.push_order_by(&mut qb);
+    qb.push(" LIMIT ")
+        .push_bind(pagination.limit())
+        .push(" OFFSET ")
+        .push_bind(pagination.offset());
+    let tasks: Vec<Task> = qb.build_query_as().fetch_all(&state.db).await?;
+
+    Ok(Json(PaginatedResponse {
+        data: task_responses(&state.db, tasks).await?,
+        page: pagination.page(),
+        limit: pagination.limit(),
+        total,
+    }))
+}
+
+pub async fn get_task(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(task_id): Path<Uuid>,
+) -> AppResult<impl IntoResponse> {
+    let task = load_task(&state.db, task_id).await?;
+    access::require_member(&state.db, task.project_id, &claims).await?;
+
+    let version = task.version;
+    Ok((
+        etag::header(version),
+        Json(task_response(&state.db, task).await?),
+    ))
+}
+
+/// Tells a newly assigned user about the assignment and the other watchers
+/// which fields changed.
+pub(crate) async fn notify_update(
+    state: &AppState,
+    old: &Task,
+    new: &Task,
+    actor: Uuid,
+    assigned: Option<Uuid>,
+) -> AppResult<()> {
+    if let Some(assignee_id) = assigned.filter(|id| *id != actor) {
+        state.notifier.send(NotificationEvent {
+            kind: "task.assigned".to_string(),
+            recipient_ids: vec![assignee_id],
+            actor_id: Some(actor),
+            payload: json!({
+                "project_id": new.project_id,
+                "task_id": new.id,
+                "title": new.title,
+            }),
+        });
+    }
+
+    let fields: Vec<String> = activity::diff(old, new)?
+        .into_iter()
+        .map(|change| change.field)
+        .collect();
+    if !fields.is_empty() {
+        let exclude: Vec<Uuid> = assigned.into_iter().collect();
+        let payload = json!({"fields": fields, "status": new.status});
+        watchers::notify_task(state, "task.updated", new, actor, &exclude, payload).await;
+    }
+    Ok(())
+}
+
+/// Updates a task. For recurring tasks `?scope=future` also applies the
+/// changes to the series and its later open occurrences; a new `recurrence`
+/// rule either starts a series (on a plain task) or replaces the rule from
+/// this occurrence onwards (with `scope=future`).
+pub async fn update_task(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(task_id): Path<Uuid>,
+    Query(params): Query<EditScopeParams>,
+    headers: HeaderMap,
+    Json(req): Json<UpdateTaskRequest>,
+) -> AppResult<impl IntoResponse> {
+    let current = load_task(&state.db, task_id).await?;
+    access::require_editor(&state.db, current.project_id, &claims).await?;
+    let expected = etag::expected_version(&headers, current.version, state.require_if_match)?;
+
+    if params.scope == EditScope::Future && current.recurrence_id.is_none() {
+        return Err(AppError::BadRequest(
+            "Task is not part of a recurring series".to_string(),
+        ));
+    }
+    let new_series = match &req.recurrence {
+        Some(_) if params.scope == EditScope::This && current.recurrence_id.is_some() => {
+            return Err(AppError::BadRequest(
+                "Changing the recurrence rule applies to all future occurrences; use scope=future"
+                    .to_string(),
+            ))
+        }
+        Some(input) if current.recurrence_id.is_none() => Some(recurrence::parse_rule(input)?),
+        Some(input) => {
+            recurrence::parse_rule(input)?;
+            None
+        }
+        None => None,
+    };
+
+    let title = req.title.as_deref().map(validate_title).transpose()?;
+    if let Some(status) = &req.status {
+        validate_status(status)?;
+    }
+    if let Some(priority) = &req.priority {
+        validate_priority(priority)?;
+    }
+    validate_estimate(req.original_estimate_minutes)?;
+    validate_estimate(req.remaining_estimate_minutes)?;
+    if let Some(Some(assignee_id)) = req.assignee_id {
+        if !access::is_project_member(&state.db, current.project_id, assignee_id).await? {
+            return Err(AppError::BadRequest(
+                "Assignee must be a member of the project".to_string(),
+            ));
+        }
+    }
+
+    let custom_fields = match &req.custom_fields {
+        Some(input) => Some(
+            apply_custom_fields(
+                &state.db,
+                current.project_id,
+                &current.custom_fields,
+                input,
+                false,
+            )
+            .await?,
+        ),
+        None => None,
+    };
+
+    let mut tx = state.db.begin().await?;
+
+    let task = sqlx::query_as::<_, Task>(&format!(
+        "UPDATE tasks AS t SET
+            title = COALESCE($2, t.title),
+            description = CASE WHEN $3 THEN $4 ELSE t.description END,
+            status = COALESCE($5::task_status, t.status),
+            priority = COALESCE($6::task_priority, t.priority),
+            assignee_id = CASE WHEN $7 THEN $8 ELSE t.assignee_id END,
+            deadline = CASE WHEN $9 THEN $10 ELSE t.deadline END,
+            custom_fields = COALESCE($11, t.custom_fields),
+            original_estimate_minutes = COALESCE($12, t.original_estimate_minutes),
+            remaining_estimate_minutes = COALESCE($13, t.remaining_estimate_minutes, $12),
+            updated_at = NOW()
+         WHERE t.id = $1 AND ($14::int IS NULL OR t.version = $14)
+         RETURNING {}",
+        TASK_COLUMNS
+    ))
+    .bind(task_id)
+    .bind(&title)
+    .bind(req.description.is_some())
+    .bind(req.description.clone().flatten())
+    .bind(&req.status)
+    .bind(&req.priority)
+    .bind(req.assignee_id.is_some())
+    .bind(req.assignee_id.flatten())
+    .bind(req.deadline.is_some())
+    .bind(req.deadline.flatten())
+    .bind(&custom_fields)
+    .bind(req.original_estimate_minutes)
+    .bind(req.remaining_estimate_minutes)
+    .bind(expected)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| etag::modified(current.version))?;
+
+    if let Some(label_ids) = &req.label_ids {
+        label::set_task_labels(&mut tx, task.project_id, task.id, label_ids).await?;
+    }
+
+    let task = match &new_series {
+        Some((rule, tz)) => {
+            let label_ids: Vec<Uuid> = match &req.label_ids {
+                Some(ids) => ids.clone(),
+                None => {
+                    sqlx::query_scalar("SELECT label_id FROM task_labels WHERE task_id = $1")
+                        .bind(task.id)
+                        .fetch_all(&mut *tx)
+                        .await?
+                }
+            };
+            recurrence::create_series(&mut tx, &task, rule, *tz, &label_ids).await?
+        }
+        None => task,
+    };
+
+    let actor = access::user_id(&claims)?;
+    activity::record_updated(
+        &mut tx,
+        task.project_id,
+        Entity::Task,
+        task.id,
+        Some(actor),
+        &current,
+        &task,
+    )
+    .await?;
+
+    if params.scope == EditScope::Future {
+        recurrence::apply_to_future(&mut tx, &task, &req, title.as_deref(), actor).await?;
+    }
+
+    let assigned = task.assignee_id.filter(|id| current.assignee_id != Some(*id));
+    if let Some(assignee_id) = assigned {
+        watchers::auto_watch(&mut tx, task.id, &[assignee_id]).await?;
+    }
+
+    tx.commit().await?;
+
+    notify_update(&state, &current, &task, actor, assigned).await?;
+
+    if task.status == "done" && current.status != "done" {
+        recurrence::on_task_completed(&state.db, &task).await?;
+    }
+
+    let version = task.version;
+    Ok((
+        etag::header(version),
+        Json(task_response(&state.db, task).await?),
+    ))
+}
+
+/// Moves a task to the trash. With `?scope=future` a recurring task's series
+/// is stopped and its later occurrences that are not done are trashed as well.
+pub async fn delete_task(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(task_id): Path<Uuid>,
+    Query(params): Query<EditScopeParams>,
+    headers: HeaderMap,
+) -> AppResult<Json<serde_json::Value>> {
+    let task = load_task(&state.db, task_id).await?;
+    access::require_editor(&state.db, task.project_id, &claims).await?;
+    let expected = etag::expected_version(&headers, task.version, state.require_if_match)?;
+    let actor = access::user_id(&claims)?;
+
+    let mut tx = state.db.begin().await?;
+
+    if params.scope == EditScope::Future {
+        let (Some(recurrence_id), Some(occurrence_at)) = (task.recurrence_id, task.occurrence_at)
+        else {
+            return Err(AppError::BadRequest(
+                "Task is not part of a recurring series".to_string(),
+            ));
+        };
+
+        recurrence::stop_series(&mut tx, recurrence_id).await?;
+        let later = sqlx::query_as::<_, Task>(&format!(
+            "UPDATE tasks AS t SET deleted_at = NOW(), deleted_by = $3
+             WHERE t.recurrence_id = $1 AND t.occurrence_at > $2 AND t.status <> 'done'
+               AND t.deleted_at IS NULL
+             RETURNING {}",
+            TASK_COLUMNS
+        ))
+        .bind(recurrence_id)
+        .bind(occurrence_at)
+        .bind(actor)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        for occurrence in &later {
+            activity::record_deleted(
+                &mut tx,
+                task.project_id,
+                Entity::Task,
+                occurrence.id,
+                Some(actor),
+                occurrence,
+            )
+            .await?;
+        }
+    }
+
+    let deleted = sqlx::query(
+        "UPDATE tasks SET deleted_at = NOW(), deleted_by = $2
+         WHERE id = $1 AND ($3::int IS NULL OR version = $3)",
+    )
+    .bind(task_id)
+    .bind(actor)
+    .bind(expected)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if deleted == 0 {
+        return Err(etag::modified(task.version));
+    }
+    activity::record_deleted(
+        &mut tx,
+        task.project_id,
+        Entity::Task,
+        task.id,
+        Some(actor),
+        &task,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    watchers::notify_task(&state, "task.deleted", &task, actor, &[], json!({})).await;
+
+    Ok(Json(json!({"message": "moved to trash"})))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn field(name: &str, field_type: &str) -> CustomField {
+        CustomField {
+            id: Uuid::new_v4(),
+            project_id: Uuid::nil(),
+            name: name.to_string(),
+            field_type: field_type.to_string(),
+            options: json!([]),
+            required: false,
+            position: 0,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn fields() -> Vec<CustomField> {
+        vec![
+            field("Points", "number"),
+            field("Due", "date"),
+            field("Done", "checkbox"),
+            field("Tags", "multi_select"),
+            field("Owner", "user"),
+            field("Est. hours", "number"),
+        ]
+    }
+
+    fn bad_request<T: std::fmt::Debug>(result: AppResult<T>) -> String {
+        match result {
+            Err(AppError::BadRequest(message)) => message,
+            other => panic!("expected a bad request, got {:?}", other),
+        }
+    }
+
+    fn filters(fields: &[CustomField], key: &str, value: &str) -> AppResult<Vec<CustomFieldFilter>> {
+        let query = HashMap::from([(key.to_string(), value.to_string())]);
+        parse_custom_filters(fields, &query)
+    }
+
+    #[test]
+    fn parses_custom_field_filters() {
+        let fields = fields();
+        let date = |raw| NaiveDate::parse_from_str(raw, "%Y-%m-%d").unwrap();
+        let owner = Uuid::new_v4().to_string();
+        let by_id = format!("cf.{}", fields[2].id);
+
+        let cases = [
+            ("cf.Points", "3", 0, FilterOp::Eq, FilterValue::Number(3.0)),
+            ("cf.points.gte", "2.5", 0, FilterOp::Gte, FilterValue::Number(2.5)),
+            ("cf.Points.ne", "-1", 0, FilterOp::Ne, FilterValue::Number(-1.0)),
+            ("cf.Due.lt", "2024-05-01", 1, FilterOp::Lt, FilterValue::Date(date("2024-05-01"))),
+            ("cf.due.gt", "2024-02-29", 1, FilterOp::Gt, FilterValue::Date(date("2024-02-29"))),
+            (&by_id, "true", 2, FilterOp::Eq, FilterValue::Bool(true)),
+            ("cf.Done.ne", "false", 2, FilterOp::Ne, FilterValue::Bool(false)),
+            ("cf.Tags", "ui", 3, FilterOp::Eq, FilterValue::Text("ui".to_string())),
+            ("cf.Owner.ne", &owner, 4, FilterOp::Ne, FilterValue::Text(owner.clone())),
+            ("cf.Est. hours.lte", "8", 5, FilterOp::Lte, FilterValue::Number(8.0)),
+        ];
+        for (key, raw, index, op, value) in cases {
+            let parsed = filters(&fields, key, raw).unwrap_or_else(|e| panic!("{}: {}", key, e));
+            assert_eq!(
+                parsed,
+                vec![CustomFieldFilter {
+                    field_key: fields[index].id.to_string(),
+                    field_type: fields[index].field_type.clone(),
+                    op,
+                    value,
+                }],
+                "{}={}",
+                key,
+                raw
+            );
+        }
+
+        // Other parameters are left to the regular filters.
+        assert!(filters(&fields, "status", "done").unwrap().is_empty());
+    }
+
+    #[test]
+    fn reports_invalid_custom_field_filters() {
+        let fields = fields();
+        let cases = [
+            ("cf.Missing", "1", "Unknown custom field filter 'cf.Missing'"),
+            ("cf.Points.between", "1", "Unknown custom field filter 'cf.Points.between'"),
+            ("cf.Missing.gt", "1", "Unknown custom field 'Missing'"),
+            ("cf.Points", "many", "Invalid value 'many' for custom field 'Points'"),
+            ("cf.Due.gte", "May 1", "Invalid value 'May 1' for custom field 'Due'"),
+            ("cf.Done", "yes", "Invalid value 'yes' for custom field 'Done'"),
+            ("cf.Tags.gt", "ui", "Custom field 'Tags' only supports eq and ne filters"),
+            ("cf.Owner.lte", "x", "Custom field 'Owner' only supports eq and ne filters"),
+            ("cf.Done.lt", "true", "Custom field 'Done' only supports eq and ne filters"),
+        ];
+        for (key, raw, message) in cases {
+            assert_eq!(bad_request(filters(&fields, key, raw)), message, "{}={}", key, raw);
+        }
+    }
+
+    #[test]
+    fn parses_sort_keys() {
+        let fields = fields();
+        let column = |column, descending| TaskSort {
+            key: SortKey::Column(column),
+            descending,
+        };
+        let custom = |index: usize, cast, descending| TaskSort {
+            key: SortKey::Custom {
+                field_key: fields[index].id.to_string(),
+                cast,
+            },
+            descending,
+        };
+
+        let cases = [
+            (None, column("t.created_at", true)),
+            (Some(""), column("t.created_at", true)),
+            (Some("  "), column("t.created_at", true)),
+            (Some("created_at"), column("t.created_at", false)),
+            (Some("-updated_at"), column("t.updated_at", true)),
+            (Some("deadline"), column("t.deadline", false)),
+            (Some(" -title "), column("LOWER(t.title)", true)),
+            (Some("status"), column("t.status", false)),
+            (Some("-priority"), column("t.priority", true)),
+            (Some("cf.Points"), custom(0, "::float8", false)),
+            (Some("-cf.due"), custom(1, "::date", true)),
+            (Some("cf.Done"), custom(2, "::boolean", false)),
+            (Some("cf.Tags"), custom(3, "", false)),
+            (Some("-cf.Owner"), custom(4, "", true)),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(parse_sort(&fields, raw).unwrap(), expected, "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn reports_unknown_sort_keys() {
+        let fields = fields();
+        for (raw, message) in [
+            ("colour", "Cannot sort by 'colour'"),
+            ("--title", "Cannot sort by '-title'"),
+            ("Points", "Cannot sort by 'Points'"),
+            ("cf.Missing", "Cannot sort by 'cf.Missing'"),
+            ("-cf.", "Cannot sort by 'cf.'"),
+        ] {
+            assert_eq!(bad_request(parse_sort(&fields, Some(raw))), message, "{}", raw);
+        }
+    }
 }
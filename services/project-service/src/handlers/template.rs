@@ -0,0 +1,185 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::Utc;
+use serde_json::json;
+use shared::{
+    errors::{AppError, AppResult},
+    models::{Claims, PaginatedResponse, PaginationParams, Project},
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    access,
+    handlers::project::{insert_project, load_project, validate_name},
+    models::{CreateTemplateRequest, InstantiateTemplateRequest, ProjectTemplate, TemplateContent},
+    quota::Resource,
+    templates::{self, SnapshotOptions},
+    AppState,
+};
+
+const TEMPLATE_COLUMNS: &str =
+    "pt.id, pt.owner_id, pt.name, pt.description, pt.content, pt.created_at, pt.updated_at";
+
+/// Loads a template visible to the caller: their own, or any for global admins.
+async fn load_template(
+    db: &PgPool,
+    template_id: Uuid,
+    claims: &Claims,
+) -> AppResult<ProjectTemplate> {
+    let template = sqlx::query_as::<_, ProjectTemplate>(&format!(
+        "SELECT {} FROM project_templates pt WHERE pt.id = $1",
+        TEMPLATE_COLUMNS
+    ))
+    .bind(template_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Template not found".to_string()))?;
+
+    if template.owner_id != access::user_id(claims)? && !access::is_global_admin(claims) {
+        return Err(AppError::NotFound("Template not found".to_string()));
+    }
+
+    Ok(template)
+}
+
+/// Saves a project's labels, custom fields, open sprints and task skeletons
+/// as a template. Statuses, assignees and people-valued fields are left out.
+pub async fn create_template(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+    Json(req): Json<CreateTemplateRequest>,
+) -> AppResult<(StatusCode, Json<ProjectTemplate>)> {
+    access::require_editor_role(&state.db, project_id, &claims).await?;
+    let owner_id = access::user_id(&claims)?;
+    let name = validate_name(&req.name)?;
+    let project = load_project(&state.db, project_id).await?;
+
+    let reference = req
+        .reference_date
+        .unwrap_or_else(|| project.created_at.date_naive());
+    let content = templates::snapshot(
+        &state.db,
+        &project,
+        reference,
+        SnapshotOptions {
+            include_tasks: true,
+            keep_progress: false,
+            keep_people: false,
+        },
+    )
+    .await?;
+    let content = serde_json::to_value(&content)
+        .map_err(|e| AppError::InternalError(format!("Failed to serialize template: {}", e)))?;
+
+    let template = sqlx::query_as::<_, ProjectTemplate>(&format!(
+        "INSERT INTO project_templates AS pt (owner_id, name, description, content)
+         VALUES ($1, $2, $3, $4)
+         RETURNING {}",
+        TEMPLATE_COLUMNS
+    ))
+    .bind(owner_id)
+    .bind(&name)
+    .bind(req.description.as_ref().or(project.description.as_ref()))
+    .bind(content)
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(template)))
+}
+
+/// Templates the caller owns; global admins see all.
+pub async fn list_templates(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(pagination): Query<PaginationParams>,
+) -> AppResult<Json<PaginatedResponse<ProjectTemplate>>> {
+    let user_id = access::user_id(&claims)?;
+    let all = access::is_global_admin(&claims);
+
+    let total: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM project_templates pt WHERE $2 OR pt.owner_id = $1",
+    )
+    .bind(user_id)
+    .bind(all)
+    .fetch_one(&state.db)
+    .await?;
+
+    let templates = sqlx::query_as::<_, ProjectTemplate>(&format!(
+        "SELECT {} FROM project_templates pt
+         WHERE $2 OR pt.owner_id = $1
+         ORDER BY LOWER(pt.name), pt.id
+         LIMIT $3 OFFSET $4",
+        TEMPLATE_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(all)
+    .bind(pagination.limit())
+    .bind(pagination.offset())
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(PaginatedResponse {
+        data: templates,
+        page: pagination.page(),
+        limit: pagination.limit(),
+        total,
+    }))
+}
+
+pub async fn get_template(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(template_id): Path<Uuid>,
+) -> AppResult<Json<ProjectTemplate>> {
+    Ok(Json(load_template(&state.db, template_id, &claims).await?))
+}
+
+pub async fn delete_template(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(template_id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    load_template(&state.db, template_id, &claims).await?;
+
+    sqlx::query("DELETE FROM project_templates WHERE id = $1")
+        .bind(template_id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(Json(json!({"message": "deleted"})))
+}
+
+/// Creates a project owned by the caller from a template, with task deadlines
+/// and sprint dates placed relative to `start_date`.
+pub async fn create_project_from_template(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(template_id): Path<Uuid>,
+    Json(req): Json<InstantiateTemplateRequest>,
+) -> AppResult<(StatusCode, Json<Project>)> {
+    let template = load_template(&state.db, template_id, &claims).await?;
+    let owner_id = access::user_id(&claims)?;
+    let name = validate_name(&req.name)?;
+
+    let content: TemplateContent = serde_json::from_value(template.content)
+        .map_err(|e| AppError::InternalError(format!("Invalid template content: {}", e)))?;
+    let description = req.description.as_ref().or(content.description.as_ref());
+    let start = req.start_date.unwrap_or_else(|| Utc::now().date_naive());
+    let quota = state.billing.quota(owner_id, owner_id).await?;
+
+    let mut tx = state.db.begin().await?;
+    quota.check(&mut tx, Resource::Projects, 1).await?;
+    quota
+        .check(&mut tx, Resource::Tasks, content.tasks.len() as i64)
+        .await?;
+    let project = insert_project(&mut tx, owner_id, &name, description.map(String::as_str)).await?;
+    templates::instantiate(&mut tx, project.id, &content, start, owner_id).await?;
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(project)))
+}
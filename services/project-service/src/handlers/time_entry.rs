@@ -0,0 +1,551 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{Duration, NaiveDate, Utc};
+use serde_json::json;
+use shared::{
+    errors::{AppError, AppResult},
+    models::Claims,
+};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    access,
+    activity::{self, Entity},
+    models::{
+        CreateTimeEntryRequest, DailyTime, DateRangeParams, StartTimerRequest, TimeEntry,
+        TimeTotal, Timesheet, UpdateTimeEntryRequest,
+    },
+    spreadsheet::escape_cell,
+    AppState,
+};
+
+const ENTRY_COLUMNS: &str = "te.id, te.task_id, te.user_id, te.entry_date, te.duration_minutes,
+    te.started_at, te.ended_at, te.note, te.created_at, te.updated_at";
+
+const MAX_ENTRY_MINUTES: i32 = 24 * 60;
+const DEFAULT_RANGE_DAYS: i64 = 30;
+const MAX_RANGE_DAYS: i64 = 366;
+
+fn validate_duration(minutes: i32) -> AppResult<()> {
+    if !(1..=MAX_ENTRY_MINUTES).contains(&minutes) {
+        return Err(AppError::ValidationError(format!(
+            "Duration must be between 1 and {} minutes",
+            MAX_ENTRY_MINUTES
+        )));
+    }
+    Ok(())
+}
+
+/// Resolves a report range, defaulting to the last 30 days.
+pub(crate) fn resolve_range(params: &DateRangeParams) -> AppResult<(NaiveDate, NaiveDate)> {
+    let to = params.to.unwrap_or_else(|| Utc::now().date_naive());
+    let from = params
+        .from
+        .unwrap_or_else(|| to - Duration::days(DEFAULT_RANGE_DAYS - 1));
+
+    if from > to {
+        return Err(AppError::BadRequest(
+            "'from' must not be after 'to'".to_string(),
+        ));
+    }
+    if (to - from).num_days() >= MAX_RANGE_DAYS {
+        return Err(AppError::BadRequest(format!(
+            "Date range cannot exceed {} days",
+            MAX_RANGE_DAYS
+        )));
+    }
+    Ok((from, to))
+}
+
+async fn load_entry(db: &PgPool, entry_id: Uuid) -> AppResult<TimeEntry> {
+    sqlx::query_as::<_, TimeEntry>(&format!(
+        "SELECT {} FROM time_entries te WHERE te.id = $1",
+        ENTRY_COLUMNS
+    ))
+    .bind(entry_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Time entry not found".to_string()))
+}
+
+/// Burns logged minutes down from the task's remaining estimate. A negative
+/// delta (edit or delete) gives the time back.
+async fn adjust_remaining(
+    tx: &mut Transaction<'_, Postgres>,
+    task_id: Uuid,
+    logged_delta: i32,
+    actor_id: Uuid,
+) -> AppResult<()> {
+    if logged_delta == 0 {
+        return Ok(());
+    }
+
+    let changed: Option<(Uuid, i32, i32)> = sqlx::query_as(
+        "UPDATE tasks t
+         SET remaining_estimate_minutes = GREATEST(o.remaining_estimate_minutes - $2, 0),
+             updated_at = NOW()
+         FROM (SELECT id, remaining_estimate_minutes FROM tasks WHERE id = $1 FOR UPDATE) o
+         WHERE t.id = o.id AND o.remaining_estimate_minutes IS NOT NULL
+         RETURNING t.project_id, o.remaining_estimate_minutes, t.remaining_estimate_minutes",
+    )
+    .bind(task_id)
+    .bind(logged_delta)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    if let Some((project_id, old, new)) = changed {
+        activity::record_field(
+            tx,
+            project_id,
+            Entity::Task,
+            task_id,
+            Some(actor_id),
+            "remaining_estimate_minutes",
+            &old,
+            &new,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Checks that the caller may change an entry: its owner or a project admin.
+async fn require_entry_access(
+    state: &AppState,
+    entry: &TimeEntry,
+    claims: &Claims,
+) -> AppResult<()> {
+    let project_id = access::task_project_id(&state.db, entry.task_id).await?;
+    let role = access::require_member(&state.db, project_id, claims).await?;
+    access::require_writable(&state.db, project_id).await?;
+
+    if entry.user_id != access::user_id(claims)? && !role.is_admin() {
+        return Err(AppError::Forbidden(
+            "Only the owner or a project admin can change this time entry".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+pub async fn create_time_entry(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(task_id): Path<Uuid>,
+    Json(req): Json<CreateTimeEntryRequest>,
+) -> AppResult<(StatusCode, Json<TimeEntry>)> {
+    let project_id = access::task_project_id(&state.db, task_id).await?;
+    access::require_editor(&state.db, project_id, &claims).await?;
+    let user_id = access::user_id(&claims)?;
+
+    validate_duration(req.duration_minutes)?;
+    let entry_date = req.entry_date.unwrap_or_else(|| Utc::now().date_naive());
+
+    let mut tx = state.db.begin().await?;
+
+    let entry = sqlx::query_as::<_, TimeEntry>(&format!(
+        "INSERT INTO time_entries AS te (task_id, user_id, entry_date, duration_minutes, note)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING {}",
+        ENTRY_COLUMNS
+    ))
+    .bind(task_id)
+    .bind(user_id)
+    .bind(entry_date)
+    .bind(req.duration_minutes)
+    .bind(&req.note)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    adjust_remaining(&mut tx, task_id, req.duration_minutes, user_id).await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+pub async fn list_task_time_entries(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(task_id): Path<Uuid>,
+) -> AppResult<Json<Vec<TimeEntry>>> {
+    let project_id = access::task_project_id(&state.db, task_id).await?;
+    access::require_member(&state.db, project_id, &claims).await?;
+
+    let entries = sqlx::query_as::<_, TimeEntry>(&format!(
+        "SELECT {} FROM time_entries te
+         WHERE te.task_id = $1
+         ORDER BY te.entry_date DESC, te.created_at DESC",
+        ENTRY_COLUMNS
+    ))
+    .bind(task_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(entries))
+}
+
+pub async fn update_time_entry(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(entry_id): Path<Uuid>,
+    Json(req): Json<UpdateTimeEntryRequest>,
+) -> AppResult<Json<TimeEntry>> {
+    let entry = load_entry(&state.db, entry_id).await?;
+    require_entry_access(&state, &entry, &claims).await?;
+
+    let Some(previous) = entry.duration_minutes else {
+        return Err(AppError::BadRequest(
+            "Stop the running timer before editing this entry".to_string(),
+        ));
+    };
+    if let Some(minutes) = req.duration_minutes {
+        validate_duration(minutes)?;
+    }
+
+    let mut tx = state.db.begin().await?;
+
+    let updated = sqlx::query_as::<_, TimeEntry>(&format!(
+        "UPDATE time_entries AS te SET
+            duration_minutes = COALESCE($2, te.duration_minutes),
+            entry_date = COALESCE($3, te.entry_date),
+            note = COALESCE($4, te.note),
+            updated_at = NOW()
+         WHERE te.id = $1
+         RETURNING {}",
+        ENTRY_COLUMNS
+    ))
+    .bind(entry_id)
+    .bind(req.duration_minutes)
+    .bind(req.entry_date)
+    .bind(&req.note)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if let Some(minutes) = req.duration_minutes {
+        let actor = access::user_id(&claims)?;
+        adjust_remaining(&mut tx, entry.task_id, minutes - previous, actor).await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(Json(updated))
+}
+
+pub async fn delete_time_entry(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(entry_id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    let entry = load_entry(&state.db, entry_id).await?;
+    require_entry_access(&state, &entry, &claims).await?;
+
+    let mut tx = state.db.begin().await?;
+
+    sqlx::query("DELETE FROM time_entries WHERE id = $1")
+        .bind(entry_id)
+        .execute(&mut *tx)
+        .await?;
+
+    if let Some(minutes) = entry.duration_minutes {
+        let actor = access::user_id(&claims)?;
+        adjust_remaining(&mut tx, entry.task_id, -minutes, actor).await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(Json(json!({"message": "deleted"})))
+}
+
+/// Starts a timer on a task. A user can only have one running timer.
+pub async fn start_timer(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(task_id): Path<Uuid>,
+    body: Option<Json<StartTimerRequest>>,
+) -> AppResult<(StatusCode, Json<TimeEntry>)> {
+    let project_id = access::task_project_id(&state.db, task_id).await?;
+    access::require_editor(&state.db, project_id, &claims).await?;
+    let user_id = access::user_id(&claims)?;
+    let note = body.and_then(|Json(req)| req.note);
+
+    let entry = sqlx::query_as::<_, TimeEntry>(&format!(
+        "INSERT INTO time_entries AS te (task_id, user_id, entry_date, started_at, note)
+         VALUES ($1, $2, CURRENT_DATE, NOW(), $3)
+         RETURNING {}",
+        ENTRY_COLUMNS
+    ))
+    .bind(task_id)
+    .bind(user_id)
+    .bind(&note)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|err| match &err {
+        sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("23505") => {
+            AppError::Conflict("You already have a running timer".to_string())
+        }
+        _ => err.into(),
+    })?;
+
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+/// Returns the caller's running timer, if any.
+pub async fn get_running_timer(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> AppResult<Json<Option<TimeEntry>>> {
+    let user_id = access::user_id(&claims)?;
+
+    let entry = sqlx::query_as::<_, TimeEntry>(&format!(
+        "SELECT {} FROM time_entries te WHERE te.user_id = $1 AND te.duration_minutes IS NULL",
+        ENTRY_COLUMNS
+    ))
+    .bind(user_id)
+    .fetch_optional(&state.db)
+    .await?;
+
+    Ok(Json(entry))
+}
+
+/// Stops the caller's running timer, rounding the elapsed time up to whole
+/// minutes.
+pub async fn stop_timer(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> AppResult<Json<TimeEntry>> {
+    let user_id = access::user_id(&claims)?;
+
+    let mut tx = state.db.begin().await?;
+
+    let entry = sqlx::query_as::<_, TimeEntry>(&format!(
+        "UPDATE time_entries AS te SET
+            ended_at = NOW(),
+            duration_minutes = LEAST(
+                GREATEST(CEIL(EXTRACT(EPOCH FROM (NOW() - te.started_at)) / 60)::int, 1),
+                $2
+            ),
+            updated_at = NOW()
+         WHERE te.user_id = $1 AND te.duration_minutes IS NULL
+         RETURNING {}",
+        ENTRY_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(MAX_ENTRY_MINUTES)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound("No running timer".to_string()))?;
+
+    adjust_remaining(
+        &mut tx,
+        entry.task_id,
+        entry.duration_minutes.unwrap_or(0),
+        user_id,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(entry))
+}
+
+/// Builds a timesheet for entries matching `scope`, which is a static SQL
+/// condition over `te`/`t` with `$1` as its only parameter.
+async fn build_timesheet(
+    db: &PgPool,
+    scope: &str,
+    scope_id: Uuid,
+    from: NaiveDate,
+    to: NaiveDate,
+    group_by_user: bool,
+) -> AppResult<Timesheet> {
+    let base = format!(
+        "FROM time_entries te
+         JOIN tasks t ON t.id = te.task_id
+         WHERE {} AND te.duration_minutes IS NOT NULL
+           AND te.entry_date BETWEEN $2 AND $3
+           AND t.deleted_at IS NULL
+           AND t.project_id IN (SELECT id FROM projects WHERE deleted_at IS NULL)",
+        scope
+    );
+
+    let by_day = sqlx::query_as::<_, DailyTime>(&format!(
+        "SELECT te.entry_date AS date, SUM(te.duration_minutes)::bigint AS minutes
+         {} GROUP BY te.entry_date ORDER BY te.entry_date",
+        base
+    ))
+    .bind(scope_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(db)
+    .await?;
+
+    let by_task = sqlx::query_as::<_, TimeTotal>(&format!(
+        "SELECT t.id, t.title AS name, SUM(te.duration_minutes)::bigint AS minutes
+         {} GROUP BY t.id, t.title ORDER BY minutes DESC, t.title",
+        base
+    ))
+    .bind(scope_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(db)
+    .await?;
+
+    let (by_user, by_project) = if group_by_user {
+        let by_user = sqlx::query_as::<_, TimeTotal>(&format!(
+            "SELECT u.id, u.name, SUM(te.duration_minutes)::bigint AS minutes
+             {} GROUP BY u.id, u.name ORDER BY minutes DESC, u.name",
+            base.replacen("WHERE", "JOIN users u ON u.id = te.user_id WHERE", 1)
+        ))
+        .bind(scope_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(db)
+        .await?;
+        (by_user, Vec::new())
+    } else {
+        let by_project = sqlx::query_as::<_, TimeTotal>(&format!(
+            "SELECT p.id, p.name, SUM(te.duration_minutes)::bigint AS minutes
+             {} GROUP BY p.id, p.name ORDER BY minutes DESC, p.name",
+            base.replacen("WHERE", "JOIN projects p ON p.id = t.project_id WHERE", 1)
+        ))
+        .bind(scope_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(db)
+        .await?;
+        (Vec::new(), by_project)
+    };
+
+    Ok(Timesheet {
+        from,
+        to,
+        total_minutes: by_day.iter().map(|d| d.minutes).sum(),
+        by_day,
+        by_user,
+        by_project,
+        by_task,
+    })
+}
+
+pub async fn project_timesheet(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+    Query(params): Query<DateRangeParams>,
+) -> AppResult<Json<Timesheet>> {
+    access::require_member(&state.db, project_id, &claims).await?;
+    let (from, to) = resolve_range(&params)?;
+
+    let timesheet =
+        build_timesheet(&state.db, "t.project_id = $1", project_id, from, to, true).await?;
+
+    Ok(Json(timesheet))
+}
+
+pub async fn user_timesheet(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(user_id): Path<Uuid>,
+    Query(params): Query<DateRangeParams>,
+) -> AppResult<Json<Timesheet>> {
+    if user_id != access::user_id(&claims)? && !access::is_global_admin(&claims) {
+        return Err(AppError::Forbidden(
+            "You can only view your own timesheet".to_string(),
+        ));
+    }
+    let (from, to) = resolve_range(&params)?;
+
+    let timesheet = build_timesheet(&state.db, "te.user_id = $1", user_id, from, to, false).await?;
+
+    Ok(Json(timesheet))
+}
+
+#[derive(sqlx::FromRow)]
+struct ExportRow {
+    entry_date: NaiveDate,
+    user_email: String,
+    user_name: String,
+    task_id: Uuid,
+    task_title: String,
+    duration_minutes: i32,
+    note: Option<String>,
+}
+
+/// Exports a project's completed time entries in the range as CSV.
+pub async fn export_time_entries(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+    Query(params): Query<DateRangeParams>,
+) -> AppResult<impl IntoResponse> {
+    access::require_member(&state.db, project_id, &claims).await?;
+    let (from, to) = resolve_range(&params)?;
+
+    let rows = sqlx::query_as::<_, ExportRow>(
+        "SELECT te.entry_date, u.email AS user_email, u.name AS user_name,
+                t.id AS task_id, t.title AS task_title, te.duration_minutes, te.note
+         FROM time_entries te
+         JOIN tasks t ON t.id = te.task_id
+         JOIN users u ON u.id = te.user_id
+         WHERE t.project_id = $1 AND te.duration_minutes IS NOT NULL
+           AND te.entry_date BETWEEN $2 AND $3 AND t.deleted_at IS NULL
+         ORDER BY te.entry_date, u.email, te.created_at",
+    )
+    .bind(project_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let csv_error = |e: csv::Error| AppError::InternalError(format!("Failed to write CSV: {}", e));
+
+    writer
+        .write_record([
+            "date",
+            "user_email",
+            "user_name",
+            "task_id",
+            "task_title",
+            "minutes",
+            "hours",
+            "note",
+        ])
+        .map_err(csv_error)?;
+
+    for row in rows {
+        writer
+            .write_record([
+                row.entry_date.to_string(),
+                escape_cell(row.user_email),
+                escape_cell(row.user_name),
+                row.task_id.to_string(),
+                escape_cell(row.task_title),
+                row.duration_minutes.to_string(),
+                format!("{:.2}", f64::from(row.duration_minutes) / 60.0),
+                escape_cell(row.note.unwrap_or_default()),
+            ])
+            .map_err(csv_error)?;
+    }
+
+    let body = writer
+        .into_inner()
+        .map_err(|e| AppError::InternalError(format!("Failed to write CSV: {}", e)))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"time-entries-{}-{}.csv\"", from, to),
+            ),
+        ],
+        body,
+    ))
+}
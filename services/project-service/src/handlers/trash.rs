@@ -0,0 +1,204 @@
+use axum::{
+    extract::{Path, Query, State},
+    Extension, Json,
+};
+use shared::{
+    errors::{AppError, AppResult},
+    models::{Claims, PaginatedResponse, PaginationParams, Project, Task},
+};
+use uuid::Uuid;
+
+use crate::{
+    access,
+    activity::{self, Entity},
+    handlers::{
+        project::PROJECT_COLUMNS,
+        task::{task_response, TASK_COLUMNS},
+    },
+    models::{TaskResponse, TrashedProject, TrashedTask},
+    AppState,
+};
+
+/// Projects in the trash that the caller owns; global admins see all.
+pub async fn list_trashed_projects(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(pagination): Query<PaginationParams>,
+) -> AppResult<Json<PaginatedResponse<TrashedProject>>> {
+    let user_id = access::user_id(&claims)?;
+    let all = access::is_global_admin(&claims);
+
+    let visible = "p.deleted_at IS NOT NULL AND ($2 OR p.owner_id = $1)";
+
+    let total: i64 = sqlx::query_scalar(&format!(
+        "SELECT COUNT(*) FROM projects p WHERE {}",
+        visible
+    ))
+    .bind(user_id)
+    .bind(all)
+    .fetch_one(&state.db)
+    .await?;
+
+    let projects = sqlx::query_as::<_, TrashedProject>(&format!(
+        "SELECT {}, p.deleted_at, p.deleted_by,
+                p.deleted_at + make_interval(days => $5) AS purge_at
+         FROM projects p WHERE {}
+         ORDER BY p.deleted_at DESC, p.id
+         LIMIT $3 OFFSET $4",
+        PROJECT_COLUMNS, visible
+    ))
+    .bind(user_id)
+    .bind(all)
+    .bind(pagination.limit())
+    .bind(pagination.offset())
+    .bind(state.trash_retention_days)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(PaginatedResponse {
+        data: projects,
+        page: pagination.page(),
+        limit: pagination.limit(),
+        total,
+    }))
+}
+
+/// Tasks of a project that are in the trash, most recently deleted first.
+pub async fn list_trashed_tasks(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+    Query(pagination): Query<PaginationParams>,
+) -> AppResult<Json<PaginatedResponse<TrashedTask>>> {
+    access::require_member(&state.db, project_id, &claims).await?;
+
+    let total: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM tasks WHERE project_id = $1 AND deleted_at IS NOT NULL",
+    )
+    .bind(project_id)
+    .fetch_one(&state.db)
+    .await?;
+
+    let tasks = sqlx::query_as::<_, TrashedTask>(&format!(
+        "SELECT {}, t.deleted_at, t.deleted_by,
+                t.deleted_at + make_interval(days => $4) AS purge_at
+         FROM tasks t
+         WHERE t.project_id = $1 AND t.deleted_at IS NOT NULL
+         ORDER BY t.deleted_at DESC, t.id
+         LIMIT $2 OFFSET $3",
+        TASK_COLUMNS
+    ))
+    .bind(project_id)
+    .bind(pagination.limit())
+    .bind(pagination.offset())
+    .bind(state.trash_retention_days)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(PaginatedResponse {
+        data: tasks,
+        page: pagination.page(),
+        limit: pagination.limit(),
+        total,
+    }))
+}
+
+/// Takes a project out of the trash with everything in it. Only the owner may
+/// do this.
+pub async fn restore_project(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+) -> AppResult<Json<Project>> {
+    let actor = access::user_id(&claims)?;
+
+    let owner_id: Uuid = sqlx::query_scalar(
+        "SELECT owner_id FROM projects WHERE id = $1 AND deleted_at IS NOT NULL",
+    )
+    .bind(project_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Project not found in trash".to_string()))?;
+
+    if owner_id != actor && !access::is_global_admin(&claims) {
+        return Err(AppError::Forbidden(
+            "Only the project owner can restore it".to_string(),
+        ));
+    }
+
+    let mut tx = state.db.begin().await?;
+
+    let project = sqlx::query_as::<_, Project>(&format!(
+        "UPDATE projects AS p SET deleted_at = NULL, deleted_by = NULL
+         WHERE p.id = $1
+         RETURNING {}",
+        PROJECT_COLUMNS
+    ))
+    .bind(project_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    activity::record_event(
+        &mut tx,
+        project_id,
+        Entity::Project,
+        project_id,
+        Some(actor),
+        "restored",
+        None,
+        None,
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(project))
+}
+
+/// Takes a task out of the trash. Its project must be active.
+pub async fn restore_task(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(task_id): Path<Uuid>,
+) -> AppResult<Json<TaskResponse>> {
+    let project_id: Uuid =
+        sqlx::query_scalar("SELECT project_id FROM tasks WHERE id = $1 AND deleted_at IS NOT NULL")
+            .bind(task_id)
+            .fetch_optional(&state.db)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Task not found in trash".to_string()))?;
+    access::require_editor(&state.db, project_id, &claims).await?;
+    let actor = access::user_id(&claims)?;
+
+    let mut tx = state.db.begin().await?;
+
+    let task = sqlx::query_as::<_, Task>(&format!(
+        "UPDATE tasks AS t SET deleted_at = NULL, deleted_by = NULL
+         WHERE t.id = $1
+         RETURNING {}",
+        TASK_COLUMNS
+    ))
+    .bind(task_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let value = serde_json::to_value(&task)
+        .map_err(|e| AppError::InternalError(format!("Failed to serialize activity: {}", e)))?;
+    activity::record_event(
+        &mut tx,
+        project_id,
+        Entity::Task,
+        task_id,
+        Some(actor),
+        "restored",
+        None,
+        None,
+        Some(value),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(task_response(&state.db, task).await?))
+}
@@ -0,0 +1,26 @@
+use axum::{extract::State, Extension, Json};
+use shared::{errors::AppResult, models::Claims};
+
+use crate::{
+    access,
+    models::{Usage, UsageItem},
+    quota, AppState,
+};
+
+/// The caller's projects and tasks against the limits of their plan.
+pub async fn get_usage(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> AppResult<Json<Usage>> {
+    let user_id = access::user_id(&claims)?;
+    let limits = state.billing.limits(user_id).await?;
+
+    let mut conn = state.db.acquire().await?;
+    let (projects, tasks) = quota::usage(&mut conn, user_id).await?;
+
+    Ok(Json(Usage {
+        plan: limits.plan,
+        projects: UsageItem::new(projects, limits.max_projects),
+        tasks: UsageItem::new(tasks, limits.max_tasks),
+    }))
+}
@@ -0,0 +1,115 @@
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use shared::{errors::AppResult, models::Claims};
+use uuid::Uuid;
+
+use crate::{
+    access,
+    models::{WatchStatus, Watcher},
+    watchers::{self, TASK_WATCHERS},
+    AppState,
+};
+
+/// Everyone notified about changes to the task, directly or through its
+/// project.
+pub async fn list_task_watchers(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(task_id): Path<Uuid>,
+) -> AppResult<Json<Vec<Watcher>>> {
+    let project_id = access::task_project_id(&state.db, task_id).await?;
+    access::require_member(&state.db, project_id, &claims).await?;
+
+    let watchers = sqlx::query_as::<_, Watcher>(&format!(
+        "SELECT w.user_id, u.name, u.email, w.source, w.since
+         FROM ({}) w
+         JOIN users u ON u.id = w.user_id
+         ORDER BY w.since, u.email",
+        TASK_WATCHERS
+    ))
+    .bind(task_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(watchers))
+}
+
+pub async fn watch_task(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(task_id): Path<Uuid>,
+) -> AppResult<Json<WatchStatus>> {
+    let project_id = access::task_project_id(&state.db, task_id).await?;
+    access::require_member(&state.db, project_id, &claims).await?;
+    let user_id = access::user_id(&claims)?;
+
+    watchers::set_task_watch(&state.db, task_id, user_id, true).await?;
+
+    Ok(Json(WatchStatus { watching: true }))
+}
+
+/// Unfollows a task. This also opts out of notifications the task would
+/// send through a watched project, and of being watched again automatically.
+pub async fn unwatch_task(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(task_id): Path<Uuid>,
+) -> AppResult<Json<WatchStatus>> {
+    let project_id = access::task_project_id(&state.db, task_id).await?;
+    access::require_member(&state.db, project_id, &claims).await?;
+    let user_id = access::user_id(&claims)?;
+
+    watchers::set_task_watch(&state.db, task_id, user_id, false).await?;
+
+    Ok(Json(WatchStatus { watching: false }))
+}
+
+pub async fn list_project_watchers(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+) -> AppResult<Json<Vec<Watcher>>> {
+    access::require_member(&state.db, project_id, &claims).await?;
+
+    let watchers = sqlx::query_as::<_, Watcher>(
+        "SELECT pw.user_id, u.name, u.email, 'project' AS source, pw.created_at AS since
+         FROM project_watchers pw
+         JOIN users u ON u.id = pw.user_id
+         WHERE pw.project_id = $1
+         ORDER BY pw.created_at, u.email",
+    )
+    .bind(project_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(watchers))
+}
+
+/// Watches every task of the project, except those the user unfollowed.
+pub async fn watch_project(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+) -> AppResult<Json<WatchStatus>> {
+    access::require_member(&state.db, project_id, &claims).await?;
+    let user_id = access::user_id(&claims)?;
+
+    watchers::set_project_watch(&state.db, project_id, user_id, true).await?;
+
+    Ok(Json(WatchStatus { watching: true }))
+}
+
+pub async fn unwatch_project(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+) -> AppResult<Json<WatchStatus>> {
+    access::require_member(&state.db, project_id, &claims).await?;
+    let user_id = access::user_id(&claims)?;
+
+    watchers::set_project_watch(&state.db, project_id, user_id, false).await?;
+
+    Ok(Json(WatchStatus { watching: false }))
+}
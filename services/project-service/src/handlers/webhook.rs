@@ -0,0 +1,249 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde_json::json;
+use shared::{
+    errors::{AppError, AppResult},
+    models::{Claims, PaginatedResponse, PaginationParams},
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    access,
+    models::{
+        CreateWebhookRequest, UpdateWebhookRequest, Webhook, WebhookDelivery, WebhookWithSecret,
+    },
+    webhooks, AppState,
+};
+
+const WEBHOOK_COLUMNS: &str = "w.id, w.project_id, w.url, w.events, w.active,
+    w.consecutive_failures, w.disabled_at, w.created_by, w.created_at, w.updated_at";
+
+const DELIVERY_COLUMNS: &str = "d.id, d.webhook_id, d.event, d.payload, d.status, d.attempts,
+    d.next_attempt_at, d.response_status, d.response_body, d.error, d.duration_ms,
+    d.redelivery_of, d.created_at, d.last_attempt_at, d.delivered_at";
+
+async fn load_webhook(db: &PgPool, webhook_id: Uuid) -> AppResult<Webhook> {
+    sqlx::query_as::<_, Webhook>(&format!(
+        "SELECT {} FROM webhooks w WHERE w.id = $1",
+        WEBHOOK_COLUMNS
+    ))
+    .bind(webhook_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Webhook not found".to_string()))
+}
+
+pub async fn list_webhooks(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+) -> AppResult<Json<Vec<Webhook>>> {
+    access::require_admin_role(&state.db, project_id, &claims).await?;
+
+    let webhooks = sqlx::query_as::<_, Webhook>(&format!(
+        "SELECT {} FROM webhooks w WHERE w.project_id = $1 ORDER BY w.created_at, w.id",
+        WEBHOOK_COLUMNS
+    ))
+    .bind(project_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(webhooks))
+}
+
+/// Subscribes a URL to project events. The secret is returned only here and
+/// when it is changed; a random one is generated when none is given.
+pub async fn create_webhook(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(project_id): Path<Uuid>,
+    Json(req): Json<CreateWebhookRequest>,
+) -> AppResult<(StatusCode, Json<WebhookWithSecret>)> {
+    access::require_admin_role(&state.db, project_id, &claims).await?;
+    let user_id = access::user_id(&claims)?;
+
+    let url = webhooks::validate_url(&req.url, state.webhook_addresses).await?;
+    let events = webhooks::validate_events(&req.events)?;
+    let secret = match req.secret.as_deref() {
+        Some(secret) => webhooks::validate_secret(secret)?,
+        None => webhooks::new_secret(),
+    };
+
+    let webhook = sqlx::query_as::<_, Webhook>(&format!(
+        "INSERT INTO webhooks AS w (project_id, url, secret, events, created_by)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING {}",
+        WEBHOOK_COLUMNS
+    ))
+    .bind(project_id)
+    .bind(&url)
+    .bind(&secret)
+    .bind(&events)
+    .bind(user_id)
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(WebhookWithSecret { webhook, secret }),
+    ))
+}
+
+pub async fn get_webhook(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(webhook_id): Path<Uuid>,
+) -> AppResult<Json<Webhook>> {
+    let webhook = load_webhook(&state.db, webhook_id).await?;
+    access::require_admin_role(&state.db, webhook.project_id, &claims).await?;
+
+    Ok(Json(webhook))
+}
+
+/// Changes a webhook. Setting `active` to true re-enables a webhook that was
+/// disabled after repeated failures and resets its failure count.
+pub async fn update_webhook(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(webhook_id): Path<Uuid>,
+    Json(req): Json<UpdateWebhookRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    let webhook = load_webhook(&state.db, webhook_id).await?;
+    access::require_admin_role(&state.db, webhook.project_id, &claims).await?;
+
+    let url = match req.url.as_deref() {
+        Some(url) => Some(webhooks::validate_url(url, state.webhook_addresses).await?),
+        None => None,
+    };
+    let events = req
+        .events
+        .as_deref()
+        .map(webhooks::validate_events)
+        .transpose()?;
+    let secret = req
+        .secret
+        .as_deref()
+        .map(webhooks::validate_secret)
+        .transpose()?;
+
+    let updated = sqlx::query_as::<_, Webhook>(&format!(
+        "UPDATE webhooks AS w SET
+            url = COALESCE($2, w.url),
+            events = COALESCE($3, w.events),
+            secret = COALESCE($4, w.secret),
+            active = COALESCE($5, w.active),
+            consecutive_failures = CASE WHEN $5 THEN 0 ELSE w.consecutive_failures END,
+            disabled_at = CASE WHEN $5 IS NULL THEN w.disabled_at
+                               WHEN $5 THEN NULL
+                               ELSE COALESCE(w.disabled_at, NOW()) END,
+            updated_at = NOW()
+         WHERE w.id = $1
+         RETURNING {}",
+        WEBHOOK_COLUMNS
+    ))
+    .bind(webhook_id)
+    .bind(&url)
+    .bind(&events)
+    .bind(&secret)
+    .bind(req.active)
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok(Json(match secret {
+        Some(secret) => json!(WebhookWithSecret {
+            webhook: updated,
+            secret,
+        }),
+        None => json!(updated),
+    }))
+}
+
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(webhook_id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    let webhook = load_webhook(&state.db, webhook_id).await?;
+    access::require_admin_role(&state.db, webhook.project_id, &claims).await?;
+
+    sqlx::query("DELETE FROM webhooks WHERE id = $1")
+        .bind(webhook_id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(Json(json!({"message": "deleted"})))
+}
+
+/// The delivery log of a webhook, newest first.
+pub async fn list_deliveries(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(webhook_id): Path<Uuid>,
+    Query(pagination): Query<PaginationParams>,
+) -> AppResult<Json<PaginatedResponse<WebhookDelivery>>> {
+    let webhook = load_webhook(&state.db, webhook_id).await?;
+    access::require_admin_role(&state.db, webhook.project_id, &claims).await?;
+
+    let total: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM webhook_deliveries WHERE webhook_id = $1")
+            .bind(webhook_id)
+            .fetch_one(&state.db)
+            .await?;
+
+    let deliveries = sqlx::query_as::<_, WebhookDelivery>(&format!(
+        "SELECT {} FROM webhook_deliveries d
+         WHERE d.webhook_id = $1
+         ORDER BY d.created_at DESC, d.id
+         LIMIT $2 OFFSET $3",
+        DELIVERY_COLUMNS
+    ))
+    .bind(webhook_id)
+    .bind(pagination.limit())
+    .bind(pagination.offset())
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(PaginatedResponse {
+        data: deliveries,
+        page: pagination.page(),
+        limit: pagination.limit(),
+        total,
+    }))
+}
+
+/// Queues the payload of an earlier delivery again, as a new delivery that
+/// points back at the original.
+pub async fn redeliver(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((webhook_id, delivery_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<(StatusCode, Json<WebhookDelivery>)> {
+    let webhook = load_webhook(&state.db, webhook_id).await?;
+    access::require_admin_role(&state.db, webhook.project_id, &claims).await?;
+
+    if !webhook.active {
+        return Err(AppError::Conflict(
+            "Webhook is disabled; re-enable it before redelivering".to_string(),
+        ));
+    }
+
+    let delivery = sqlx::query_as::<_, WebhookDelivery>(&format!(
+        "INSERT INTO webhook_deliveries AS d (webhook_id, event, payload, next_attempt_at,
+                                              redelivery_of)
+         SELECT webhook_id, event, payload, NOW(), id FROM webhook_deliveries
+         WHERE id = $1 AND webhook_id = $2
+         RETURNING {}",
+        DELIVERY_COLUMNS
+    ))
+    .bind(delivery_id)
+    .bind(webhook_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Delivery not found".to_string()))?;
+
+    Ok((StatusCode::ACCEPTED, Json(delivery)))
+}
@@ -0,0 +1,216 @@
+//! A small iCalendar (RFC 5545) writer for the deadline feeds.
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+const PRODID: &str = "-//Mini-SaaS//Project Service//EN";
+
+/// Longest content line, in octets, before it is folded.
+const LINE_LIMIT: usize = 75;
+
+pub enum EventTime {
+    /// A point in time, such as a task deadline.
+    At(DateTime<Utc>),
+    /// Whole days; `end` is exclusive.
+    Days { start: NaiveDate, end: NaiveDate },
+}
+
+pub struct Event {
+    /// Must stay the same across fetches so calendar apps update the event
+    /// instead of adding a new one.
+    pub uid: String,
+    pub summary: String,
+    pub description: Option<String>,
+    pub time: EventTime,
+    pub last_modified: DateTime<Utc>,
+    /// Revision number, starting at 0; calendar apps only pick up changes
+    /// to an event when it grows.
+    pub sequence: i32,
+}
+
+pub struct Calendar {
+    name: String,
+    events: Vec<Event>,
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace(['\n', '\r'], "\\n")
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn date(day: NaiveDate) -> String {
+    day.format("%Y%m%d").to_string()
+}
+
+/// Writes a content line, folding it onto continuation lines (which start
+/// with a space) without splitting a UTF-8 character.
+fn push_line(out: &mut String, line: &str) {
+    let mut limit = LINE_LIMIT;
+    let mut rest = line;
+    while rest.len() > limit {
+        let mut split = limit;
+        while !rest.is_char_boundary(split) {
+            split -= 1;
+        }
+        out.push_str(&rest[..split]);
+        out.push_str("\r\n ");
+        rest = &rest[split..];
+        limit = LINE_LIMIT - 1;
+    }
+    out.push_str(rest);
+    out.push_str("\r\n");
+}
+
+impl Calendar {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        push_line(&mut out, "BEGIN:VCALENDAR");
+        push_line(&mut out, "VERSION:2.0");
+        push_line(&mut out, &format!("PRODID:{}", PRODID));
+        push_line(&mut out, "CALSCALE:GREGORIAN");
+        push_line(&mut out, "METHOD:PUBLISH");
+        push_line(&mut out, &format!("X-WR-CALNAME:{}", escape(&self.name)));
+        push_line(&mut out, "REFRESH-INTERVAL;VALUE=DURATION:PT1H");
+        push_line(&mut out, "X-PUBLISHED-TTL:PT1H");
+
+        for event in &self.events {
+            push_line(&mut out, "BEGIN:VEVENT");
+            push_line(&mut out, &format!("UID:{}", escape(&event.uid)));
+            push_line(
+                &mut out,
+                &format!("DTSTAMP:{}", timestamp(event.last_modified)),
+            );
+            push_line(
+                &mut out,
+                &format!("LAST-MODIFIED:{}", timestamp(event.last_modified)),
+            );
+            push_line(&mut out, &format!("SEQUENCE:{}", event.sequence));
+            match event.time {
+                EventTime::At(at) => {
+                    push_line(&mut out, &format!("DTSTART:{}", timestamp(at)));
+                }
+                EventTime::Days { start, end } => {
+                    push_line(&mut out, &format!("DTSTART;VALUE=DATE:{}", date(start)));
+                    push_line(&mut out, &format!("DTEND;VALUE=DATE:{}", date(end)));
+                }
+            }
+            push_line(&mut out, &format!("SUMMARY:{}", escape(&event.summary)));
+            if let Some(description) = &event.description {
+                push_line(&mut out, &format!("DESCRIPTION:{}", escape(description)));
+            }
+            push_line(&mut out, "TRANSP:TRANSPARENT");
+            push_line(&mut out, "END:VEVENT");
+        }
+
+        push_line(&mut out, "END:VCALENDAR");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn lines(text: &str) -> Vec<String> {
+        let mut out = String::new();
+        push_line(&mut out, text);
+        assert!(out.ends_with("\r\n"));
+        out[..out.len() - 2]
+            .split("\r\n")
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn escapes_text_values() {
+        let cases = [
+            ("plain text", "plain text"),
+            ("a, b; c", "a\\, b\\; c"),
+            ("C:\\temp", "C:\\\\temp"),
+            ("one\ntwo", "one\\ntwo"),
+            ("one\r\ntwo", "one\\ntwo"),
+            ("one\rtwo", "one\\ntwo"),
+            ("\\,", "\\\\\\,"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(escape(text), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn folds_long_lines() {
+        assert_eq!(lines(&"a".repeat(LINE_LIMIT)), ["a".repeat(LINE_LIMIT)]);
+
+        let folded = lines(&"a".repeat(200));
+        assert_eq!(folded.len(), 3);
+        assert_eq!(folded[0].len(), LINE_LIMIT);
+        assert_eq!(folded[1], format!(" {}", "a".repeat(LINE_LIMIT - 1)));
+        assert_eq!(
+            folded[2],
+            format!(" {}", "a".repeat(200 - 2 * LINE_LIMIT + 1))
+        );
+    }
+
+    #[test]
+    fn folds_without_splitting_characters() {
+        // "é" is two octets, so the first line cannot end on the 75th.
+        let text = format!("SUMMARY:{}", "é".repeat(60));
+        let folded = lines(&text);
+        assert!(folded.iter().all(|line| line.len() <= LINE_LIMIT));
+        assert_eq!(folded[0].len(), LINE_LIMIT - 1);
+        assert_eq!(folded.concat().replace(" é", "é"), text);
+
+        // Four-octet characters.
+        let text = "🎉".repeat(40);
+        let folded = lines(&text);
+        assert!(folded.iter().all(|line| line.len() <= LINE_LIMIT));
+        assert_eq!(folded[0].len(), 72);
+        assert_eq!(
+            folded
+                .iter()
+                .map(|line| line.trim_start_matches(' '))
+                .collect::<String>(),
+            text
+        );
+    }
+
+    #[test]
+    fn renders_events_with_their_sequence() {
+        let mut calendar = Calendar::new("Deadlines, Q1");
+        calendar.push(Event {
+            uid: "task-1@project-service".to_string(),
+            summary: "Ship; then rest".to_string(),
+            description: None,
+            time: EventTime::At(Utc.with_ymd_and_hms(2024, 3, 1, 17, 0, 0).unwrap()),
+            last_modified: Utc.with_ymd_and_hms(2024, 2, 1, 9, 30, 0).unwrap(),
+            sequence: 3,
+        });
+        let ics = calendar.render();
+
+        assert!(ics.contains("X-WR-CALNAME:Deadlines\\, Q1\r\n"));
+        assert!(ics.contains(
+            "BEGIN:VEVENT\r\nUID:task-1@project-service\r\nDTSTAMP:20240201T093000Z\r\n\
+             LAST-MODIFIED:20240201T093000Z\r\nSEQUENCE:3\r\nDTSTART:20240301T170000Z\r\n\
+             SUMMARY:Ship\\; then rest\r\nTRANSP:TRANSPARENT\r\nEND:VEVENT\r\n"
+        ));
+        assert!(!ics.contains("DESCRIPTION"));
+    }
+}
@@ -0,0 +1,566 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde_json::{Map, Value};
+use shared::{errors::AppResult, models::Task};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::{
+    access,
+    activity::{self, Entity},
+    custom_fields,
+    handlers::{
+        custom_field, label,
+        task::{
+            validate_estimate, validate_priority, validate_status, validate_title, TASK_COLUMNS,
+        },
+    },
+    models::CustomField,
+};
+
+/// A task read from a file or another tool, with every value still as text.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TaskDraft {
+    pub title: String,
+    pub description: Option<String>,
+    pub status: Option<String>,
+    pub priority: Option<String>,
+    pub assignee_email: Option<String>,
+    pub deadline: Option<String>,
+    pub labels: Vec<String>,
+    pub estimate: Option<String>,
+    /// Values keyed by custom field id or name.
+    pub custom_fields: Vec<(String, String)>,
+}
+
+/// A draft that passed validation, ready to be inserted.
+#[derive(Debug, Clone)]
+pub struct ValidTask {
+    pub title: String,
+    pub description: Option<String>,
+    pub status: String,
+    pub priority: String,
+    pub assignee_id: Option<Uuid>,
+    pub deadline: Option<DateTime<Utc>>,
+    pub labels: Vec<String>,
+    pub original_estimate_minutes: Option<i32>,
+    pub custom_fields: Value,
+}
+
+/// What an import adds to a project.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImportSummary {
+    pub tasks_created: usize,
+    pub labels_created: usize,
+}
+
+fn blank(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// Normalizes spreadsheet spellings such as `In Progress` to `in_progress`.
+fn enum_value(raw: &str) -> String {
+    raw.trim().to_lowercase().replace([' ', '-'], "_")
+}
+
+fn split_list(raw: &str) -> Vec<String> {
+    let mut items: Vec<String> = Vec::new();
+    for item in raw
+        .split([',', ';'])
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+    {
+        if !items.iter().any(|i| i.eq_ignore_ascii_case(item)) {
+            items.push(item.to_string());
+        }
+    }
+    items
+}
+
+/// Accepts RFC 3339 timestamps, `YYYY-MM-DD HH:MM` (UTC) and plain dates
+/// (midnight UTC).
+pub fn parse_deadline(raw: &str) -> Option<DateTime<Utc>> {
+    let raw = raw.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(raw) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    if let Ok(dt) = NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M") {
+        return Some(dt.and_utc());
+    }
+    NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+        .ok()
+        .map(|d| d.and_time(chrono::NaiveTime::MIN).and_utc())
+}
+
+/// Accepts whole minutes (`90`) or hours and minutes (`1.5h`, `2h30m`, `45m`).
+pub fn parse_estimate(raw: &str) -> Option<i32> {
+    let raw = raw.trim().to_lowercase().replace(' ', "");
+    if let Ok(minutes) = raw.parse::<i32>() {
+        return Some(minutes);
+    }
+
+    let (hours, rest) = match raw.split_once('h') {
+        Some((hours, rest)) => (hours.parse::<f64>().ok()?, rest),
+        None => (0.0, raw.as_str()),
+    };
+    let minutes = match rest.strip_suffix('m') {
+        Some(minutes) => minutes.parse::<f64>().ok()?,
+        None if rest.is_empty() => 0.0,
+        None => return None,
+    };
+
+    let total = (hours * 60.0 + minutes).round();
+    (total.is_finite() && total >= 0.0 && total <= f64::from(i32::MAX)).then_some(total as i32)
+}
+
+/// Project data drafts are validated against.
+pub struct ImportContext {
+    fields: Vec<CustomField>,
+    members: HashMap<String, Uuid>,
+}
+
+impl ImportContext {
+    pub async fn load(db: &PgPool, project_id: Uuid) -> AppResult<Self> {
+        let fields = custom_field::project_fields(db, project_id).await?;
+        let members = access::project_member_emails(db, project_id).await?;
+        Ok(Self::new(fields, members))
+    }
+
+    /// Context for a project that is not committed yet.
+    pub fn new(fields: Vec<CustomField>, members: Vec<(Uuid, String)>) -> Self {
+        let members = members
+            .into_iter()
+            .map(|(id, email)| (email.to_lowercase(), id))
+            .collect();
+        Self { fields, members }
+    }
+
+    pub fn fields(&self) -> &[CustomField] {
+        &self.fields
+    }
+
+    fn member(&self, email: &str) -> Option<Uuid> {
+        self.members.get(&email.trim().to_lowercase()).copied()
+    }
+
+    /// Converts a text value to the JSON shape its field expects. `user`
+    /// fields accept a member's email as well as an id.
+    fn field_value(&self, field: &CustomField, raw: &str) -> Result<Value, String> {
+        let invalid = || format!("Invalid value '{}' for custom field '{}'", raw, field.name);
+        Ok(match field.field_type.as_str() {
+            "number" => raw
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(Value::Number)
+                .ok_or_else(invalid)?,
+            "checkbox" => match raw.to_lowercase().as_str() {
+                "true" | "yes" | "y" | "1" | "x" => Value::Bool(true),
+                "false" | "no" | "n" | "0" => Value::Bool(false),
+                _ => return Err(invalid()),
+            },
+            "multi_select" => {
+                Value::Array(split_list(raw).into_iter().map(Value::String).collect())
+            }
+            "user" => match self.member(raw) {
+                Some(id) => Value::String(id.to_string()),
+                None => Value::String(raw.to_string()),
+            },
+            _ => Value::String(raw.to_string()),
+        })
+    }
+
+    /// Checks a draft against the rules of task creation, collecting every
+    /// problem rather than stopping at the first.
+    pub fn validate(&self, draft: &TaskDraft) -> Result<ValidTask, Vec<String>> {
+        let mut errors = Vec::new();
+
+        let title = validate_title(&draft.title).map_err(|e| errors.push(e.to_string()));
+
+        let status = blank(&draft.status)
+            .map(enum_value)
+            .unwrap_or_else(|| "todo".to_string());
+        if let Err(e) = validate_status(&status) {
+            errors.push(e.to_string());
+        }
+        let priority = blank(&draft.priority)
+            .map(enum_value)
+            .unwrap_or_else(|| "medium".to_string());
+        if let Err(e) = validate_priority(&priority) {
+            errors.push(e.to_string());
+        }
+
+        let assignee_id = blank(&draft.assignee_email).and_then(|email| {
+            let id = self.member(email);
+            if id.is_none() {
+                errors.push(format!(
+                    "Assignee '{}' is not a member of the project",
+                    email
+                ));
+            }
+            id
+        });
+
+        let deadline = blank(&draft.deadline).and_then(|raw| {
+            let deadline = parse_deadline(raw);
+            if deadline.is_none() {
+                errors.push(format!(
+                    "Invalid deadline '{}', expected YYYY-MM-DD or an RFC 3339 timestamp",
+                    raw
+                ));
+            }
+            deadline
+        });
+
+        let original_estimate_minutes = blank(&draft.estimate).and_then(|raw| {
+            let minutes = parse_estimate(raw);
+            match minutes {
+                None => errors.push(format!(
+                    "Invalid estimate '{}', expected minutes or a duration like 1h30m",
+                    raw
+                )),
+                Some(_) => {
+                    if let Err(e) = validate_estimate(minutes) {
+                        errors.push(e.to_string());
+                    }
+                }
+            }
+            minutes
+        });
+
+        let mut labels = Vec::new();
+        for name in draft.labels.iter().flat_map(|l| split_list(l)) {
+            match label::validate_name(&name) {
+                Ok(name) => labels.push(name),
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+
+        let mut input = Map::new();
+        for (key, raw) in &draft.custom_fields {
+            let raw = raw.trim();
+            if raw.is_empty() {
+                continue;
+            }
+            match custom_fields::find_field(&self.fields, key) {
+                Some(field) => match self.field_value(field, raw) {
+                    Ok(value) => {
+                        input.insert(field.id.to_string(), value);
+                    }
+                    Err(e) => errors.push(e),
+                },
+                None => errors.push(format!("Unknown custom field '{}'", key)),
+            }
+        }
+        let members: Vec<Uuid> = self.members.values().copied().collect();
+        let custom_fields = custom_fields::apply_values(
+            &self.fields,
+            &Value::Object(Map::new()),
+            &input,
+            &members,
+            true,
+        )
+        .map_err(|e| errors.push(e.to_string()));
+
+        match (title, custom_fields) {
+            (Ok(title), Ok(custom_fields)) if errors.is_empty() => Ok(ValidTask {
+                title,
+                description: blank(&draft.description).map(str::to_string),
+                status,
+                priority,
+                assignee_id,
+                deadline,
+                labels,
+                original_estimate_minutes,
+                custom_fields,
+            }),
+            _ => Err(errors),
+        }
+    }
+}
+
+/// Inserts validated tasks, creating labels that do not exist yet.
+pub async fn insert_tasks(
+    tx: &mut Transaction<'_, Postgres>,
+    project_id: Uuid,
+    tasks: &[ValidTask],
+    actor_id: Option<Uuid>,
+) -> AppResult<(ImportSummary, Vec<Task>)> {
+    let mut names: Vec<String> = Vec::new();
+    for name in tasks.iter().flat_map(|t| &t.labels) {
+        if !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+            names.push(name.clone());
+        }
+    }
+
+    let mut labels_created = 0;
+    for name in &names {
+        labels_created += sqlx::query(
+            "INSERT INTO labels (project_id, name, color) VALUES ($1, $2, $3)
+             ON CONFLICT (project_id, LOWER(name)) DO NOTHING",
+        )
+        .bind(project_id)
+        .bind(name)
+        .bind(label::DEFAULT_COLOR)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected() as usize;
+    }
+
+    let label_ids: HashMap<String, Uuid> = sqlx::query_as::<_, (String, Uuid)>(
+        "SELECT LOWER(name), id FROM labels WHERE project_id = $1",
+    )
+    .bind(project_id)
+    .fetch_all(&mut **tx)
+    .await?
+    .into_iter()
+    .collect();
+
+    let mut created = Vec::with_capacity(tasks.len());
+    for t in tasks {
+        let task = sqlx::query_as::<_, Task>(&format!(
+            "INSERT INTO tasks AS t (project_id, title, description, status, priority, assignee_id,
+                                     deadline, custom_fields, original_estimate_minutes,
+                                     remaining_estimate_minutes)
+             VALUES ($1, $2, $3, $4::task_status, $5::task_priority, $6, $7, $8, $9, $9)
+             RETURNING {}",
+            TASK_COLUMNS
+        ))
+        .bind(project_id)
+        .bind(&t.title)
+        .bind(&t.description)
+        .bind(&t.status)
+        .bind(&t.priority)
+        .bind(t.assignee_id)
+        .bind(t.deadline)
+        .bind(&t.custom_fields)
+        .bind(t.original_estimate_minutes)
+        .fetch_one(&mut **tx)
+        .await?;
+
+        let ids: Vec<Uuid> = t
+            .labels
+            .iter()
+            .filter_map(|name| label_ids.get(&name.to_lowercase()).copied())
+            .collect();
+        if !ids.is_empty() {
+            label::set_task_labels(tx, project_id, task.id, &ids).await?;
+        }
+
+        activity::record_created(tx, project_id, Entity::Task, task.id, actor_id, &task).await?;
+        created.push(task);
+    }
+
+    Ok((
+        ImportSummary {
+            tasks_created: created.len(),
+            labels_created,
+        },
+        created,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    fn field(name: &str, field_type: &str, options: &[&str], required: bool) -> CustomField {
+        CustomField {
+            id: Uuid::new_v4(),
+            project_id: Uuid::nil(),
+            name: name.to_string(),
+            field_type: field_type.to_string(),
+            options: json!(options),
+            required,
+            position: 0,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn parses_deadlines() {
+        let at = |h, m| Some(Utc.with_ymd_and_hms(2024, 5, 1, h, m, 0).unwrap());
+        let cases = [
+            ("2024-05-01T12:00:00Z", at(12, 0)),
+            ("2024-05-01T14:00:00+02:00", at(12, 0)),
+            ("2024-05-01 09:30", at(9, 30)),
+            (" 2024-05-01 ", at(0, 0)),
+            ("05/01/2024", None),
+            ("2024-13-01", None),
+            ("2024-05-01 25:00", None),
+            ("tomorrow", None),
+            ("", None),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(parse_deadline(raw), expected, "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn parses_estimates() {
+        let cases = [
+            ("90", Some(90)),
+            ("0", Some(0)),
+            ("-30", Some(-30)),
+            ("45m", Some(45)),
+            ("1h", Some(60)),
+            ("1.5h", Some(90)),
+            ("2h30m", Some(150)),
+            ("2 H 30 M", Some(150)),
+            ("0.5m", Some(1)),
+            ("35791394h", Some(2_147_483_640)),
+            ("35791395h", None),
+            ("99999999999", None),
+            ("-1h", None),
+            ("-5m", None),
+            ("1h2", None),
+            ("1m30h", None),
+            ("h", None),
+            ("m", None),
+            ("nanh", None),
+            ("infh", None),
+            ("lots", None),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(parse_estimate(raw), expected, "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn normalizes_enum_values() {
+        assert_eq!(enum_value("In Progress"), "in_progress");
+        assert_eq!(enum_value(" in-progress "), "in_progress");
+        assert_eq!(enum_value("DONE"), "done");
+    }
+
+    #[test]
+    fn splits_lists_without_duplicates() {
+        assert_eq!(split_list("bug, ui;backend"), ["bug", "ui", "backend"]);
+        assert_eq!(split_list("Bug, bug; BUG"), ["Bug"]);
+        assert!(split_list(" , ;").is_empty());
+        assert!(split_list("").is_empty());
+    }
+
+    #[test]
+    fn validates_a_draft() {
+        let ann = Uuid::new_v4();
+        let points = field("Points", "number", &[], false);
+        let kind = field("Kind", "single_select", &["Bug", "Feature"], false);
+        let context = ImportContext::new(
+            vec![points.clone(), kind.clone()],
+            vec![(ann, "Ann@Example.com".to_string())],
+        );
+
+        let task = context
+            .validate(&TaskDraft {
+                title: " Fix login ".to_string(),
+                description: Some("  ".to_string()),
+                status: Some("In Progress".to_string()),
+                priority: Some(String::new()),
+                assignee_email: Some("ann@example.com".to_string()),
+                deadline: Some("2024-05-01".to_string()),
+                labels: vec!["bug, Bug; ui".to_string()],
+                estimate: Some("1h30m".to_string()),
+                custom_fields: vec![
+                    ("Points".to_string(), "3".to_string()),
+                    ("kind".to_string(), "Bug".to_string()),
+                    ("Points".to_string(), " ".to_string()),
+                ],
+            })
+            .unwrap();
+        assert_eq!(task.title, "Fix login");
+        assert_eq!(task.description, None);
+        assert_eq!(task.status, "in_progress");
+        assert_eq!(task.priority, "medium");
+        assert_eq!(task.assignee_id, Some(ann));
+        assert_eq!(task.deadline, parse_deadline("2024-05-01"));
+        assert_eq!(task.labels, ["bug", "ui"]);
+        assert_eq!(task.original_estimate_minutes, Some(90));
+        assert_eq!(task.custom_fields[points.id.to_string()], json!(3.0));
+        assert_eq!(task.custom_fields[kind.id.to_string()], json!("Bug"));
+
+        let minimal = context
+            .validate(&TaskDraft {
+                title: "Minimal".to_string(),
+                ..TaskDraft::default()
+            })
+            .unwrap();
+        assert_eq!(
+            (minimal.status.as_str(), minimal.priority.as_str()),
+            ("todo", "medium")
+        );
+        assert_eq!(minimal.assignee_id, None);
+    }
+
+    #[test]
+    fn collects_every_error_in_a_draft() {
+        let context = ImportContext::new(vec![field("Points", "number", &[], false)], Vec::new());
+
+        let errors = context
+            .validate(&TaskDraft {
+                title: "  ".to_string(),
+                description: None,
+                status: Some("someday".to_string()),
+                priority: Some("urgent".to_string()),
+                assignee_email: Some("bob@example.com".to_string()),
+                deadline: Some("soon".to_string()),
+                labels: vec!["x".repeat(51)],
+                estimate: Some("-30".to_string()),
+                custom_fields: vec![
+                    ("Points".to_string(), "lots".to_string()),
+                    ("Nope".to_string(), "1".to_string()),
+                ],
+            })
+            .unwrap_err();
+
+        let expected = [
+            "title must be between 1 and 255",
+            "Invalid status 'someday'",
+            "Invalid priority 'urgent'",
+            "Assignee 'bob@example.com' is not a member",
+            "Invalid deadline 'soon'",
+            "Label name must be between 1 and 50",
+            "Estimates cannot be negative",
+            "Invalid value 'lots' for custom field 'Points'",
+            "Unknown custom field 'Nope'",
+        ];
+        assert_eq!(errors.len(), expected.len(), "{:?}", errors);
+        for text in expected {
+            assert!(
+                errors.iter().any(|e| e.contains(text)),
+                "{}: {:?}",
+                text,
+                errors
+            );
+        }
+
+        let errors = context
+            .validate(&TaskDraft {
+                title: "Estimate".to_string(),
+                estimate: Some("soon".to_string()),
+                ..TaskDraft::default()
+            })
+            .unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("Invalid estimate 'soon'"));
+    }
+
+    #[test]
+    fn reports_missing_required_fields() {
+        let context = ImportContext::new(
+            vec![field("Kind", "single_select", &["Bug"], true)],
+            Vec::new(),
+        );
+        let errors = context
+            .validate(&TaskDraft {
+                title: "Needs a kind".to_string(),
+                ..TaskDraft::default()
+            })
+            .unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("'Kind' is required"), "{:?}", errors);
+    }
+}
@@ -0,0 +1,407 @@
+//! Imports of Trello boards and Jira exports into new projects. Export files
+//! are parsed when they are uploaded; the import itself runs as a background
+//! job whose progress is kept in `import_jobs`.
+
+pub mod jira;
+pub mod trello;
+
+use chrono::{DateTime, Utc};
+use shared::errors::{AppError, AppResult};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::{
+    handlers::project::{insert_project, validate_name},
+    import::{self, ImportContext, TaskDraft},
+    models::ImportJob,
+    quota::{Quota, Resource},
+};
+
+pub(crate) const JOB_COLUMNS: &str = "j.id, j.source, j.status, j.requested_by, j.project_id,
+    j.project_name, j.total_items, j.processed_items,
+    CASE WHEN j.status = 'completed' THEN 100
+         WHEN j.total_items = 0 THEN 0
+         ELSE j.processed_items * 100 / j.total_items END AS progress_percent,
+    j.warnings, j.error, j.created_at, j.started_at, j.finished_at";
+
+/// Tasks inserted between two progress updates.
+const BATCH_SIZE: usize = 50;
+
+/// Warnings kept on a job; later ones are dropped.
+const MAX_WARNINGS: usize = 200;
+
+/// A board or issue list read from an export, before anyone in it is matched
+/// against the users of this service.
+#[derive(Debug, Default, PartialEq)]
+pub struct SourceProject {
+    pub name: String,
+    pub description: Option<String>,
+    /// Emails of the people on the board; those who already share a project
+    /// with the uploader become editors of the new project.
+    pub member_emails: Vec<String>,
+    pub tasks: Vec<SourceTask>,
+    /// Things left out while parsing, such as archived cards.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct SourceTask {
+    /// How the card or issue is named in warnings.
+    pub key: String,
+    pub draft: TaskDraft,
+    pub comments: Vec<SourceComment>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SourceComment {
+    pub author_email: Option<String>,
+    /// Shown on the comment when the author is not matched to a user.
+    pub author_name: String,
+    pub body: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// Emails for the people of an export, keyed by Trello id, username or full
+/// name, or by Jira account id or display name. Neither tool exports emails
+/// reliably, so the uploader supplies them.
+#[derive(Debug, Default)]
+pub struct UserMap(HashMap<String, String>);
+
+impl UserMap {
+    pub fn new(entries: HashMap<String, String>) -> Self {
+        Self(
+            entries
+                .into_iter()
+                .map(|(key, email)| (key.trim().to_lowercase(), email.trim().to_string()))
+                .collect(),
+        )
+    }
+
+    /// The first identifier that is an email or is mapped to one.
+    pub fn resolve<'a>(&self, ids: impl IntoIterator<Item = &'a str>) -> Option<String> {
+        ids.into_iter()
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .find_map(|id| {
+                if id.contains('@') {
+                    Some(id.to_string())
+                } else {
+                    self.0.get(&id.to_lowercase()).cloned()
+                }
+            })
+    }
+}
+
+/// Maps a list or workflow status name onto a task status.
+pub fn status_from_name(name: &str) -> &'static str {
+    let name = name.to_lowercase();
+    let has = |words: &[&str]| words.iter().any(|w| name.contains(w));
+    if has(&[
+        "done", "complete", "closed", "resolved", "finished", "shipped",
+    ]) {
+        "done"
+    } else if has(&[
+        "progress", "doing", "review", "testing", "active", "started",
+    ]) {
+        "in_progress"
+    } else {
+        "todo"
+    }
+}
+
+/// Maps a Jira priority, or a Trello label such as `High priority`, onto a
+/// task priority.
+pub fn priority_from_name(name: &str) -> Option<&'static str> {
+    let name = name.trim().to_lowercase();
+    match name.trim_end_matches("priority").trim() {
+        "highest" | "high" | "blocker" | "critical" | "urgent" => Some("high"),
+        "medium" | "normal" | "major" => Some("medium"),
+        "low" | "lowest" | "minor" | "trivial" => Some("low"),
+        _ => None,
+    }
+}
+
+fn warn(warnings: &mut Vec<String>, message: String) {
+    if warnings.len() < MAX_WARNINGS {
+        warnings.push(message);
+    }
+}
+
+pub async fn load_job(db: &PgPool, job_id: Uuid) -> AppResult<ImportJob> {
+    sqlx::query_as::<_, ImportJob>(&format!(
+        "SELECT {} FROM import_jobs j WHERE j.id = $1",
+        JOB_COLUMNS
+    ))
+    .bind(job_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Import job not found".to_string()))
+}
+
+/// Records a job for an uploaded export and starts it in the background.
+pub async fn start_job(
+    db: &PgPool,
+    source: &str,
+    requested_by: Uuid,
+    quota: Quota,
+    mut project: SourceProject,
+) -> AppResult<ImportJob> {
+    project.name = validate_name(&project.name)?;
+    project.warnings.truncate(MAX_WARNINGS);
+
+    let job = sqlx::query_as::<_, ImportJob>(&format!(
+        "INSERT INTO import_jobs AS j (source, requested_by, project_name, total_items, warnings)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING {}",
+        JOB_COLUMNS
+    ))
+    .bind(source)
+    .bind(requested_by)
+    .bind(&project.name)
+    .bind(project.tasks.len() as i32)
+    .bind(serde_json::json!(project.warnings))
+    .fetch_one(db)
+    .await?;
+
+    let db = db.clone();
+    let job_id = job.id;
+    tokio::spawn(async move { run(db, job_id, requested_by, quota, project).await });
+
+    Ok(job)
+}
+
+async fn run(db: PgPool, job_id: Uuid, owner_id: Uuid, quota: Quota, project: SourceProject) {
+    if let Err(e) =
+        sqlx::query("UPDATE import_jobs SET status = 'running', started_at = NOW() WHERE id = $1")
+            .bind(job_id)
+            .execute(&db)
+            .await
+    {
+        tracing::warn!("Failed to start import job {}: {}", job_id, e);
+        return;
+    }
+
+    let mut warnings = project.warnings.clone();
+    let outcome = import_project(&db, job_id, owner_id, &quota, &project, &mut warnings).await;
+
+    let update = match &outcome {
+        Ok(project_id) => sqlx::query(
+            "UPDATE import_jobs
+             SET status = 'completed', project_id = $2, processed_items = total_items,
+                 warnings = $3, finished_at = NOW()
+             WHERE id = $1",
+        )
+        .bind(job_id)
+        .bind(project_id)
+        .bind(serde_json::json!(warnings)),
+        Err(e) => sqlx::query(
+            "UPDATE import_jobs
+             SET status = 'failed', error = $2, warnings = $3, finished_at = NOW()
+             WHERE id = $1",
+        )
+        .bind(job_id)
+        .bind(e.to_string())
+        .bind(serde_json::json!(warnings)),
+    };
+    if let Err(e) = update.execute(&db).await {
+        tracing::warn!("Failed to finish import job {}: {}", job_id, e);
+    }
+
+    match outcome {
+        Ok(project_id) => tracing::info!("Import job {} created project {}", job_id, project_id),
+        Err(e) => tracing::warn!("Import job {} failed: {}", job_id, e),
+    }
+}
+
+/// Creates the project, its members and its tasks in one transaction, so a
+/// failed import leaves nothing behind, including one that would go over the
+/// owner's plan limits. Progress is written outside the transaction so it can
+/// be followed while the job runs.
+///
+/// People in the export are only matched to users the uploader already
+/// shares a project with; an export cannot pull anyone else into a project,
+/// and the warnings read the same whether or not an email has an account.
+async fn import_project(
+    db: &PgPool,
+    job_id: Uuid,
+    owner_id: Uuid,
+    quota: &Quota,
+    project: &SourceProject,
+    warnings: &mut Vec<String>,
+) -> AppResult<Uuid> {
+    let mut emails: Vec<String> = project
+        .member_emails
+        .iter()
+        .chain(
+            project
+                .tasks
+                .iter()
+                .filter_map(|t| t.draft.assignee_email.as_ref()),
+        )
+        .chain(
+            project
+                .tasks
+                .iter()
+                .flat_map(|t| &t.comments)
+                .filter_map(|c| c.author_email.as_ref()),
+        )
+        .map(|email| email.trim().to_lowercase())
+        .collect();
+    emails.sort();
+    emails.dedup();
+
+    let users = sqlx::query_as::<_, (Uuid, String)>(
+        "WITH shared AS (
+             SELECT id FROM projects WHERE owner_id = $2 AND deleted_at IS NULL
+             UNION
+             SELECT pm.project_id
+             FROM project_members pm
+             JOIN projects p ON p.id = pm.project_id
+             WHERE pm.user_id = $2 AND p.deleted_at IS NULL
+         )
+         SELECT u.id, u.email
+         FROM users u
+         WHERE u.id = $2
+            OR (LOWER(u.email) = ANY($1)
+                AND u.id IN (
+                    SELECT owner_id FROM projects WHERE id IN (SELECT id FROM shared)
+                    UNION
+                    SELECT user_id FROM project_members WHERE project_id IN (SELECT id FROM shared)
+                ))",
+    )
+    .bind(&emails)
+    .bind(owner_id)
+    .fetch_all(db)
+    .await?;
+    let user_ids: HashMap<String, Uuid> = users
+        .iter()
+        .map(|(id, email)| (email.to_lowercase(), *id))
+        .collect();
+
+    let mut tx = db.begin().await?;
+    quota.check(&mut tx, Resource::Projects, 1).await?;
+    let created = insert_project(
+        &mut tx,
+        owner_id,
+        &project.name,
+        project.description.as_deref(),
+    )
+    .await?;
+
+    for (user_id, _) in users.iter().filter(|(id, _)| *id != owner_id) {
+        sqlx::query(
+            "INSERT INTO project_members (project_id, user_id, role)
+             VALUES ($1, $2, 'editor')
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(created.id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let context = ImportContext::new(Vec::new(), users);
+    let mut processed = 0;
+
+    for batch in project.tasks.chunks(BATCH_SIZE) {
+        let mut valid = Vec::with_capacity(batch.len());
+        let mut comments = Vec::with_capacity(batch.len());
+
+        for source in batch {
+            let mut draft = source.draft.clone();
+            if let Some(email) = draft.assignee_email.take() {
+                if user_ids.contains_key(&email.trim().to_lowercase()) {
+                    draft.assignee_email = Some(email);
+                } else {
+                    warn(
+                        warnings,
+                        format!(
+                            "{}: {} is not in any of your projects, left unassigned",
+                            source.key, email
+                        ),
+                    );
+                }
+            }
+
+            match context.validate(&draft) {
+                Ok(task) => {
+                    valid.push(task);
+                    comments.push(&source.comments);
+                }
+                Err(errors) => warn(
+                    warnings,
+                    format!("{}: not imported: {}", source.key, errors.join("; ")),
+                ),
+            }
+        }
+
+        quota
+            .check(&mut tx, Resource::Tasks, valid.len() as i64)
+            .await?;
+        let (_, tasks) = import::insert_tasks(&mut tx, created.id, &valid, Some(owner_id)).await?;
+        for (task, comments) in tasks.iter().zip(comments) {
+            for comment in comments {
+                insert_comment(&mut tx, task.id, owner_id, &user_ids, comment).await?;
+            }
+        }
+
+        processed += batch.len();
+        sqlx::query("UPDATE import_jobs SET processed_items = $2 WHERE id = $1")
+            .bind(job_id)
+            .bind(processed as i32)
+            .execute(db)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(created.id)
+}
+
+/// Comments by people who were not matched are posted as the importing user,
+/// with the original author named above the body.
+async fn insert_comment(
+    tx: &mut Transaction<'_, Postgres>,
+    task_id: Uuid,
+    owner_id: Uuid,
+    user_ids: &HashMap<String, Uuid>,
+    comment: &SourceComment,
+) -> AppResult<()> {
+    let author = comment
+        .author_email
+        .as_ref()
+        .and_then(|email| user_ids.get(&email.trim().to_lowercase()));
+    let body = match author {
+        Some(_) => comment.body.clone(),
+        None => format!(
+            "_Originally posted by {}_\n\n{}",
+            comment.author_name, comment.body
+        ),
+    };
+
+    sqlx::query(
+        "INSERT INTO task_comments (task_id, author_id, body, created_at, updated_at)
+         VALUES ($1, $2, $3, COALESCE($4, NOW()), COALESCE($4, NOW()))",
+    )
+    .bind(task_id)
+    .bind(author.copied().unwrap_or(owner_id))
+    .bind(body)
+    .bind(comment.created_at)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Jobs cut off by a restart cannot be resumed, since their upload is only
+/// held in memory; they are marked failed so they can be started again.
+pub async fn fail_interrupted(db: &PgPool) -> AppResult<u64> {
+    Ok(sqlx::query(
+        "UPDATE import_jobs
+         SET status = 'failed', error = 'Interrupted by a service restart', finished_at = NOW()
+         WHERE status IN ('pending', 'running')",
+    )
+    .execute(db)
+    .await?
+    .rows_affected())
+}
@@ -0,0 +1,502 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use roxmltree::{Document, Node};
+use shared::errors::{AppError, AppResult};
+
+use super::{
+    priority_from_name, status_from_name, SourceComment, SourceProject, SourceTask, UserMap,
+};
+use crate::import::{self, TaskDraft};
+
+const DEFAULT_NAME: &str = "Jira import";
+
+/// Reads a Jira issue export, either CSV ("Export Excel CSV (all fields)")
+/// or XML ("Export XML"). The format is detected from the content.
+pub fn parse(data: &[u8], users: &UserMap) -> AppResult<SourceProject> {
+    let start = data
+        .strip_prefix(b"\xEF\xBB\xBF")
+        .unwrap_or(data)
+        .iter()
+        .find(|b| !b.is_ascii_whitespace());
+    if start == Some(&b'<') {
+        parse_xml(data, users)
+    } else {
+        parse_csv(data, users)
+    }
+}
+
+/// Jira writes dates as `12/Mar/24 10:15 AM` in CSV and as RFC 2822 in XML.
+fn parse_date(raw: &str) -> Option<DateTime<Utc>> {
+    let raw = raw.trim();
+    DateTime::parse_from_rfc2822(raw)
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(raw, "%d/%b/%y %I:%M %p")
+                .ok()
+                .map(|dt| dt.and_utc())
+        })
+        .or_else(|| {
+            NaiveDate::parse_from_str(raw, "%d/%b/%y")
+                .ok()
+                .map(|d| d.and_time(NaiveTime::MIN).and_utc())
+        })
+        .or_else(|| import::parse_deadline(raw))
+}
+
+/// Estimates are exported in seconds.
+fn estimate_minutes(seconds: &str) -> Option<String> {
+    seconds
+        .trim()
+        .parse::<i64>()
+        .ok()
+        .map(|s| (s / 60).to_string())
+}
+
+fn task_key(key: Option<&str>, summary: &str) -> String {
+    match key {
+        Some(key) => format!("Issue {}", key),
+        None => format!("Issue '{}'", summary),
+    }
+}
+
+fn parse_csv(data: &[u8], users: &UserMap) -> AppResult<SourceProject> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(data);
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| AppError::BadRequest(format!("Invalid CSV header: {}", e)))?
+        .iter()
+        .map(|h| h.trim_start_matches('\u{feff}').trim().to_lowercase())
+        .collect();
+    if !headers.iter().any(|h| h == "summary") {
+        return Err(AppError::BadRequest(
+            "Not a Jira CSV export: no 'Summary' column".to_string(),
+        ));
+    }
+
+    let mut project = SourceProject::default();
+
+    for record in reader.records() {
+        let record = record.map_err(|e| AppError::BadRequest(format!("Invalid CSV row: {}", e)))?;
+        // Jira repeats a column once per value, e.g. one "Labels" per label.
+        let all = |name: &str| -> Vec<&str> {
+            headers
+                .iter()
+                .zip(record.iter())
+                .filter(|(h, v)| *h == name && !v.trim().is_empty())
+                .map(|(_, v)| v.trim())
+                .collect()
+        };
+        let first = |name: &str| all(name).into_iter().next();
+
+        if project.name.is_empty() {
+            project.name = first("project name").unwrap_or_default().to_string();
+        }
+
+        let summary = first("summary").unwrap_or_default().to_string();
+        let key = task_key(first("issue key"), &summary);
+
+        let assignee = first("assignee");
+        let assignee_email = users.resolve(
+            assignee
+                .into_iter()
+                .chain(first("assignee id"))
+                .chain(first("assignee email")),
+        );
+        if let (Some(name), None) = (assignee, &assignee_email) {
+            project
+                .warnings
+                .push(format!("{}: no email for assignee {}", key, name));
+        }
+
+        let comments = all("comment")
+            .into_iter()
+            .map(|raw| {
+                // "12/Mar/24 10:15 AM;<account id>;<body>"
+                let mut parts = raw.splitn(3, ';');
+                match (
+                    parts.next().and_then(parse_date),
+                    parts.next(),
+                    parts.next(),
+                ) {
+                    (Some(created_at), Some(author), Some(body)) => SourceComment {
+                        author_email: users.resolve([author]),
+                        author_name: author.to_string(),
+                        body: body.to_string(),
+                        created_at: Some(created_at),
+                    },
+                    _ => SourceComment {
+                        author_email: None,
+                        author_name: "a Jira user".to_string(),
+                        body: raw.to_string(),
+                        created_at: None,
+                    },
+                }
+            })
+            .collect();
+
+        project.tasks.push(SourceTask {
+            key,
+            draft: TaskDraft {
+                title: summary,
+                description: first("description").map(str::to_string),
+                status: first("status").map(|s| status_from_name(s).to_string()),
+                priority: first("priority")
+                    .and_then(priority_from_name)
+                    .map(str::to_string),
+                assignee_email,
+                deadline: first("due date")
+                    .and_then(parse_date)
+                    .map(|d| d.to_rfc3339()),
+                labels: all("labels").into_iter().map(str::to_string).collect(),
+                estimate: first("original estimate").and_then(estimate_minutes),
+                ..Default::default()
+            },
+            comments,
+        });
+    }
+
+    if project.name.is_empty() {
+        project.name = DEFAULT_NAME.to_string();
+    }
+    Ok(project)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name)
+        .and_then(|n| n.text())
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+}
+
+/// Jira XML carries descriptions and comments as HTML. Tags are dropped,
+/// keeping line breaks, so the text reads as plain Markdown.
+fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(open) = rest.find('<') {
+        text.push_str(&rest[..open]);
+        let Some(close) = rest[open..].find('>') else {
+            // A stray `<` is kept as text.
+            rest = &rest[open..];
+            break;
+        };
+        let tag = rest[open + 1..open + close]
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        if matches!(tag.as_str(), "br" | "p" | "div" | "li" | "tr") {
+            text.push('\n');
+        }
+        rest = &rest[open + close + 1..];
+    }
+    text.push_str(rest);
+
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&");
+
+    let mut lines: Vec<&str> = Vec::new();
+    for line in text.lines().map(str::trim_end) {
+        if !(line.is_empty() && lines.last().is_some_and(|l| l.is_empty())) {
+            lines.push(line);
+        }
+    }
+    lines.join("\n").trim().to_string()
+}
+
+fn parse_xml(data: &[u8], users: &UserMap) -> AppResult<SourceProject> {
+    let text = std::str::from_utf8(data)
+        .map_err(|_| AppError::BadRequest("Jira XML export must be UTF-8".to_string()))?;
+    let doc = Document::parse(text.trim_start_matches('\u{feff}'))
+        .map_err(|e| AppError::BadRequest(format!("Invalid Jira XML export: {}", e)))?;
+    let channel = doc
+        .descendants()
+        .find(|n| n.has_tag_name("channel"))
+        .ok_or_else(|| {
+            AppError::BadRequest("Not a Jira XML export: no channel element".to_string())
+        })?;
+
+    let mut project = SourceProject::default();
+
+    for item in channel.children().filter(|n| n.has_tag_name("item")) {
+        if project.name.is_empty() {
+            project.name = child_text(item, "project").unwrap_or_default().to_string();
+        }
+
+        let summary = child_text(item, "summary")
+            .or_else(|| child_text(item, "title"))
+            .unwrap_or_default()
+            .to_string();
+        let key = task_key(child_text(item, "key"), &summary);
+
+        let assignee = child(item, "assignee");
+        let assignee_email = assignee.and_then(|a| {
+            users.resolve(
+                a.text()
+                    .into_iter()
+                    .chain(a.attribute("accountid"))
+                    .chain(a.attribute("username")),
+            )
+        });
+        let assignee_name = assignee
+            .and_then(|a| a.text())
+            .map(str::trim)
+            .filter(|name| !name.is_empty() && *name != "Unassigned");
+        if let (Some(name), None) = (assignee_name, &assignee_email) {
+            project
+                .warnings
+                .push(format!("{}: no email for assignee {}", key, name));
+        }
+
+        let labels = child(item, "labels")
+            .into_iter()
+            .flat_map(|l| l.children().filter(|n| n.has_tag_name("label")))
+            .filter_map(|l| l.text())
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect();
+
+        let comments = child(item, "comments")
+            .into_iter()
+            .flat_map(|c| c.children().filter(|n| n.has_tag_name("comment")))
+            .map(|c| {
+                let author = c.attribute("author").unwrap_or_default();
+                SourceComment {
+                    author_email: users.resolve([author]),
+                    author_name: if author.is_empty() {
+                        "a Jira user".to_string()
+                    } else {
+                        author.to_string()
+                    },
+                    body: html_to_text(c.text().unwrap_or_default()),
+                    created_at: c.attribute("created").and_then(parse_date),
+                }
+            })
+            .collect();
+
+        project.tasks.push(SourceTask {
+            key,
+            draft: TaskDraft {
+                title: summary,
+                description: child_text(item, "description").map(html_to_text),
+                status: child_text(item, "status").map(|s| status_from_name(s).to_string()),
+                priority: child_text(item, "priority")
+                    .and_then(priority_from_name)
+                    .map(str::to_string),
+                assignee_email,
+                deadline: child_text(item, "due")
+                    .and_then(parse_date)
+                    .map(|d| d.to_rfc3339()),
+                labels,
+                estimate: child(item, "timeoriginalestimate")
+                    .and_then(|e| e.attribute("seconds"))
+                    .and_then(estimate_minutes),
+                ..Default::default()
+            },
+            comments,
+        });
+    }
+
+    if project.name.is_empty() {
+        project.name = DEFAULT_NAME.to_string();
+    }
+    Ok(project)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const CSV: &[u8] = include_bytes!("fixtures/jira_issues.csv");
+    const XML: &[u8] = include_bytes!("fixtures/jira_issues.xml");
+
+    fn users() -> UserMap {
+        UserMap::new(HashMap::from([(
+            "acc-alice".to_string(),
+            "alice@example.com".to_string(),
+        )]))
+    }
+
+    fn at(raw: &str) -> Option<DateTime<Utc>> {
+        Some(raw.parse().unwrap())
+    }
+
+    fn comment(author: &str, email: Option<&str>, body: &str, created_at: &str) -> SourceComment {
+        SourceComment {
+            author_email: email.map(str::to_string),
+            author_name: author.to_string(),
+            body: body.to_string(),
+            created_at: at(created_at),
+        }
+    }
+
+    /// What both export formats of the fixture issues come to; they differ
+    /// in the description and comments only.
+    fn expected(first_description: &str, first_comments: Vec<SourceComment>) -> SourceProject {
+        SourceProject {
+            name: "Web App".to_string(),
+            description: None,
+            member_emails: Vec::new(),
+            tasks: vec![
+                SourceTask {
+                    key: "Issue PROJ-1".to_string(),
+                    draft: TaskDraft {
+                        title: "Login fails".to_string(),
+                        description: Some(first_description.to_string()),
+                        status: Some("in_progress".to_string()),
+                        priority: Some("high".to_string()),
+                        assignee_email: Some("alice@example.com".to_string()),
+                        deadline: Some("2024-03-15T00:00:00+00:00".to_string()),
+                        labels: vec!["auth".to_string(), "bug".to_string()],
+                        ..Default::default()
+                    },
+                    comments: first_comments,
+                },
+                SourceTask {
+                    key: "Issue PROJ-2".to_string(),
+                    draft: TaskDraft {
+                        title: "Update docs".to_string(),
+                        status: Some("done".to_string()),
+                        priority: Some("low".to_string()),
+                        ..Default::default()
+                    },
+                    comments: Vec::new(),
+                },
+                SourceTask {
+                    key: "Issue 'Cleanup'".to_string(),
+                    draft: TaskDraft {
+                        title: "Cleanup".to_string(),
+                        status: Some("todo".to_string()),
+                        ..Default::default()
+                    },
+                    comments: Vec::new(),
+                },
+            ],
+            warnings: vec!["Issue PROJ-2: no email for assignee Bob".to_string()],
+        }
+    }
+
+    #[test]
+    fn reads_a_csv_export() {
+        let mut expected = expected(
+            "Steps:\n1. Open the login page",
+            vec![
+                comment(
+                    "acc-alice",
+                    Some("alice@example.com"),
+                    "Looks like a cookie issue",
+                    "2024-03-12T10:15:00Z",
+                ),
+                comment(
+                    "acc-carol",
+                    None,
+                    "Cannot reproduce; works here",
+                    "2024-03-13T21:05:00Z",
+                ),
+            ],
+        );
+        expected.tasks[0].draft.estimate = Some("60".to_string());
+        expected.tasks[1].draft.labels = vec!["docs".to_string()];
+        expected.tasks[1].comments = vec![SourceComment {
+            author_email: None,
+            author_name: "a Jira user".to_string(),
+            body: "not a structured comment".to_string(),
+            created_at: None,
+        }];
+        expected.tasks[2].draft.priority = Some("medium".to_string());
+
+        assert_eq!(parse(CSV, &users()).unwrap(), expected);
+
+        // A byte order mark is not part of the first header.
+        let with_bom = [b"\xEF\xBB\xBF".as_slice(), CSV].concat();
+        assert_eq!(parse(&with_bom, &users()).unwrap(), expected);
+    }
+
+    #[test]
+    fn reads_an_xml_export() {
+        let mut expected = expected(
+            "Steps:\n1. Open the login page\n\nCookies & cache cleared",
+            vec![
+                comment(
+                    "acc-alice",
+                    Some("alice@example.com"),
+                    "Looks like a cookie issue",
+                    "2024-03-12T10:15:00Z",
+                ),
+                comment("acc-carol", None, "Cannot reproduce", "2024-03-13T21:05:00Z"),
+            ],
+        );
+        expected.tasks[0].draft.estimate = Some("90".to_string());
+
+        assert_eq!(parse(XML, &users()).unwrap(), expected);
+    }
+
+    #[test]
+    fn names_projects_without_one() {
+        let project = parse(b"Summary\nFirst\n", &UserMap::default()).unwrap();
+        assert_eq!(project.name, DEFAULT_NAME);
+        assert_eq!(project.tasks[0].key, "Issue 'First'");
+
+        let project = parse(b"<rss><channel/></rss>", &UserMap::default()).unwrap();
+        assert_eq!(project.name, DEFAULT_NAME);
+        assert!(project.tasks.is_empty());
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let cases: [(&[u8], &str); 3] = [
+            (b"Title,Status\nA,Done\n", "Not a Jira CSV export: no 'Summary' column"),
+            (b"<rss><item/></rss>", "Not a Jira XML export: no channel element"),
+            (b"  <rss><channel>", "Invalid Jira XML export: "),
+        ];
+        for (data, message) in cases {
+            match parse(data, &UserMap::default()) {
+                Err(AppError::BadRequest(error)) => assert!(error.starts_with(message), "{}", error),
+                other => panic!("{:?} gave {:?}", String::from_utf8_lossy(data), other),
+            }
+        }
+    }
+
+    #[test]
+    fn reads_jira_dates() {
+        let cases = [
+            ("12/Mar/24 10:15 AM", "2024-03-12T10:15:00Z"),
+            ("12/Mar/24 12:05 AM", "2024-03-12T00:05:00Z"),
+            ("15/Mar/24", "2024-03-15T00:00:00Z"),
+            ("Tue, 12 Mar 2024 10:15:00 +0100", "2024-03-12T09:15:00Z"),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(parse_date(raw), at(expected), "{}", raw);
+        }
+        assert_eq!(parse_date("next week"), None);
+    }
+
+    #[test]
+    fn converts_html_to_text() {
+        let cases = [
+            ("plain", "plain"),
+            ("<p>One</p><p>Two</p>", "One\n\nTwo"),
+            ("a<br>b<br/>c<BR />d", "a\nb\nc\nd"),
+            ("<ul><li>x</li><li>y</li></ul>", "x\n\ny"),
+            ("<p>A</p><p></p><p></p><p>B</p>", "A\n\nB"),
+            ("<b>bold</b> &amp; <i>italic</i>", "bold & italic"),
+            ("&lt;script&gt; &quot;q&quot; &#39;s&#39;&nbsp;x", "<script> \"q\" 's' x"),
+            ("&amp;lt;", "&lt;"),
+            ("unclosed <b", "unclosed <b"),
+        ];
+        for (html, text) in cases {
+            assert_eq!(html_to_text(html), text, "{}", html);
+        }
+    }
+}
@@ -0,0 +1,384 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use shared::errors::{AppError, AppResult};
+use std::collections::HashMap;
+
+use super::{
+    priority_from_name, status_from_name, SourceComment, SourceProject, SourceTask, UserMap,
+};
+use crate::import::TaskDraft;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Board {
+    name: String,
+    #[serde(default)]
+    desc: String,
+    #[serde(default)]
+    lists: Vec<List>,
+    #[serde(default)]
+    cards: Vec<Card>,
+    #[serde(default)]
+    labels: Vec<Label>,
+    #[serde(default)]
+    members: Vec<Member>,
+    #[serde(default)]
+    actions: Vec<Action>,
+    #[serde(default)]
+    checklists: Vec<Checklist>,
+}
+
+#[derive(Deserialize)]
+struct List {
+    id: String,
+    name: String,
+    #[serde(default)]
+    closed: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Card {
+    id: String,
+    name: String,
+    #[serde(default)]
+    desc: String,
+    id_list: String,
+    #[serde(default)]
+    id_members: Vec<String>,
+    #[serde(default)]
+    id_labels: Vec<String>,
+    due: Option<String>,
+    #[serde(default)]
+    due_complete: bool,
+    #[serde(default)]
+    closed: bool,
+}
+
+#[derive(Deserialize)]
+struct Label {
+    id: String,
+    #[serde(default)]
+    name: String,
+    color: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Member {
+    id: String,
+    #[serde(default)]
+    username: String,
+    #[serde(default)]
+    full_name: String,
+}
+
+impl Member {
+    fn display_name(&self) -> &str {
+        if self.full_name.is_empty() {
+            &self.username
+        } else {
+            &self.full_name
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Action {
+    #[serde(rename = "type")]
+    kind: String,
+    date: Option<DateTime<Utc>>,
+    #[serde(default)]
+    data: ActionData,
+    member_creator: Option<Member>,
+}
+
+#[derive(Default, Deserialize)]
+struct ActionData {
+    card: Option<CardRef>,
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CardRef {
+    id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Checklist {
+    id_card: String,
+    name: String,
+    #[serde(default)]
+    check_items: Vec<CheckItem>,
+}
+
+#[derive(Deserialize)]
+struct CheckItem {
+    name: String,
+    state: String,
+}
+
+/// Reads a board exported with Trello's "Export as JSON". Cards become tasks
+/// with a status taken from their list; archived cards and lists are left
+/// out. Checklists are appended to the description.
+pub fn parse(data: &[u8], users: &UserMap) -> AppResult<SourceProject> {
+    let board: Board = serde_json::from_slice(data)
+        .map_err(|e| AppError::BadRequest(format!("Not a Trello board export: {}", e)))?;
+
+    let lists: HashMap<&str, &List> = board.lists.iter().map(|l| (l.id.as_str(), l)).collect();
+    let labels: HashMap<&str, &Label> = board.labels.iter().map(|l| (l.id.as_str(), l)).collect();
+    let resolve = |m: &Member| users.resolve([m.id.as_str(), &m.username, &m.full_name]);
+    let members: HashMap<&str, (&Member, Option<String>)> = board
+        .members
+        .iter()
+        .map(|m| (m.id.as_str(), (m, resolve(m))))
+        .collect();
+
+    let mut project = SourceProject {
+        name: board.name.clone(),
+        description: Some(board.desc.trim().to_string()).filter(|d| !d.is_empty()),
+        member_emails: members.values().filter_map(|(_, e)| e.clone()).collect(),
+        ..Default::default()
+    };
+    for (member, email) in members.values() {
+        if email.is_none() {
+            project.warnings.push(format!(
+                "No email for board member {}; their cards are left unassigned",
+                member.display_name()
+            ));
+        }
+    }
+
+    let mut checklists: HashMap<&str, Vec<&Checklist>> = HashMap::new();
+    for checklist in &board.checklists {
+        checklists
+            .entry(checklist.id_card.as_str())
+            .or_default()
+            .push(checklist);
+    }
+
+    let mut comments: HashMap<&str, Vec<SourceComment>> = HashMap::new();
+    for action in board.actions.iter().filter(|a| a.kind == "commentCard") {
+        let (Some(card), Some(text)) = (&action.data.card, &action.data.text) else {
+            continue;
+        };
+        let author = action.member_creator.as_ref();
+        comments
+            .entry(card.id.as_str())
+            .or_default()
+            .push(SourceComment {
+                author_email: author.and_then(resolve),
+                author_name: author
+                    .map_or("a Trello user", |m| m.display_name())
+                    .to_string(),
+                body: text.clone(),
+                created_at: action.date,
+            });
+    }
+
+    let mut archived = 0;
+    for card in &board.cards {
+        let list = lists.get(card.id_list.as_str());
+        if card.closed || list.is_some_and(|l| l.closed) {
+            archived += 1;
+            continue;
+        }
+        let key = format!("Card '{}'", card.name);
+
+        let card_labels: Vec<String> = card
+            .id_labels
+            .iter()
+            .filter_map(|id| labels.get(id.as_str()))
+            .filter_map(|l| {
+                let name = l.name.trim();
+                if name.is_empty() {
+                    l.color.clone()
+                } else {
+                    Some(name.to_string())
+                }
+            })
+            .collect();
+        let priority = card_labels.iter().find_map(|l| priority_from_name(l));
+
+        let assignees: Vec<String> = card
+            .id_members
+            .iter()
+            .filter_map(|id| {
+                members
+                    .get(id.as_str())
+                    .and_then(|(_, email)| email.clone())
+            })
+            .collect();
+        if assignees.len() > 1 {
+            project.warnings.push(format!(
+                "{}: has {} members, only the first is assigned",
+                key,
+                assignees.len()
+            ));
+        }
+
+        let mut description = card.desc.trim().to_string();
+        for checklist in checklists.remove(card.id.as_str()).unwrap_or_default() {
+            description.push_str(&format!("\n\n### {}\n", checklist.name));
+            for item in &checklist.check_items {
+                let mark = if item.state == "complete" { "x" } else { " " };
+                description.push_str(&format!("\n- [{}] {}", mark, item.name));
+            }
+        }
+
+        let status = if card.due_complete {
+            "done"
+        } else {
+            list.map_or("todo", |l| status_from_name(&l.name))
+        };
+
+        let mut card_comments = comments.remove(card.id.as_str()).unwrap_or_default();
+        // Trello lists actions newest first.
+        card_comments.sort_by_key(|c| c.created_at);
+
+        project.tasks.push(SourceTask {
+            key,
+            draft: TaskDraft {
+                title: card.name.clone(),
+                description: Some(description.trim().to_string()).filter(|d| !d.is_empty()),
+                status: Some(status.to_string()),
+                priority: priority.map(str::to_string),
+                assignee_email: assignees.into_iter().next(),
+                deadline: card.due.clone(),
+                labels: card_labels,
+                ..Default::default()
+            },
+            comments: card_comments,
+        });
+    }
+
+    if archived > 0 {
+        project
+            .warnings
+            .push(format!("{} archived card(s) were not imported", archived));
+    }
+
+    Ok(project)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOARD: &[u8] = include_bytes!("fixtures/trello_board.json");
+
+    fn users() -> UserMap {
+        UserMap::new(HashMap::from([
+            ("alice".to_string(), "alice@example.com".to_string()),
+            ("Bob Brown".to_string(), "bob@example.com".to_string()),
+        ]))
+    }
+
+    fn at(raw: &str) -> Option<DateTime<Utc>> {
+        Some(raw.parse().unwrap())
+    }
+
+    #[test]
+    fn reads_a_board_export() {
+        let mut project = parse(BOARD, &users()).unwrap();
+        project.member_emails.sort();
+
+        let expected = SourceProject {
+            name: "Website relaunch".to_string(),
+            description: Some("Everything for the new site".to_string()),
+            member_emails: vec![
+                "alice@example.com".to_string(),
+                "bob@example.com".to_string(),
+            ],
+            tasks: vec![
+                SourceTask {
+                    key: "Card 'Write spec'".to_string(),
+                    draft: TaskDraft {
+                        title: "Write spec".to_string(),
+                        description: Some(
+                            "Card description\n\n### Steps\n\n- [x] Draft\n- [ ] Review"
+                                .to_string(),
+                        ),
+                        status: Some("todo".to_string()),
+                        priority: Some("high".to_string()),
+                        assignee_email: Some("alice@example.com".to_string()),
+                        deadline: Some("2024-05-01T12:00:00.000Z".to_string()),
+                        labels: vec!["High priority".to_string(), "green".to_string()],
+                        ..Default::default()
+                    },
+                    comments: vec![
+                        SourceComment {
+                            author_email: None,
+                            author_name: "Carol Jones".to_string(),
+                            body: "First!".to_string(),
+                            created_at: at("2024-03-01T08:00:00Z"),
+                        },
+                        SourceComment {
+                            author_email: Some("alice@example.com".to_string()),
+                            author_name: "Alice Smith".to_string(),
+                            body: "Second thoughts".to_string(),
+                            created_at: at("2024-03-02T09:30:00Z"),
+                        },
+                    ],
+                },
+                SourceTask {
+                    key: "Card 'Ship it'".to_string(),
+                    draft: TaskDraft {
+                        title: "Ship it".to_string(),
+                        status: Some("done".to_string()),
+                        labels: vec!["bug".to_string()],
+                        ..Default::default()
+                    },
+                    comments: vec![SourceComment {
+                        author_email: None,
+                        author_name: "a Trello user".to_string(),
+                        body: "From a deleted account".to_string(),
+                        created_at: at("2024-03-03T08:00:00Z"),
+                    }],
+                },
+                SourceTask {
+                    key: "Card 'Review'".to_string(),
+                    draft: TaskDraft {
+                        title: "Review".to_string(),
+                        status: Some("done".to_string()),
+                        ..Default::default()
+                    },
+                    comments: Vec::new(),
+                },
+            ],
+            warnings: vec![
+                "No email for board member Carol Jones; their cards are left unassigned"
+                    .to_string(),
+                "Card 'Write spec': has 2 members, only the first is assigned".to_string(),
+                "2 archived card(s) were not imported".to_string(),
+            ],
+        };
+        assert_eq!(project, expected);
+    }
+
+    #[test]
+    fn accepts_a_minimal_board() {
+        let project = parse(br#"{"name": "Empty"}"#, &UserMap::default()).unwrap();
+        assert_eq!(
+            project,
+            SourceProject {
+                name: "Empty".to_string(),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        for data in [&b"not json"[..], br#"{"cards": []}"#, br#"[]"#] {
+            match parse(data, &UserMap::default()) {
+                Err(AppError::BadRequest(message)) => {
+                    assert!(message.starts_with("Not a Trello board export: "), "{}", message)
+                }
+                other => panic!("{:?} gave {:?}", data, other),
+            }
+        }
+    }
+}
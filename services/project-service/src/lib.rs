@@ -1,5 +1,52 @@
+// Project service
+pub mod access;
+pub mod activity;
+pub mod custom_fields;
+pub mod etag;
 pub mod handlers;
+pub mod ical;
+pub mod import;
+pub mod importers;
+pub mod markdown;
+pub mod middleware;
 pub mod models;
+pub mod notifier;
+pub mod quota;
+pub mod recurrence;
+pub mod rrule;
+pub mod spreadsheet;
+pub mod storage;
+pub mod templates;
+pub mod tql;
+pub mod trash;
+pub mod watchers;
+pub mod webhooks;
+
+use shared::auth::AuthService;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use notifier::Notifier;
+use quota::Billing;
+use storage::{Storage, UrlSigner};
+use webhooks::AddressPolicy;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub db: PgPool,
+    pub auth: Arc<AuthService>,
+    pub notifier: Notifier,
+    /// Plan limits from billing-service.
+    pub billing: Billing,
+    pub storage: Arc<dyn Storage>,
+    pub url_signer: UrlSigner,
+    /// Days deleted projects and tasks stay in the trash before being purged.
+    pub trash_retention_days: i32,
+    /// Reject project and task writes that carry no `If-Match` header.
+    pub require_if_match: bool,
+    /// Where outbound webhooks may be sent.
+    pub webhook_addresses: AddressPolicy,
+}
 
 pub fn init() {
     tracing::info!("Project service initialized");
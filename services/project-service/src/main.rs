@@ -1,24 +1,31 @@
 use axum::{
+    extract::DefaultBodyLimit,
+    middleware::from_fn_with_state,
     routing::{delete, get, patch, post},
     Json, Router,
 };
+use project_service::{
+    handlers::{
+        activity, attachment, bulk, calendar, comment, custom_field, export, import, label, project,
+        recurrence, report, sprint, task, template, time_entry, trash, usage, watcher,
+        webhook,
+    },
+    importers,
+    middleware::auth_middleware,
+    notifier::Notifier,
+    quota::Billing,
+    recurrence::spawn_scheduler,
+    trash::spawn_purger,
+    webhooks::{spawn_dispatcher, AddressPolicy},
+    storage::{self, UrlSigner},
+    AppState,
+};
 use serde_json::json;
 use shared::{
     auth::AuthService,
     database::init_pool,
 };
-use sqlx::PgPool;
-use std::sync::Arc;
-
-mod handlers;
-
-use handlers::{project, task};
-
-#[derive(Clone)]
-pub struct AppState {
-    pub db: PgPool,
-    pub auth: Arc<AuthService>,
-}
+use std::{sync::Arc, time::Duration};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -37,16 +44,96 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .unwrap_or_else(|_| "3600".to_string())
         .parse()
         .unwrap_or(3600);
+    let notification_service_url = std::env::var("NOTIFICATION_SERVICE_URL")
+        .unwrap_or_else(|_| "http://localhost:3004".to_string());
+    let service_secret = std::env::var("SERVICE_SECRET")
+        .ok()
+        .filter(|secret| !secret.is_empty())
+        .expect("SERVICE_SECRET must be set");
+    let billing_service_url = std::env::var("BILLING_SERVICE_URL")
+        .unwrap_or_else(|_| "http://localhost:3003".to_string());
+    let plan_limits_cache_secs: u64 = std::env::var("PLAN_LIMITS_CACHE_SECS")
+        .unwrap_or_else(|_| "60".to_string())
+        .parse()
+        .unwrap_or(60);
+    let attachment_url_secret = std::env::var("ATTACHMENT_URL_SECRET")
+        .unwrap_or_else(|_| jwt_secret.clone());
+    let attachment_url_ttl: i64 = std::env::var("ATTACHMENT_URL_TTL")
+        .unwrap_or_else(|_| "900".to_string())
+        .parse()
+        .unwrap_or(900);
+    let recurrence_poll_secs: u64 = std::env::var("RECURRENCE_POLL_SECS")
+        .unwrap_or_else(|_| "60".to_string())
+        .parse()
+        .unwrap_or(60);
+    let trash_retention_days: i32 = std::env::var("TRASH_RETENTION_DAYS")
+        .unwrap_or_else(|_| "30".to_string())
+        .parse::<i32>()
+        .unwrap_or(30)
+        .max(0);
+    let trash_purge_poll_secs: u64 = std::env::var("TRASH_PURGE_POLL_SECS")
+        .unwrap_or_else(|_| "3600".to_string())
+        .parse()
+        .unwrap_or(3600);
+    let webhook_poll_secs: u64 = std::env::var("WEBHOOK_POLL_SECS")
+        .unwrap_or_else(|_| "5".to_string())
+        .parse()
+        .unwrap_or(5);
+
+    let require_if_match = std::env::var("REQUIRE_IF_MATCH")
+        .map(|v| v == "true" || v == "1")
+        .unwrap_or(false);
+    let webhook_addresses = AddressPolicy {
+        allow_loopback: std::env::var("WEBHOOK_ALLOW_LOOPBACK")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false),
+    };
 
     let db = init_pool(&database_url, 5)
         .await
         .expect("Failed to initialize database pool");
 
+    spawn_scheduler(db.clone(), Duration::from_secs(recurrence_poll_secs.max(1)));
+    spawn_dispatcher(
+        db.clone(),
+        Duration::from_secs(webhook_poll_secs.max(1)),
+        webhook_addresses,
+    );
+
+    match importers::fail_interrupted(&db).await {
+        Ok(0) => {}
+        Ok(n) => tracing::warn!("Marked {} interrupted import job(s) as failed", n),
+        Err(e) => tracing::warn!("Failed to clean up interrupted import jobs: {}", e),
+    }
+
     let auth = Arc::new(AuthService::new(jwt_secret, jwt_expiration));
-    let state = AppState { db, auth };
+    let notifier = Notifier::new(notification_service_url, service_secret.clone());
+    let billing = Billing::new(
+        billing_service_url,
+        service_secret,
+        Duration::from_secs(plan_limits_cache_secs),
+    );
+    let storage = storage::from_env();
+    spawn_purger(
+        db.clone(),
+        storage.clone(),
+        trash_retention_days,
+        Duration::from_secs(trash_purge_poll_secs.max(1)),
+    );
 
-    let router = Router::new()
-        .route("/health", get(health_check))
+    let state = AppState {
+        db,
+        auth,
+        notifier,
+        billing,
+        storage,
+        url_signer: UrlSigner::new(attachment_url_secret, attachment_url_ttl),
+        trash_retention_days,
+        require_if_match,
+        webhook_addresses,
+    };
+
+    let api = Router::new()
         .route("/projects", post(project::create_project))
         .route("/projects", get(project::list_projects))
         .route("/projects/:id", get(project::get_project))
@@ -55,11 +142,125 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/projects/:id/members", get(project::get_members))
         .route("/projects/:id/members", post(project::add_member))
         .route("/projects/:id/members/:user_id", delete(project::remove_member))
+        .route("/projects/:id/clone", post(project::clone_project))
+        .route("/projects/:id/watchers", get(watcher::list_project_watchers))
+        .route("/projects/:id/watch", post(watcher::watch_project))
+        .route("/projects/:id/watch", delete(watcher::unwatch_project))
+        .route("/projects/:id/restore", post(trash::restore_project))
+        .route("/projects/:id/trash", get(trash::list_trashed_tasks))
+        .route("/trash/projects", get(trash::list_trashed_projects))
+        .route("/projects/:id/templates", post(template::create_template))
+        .route("/templates", get(template::list_templates))
+        .route("/templates/:id", get(template::get_template))
+        .route("/templates/:id", delete(template::delete_template))
+        .route("/templates/:id/projects", post(template::create_project_from_template))
+        .route("/projects/:id/export", get(export::export_project))
+        .route(
+            "/projects/:id/import/csv",
+            post(import::import_tasks_csv).layer(DefaultBodyLimit::max(import::MAX_IMPORT_BYTES)),
+        )
+        .route(
+            "/imports/trello",
+            post(import::import_trello).layer(DefaultBodyLimit::max(import::MAX_IMPORT_BYTES)),
+        )
+        .route(
+            "/imports/jira",
+            post(import::import_jira).layer(DefaultBodyLimit::max(import::MAX_IMPORT_BYTES)),
+        )
+        .route("/imports", get(import::list_import_jobs))
+        .route("/imports/:id", get(import::get_import_job))
+        .route("/calendar/feed", get(calendar::get_my_feed))
+        .route("/calendar/feed/regenerate", post(calendar::regenerate_my_feed))
+        .route("/projects/:id/calendar/feed", get(calendar::get_project_feed))
+        .route(
+            "/projects/:id/calendar/feed/regenerate",
+            post(calendar::regenerate_project_feed),
+        )
+        .route("/projects/:id/webhooks", get(webhook::list_webhooks))
+        .route("/projects/:id/webhooks", post(webhook::create_webhook))
+        .route("/webhooks/:id", get(webhook::get_webhook))
+        .route("/webhooks/:id", patch(webhook::update_webhook))
+        .route("/webhooks/:id", delete(webhook::delete_webhook))
+        .route("/webhooks/:id/deliveries", get(webhook::list_deliveries))
+        .route(
+            "/webhooks/:id/deliveries/:delivery_id/redeliver",
+            post(webhook::redeliver),
+        )
+        .route("/projects/:id/activity", get(activity::get_project_activity))
+        .route("/projects/:id/metrics/flow", get(activity::get_flow_metrics))
+        .route("/reports/workload", get(report::get_workload_report))
         .route("/projects/:id/tasks", post(task::create_task))
         .route("/projects/:id/tasks", get(task::list_tasks))
+        .route("/tasks/bulk", post(bulk::bulk_update_tasks))
+        .route("/tasks/search", get(task::search_tasks))
+        .route("/usage", get(usage::get_usage))
         .route("/tasks/:id", get(task::get_task))
         .route("/tasks/:id", patch(task::update_task))
         .route("/tasks/:id", delete(task::delete_task))
+        .route("/tasks/:id/restore", post(trash::restore_task))
+        .route("/tasks/:id/activity", get(activity::get_task_activity))
+        .route("/tasks/:id/watchers", get(watcher::list_task_watchers))
+        .route("/tasks/:id/watch", post(watcher::watch_task))
+        .route("/tasks/:id/watch", delete(watcher::unwatch_task))
+        .route("/tasks/:id/recurrence", get(recurrence::get_task_recurrence))
+        .route("/tasks/:id/recurrence", delete(recurrence::stop_task_recurrence))
+        .route("/projects/:id/labels", get(label::list_labels))
+        .route("/projects/:id/labels", post(label::create_label))
+        .route("/labels/:id", patch(label::update_label))
+        .route("/labels/:id", delete(label::delete_label))
+        .route("/labels/:id/merge", post(label::merge_label))
+        .route("/tasks/:id/labels/:label_id", post(label::attach_label))
+        .route("/tasks/:id/labels/:label_id", delete(label::detach_label))
+        .route("/projects/:id/custom-fields", get(custom_field::list_custom_fields))
+        .route("/projects/:id/custom-fields", post(custom_field::create_custom_field))
+        .route("/custom-fields/:id", patch(custom_field::update_custom_field))
+        .route("/custom-fields/:id", delete(custom_field::delete_custom_field))
+        .route("/tasks/:id/time-entries", get(time_entry::list_task_time_entries))
+        .route("/tasks/:id/time-entries", post(time_entry::create_time_entry))
+        .route("/time-entries/:id", patch(time_entry::update_time_entry))
+        .route("/time-entries/:id", delete(time_entry::delete_time_entry))
+        .route("/tasks/:id/timer/start", post(time_entry::start_timer))
+        .route("/timer", get(time_entry::get_running_timer))
+        .route("/timer/stop", post(time_entry::stop_timer))
+        .route("/projects/:id/timesheet", get(time_entry::project_timesheet))
+        .route("/projects/:id/time-entries/export", get(time_entry::export_time_entries))
+        .route("/users/:id/timesheet", get(time_entry::user_timesheet))
+        .route("/projects/:id/sprints", get(sprint::list_sprints))
+        .route("/projects/:id/sprints", post(sprint::create_sprint))
+        .route("/sprints/:id", get(sprint::get_sprint))
+        .route("/sprints/:id", patch(sprint::update_sprint))
+        .route("/sprints/:id", delete(sprint::delete_sprint))
+        .route("/sprints/:id/start", post(sprint::start_sprint))
+        .route("/sprints/:id/complete", post(sprint::complete_sprint))
+        .route("/sprints/:id/tasks", post(sprint::add_sprint_tasks))
+        .route("/sprints/:id/tasks/:task_id", delete(sprint::remove_sprint_task))
+        .route("/sprints/:id/burndown", get(sprint::get_burndown))
+        .route("/tasks/:id/comments", get(comment::list_comments))
+        .route("/tasks/:id/comments", post(comment::create_comment))
+        .route("/comments/:id", patch(comment::update_comment))
+        .route("/comments/:id", delete(comment::delete_comment))
+        .route("/comments/:id/history", get(comment::get_comment_history))
+        .route(
+            "/projects/:id/attachments",
+            post(attachment::upload_project_attachment)
+                .layer(DefaultBodyLimit::max(attachment::MAX_UPLOAD_BYTES)),
+        )
+        .route("/projects/:id/attachments", get(attachment::list_project_attachments))
+        .route(
+            "/tasks/:id/attachments",
+            post(attachment::upload_task_attachment)
+                .layer(DefaultBodyLimit::max(attachment::MAX_UPLOAD_BYTES)),
+        )
+        .route("/tasks/:id/attachments", get(attachment::list_task_attachments))
+        .route("/attachments/:id", get(attachment::get_attachment))
+        .route("/attachments/:id", delete(attachment::delete_attachment))
+        .route_layer(from_fn_with_state(state.clone(), auth_middleware));
+
+    let router = Router::new()
+        .route("/health", get(health_check))
+        .route("/attachments/:id/download", get(attachment::download_attachment))
+        .route("/calendar/:file", get(calendar::get_calendar))
+        .merge(api)
         .with_state(state)
         .layer(tower_http::cors::CorsLayer::permissive())
         .layer(
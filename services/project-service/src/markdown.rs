@@ -0,0 +1,218 @@
+use lazy_static::lazy_static;
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag};
+use regex::Regex;
+use uuid::Uuid;
+
+lazy_static! {
+    static ref MENTION_RE: Regex =
+        Regex::new(r"(?:^|[^\w@.])@([A-Za-z0-9._+-]+(?:@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)+)?)")
+            .expect("valid mention regex");
+}
+
+const SAFE_SCHEMES: [&str; 3] = ["http:", "https:", "mailto:"];
+
+fn markdown_options() -> Options {
+    Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS
+}
+
+fn safe_url(url: CowStr<'_>) -> CowStr<'_> {
+    let lower = url.trim().to_ascii_lowercase();
+    let has_scheme = lower
+        .split_once(':')
+        .map(|(scheme, _)| !scheme.contains('/'))
+        .unwrap_or(false);
+
+    if !has_scheme || SAFE_SCHEMES.iter().any(|scheme| lower.starts_with(scheme)) {
+        url
+    } else {
+        CowStr::Borrowed("")
+    }
+}
+
+/// Renders a Markdown body to HTML. Raw HTML in the source is escaped rather
+/// than passed through and links are restricted to safe schemes.
+pub fn render_html(markdown: &str) -> String {
+    let parser = Parser::new_ext(markdown, markdown_options()).map(|event| match event {
+        Event::Html(raw) => Event::Text(raw),
+        Event::Start(Tag::Link(kind, url, title)) => {
+            Event::Start(Tag::Link(kind, safe_url(url), title))
+        }
+        Event::Start(Tag::Image(kind, url, title)) => {
+            Event::Start(Tag::Image(kind, safe_url(url), title))
+        }
+        other => other,
+    });
+
+    let mut out = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut out, parser);
+    out
+}
+
+/// Extracts `@handle` mentions from a Markdown body, ignoring code spans and
+/// code blocks. Handles are lowercased and deduplicated in order of appearance.
+pub fn extract_mentions(markdown: &str) -> Vec<String> {
+    let mut handles: Vec<String> = Vec::new();
+    let mut text = String::new();
+    let mut in_code_block = false;
+
+    let flush = |text: &mut String, handles: &mut Vec<String>| {
+        for caps in MENTION_RE.captures_iter(text) {
+            let handle = caps[1].trim_end_matches('.').to_lowercase();
+            if !handle.is_empty() && !handles.contains(&handle) {
+                handles.push(handle);
+            }
+        }
+        text.clear();
+    };
+
+    for event in Parser::new_ext(markdown, markdown_options()) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => {
+                flush(&mut text, &mut handles);
+                in_code_block = true;
+            }
+            Event::End(Tag::CodeBlock(_)) => in_code_block = false,
+            Event::Text(chunk) if !in_code_block => text.push_str(&chunk),
+            _ => flush(&mut text, &mut handles),
+        }
+    }
+    flush(&mut text, &mut handles);
+
+    handles
+}
+
+/// Matches mention handles against `(user_id, email)` pairs. A handle matches
+/// either the full email address or its local part, case-insensitively.
+pub fn resolve_mentions(handles: &[String], members: &[(Uuid, String)]) -> Vec<Uuid> {
+    let mut resolved = Vec::new();
+
+    for handle in handles {
+        let matched = members.iter().find(|(_, email)| {
+            let email = email.to_lowercase();
+            let local = email.split('@').next().unwrap_or_default();
+            email == *handle || local == handle
+        });
+
+        if let Some((id, _)) = matched {
+            if !resolved.contains(id) {
+                resolved.push(*id);
+            }
+        }
+    }
+
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_only_safe_url_schemes() {
+        let cases = [
+            ("https://example.com/a?b=c", true),
+            ("HTTP://example.com", true),
+            ("mailto:someone@example.com", true),
+            ("/projects/1/tasks", true),
+            ("tasks/1", true),
+            ("#comments", true),
+            ("?page=2", true),
+            ("docs/a:b", true),
+            ("javascript:alert(1)", false),
+            ("JavaScript:alert(1)", false),
+            ("  javascript:alert(1)", false),
+            ("data:text/html;base64,PHNjcmlwdD4=", false),
+            ("data:image/png;base64,iVBORw0KGgo=", false),
+            ("vbscript:msgbox(1)", false),
+            ("file:///etc/passwd", false),
+        ];
+        for (url, kept) in cases {
+            let result = safe_url(CowStr::Borrowed(url));
+            assert_eq!(result.as_ref(), if kept { url } else { "" }, "{}", url);
+        }
+    }
+
+    #[test]
+    fn strips_unsafe_links_and_images() {
+        let cases = [
+            ("[x](javascript:alert(1))", "<p><a href=\"\">x</a></p>\n"),
+            ("[x](javascript&#58;alert(1))", "<p><a href=\"\">x</a></p>\n"),
+            ("<javascript:alert(1)>", "<p><a href=\"\">javascript:alert(1)</a></p>\n"),
+            ("[x][r]\n\n[r]: javascript:alert(1)", "<p><a href=\"\">x</a></p>\n"),
+            (
+                "![x](data:image/svg+xml;base64,PHN2Zz4=)",
+                "<p><img src=\"\" alt=\"x\" /></p>\n",
+            ),
+            (
+                "[x](https://example.com \"Title\")",
+                "<p><a href=\"https://example.com\" title=\"Title\">x</a></p>\n",
+            ),
+            ("![x](/files/a.png)", "<p><img src=\"/files/a.png\" alt=\"x\" /></p>\n"),
+        ];
+        for (markdown, html) in cases {
+            assert_eq!(render_html(markdown), html, "{}", markdown);
+        }
+    }
+
+    #[test]
+    fn escapes_raw_html() {
+        assert_eq!(
+            render_html("<script>alert(1)</script>"),
+            "&lt;script&gt;alert(1)&lt;/script&gt;"
+        );
+        assert_eq!(
+            render_html("Hi <img src=x onerror=alert(1)>"),
+            "<p>Hi &lt;img src=x onerror=alert(1)&gt;</p>\n"
+        );
+        assert_eq!(
+            render_html("**bold** and ~~gone~~"),
+            "<p><strong>bold</strong> and <del>gone</del></p>\n"
+        );
+    }
+
+    #[test]
+    fn extracts_mentions() {
+        let cases: [(&str, &[&str]); 14] = [
+            ("@alice and @Bob", &["alice", "bob"]),
+            ("@alice, @ALICE and @alice again", &["alice"]),
+            ("thanks @alice.", &["alice"]),
+            ("(@alice) [@bob]", &["alice", "bob"]),
+            ("**@alice** and _@bob_", &["alice", "bob"]),
+            ("ask @alice@example.com", &["alice@example.com"]),
+            ("@first.last and @a_b-c+d", &["first.last", "a_b-c+d"]),
+            ("mail alice@example.com", &[]),
+            ("a@b @ alone", &[]),
+            ("`@alice` but @bob", &["bob"]),
+            ("```\n@alice\n```\n@bob", &["bob"]),
+            ("    @alice indented\n\n@bob", &["bob"]),
+            ("- [ ] @alice\n- [x] @bob", &["alice", "bob"]),
+            ("", &[]),
+        ];
+        for (markdown, expected) in cases {
+            assert_eq!(extract_mentions(markdown), expected, "{}", markdown);
+        }
+    }
+
+    #[test]
+    fn resolves_mentions_by_email_or_local_part() {
+        let alice = Uuid::new_v4();
+        let bob = Uuid::new_v4();
+        let members = vec![
+            (alice, "Alice@Example.com".to_string()),
+            (bob, "bob@example.org".to_string()),
+        ];
+        let handles = |handles: &[&str]| -> Vec<String> {
+            handles.iter().map(|h| h.to_string()).collect()
+        };
+
+        assert_eq!(
+            resolve_mentions(&handles(&["bob", "alice"]), &members),
+            [bob, alice]
+        );
+        assert_eq!(
+            resolve_mentions(&handles(&["alice@example.com", "alice"]), &members),
+            [alice]
+        );
+        assert!(resolve_mentions(&handles(&["carol", "bob@example.com"]), &members).is_empty());
+    }
+}
@@ -1 +1,990 @@
 // Project service models
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use shared::models::{PaginationParams, Project, Task};
+use sqlx::FromRow;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::markdown;
+
+// ============= COMMENT =============
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Comment {
+    pub id: Uuid,
+    pub task_id: Uuid,
+    pub author_id: Option<Uuid>,
+    pub parent_id: Option<Uuid>,
+    pub body: String,
+    pub mentions: Vec<Uuid>,
+    pub edited_at: Option<DateTime<Utc>>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CommentEdit {
+    pub id: Uuid,
+    pub comment_id: Uuid,
+    pub editor_id: Option<Uuid>,
+    pub previous_body: String,
+    pub edited_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateCommentRequest {
+    pub body: String,
+    pub parent_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateCommentRequest {
+    pub body: String,
+}
+
+/// A comment as returned by the API, with rendered Markdown and its replies.
+#[derive(Debug, Serialize)]
+pub struct CommentResponse {
+    pub id: Uuid,
+    pub task_id: Uuid,
+    pub author_id: Option<Uuid>,
+    pub parent_id: Option<Uuid>,
+    pub body: String,
+    pub body_html: String,
+    pub mentions: Vec<Uuid>,
+    pub edited_at: Option<DateTime<Utc>>,
+    pub deleted: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub replies: Vec<CommentResponse>,
+}
+
+impl From<Comment> for CommentResponse {
+    fn from(comment: Comment) -> Self {
+        let deleted = comment.deleted_at.is_some();
+        let (body, body_html) = if deleted {
+            (String::new(), String::new())
+        } else {
+            let html = markdown::render_html(&comment.body);
+            (comment.body, html)
+        };
+
+        CommentResponse {
+            id: comment.id,
+            task_id: comment.task_id,
+            author_id: comment.author_id,
+            parent_id: comment.parent_id,
+            body,
+            body_html,
+            mentions: if deleted {
+                Vec::new()
+            } else {
+                comment.mentions
+            },
+            edited_at: comment.edited_at,
+            deleted,
+            created_at: comment.created_at,
+            updated_at: comment.updated_at,
+            replies: Vec::new(),
+        }
+    }
+}
+
+// ============= ATTACHMENT =============
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Attachment {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub task_id: Option<Uuid>,
+    pub uploader_id: Option<Uuid>,
+    pub file_name: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub checksum_sha256: String,
+    #[serde(skip)]
+    pub storage_key: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AttachmentResponse {
+    #[serde(flatten)]
+    pub attachment: Attachment,
+    pub download_url: String,
+    pub download_expires_at: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadParams {
+    pub expires: i64,
+    pub signature: String,
+}
+
+// ============= LABEL =============
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Label {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub name: String,
+    pub color: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct LabelWithCounts {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub label: Label,
+    pub task_count: i64,
+    pub open_task_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateLabelRequest {
+    pub name: String,
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateLabelRequest {
+    pub name: Option<String>,
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MergeLabelRequest {
+    pub into_label_id: Uuid,
+}
+
+// ============= TASK =============
+
+/// A task together with the data attached to it by this service.
+#[derive(Debug, Serialize)]
+pub struct TaskResponse {
+    #[serde(flatten)]
+    pub task: Task,
+    pub labels: Vec<Label>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TaskListParams {
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+    pub status: Option<String>,
+    pub priority: Option<String>,
+    pub assignee_id: Option<Uuid>,
+    /// Comma-separated label ids or names.
+    pub labels: Option<String>,
+    /// `any` (default) or `all`.
+    pub label_match: Option<String>,
+    /// A sprint id, or `none` for tasks in the backlog.
+    pub sprint_id: Option<String>,
+    /// Sort key, prefixed with `-` for descending order.
+    pub sort: Option<String>,
+}
+
+impl TaskListParams {
+    pub fn pagination(&self) -> PaginationParams {
+        PaginationParams {
+            page: self.page,
+            limit: self.limit,
+        }
+    }
+}
+
+/// `GET /tasks/search`: a TQL query across the projects the caller can see.
+#[derive(Debug, Deserialize)]
+pub struct TaskSearchParams {
+    /// The query, e.g. `status != done AND assignee = me ORDER BY deadline`.
+    #[serde(default)]
+    pub q: String,
+    /// Restricts the search to one project.
+    pub project_id: Option<Uuid>,
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+impl TaskSearchParams {
+    pub fn pagination(&self) -> PaginationParams {
+        PaginationParams {
+            page: self.page,
+            limit: self.limit,
+        }
+    }
+}
+
+// ============= CUSTOM FIELD =============
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CustomField {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub name: String,
+    pub field_type: String,
+    pub options: serde_json::Value,
+    pub required: bool,
+    pub position: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateCustomFieldRequest {
+    pub name: String,
+    pub field_type: String,
+    pub options: Option<Vec<String>>,
+    pub required: Option<bool>,
+    pub position: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateCustomFieldRequest {
+    pub name: Option<String>,
+    pub options: Option<Vec<String>>,
+    pub required: Option<bool>,
+    pub position: Option<i32>,
+}
+
+// ============= TIME TRACKING =============
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct TimeEntry {
+    pub id: Uuid,
+    pub task_id: Uuid,
+    pub user_id: Uuid,
+    pub entry_date: NaiveDate,
+    pub duration_minutes: Option<i32>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateTimeEntryRequest {
+    pub duration_minutes: i32,
+    pub entry_date: Option<NaiveDate>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateTimeEntryRequest {
+    pub duration_minutes: Option<i32>,
+    pub entry_date: Option<NaiveDate>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StartTimerRequest {
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DateRangeParams {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TimeTotal {
+    pub id: Uuid,
+    pub name: String,
+    pub minutes: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct DailyTime {
+    pub date: NaiveDate,
+    pub minutes: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Timesheet {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub total_minutes: i64,
+    pub by_day: Vec<DailyTime>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub by_user: Vec<TimeTotal>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub by_project: Vec<TimeTotal>,
+    pub by_task: Vec<TimeTotal>,
+}
+
+// ============= RECURRENCE =============
+
+/// A recurring task series: the rule, its timezone and the template every
+/// occurrence is created from.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct TaskRecurrence {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub rrule: String,
+    pub timezone: String,
+    pub dtstart: NaiveDateTime,
+    pub title: String,
+    pub description: Option<String>,
+    pub priority: String,
+    pub assignee_id: Option<Uuid>,
+    pub custom_fields: serde_json::Value,
+    pub label_ids: Vec<Uuid>,
+    pub original_estimate_minutes: Option<i32>,
+    pub next_occurrence_at: Option<DateTime<Utc>>,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecurrenceResponse {
+    #[serde(flatten)]
+    pub recurrence: TaskRecurrence,
+    pub upcoming: Vec<DateTime<Utc>>,
+}
+
+/// Whether a change to a recurring task applies to this occurrence only or to
+/// this and all future occurrences.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EditScope {
+    #[default]
+    This,
+    Future,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct EditScopeParams {
+    #[serde(default)]
+    pub scope: EditScope,
+}
+
+// ============= SPRINT =============
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Sprint {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub name: String,
+    pub goal: Option<String>,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub status: String,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct SprintWithCounts {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub sprint: Sprint,
+    pub task_count: i64,
+    pub done_task_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateSprintRequest {
+    pub name: String,
+    pub goal: Option<String>,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateSprintRequest {
+    pub name: Option<String>,
+    pub goal: Option<String>,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SprintTasksRequest {
+    pub task_ids: Vec<Uuid>,
+}
+
+/// Where unfinished tasks go when a sprint completes: the given sprint, or
+/// the next planned sprint when omitted (the backlog if there is none).
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CompleteSprintRequest {
+    pub rollover_sprint_id: Option<Uuid>,
+    #[serde(default)]
+    pub to_backlog: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SprintCompletion {
+    pub sprint: Sprint,
+    pub completed_tasks: i64,
+    pub rolled_over_tasks: i64,
+    pub rolled_over_to: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct BurndownPoint {
+    pub date: NaiveDate,
+    /// `None` for days that have not happened yet.
+    pub remaining_tasks: Option<i64>,
+    pub remaining_estimate_minutes: Option<i64>,
+    #[sqlx(skip)]
+    pub ideal_tasks: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Burndown {
+    pub sprint_id: Uuid,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub total_tasks: i64,
+    pub total_estimate_minutes: i64,
+    pub points: Vec<BurndownPoint>,
+}
+
+// ============= ACTIVITY =============
+
+/// One entry of a task or project timeline. `updated` entries carry a single
+/// field with its old and new value.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ActivityEntry {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub entity_type: String,
+    pub entity_id: Uuid,
+    pub actor_id: Option<Uuid>,
+    pub action: String,
+    pub field: Option<String>,
+    pub old_value: Option<serde_json::Value>,
+    pub new_value: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ActivityParams {
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+    /// `project` or `task`.
+    pub entity_type: Option<String>,
+    pub actor_id: Option<Uuid>,
+}
+
+impl ActivityParams {
+    pub fn pagination(&self) -> PaginationParams {
+        PaginationParams {
+            page: self.page,
+            limit: self.limit,
+        }
+    }
+}
+
+/// Lead time (created to done) and cycle time (first started to done) of the
+/// tasks completed in a date range, in hours.
+#[derive(Debug, Serialize)]
+pub struct FlowMetrics {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub completed_tasks: i64,
+    pub avg_lead_time_hours: Option<f64>,
+    pub median_lead_time_hours: Option<f64>,
+    pub p85_lead_time_hours: Option<f64>,
+    pub avg_cycle_time_hours: Option<f64>,
+    pub median_cycle_time_hours: Option<f64>,
+    pub p85_cycle_time_hours: Option<f64>,
+}
+
+// ============= TEMPLATE =============
+
+/// Portable snapshot of a project's structure. Labels, fields and sprints are
+/// referenced by name or position so a snapshot can be applied to any project.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TemplateContent {
+    pub description: Option<String>,
+    #[serde(default)]
+    pub labels: Vec<TemplateLabel>,
+    #[serde(default)]
+    pub custom_fields: Vec<TemplateField>,
+    #[serde(default)]
+    pub sprints: Vec<TemplateSprint>,
+    #[serde(default)]
+    pub tasks: Vec<TemplateTask>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateLabel {
+    pub name: String,
+    pub color: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateField {
+    pub name: String,
+    pub field_type: String,
+    pub options: serde_json::Value,
+    pub required: bool,
+    pub position: i32,
+}
+
+/// Sprint dates are kept as day offsets from the template's start date.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateSprint {
+    pub name: String,
+    pub goal: Option<String>,
+    pub start_offset_days: i64,
+    pub end_offset_days: i64,
+}
+
+/// A task skeleton. The deadline is an offset in minutes from midnight UTC of
+/// the start date; custom field values are keyed by field name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateTask {
+    pub title: String,
+    pub description: Option<String>,
+    pub priority: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assignee_id: Option<Uuid>,
+    pub deadline_offset_minutes: Option<i64>,
+    pub original_estimate_minutes: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining_estimate_minutes: Option<i32>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub custom_fields: serde_json::Map<String, serde_json::Value>,
+    /// Index into the snapshot's sprints.
+    pub sprint: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ProjectTemplate {
+    pub id: Uuid,
+    pub owner_id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub content: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateTemplateRequest {
+    pub name: String,
+    pub description: Option<String>,
+    /// Date task deadlines are made relative to; the project's creation date
+    /// by default.
+    pub reference_date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstantiateTemplateRequest {
+    pub name: String,
+    pub description: Option<String>,
+    /// Defaults to today.
+    pub start_date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CloneProjectRequest {
+    pub name: Option<String>,
+    #[serde(default = "default_true")]
+    pub include_tasks: bool,
+    #[serde(default)]
+    pub include_members: bool,
+    #[serde(default)]
+    pub reset_statuses: bool,
+}
+
+impl Default for CloneProjectRequest {
+    fn default() -> Self {
+        Self {
+            name: None,
+            include_tasks: true,
+            include_members: false,
+            reset_statuses: false,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// ============= TRASH =============
+
+/// A project in the trash; `purge_at` is when it is deleted for good.
+#[derive(Debug, Serialize, FromRow)]
+pub struct TrashedProject {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub project: Project,
+    pub deleted_at: DateTime<Utc>,
+    pub deleted_by: Option<Uuid>,
+    pub purge_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TrashedTask {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub task: Task,
+    pub deleted_at: DateTime<Utc>,
+    pub deleted_by: Option<Uuid>,
+    pub purge_at: DateTime<Utc>,
+}
+
+// ============= BULK =============
+
+#[derive(Debug, Deserialize)]
+pub struct BulkTaskRequest {
+    /// Tasks to change; give either these or `filter`.
+    pub task_ids: Option<Vec<Uuid>>,
+    pub filter: Option<BulkTaskFilter>,
+    pub operations: Vec<BulkOperation>,
+}
+
+/// Selects the tasks of one project with the filters of the task list.
+#[derive(Debug, Deserialize)]
+pub struct BulkTaskFilter {
+    pub project_id: Uuid,
+    /// Custom field filters keyed like the list query without the `cf.`
+    /// prefix, e.g. `points.gte`.
+    #[serde(default)]
+    pub custom_fields: HashMap<String, String>,
+    #[serde(flatten)]
+    pub params: TaskListParams,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BulkOperation {
+    SetStatus { status: String },
+    SetPriority { priority: String },
+    SetAssignee { assignee_id: Option<Uuid> },
+    AddLabels { label_ids: Vec<Uuid> },
+    RemoveLabels { label_ids: Vec<Uuid> },
+    SetSprint { sprint_id: Option<Uuid> },
+    Delete,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkTaskFailure {
+    pub task_id: Uuid,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkTaskResult {
+    pub matched: usize,
+    pub succeeded: Vec<Uuid>,
+    pub failed: Vec<BulkTaskFailure>,
+}
+
+// ============= IMPORT / EXPORT =============
+
+#[derive(Debug, Deserialize)]
+pub struct ExportParams {
+    /// `json` (default) or `csv`.
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportParams {
+    /// Validate only; nothing is written.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportRowError {
+    /// Line of the row in the source file.
+    pub row: u64,
+    pub errors: Vec<String>,
+}
+
+/// Outcome of an import. Rows are imported only when none has errors.
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub total_rows: usize,
+    pub valid_rows: usize,
+    pub imported: usize,
+    pub labels_created: usize,
+    pub errors: Vec<ImportRowError>,
+}
+
+/// Versioned JSON export of a project and everything needed to rebuild it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProjectBundle {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub project: Project,
+    pub members: Vec<BundleMember>,
+    pub labels: Vec<Label>,
+    pub custom_fields: Vec<CustomField>,
+    pub sprints: Vec<Sprint>,
+    pub tasks: Vec<BundleTask>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct BundleMember {
+    pub user_id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub role: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleTask {
+    #[serde(flatten)]
+    pub task: Task,
+    pub assignee_email: Option<String>,
+    pub labels: Vec<String>,
+    pub comments: Vec<BundleComment>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct BundleComment {
+    pub id: Uuid,
+    pub task_id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub author_email: Option<String>,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub edited_at: Option<DateTime<Utc>>,
+}
+
+/// A Trello or Jira import running in the background.
+#[derive(Debug, Serialize, FromRow)]
+pub struct ImportJob {
+    pub id: Uuid,
+    pub source: String,
+    /// `pending`, `running`, `completed` or `failed`.
+    pub status: String,
+    pub requested_by: Uuid,
+    pub project_id: Option<Uuid>,
+    pub project_name: String,
+    pub total_items: i32,
+    pub processed_items: i32,
+    pub progress_percent: i32,
+    /// Items that were skipped or imported only in part.
+    pub warnings: serde_json::Value,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+// ============= CALENDAR =============
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct CalendarFeed {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    /// `None` for the personal feed of assigned tasks.
+    pub project_id: Option<Uuid>,
+    pub token: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CalendarFeedResponse {
+    #[serde(flatten)]
+    pub feed: CalendarFeed,
+    /// Relative path of the `.ics` feed; anyone with it can read the feed.
+    pub url: String,
+}
+
+// ============= WEBHOOK =============
+
+/// A webhook subscription. The secret is only returned when it is set.
+#[derive(Debug, Serialize, FromRow)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub url: String,
+    pub events: Vec<String>,
+    pub active: bool,
+    pub consecutive_failures: i32,
+    pub disabled_at: Option<DateTime<Utc>>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookWithSecret {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    pub secret: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    /// Generated when omitted.
+    pub secret: Option<String>,
+    pub events: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateWebhookRequest {
+    pub url: Option<String>,
+    pub secret: Option<String>,
+    pub events: Option<Vec<String>>,
+    /// Setting `true` re-enables a webhook disabled after failures.
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: String,
+    pub payload: serde_json::Value,
+    /// `pending`, `succeeded` or `failed`.
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub response_status: Option<i32>,
+    pub response_body: Option<String>,
+    pub error: Option<String>,
+    pub duration_ms: Option<i32>,
+    pub redelivery_of: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+// ============= REPORT =============
+
+#[derive(Debug, Deserialize)]
+pub struct ReportParams {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    /// Limits the report to one project; all visible projects otherwise.
+    pub project_id: Option<Uuid>,
+}
+
+impl ReportParams {
+    pub fn range(&self) -> DateRangeParams {
+        DateRangeParams {
+            from: self.from,
+            to: self.to,
+        }
+    }
+}
+
+/// Current load of one assignee, plus what they completed in the range.
+/// `assignee_id` is `None` for unassigned tasks.
+#[derive(Debug, Serialize, FromRow)]
+pub struct AssigneeWorkload {
+    pub assignee_id: Option<Uuid>,
+    pub assignee_name: Option<String>,
+    pub assignee_email: Option<String>,
+    pub open_tasks: i64,
+    pub in_progress_tasks: i64,
+    pub overdue_tasks: i64,
+    pub high_priority_open_tasks: i64,
+    /// Remaining estimate of open tasks, in minutes.
+    pub open_estimate_minutes: i64,
+    pub completed_tasks: i64,
+    pub avg_completion_hours: Option<f64>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WeeklyThroughput {
+    /// Monday of the week.
+    pub week_start: NaiveDate,
+    pub created_tasks: i64,
+    pub completed_tasks: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct PriorityCount {
+    pub priority: String,
+    pub open_tasks: i64,
+    pub overdue_tasks: i64,
+    pub completed_tasks: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ReportTotals {
+    pub open_tasks: i64,
+    pub overdue_tasks: i64,
+    pub completed_tasks: i64,
+    /// Created to done, for tasks completed in the range.
+    pub avg_completion_hours: Option<f64>,
+    pub median_completion_hours: Option<f64>,
+}
+
+/// Workload and productivity across the projects a user can see. Open and
+/// overdue counts describe the present; completions and throughput cover the
+/// date range.
+#[derive(Debug, Serialize)]
+pub struct WorkloadReport {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub project_id: Option<Uuid>,
+    pub totals: ReportTotals,
+    pub by_assignee: Vec<AssigneeWorkload>,
+    pub by_priority: Vec<PriorityCount>,
+    pub throughput: Vec<WeeklyThroughput>,
+}
+
+// ============= WATCHER =============
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Watcher {
+    pub user_id: Uuid,
+    pub name: String,
+    pub email: String,
+    /// `task` when watching the task itself, `project` when watching its
+    /// project.
+    pub source: String,
+    pub since: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WatchStatus {
+    pub watching: bool,
+}
+
+// ============= USAGE =============
+
+#[derive(Debug, Serialize)]
+pub struct UsageItem {
+    pub used: i64,
+    /// `None` is unlimited.
+    pub limit: Option<i64>,
+    pub over_limit: bool,
+}
+
+impl UsageItem {
+    pub fn new(used: i64, limit: Option<i64>) -> Self {
+        Self {
+            used,
+            limit,
+            over_limit: limit.is_some_and(|limit| used > limit),
+        }
+    }
+}
+
+/// Usage against the caller's plan. Over a limit (after a downgrade) nothing
+/// is removed, but creating more of it is refused.
+#[derive(Debug, Serialize)]
+pub struct Usage {
+    pub plan: String,
+    pub projects: UsageItem,
+    pub tasks: UsageItem,
+}
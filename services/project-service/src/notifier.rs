@@ -0,0 +1,46 @@
+use reqwest::Client;
+use shared::{auth::SERVICE_SECRET_HEADER, models::NotificationEvent};
+
+/// Fire-and-forget client for notification-service.
+#[derive(Clone)]
+pub struct Notifier {
+    base_url: String,
+    service_secret: String,
+    client: Client,
+}
+
+impl Notifier {
+    pub fn new(base_url: String, service_secret: String) -> Self {
+        Self {
+            base_url,
+            service_secret,
+            client: Client::new(),
+        }
+    }
+
+    /// Hands the event to notification-service in the background. Delivery
+    /// failures are logged and never fail the originating request.
+    pub fn send(&self, event: NotificationEvent) {
+        if event.recipient_ids.is_empty() {
+            return;
+        }
+
+        let client = self.client.clone();
+        let url = format!("{}/events", self.base_url);
+        let service_secret = self.service_secret.clone();
+
+        tokio::spawn(async move {
+            let result = client
+                .post(&url)
+                .header(SERVICE_SECRET_HEADER, service_secret)
+                .json(&event)
+                .send()
+                .await
+                .and_then(|resp| resp.error_for_status());
+
+            if let Err(err) = result {
+                tracing::warn!(kind = %event.kind, error = %err, "Failed to deliver notification event");
+            }
+        });
+    }
+}
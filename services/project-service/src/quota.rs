@@ -0,0 +1,278 @@
+//! Plan limits on how many projects and tasks a user may own.
+//!
+//! Limits come from billing-service and are cached for a short while. Tasks
+//! count against the owner of their project. Only creating is limited: after a
+//! downgrade leaves a user over quota, their projects and tasks stay readable
+//! and editable, and creating more is refused until they delete enough or
+//! upgrade again. Tasks spawned by recurrence rules and restores from the
+//! trash are not limited.
+
+use reqwest::Client;
+use serde::Deserialize;
+use shared::{
+    auth::SERVICE_SECRET_HEADER,
+    errors::{AppError, AppResult},
+};
+use sqlx::{PgConnection, PgPool};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlanLimits {
+    pub plan: String,
+    /// `None` is unlimited.
+    pub max_projects: Option<i64>,
+    pub max_tasks: Option<i64>,
+}
+
+/// Client for billing-service's plan limits, with a per-user cache.
+#[derive(Clone)]
+pub struct Billing {
+    base_url: String,
+    service_secret: String,
+    client: Client,
+    ttl: Duration,
+    cache: Arc<Mutex<HashMap<Uuid, (Instant, PlanLimits)>>>,
+}
+
+impl Billing {
+    pub fn new(base_url: String, service_secret: String, ttl: Duration) -> Self {
+        Self {
+            base_url,
+            service_secret,
+            client: Client::builder()
+                .timeout(Duration::from_secs(5))
+                .build()
+                .unwrap_or_default(),
+            ttl,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    async fn fetch(&self, user_id: Uuid) -> Result<PlanLimits, reqwest::Error> {
+        self.client
+            .get(format!(
+                "{}/internal/users/{}/limits",
+                self.base_url, user_id
+            ))
+            .header(SERVICE_SECRET_HEADER, &self.service_secret)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+
+    /// The user's limits, from the cache while fresh. When billing-service
+    /// cannot be reached or refuses the call, the last known limits are used,
+    /// however old; without any the call fails.
+    pub async fn limits(&self, user_id: Uuid) -> AppResult<PlanLimits> {
+        let cached = self.cache.lock().unwrap().get(&user_id).cloned();
+        if let Some((at, limits)) = &cached {
+            if at.elapsed() < self.ttl {
+                return Ok(limits.clone());
+            }
+        }
+
+        match self.fetch(user_id).await {
+            Ok(limits) => {
+                let mut cache = self.cache.lock().unwrap();
+                cache.retain(|_, (at, _)| at.elapsed() < self.ttl);
+                cache.insert(user_id, (Instant::now(), limits.clone()));
+                Ok(limits)
+            }
+            Err(err) => match cached {
+                Some((_, limits)) => {
+                    tracing::warn!(%user_id, error = %err, "Using stale plan limits");
+                    Ok(limits)
+                }
+                None => {
+                    tracing::warn!(%user_id, error = %err, "Could not load plan limits");
+                    Err(AppError::ServiceUnavailable(
+                        "Plan limits are unavailable, try again later".to_string(),
+                    ))
+                }
+            },
+        }
+    }
+
+    /// The quota `owner_id`'s creations are checked against. Fails with
+    /// `ServiceUnavailable` when the limits are unknown, so that creating is
+    /// refused rather than left unlimited.
+    pub async fn quota(&self, owner_id: Uuid, caller_id: Uuid) -> AppResult<Quota> {
+        Ok(Quota {
+            limits: self.limits(owner_id).await?,
+            owner_id,
+            upgradable: owner_id == caller_id,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Projects,
+    Tasks,
+}
+
+impl Resource {
+    fn limit_name(self) -> &'static str {
+        match self {
+            Resource::Projects => "max_projects",
+            Resource::Tasks => "max_tasks",
+        }
+    }
+
+    fn noun(self) -> &'static str {
+        match self {
+            Resource::Projects => "projects",
+            Resource::Tasks => "tasks",
+        }
+    }
+}
+
+/// Live projects and tasks owned by the user.
+pub async fn usage(conn: &mut PgConnection, owner_id: Uuid) -> AppResult<(i64, i64)> {
+    let usage = sqlx::query_as(
+        "SELECT
+            (SELECT COUNT(*) FROM projects WHERE owner_id = $1 AND deleted_at IS NULL),
+            (SELECT COUNT(*) FROM tasks t JOIN projects p ON p.id = t.project_id
+             WHERE p.owner_id = $1 AND p.deleted_at IS NULL AND t.deleted_at IS NULL)",
+    )
+    .bind(owner_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(usage)
+}
+
+pub async fn project_owner(db: &PgPool, project_id: Uuid) -> AppResult<Uuid> {
+    sqlx::query_scalar("SELECT owner_id FROM projects WHERE id = $1")
+        .bind(project_id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))
+}
+
+pub struct Quota {
+    limits: PlanLimits,
+    owner_id: Uuid,
+    upgradable: bool,
+}
+
+impl Quota {
+    /// Refuses adding `count` projects or tasks beyond the owner's limit.
+    /// Holds a per-owner lock for the rest of the transaction, so concurrent
+    /// creations cannot both slip under the limit.
+    pub async fn check(
+        &self,
+        conn: &mut PgConnection,
+        resource: Resource,
+        count: i64,
+    ) -> AppResult<()> {
+        let limits = &self.limits;
+        let max = match resource {
+            Resource::Projects => limits.max_projects,
+            Resource::Tasks => limits.max_tasks,
+        };
+        let Some(max) = max else {
+            return Ok(());
+        };
+        if count <= 0 {
+            return Ok(());
+        }
+
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended('quota:' || $1::text, 0))")
+            .bind(self.owner_id)
+            .execute(&mut *conn)
+            .await?;
+
+        let (projects, tasks) = usage(conn, self.owner_id).await?;
+        let current = match resource {
+            Resource::Projects => projects,
+            Resource::Tasks => tasks,
+        };
+        if current + count <= max {
+            return Ok(());
+        }
+
+        let message = if self.upgradable {
+            format!(
+                "Your {} plan allows at most {} {} and you have {}; upgrade to create more",
+                limits.plan,
+                max,
+                resource.noun(),
+                current
+            )
+        } else {
+            format!(
+                "The project owner's {} plan allows at most {} {} and they have {}",
+                limits.plan,
+                max,
+                resource.noun(),
+                current
+            )
+        };
+        Err(AppError::LimitExceeded {
+            limit: resource.limit_name().to_string(),
+            plan: limits.plan.clone(),
+            max,
+            current,
+            message,
+            upgradable: self.upgradable,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A billing client pointed at a port nothing listens on.
+    async fn unreachable_billing() -> Billing {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        Billing::new(url, "secret".to_string(), Duration::from_secs(60))
+    }
+
+    #[tokio::test]
+    async fn quota_is_refused_without_any_limits() {
+        let billing = unreachable_billing().await;
+        let user_id = Uuid::new_v4();
+
+        let err = billing.quota(user_id, user_id).await.err().unwrap();
+        assert!(matches!(err, AppError::ServiceUnavailable(_)), "{err}");
+        assert!(matches!(
+            billing.limits(user_id).await,
+            Err(AppError::ServiceUnavailable(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn stale_limits_are_used_when_billing_is_unreachable() {
+        let billing = unreachable_billing().await;
+        let user_id = Uuid::new_v4();
+        let stale = Instant::now()
+            .checked_sub(Duration::from_secs(3600))
+            .unwrap_or_else(Instant::now);
+        billing.cache.lock().unwrap().insert(
+            user_id,
+            (
+                stale,
+                PlanLimits {
+                    plan: "free".to_string(),
+                    max_projects: Some(3),
+                    max_tasks: Some(100),
+                },
+            ),
+        );
+
+        let quota = billing.quota(user_id, user_id).await.unwrap();
+        assert_eq!(quota.limits.plan, "free");
+        assert_eq!(quota.limits.max_projects, Some(3));
+        assert!(quota.upgradable);
+    }
+}
@@ -0,0 +1,409 @@
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use shared::{
+    errors::{AppError, AppResult},
+    models::{RecurrenceRule, Task, UpdateTaskRequest},
+};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::{
+    activity::{self, Entity},
+    handlers::{label, task::TASK_COLUMNS},
+    models::TaskRecurrence,
+    rrule::RRule,
+};
+
+pub(crate) const RECURRENCE_COLUMNS: &str = "r.id, r.project_id, r.rrule, r.timezone, r.dtstart,
+    r.title, r.description, r.priority::text AS priority, r.assignee_id, r.custom_fields,
+    r.label_ids, r.original_estimate_minutes, r.next_occurrence_at, r.active,
+    r.created_at, r.updated_at";
+
+/// Series due for materialization handled per scheduler tick.
+const DUE_BATCH_SIZE: i64 = 100;
+
+/// Validates a submitted rule and timezone.
+pub fn parse_rule(input: &RecurrenceRule) -> AppResult<(RRule, Tz)> {
+    let rule: RRule = input
+        .rrule
+        .parse()
+        .map_err(|e| AppError::ValidationError(format!("Invalid recurrence rule: {}", e)))?;
+    let timezone = input.timezone.as_deref().unwrap_or("UTC");
+    let tz: Tz = timezone
+        .parse()
+        .map_err(|_| AppError::ValidationError(format!("Unknown timezone '{}'", timezone)))?;
+    Ok((rule, tz))
+}
+
+fn series_rule(series: &TaskRecurrence) -> AppResult<(RRule, Tz)> {
+    parse_rule(&RecurrenceRule {
+        rrule: series.rrule.clone(),
+        timezone: Some(series.timezone.clone()),
+    })
+    .map_err(|e| AppError::InternalError(format!("Recurrence {}: {}", series.id, e)))
+}
+
+/// Upcoming occurrences of a series that have not been created yet.
+pub fn upcoming(series: &TaskRecurrence, limit: usize) -> AppResult<Vec<DateTime<Utc>>> {
+    let Some(next) = series.next_occurrence_at.filter(|_| series.active) else {
+        return Ok(Vec::new());
+    };
+    let (rule, tz) = series_rule(series)?;
+    Ok(rule
+        .occurrences(series.dtstart, tz)
+        .map(|o| o.with_timezone(&Utc))
+        .skip_while(|o| *o < next)
+        .take(limit)
+        .collect())
+}
+
+pub async fn load_series(db: &PgPool, recurrence_id: Uuid) -> AppResult<TaskRecurrence> {
+    sqlx::query_as::<_, TaskRecurrence>(&format!(
+        "SELECT {} FROM task_recurrences r WHERE r.id = $1",
+        RECURRENCE_COLUMNS
+    ))
+    .bind(recurrence_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Recurrence not found".to_string()))
+}
+
+/// Turns `task` into the first occurrence of a new series. The task's deadline
+/// is the series start; later occurrences copy the task as it is now.
+pub async fn create_series(
+    tx: &mut Transaction<'_, Postgres>,
+    task: &Task,
+    rule: &RRule,
+    tz: Tz,
+    label_ids: &[Uuid],
+) -> AppResult<Task> {
+    let start = task.deadline.ok_or_else(|| {
+        AppError::ValidationError(
+            "Recurring tasks need a deadline for their first occurrence".to_string(),
+        )
+    })?;
+    let dtstart = start.with_timezone(&tz).naive_local();
+    let next = rule.next_after(dtstart, tz, start);
+
+    let recurrence_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO task_recurrences (project_id, rrule, timezone, dtstart, title, description,
+                                       priority, assignee_id, custom_fields, label_ids,
+                                       original_estimate_minutes, next_occurrence_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7::task_priority, $8, $9, $10, $11, $12)
+         RETURNING id",
+    )
+    .bind(task.project_id)
+    .bind(rule.to_string())
+    .bind(tz.name())
+    .bind(dtstart)
+    .bind(&task.title)
+    .bind(&task.description)
+    .bind(&task.priority)
+    .bind(task.assignee_id)
+    .bind(&task.custom_fields)
+    .bind(label_ids)
+    .bind(task.original_estimate_minutes)
+    .bind(next)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    let task = sqlx::query_as::<_, Task>(&format!(
+        "UPDATE tasks AS t SET recurrence_id = $2, occurrence_at = $3, updated_at = NOW()
+         WHERE t.id = $1
+         RETURNING {}",
+        TASK_COLUMNS
+    ))
+    .bind(task.id)
+    .bind(recurrence_id)
+    .bind(start)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(task)
+}
+
+/// Applies an edit made with the "this and all future occurrences" scope:
+/// the series template takes on the updated task, later occurrences that are
+/// not done yet receive the same changes, and a new rule restarts the series
+/// from this occurrence.
+pub async fn apply_to_future(
+    tx: &mut Transaction<'_, Postgres>,
+    task: &Task,
+    req: &UpdateTaskRequest,
+    title: Option<&str>,
+    actor_id: Uuid,
+) -> AppResult<()> {
+    let recurrence_id = task.recurrence_id.ok_or_else(|| {
+        AppError::BadRequest("Task is not part of a recurring series".to_string())
+    })?;
+    let occurrence_at = task
+        .occurrence_at
+        .or(task.deadline)
+        .unwrap_or_else(Utc::now);
+
+    sqlx::query(
+        "UPDATE task_recurrences SET
+            title = $2,
+            description = $3,
+            priority = $4::task_priority,
+            assignee_id = $5,
+            custom_fields = $6,
+            original_estimate_minutes = $7,
+            label_ids = COALESCE($8, label_ids),
+            updated_at = NOW()
+         WHERE id = $1",
+    )
+    .bind(recurrence_id)
+    .bind(&task.title)
+    .bind(&task.description)
+    .bind(&task.priority)
+    .bind(task.assignee_id)
+    .bind(&task.custom_fields)
+    .bind(task.original_estimate_minutes)
+    .bind(&req.label_ids)
+    .execute(&mut **tx)
+    .await?;
+
+    let previous = sqlx::query_as::<_, Task>(&format!(
+        "SELECT {} FROM tasks t
+         WHERE t.recurrence_id = $1 AND t.occurrence_at > $2 AND t.status <> 'done'
+           AND t.deleted_at IS NULL
+         ORDER BY t.occurrence_at
+         FOR UPDATE",
+        TASK_COLUMNS
+    ))
+    .bind(recurrence_id)
+    .bind(occurrence_at)
+    .fetch_all(&mut **tx)
+    .await?;
+    let ids: Vec<Uuid> = previous.iter().map(|t| t.id).collect();
+
+    let updated = sqlx::query_as::<_, Task>(&format!(
+        "UPDATE tasks AS t SET
+            title = COALESCE($2, t.title),
+            description = CASE WHEN $3 THEN $4 ELSE t.description END,
+            priority = COALESCE($5::task_priority, t.priority),
+            assignee_id = CASE WHEN $6 THEN $7 ELSE t.assignee_id END,
+            custom_fields = COALESCE($8, t.custom_fields),
+            original_estimate_minutes = COALESCE($9, t.original_estimate_minutes),
+            updated_at = NOW()
+         WHERE t.id = ANY($1)
+         RETURNING {}",
+        TASK_COLUMNS
+    ))
+    .bind(&ids)
+    .bind(title)
+    .bind(req.description.is_some())
+    .bind(req.description.clone().flatten())
+    .bind(&req.priority)
+    .bind(req.assignee_id.is_some())
+    .bind(req.assignee_id.flatten())
+    .bind(req.custom_fields.as_ref().map(|_| &task.custom_fields))
+    .bind(req.original_estimate_minutes)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    for new in &updated {
+        if let Some(old) = previous.iter().find(|t| t.id == new.id) {
+            activity::record_updated(
+                tx,
+                new.project_id,
+                Entity::Task,
+                new.id,
+                Some(actor_id),
+                old,
+                new,
+            )
+            .await?;
+        }
+    }
+
+    if let Some(label_ids) = &req.label_ids {
+        for task_id in ids {
+            label::set_task_labels(tx, task.project_id, task_id, label_ids).await?;
+        }
+    }
+
+    if let Some(input) = &req.recurrence {
+        let (rule, tz) = parse_rule(input)?;
+        let dtstart = occurrence_at.with_timezone(&tz).naive_local();
+
+        let latest: Option<DateTime<Utc>> =
+            sqlx::query_scalar("SELECT MAX(occurrence_at) FROM tasks WHERE recurrence_id = $1")
+                .bind(recurrence_id)
+                .fetch_one(&mut **tx)
+                .await?;
+        let next = rule.next_after(
+            dtstart,
+            tz,
+            latest.unwrap_or(occurrence_at).max(occurrence_at),
+        );
+
+        sqlx::query(
+            "UPDATE task_recurrences SET
+                rrule = $2, timezone = $3, dtstart = $4, next_occurrence_at = $5,
+                active = TRUE, updated_at = NOW()
+             WHERE id = $1",
+        )
+        .bind(recurrence_id)
+        .bind(rule.to_string())
+        .bind(tz.name())
+        .bind(dtstart)
+        .bind(next)
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Stops a series from producing further occurrences. Existing tasks are kept.
+pub async fn stop_series(tx: &mut Transaction<'_, Postgres>, recurrence_id: Uuid) -> AppResult<()> {
+    sqlx::query(
+        "UPDATE task_recurrences SET active = FALSE, next_occurrence_at = NULL, updated_at = NOW()
+         WHERE id = $1",
+    )
+    .bind(recurrence_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Creates the task for the series' next pending occurrence and advances the
+/// series. Safe to call concurrently: the series row is locked and an
+/// occurrence is only ever created once.
+pub async fn materialize_next(db: &PgPool, recurrence_id: Uuid) -> AppResult<Option<Task>> {
+    let mut tx = db.begin().await?;
+
+    let series = sqlx::query_as::<_, TaskRecurrence>(&format!(
+        "SELECT {} FROM task_recurrences r WHERE r.id = $1 AND r.active FOR UPDATE",
+        RECURRENCE_COLUMNS
+    ))
+    .bind(recurrence_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(series) = series else {
+        return Ok(None);
+    };
+    let Some(occurrence_at) = series.next_occurrence_at else {
+        return Ok(None);
+    };
+    let (rule, tz) = series_rule(&series)?;
+
+    let task = sqlx::query_as::<_, Task>(&format!(
+        "INSERT INTO tasks AS t (project_id, title, description, priority, assignee_id, deadline,
+                                 custom_fields, original_estimate_minutes,
+                                 remaining_estimate_minutes, recurrence_id, occurrence_at)
+         VALUES ($1, $2, $3, $4::task_priority, $5, $6, $7, $8, $8, $9, $6)
+         ON CONFLICT (recurrence_id, occurrence_at) DO NOTHING
+         RETURNING {}",
+        TASK_COLUMNS
+    ))
+    .bind(series.project_id)
+    .bind(&series.title)
+    .bind(&series.description)
+    .bind(&series.priority)
+    .bind(series.assignee_id)
+    .bind(occurrence_at)
+    .bind(&series.custom_fields)
+    .bind(series.original_estimate_minutes)
+    .bind(series.id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if let Some(task) = &task {
+        activity::record_created(&mut tx, task.project_id, Entity::Task, task.id, None, task)
+            .await?;
+
+        // Labels deleted since the template was saved are skipped.
+        sqlx::query(
+            "INSERT INTO task_labels (task_id, label_id)
+             SELECT $1, l.id FROM labels l WHERE l.project_id = $2 AND l.id = ANY($3)",
+        )
+        .bind(task.id)
+        .bind(series.project_id)
+        .bind(&series.label_ids)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    sqlx::query(
+        "UPDATE task_recurrences SET next_occurrence_at = $2, updated_at = NOW() WHERE id = $1",
+    )
+    .bind(series.id)
+    .bind(rule.next_after(series.dtstart, tz, occurrence_at))
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(task)
+}
+
+/// Creates the next occurrence right away when the latest one is completed,
+/// rather than waiting for its date.
+pub async fn on_task_completed(db: &PgPool, task: &Task) -> AppResult<Option<Task>> {
+    let (Some(recurrence_id), Some(occurrence_at)) = (task.recurrence_id, task.occurrence_at)
+    else {
+        return Ok(None);
+    };
+
+    let has_later: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM tasks WHERE recurrence_id = $1 AND occurrence_at > $2)",
+    )
+    .bind(recurrence_id)
+    .bind(occurrence_at)
+    .fetch_one(db)
+    .await?;
+
+    if has_later {
+        return Ok(None);
+    }
+    materialize_next(db, recurrence_id).await
+}
+
+/// Creates the occurrences whose date has arrived. Returns how many tasks
+/// were created. Series in archived or trashed projects wait until the
+/// project is active again.
+pub async fn materialize_due(db: &PgPool) -> AppResult<usize> {
+    let due: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT id FROM task_recurrences
+         WHERE active AND next_occurrence_at <= NOW()
+           AND project_id IN (SELECT id FROM projects
+                              WHERE status = 'active' AND deleted_at IS NULL)
+         ORDER BY next_occurrence_at
+         LIMIT $1",
+    )
+    .bind(DUE_BATCH_SIZE)
+    .fetch_all(db)
+    .await?;
+
+    let mut created = 0;
+    for recurrence_id in due {
+        match materialize_next(db, recurrence_id).await {
+            Ok(Some(_)) => created += 1,
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Failed to materialize recurrence {}: {}", recurrence_id, e),
+        }
+    }
+
+    Ok(created)
+}
+
+/// Runs `materialize_due` every `every` in the background.
+pub fn spawn_scheduler(db: PgPool, every: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(every);
+        loop {
+            ticker.tick().await;
+            match materialize_due(&db).await {
+                Ok(0) => {}
+                Ok(created) => tracing::info!("Created {} recurring task occurrence(s)", created),
+                Err(e) => tracing::warn!("Recurring task scheduler failed: {}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,880 @@
+//! A subset of RFC 5545 recurrence rules: `FREQ` (DAILY, WEEKLY, MONTHLY,
+//! YEARLY), `INTERVAL`, `COUNT`, `UNTIL`, `BYDAY` (with ordinals such as
+//! `-1FR`), `BYMONTHDAY`, `BYMONTH` and `WKST`. Occurrences are computed on
+//! the wall clock of a timezone so a 09:00 meeting stays at 09:00 across DST.
+
+use chrono::{
+    DateTime, Datelike, Duration, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use std::{collections::VecDeque, fmt, str::FromStr};
+
+/// Periods in a row that may produce no occurrence before the rule is
+/// considered exhausted (e.g. `FREQ=MONTHLY;BYMONTHDAY=31;BYMONTH=2`).
+const MAX_EMPTY_PERIODS: u32 = 1_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// A `BYDAY` entry: a weekday, optionally restricted to its n-th (or n-th
+/// last, when negative) occurrence within the month or year.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeekdayNum {
+    pub ordinal: Option<i32>,
+    pub weekday: Weekday,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Until {
+    Date(NaiveDate),
+    Local(NaiveDateTime),
+    Utc(DateTime<Utc>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RRule {
+    pub freq: Frequency,
+    pub interval: u32,
+    pub count: Option<u32>,
+    pub until: Option<Until>,
+    pub by_day: Vec<WeekdayNum>,
+    pub by_month_day: Vec<i32>,
+    pub by_month: Vec<u32>,
+    pub week_start: Weekday,
+}
+
+fn parse_weekday(code: &str) -> Result<Weekday, String> {
+    match code {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        _ => Err(format!("invalid weekday '{}'", code)),
+    }
+}
+
+fn weekday_code(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+fn parse_number<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} must be a number, got '{}'", key, value))
+}
+
+fn parse_weekday_num(value: &str) -> Result<WeekdayNum, String> {
+    let split = value.len().saturating_sub(2);
+    let (ordinal, code) = value.split_at(split);
+    let weekday = parse_weekday(code)?;
+
+    if ordinal.is_empty() {
+        return Ok(WeekdayNum { ordinal: None, weekday });
+    }
+    let n: i32 = parse_number("BYDAY", ordinal.trim_start_matches('+'))?;
+    if n == 0 || n.abs() > 53 {
+        return Err(format!("BYDAY ordinal out of range in '{}'", value));
+    }
+    Ok(WeekdayNum { ordinal: Some(n), weekday })
+}
+
+fn parse_until(value: &str) -> Result<Until, String> {
+    if let Some(utc) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .map(|dt| Until::Utc(dt.and_utc()))
+            .map_err(|_| format!("invalid UNTIL '{}'", value));
+    }
+    if value.contains('T') {
+        return NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+            .map(Until::Local)
+            .map_err(|_| format!("invalid UNTIL '{}'", value));
+    }
+    NaiveDate::parse_from_str(value, "%Y%m%d")
+        .map(Until::Date)
+        .map_err(|_| format!("invalid UNTIL '{}'", value))
+}
+
+fn parse_list<T>(value: &str, parse: impl Fn(&str) -> Result<T, String>) -> Result<Vec<T>, String> {
+    value.split(',').map(|item| parse(item.trim())).collect()
+}
+
+impl FromStr for RRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let body = if s.len() >= 6 && s[..6].eq_ignore_ascii_case("RRULE:") {
+            &s[6..]
+        } else {
+            s
+        };
+
+        let mut freq = None;
+        let mut interval = None;
+        let mut count = None;
+        let mut until = None;
+        let mut by_day = None;
+        let mut by_month_day = None;
+        let mut by_month = None;
+        let mut week_start = None;
+
+        for part in body.split(';').filter(|p| !p.trim().is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", part))?;
+            let key = key.trim().to_ascii_uppercase();
+            let value = value.trim().to_ascii_uppercase();
+
+            let duplicate = match key.as_str() {
+                "FREQ" => freq
+                    .replace(match value.as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        "SECONDLY" | "MINUTELY" | "HOURLY" => {
+                            return Err(format!("FREQ={} is not supported", value))
+                        }
+                        _ => return Err(format!("invalid FREQ '{}'", value)),
+                    })
+                    .is_some(),
+                "INTERVAL" => {
+                    let n: u32 = parse_number(&key, &value)?;
+                    if n == 0 {
+                        return Err("INTERVAL must be at least 1".to_string());
+                    }
+                    interval.replace(n).is_some()
+                }
+                "COUNT" => {
+                    let n: u32 = parse_number(&key, &value)?;
+                    if n == 0 {
+                        return Err("COUNT must be at least 1".to_string());
+                    }
+                    count.replace(n).is_some()
+                }
+                "UNTIL" => until.replace(parse_until(&value)?).is_some(),
+                "BYDAY" => by_day.replace(parse_list(&value, parse_weekday_num)?).is_some(),
+                "BYMONTHDAY" => {
+                    let days = parse_list(&value, |v| {
+                        let n: i32 = parse_number("BYMONTHDAY", v)?;
+                        if n == 0 || n.abs() > 31 {
+                            return Err(format!("BYMONTHDAY out of range: {}", n));
+                        }
+                        Ok(n)
+                    })?;
+                    by_month_day.replace(days).is_some()
+                }
+                "BYMONTH" => {
+                    let months = parse_list(&value, |v| {
+                        let n: u32 = parse_number("BYMONTH", v)?;
+                        if !(1..=12).contains(&n) {
+                            return Err(format!("BYMONTH out of range: {}", n));
+                        }
+                        Ok(n)
+                    })?;
+                    by_month.replace(months).is_some()
+                }
+                "WKST" => week_start.replace(parse_weekday(&value)?).is_some(),
+                "BYSECOND" | "BYMINUTE" | "BYHOUR" | "BYYEARDAY" | "BYWEEKNO" | "BYSETPOS"
+                | "RSCALE" | "SKIP" => return Err(format!("{} is not supported", key)),
+                _ => return Err(format!("unknown rule part '{}'", key)),
+            };
+            if duplicate {
+                return Err(format!("{} may only appear once", key));
+            }
+        }
+
+        let freq = freq.ok_or_else(|| "FREQ is required".to_string())?;
+        if count.is_some() && until.is_some() {
+            return Err("COUNT and UNTIL cannot be combined".to_string());
+        }
+
+        let by_day: Vec<WeekdayNum> = by_day.unwrap_or_default();
+        let by_month_day: Vec<i32> = by_month_day.unwrap_or_default();
+        if by_day.iter().any(|d| d.ordinal.is_some())
+            && !matches!(freq, Frequency::Monthly | Frequency::Yearly)
+        {
+            return Err("BYDAY ordinals require FREQ=MONTHLY or FREQ=YEARLY".to_string());
+        }
+        if freq == Frequency::Monthly && by_day.iter().any(|d| d.ordinal.unwrap_or(1).abs() > 5) {
+            return Err("BYDAY ordinals must be between -5 and 5 with FREQ=MONTHLY".to_string());
+        }
+        if freq == Frequency::Weekly && !by_month_day.is_empty() {
+            return Err("BYMONTHDAY cannot be used with FREQ=WEEKLY".to_string());
+        }
+
+        let mut by_month = by_month.unwrap_or_default();
+        by_month.sort_unstable();
+        by_month.dedup();
+
+        Ok(RRule {
+            freq,
+            interval: interval.unwrap_or(1),
+            count,
+            until,
+            by_day,
+            by_month_day,
+            by_month,
+            week_start: week_start.unwrap_or(Weekday::Mon),
+        })
+    }
+}
+
+impl fmt::Display for RRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let freq = match self.freq {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        };
+        write!(f, "FREQ={}", freq)?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.by_month.is_empty() {
+            let months: Vec<String> = self.by_month.iter().map(u32::to_string).collect();
+            write!(f, ";BYMONTH={}", months.join(","))?;
+        }
+        if !self.by_month_day.is_empty() {
+            let days: Vec<String> = self.by_month_day.iter().map(i32::to_string).collect();
+            write!(f, ";BYMONTHDAY={}", days.join(","))?;
+        }
+        if !self.by_day.is_empty() {
+            let days: Vec<String> = self
+                .by_day
+                .iter()
+                .map(|d| match d.ordinal {
+                    Some(n) => format!("{}{}", n, weekday_code(d.weekday)),
+                    None => weekday_code(d.weekday).to_string(),
+                })
+                .collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if self.week_start != Weekday::Mon {
+            write!(f, ";WKST={}", weekday_code(self.week_start))?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+        match self.until {
+            Some(Until::Date(d)) => write!(f, ";UNTIL={}", d.format("%Y%m%d")),
+            Some(Until::Local(dt)) => write!(f, ";UNTIL={}", dt.format("%Y%m%dT%H%M%S")),
+            Some(Until::Utc(dt)) => write!(f, ";UNTIL={}", dt.format("%Y%m%dT%H%M%SZ")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Maps a wall-clock time onto the timezone. Ambiguous times (DST fall back)
+/// take the earlier instant; times skipped by DST spring-forward move ahead
+/// by an hour.
+pub fn resolve_local(tz: &Tz, local: NaiveDateTime) -> DateTime<Tz> {
+    match tz.from_local_datetime(&local) {
+        LocalResult::Single(dt) => dt,
+        LocalResult::Ambiguous(earliest, _) => earliest,
+        LocalResult::None => tz
+            .from_local_datetime(&(local + Duration::hours(1)))
+            .earliest()
+            .unwrap_or_else(|| tz.from_utc_datetime(&local)),
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    NaiveDate::from_ymd_opt(next_year, next_month, 1)
+        .and_then(|d| d.pred_opt())
+        .map(|d| d.day())
+        .unwrap_or(28)
+}
+
+impl RRule {
+    /// Dates in `[start, end)` matching `BYDAY`, honouring ordinals.
+    fn by_day_dates(&self, start: NaiveDate, end: NaiveDate) -> Vec<NaiveDate> {
+        let mut dates = Vec::new();
+        for entry in &self.by_day {
+            let matching: Vec<NaiveDate> = start
+                .iter_days()
+                .take_while(|d| *d < end)
+                .filter(|d| d.weekday() == entry.weekday)
+                .collect();
+            match entry.ordinal {
+                None => dates.extend(matching),
+                Some(n) if n > 0 => dates.extend(matching.get(n as usize - 1)),
+                Some(n) => dates.extend(
+                    matching
+                        .len()
+                        .checked_sub(n.unsigned_abs() as usize)
+                        .and_then(|i| matching.get(i)),
+                ),
+            }
+        }
+        dates
+    }
+
+    fn month_day_dates(&self, year: i32, month: u32) -> Vec<NaiveDate> {
+        let last = days_in_month(year, month) as i32;
+        self.by_month_day
+            .iter()
+            .filter_map(|&d| {
+                let day = if d > 0 { d } else { last + d + 1 };
+                if day < 1 || day > last {
+                    return None;
+                }
+                NaiveDate::from_ymd_opt(year, month, day as u32)
+            })
+            .collect()
+    }
+
+    /// Candidate dates of one month for MONTHLY rules and YEARLY rules with
+    /// `BYMONTH`.
+    fn month_dates(&self, year: i32, month: u32, default_day: u32) -> Vec<NaiveDate> {
+        if self.by_day.is_empty() && self.by_month_day.is_empty() {
+            return NaiveDate::from_ymd_opt(year, month, default_day)
+                .into_iter()
+                .collect();
+        }
+        let Some(first) = NaiveDate::from_ymd_opt(year, month, 1) else {
+            return Vec::new();
+        };
+        let end = first + Duration::days(days_in_month(year, month) as i64);
+
+        match (self.by_month_day.is_empty(), self.by_day.is_empty()) {
+            (false, true) => self.month_day_dates(year, month),
+            (true, false) => self.by_day_dates(first, end),
+            _ => {
+                let weekdays = self.by_day_dates(first, end);
+                self.month_day_dates(year, month)
+                    .into_iter()
+                    .filter(|d| weekdays.contains(d))
+                    .collect()
+            }
+        }
+    }
+
+    fn matches_filters(&self, date: NaiveDate) -> bool {
+        (self.by_month.is_empty() || self.by_month.contains(&date.month()))
+            && (self.by_day.is_empty() || self.by_day.iter().any(|d| d.weekday == date.weekday()))
+            && (self.by_month_day.is_empty()
+                || self.month_day_dates(date.year(), date.month()).contains(&date))
+    }
+
+    /// Candidate dates of the `index`-th period after the one containing
+    /// `start`, unsorted. `None` once dates leave chrono's range.
+    fn period_dates(&self, start: NaiveDate, index: i64) -> Option<Vec<NaiveDate>> {
+        let step = index.checked_mul(self.interval as i64)?;
+
+        let dates = match self.freq {
+            Frequency::Daily => {
+                let day = start.checked_add_signed(Duration::try_days(step)?)?;
+                vec![day].into_iter().filter(|d| self.matches_filters(*d)).collect()
+            }
+            Frequency::Weekly => {
+                let offset = (7 + start.weekday().num_days_from_monday() as i64
+                    - self.week_start.num_days_from_monday() as i64)
+                    % 7;
+                let week = start
+                    .checked_sub_signed(Duration::days(offset))?
+                    .checked_add_signed(Duration::try_weeks(step)?)?;
+                (0..7)
+                    .map(|i| week + Duration::days(i))
+                    .filter(|d| {
+                        let weekday_ok = if self.by_day.is_empty() {
+                            d.weekday() == start.weekday()
+                        } else {
+                            self.by_day.iter().any(|b| b.weekday == d.weekday())
+                        };
+                        weekday_ok
+                            && (self.by_month.is_empty() || self.by_month.contains(&d.month()))
+                    })
+                    .collect()
+            }
+            Frequency::Monthly => {
+                let months = (start.month0() as i64).checked_add(step)?;
+                let year = i32::try_from(start.year() as i64 + months.div_euclid(12)).ok()?;
+                let month = months.rem_euclid(12) as u32 + 1;
+                NaiveDate::from_ymd_opt(year, month, 1)?;
+                if !self.by_month.is_empty() && !self.by_month.contains(&month) {
+                    Vec::new()
+                } else {
+                    self.month_dates(year, month, start.day())
+                }
+            }
+            Frequency::Yearly => {
+                let year = i32::try_from(start.year() as i64 + step).ok()?;
+                NaiveDate::from_ymd_opt(year, 1, 1)?;
+                if !self.by_month.is_empty() {
+                    self.by_month
+                        .iter()
+                        .flat_map(|&m| self.month_dates(year, m, start.day()))
+                        .collect()
+                } else if !self.by_month_day.is_empty() {
+                    (1..=12)
+                        .flat_map(|m| self.month_dates(year, m, start.day()))
+                        .collect()
+                } else if !self.by_day.is_empty() {
+                    let first = NaiveDate::from_ymd_opt(year, 1, 1)?;
+                    let end = NaiveDate::from_ymd_opt(year + 1, 1, 1)?;
+                    self.by_day_dates(first, end)
+                } else {
+                    NaiveDate::from_ymd_opt(year, start.month(), start.day())
+                        .into_iter()
+                        .collect()
+                }
+            }
+        };
+        Some(dates)
+    }
+
+    fn before_until(&self, at: &DateTime<Tz>) -> bool {
+        match self.until {
+            None => true,
+            Some(Until::Date(date)) => at.date_naive() <= date,
+            Some(Until::Local(local)) => at.naive_local() <= local,
+            Some(Until::Utc(utc)) => at.with_timezone(&Utc) <= utc,
+        }
+    }
+
+    /// Iterates the occurrences of the rule starting at `dtstart`, a wall-clock
+    /// time in `tz`. As in RFC 5545, `dtstart` is always the first occurrence.
+    pub fn occurrences(&self, dtstart: NaiveDateTime, tz: Tz) -> Occurrences<'_> {
+        Occurrences {
+            rule: self,
+            tz,
+            dtstart,
+            period: 0,
+            pending: VecDeque::new(),
+            emitted: 0,
+            empty_periods: 0,
+            done: false,
+        }
+    }
+
+    /// The first occurrence strictly after `after`, if the rule has one.
+    pub fn next_after(
+        &self,
+        dtstart: NaiveDateTime,
+        tz: Tz,
+        after: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        self.occurrences(dtstart, tz)
+            .map(|o| o.with_timezone(&Utc))
+            .find(|o| *o > after)
+    }
+}
+
+pub struct Occurrences<'a> {
+    rule: &'a RRule,
+    tz: Tz,
+    dtstart: NaiveDateTime,
+    period: i64,
+    pending: VecDeque<NaiveDateTime>,
+    emitted: u32,
+    empty_periods: u32,
+    done: bool,
+}
+
+impl Occurrences<'_> {
+    fn emit(&mut self, local: NaiveDateTime) -> Option<DateTime<Tz>> {
+        let at = resolve_local(&self.tz, local);
+        if !self.rule.before_until(&at) {
+            self.done = true;
+            return None;
+        }
+        self.emitted += 1;
+        if self.rule.count.is_some_and(|c| self.emitted >= c) {
+            self.done = true;
+        }
+        Some(at)
+    }
+}
+
+impl Iterator for Occurrences<'_> {
+    type Item = DateTime<Tz>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if self.emitted == 0 {
+            return self.emit(self.dtstart);
+        }
+
+        loop {
+            if let Some(local) = self.pending.pop_front() {
+                return self.emit(local);
+            }
+
+            if self.empty_periods >= MAX_EMPTY_PERIODS {
+                self.done = true;
+                return None;
+            }
+
+            let Some(mut dates) = self.rule.period_dates(self.dtstart.date(), self.period) else {
+                self.done = true;
+                return None;
+            };
+            self.period += 1;
+
+            dates.sort_unstable();
+            dates.dedup();
+            let time = self.dtstart.time();
+            self.pending.extend(
+                dates
+                    .into_iter()
+                    .map(|d| d.and_time(time))
+                    .filter(|local| *local > self.dtstart),
+            );
+
+            if self.pending.is_empty() {
+                self.empty_periods += 1;
+            } else {
+                self.empty_periods = 0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(input: &str) -> RRule {
+        input
+            .parse()
+            .unwrap_or_else(|e| panic!("{:?} failed: {}", input, e))
+    }
+
+    fn error(input: &str) -> String {
+        match input.parse::<RRule>() {
+            Ok(rule) => panic!("{:?} parsed as {:?}", input, rule),
+            Err(err) => err,
+        }
+    }
+
+    fn local(raw: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    /// The first `limit` occurrences as local dates.
+    fn dates(input: &str, start: &str, limit: usize) -> Vec<String> {
+        rule(input)
+            .occurrences(local(&format!("{} 09:00", start)), Tz::UTC)
+            .take(limit)
+            .map(|o| o.format("%Y-%m-%d").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn parses_every_supported_part() {
+        let parsed = rule(
+            "RRULE:FREQ=MONTHLY;INTERVAL=2;BYDAY=-1FR,+2MO,TU;BYMONTHDAY=1,-1;BYMONTH=6,1,6;WKST=SU;COUNT=5",
+        );
+        assert_eq!(parsed.freq, Frequency::Monthly);
+        assert_eq!(parsed.interval, 2);
+        assert_eq!(parsed.count, Some(5));
+        assert_eq!(
+            parsed.by_day,
+            vec![
+                WeekdayNum { ordinal: Some(-1), weekday: Weekday::Fri },
+                WeekdayNum { ordinal: Some(2), weekday: Weekday::Mon },
+                WeekdayNum { ordinal: None, weekday: Weekday::Tue },
+            ]
+        );
+        assert_eq!(parsed.by_month_day, vec![1, -1]);
+        assert_eq!(parsed.by_month, vec![1, 6]);
+        assert_eq!(parsed.week_start, Weekday::Sun);
+
+        assert_eq!(
+            rule("freq=weekly; until=20240131").until,
+            Some(Until::Date(NaiveDate::from_ymd_opt(2024, 1, 31).unwrap()))
+        );
+        assert_eq!(
+            rule("FREQ=DAILY;UNTIL=20240131T120000").until,
+            Some(Until::Local(local("2024-01-31 12:00")))
+        );
+        assert_eq!(
+            rule("FREQ=DAILY;UNTIL=20240131T120000Z").until,
+            Some(Until::Utc(local("2024-01-31 12:00").and_utc()))
+        );
+    }
+
+    #[test]
+    fn displays_rules_in_canonical_form() {
+        for input in [
+            "FREQ=DAILY",
+            "FREQ=WEEKLY;INTERVAL=2;BYDAY=TU,SU;WKST=SU;COUNT=4",
+            "FREQ=MONTHLY;BYDAY=-1FR;UNTIL=20241231",
+            "FREQ=YEARLY;BYMONTH=11;BYDAY=4TH;UNTIL=20301231T090000Z",
+            "FREQ=MONTHLY;BYMONTHDAY=-1;UNTIL=20240601T090000",
+        ] {
+            assert_eq!(rule(input).to_string(), input);
+            assert_eq!(rule(&rule(input).to_string()), rule(input));
+        }
+        assert_eq!(
+            rule("RRULE:COUNT=3;FREQ=WEEKLY;INTERVAL=1;WKST=MO").to_string(),
+            "FREQ=WEEKLY;COUNT=3"
+        );
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        let cases = [
+            ("", "FREQ is required"),
+            ("INTERVAL=2", "FREQ is required"),
+            ("FREQ", "expected KEY=VALUE, got 'FREQ'"),
+            ("FREQ=FORTNIGHTLY", "invalid FREQ 'FORTNIGHTLY'"),
+            ("FREQ=HOURLY", "FREQ=HOURLY is not supported"),
+            ("FREQ=DAILY;FREQ=WEEKLY", "FREQ may only appear once"),
+            ("FREQ=DAILY;INTERVAL=0", "INTERVAL must be at least 1"),
+            ("FREQ=DAILY;INTERVAL=two", "INTERVAL must be a number, got 'TWO'"),
+            ("FREQ=DAILY;COUNT=0", "COUNT must be at least 1"),
+            ("FREQ=DAILY;COUNT=-1", "COUNT must be a number, got '-1'"),
+            ("FREQ=DAILY;UNTIL=2024-01-31", "invalid UNTIL '2024-01-31'"),
+            ("FREQ=DAILY;UNTIL=20240230", "invalid UNTIL '20240230'"),
+            (
+                "FREQ=DAILY;COUNT=2;UNTIL=20240131",
+                "COUNT and UNTIL cannot be combined",
+            ),
+            ("FREQ=WEEKLY;BYDAY=XX", "invalid weekday 'XX'"),
+            ("FREQ=MONTHLY;BYDAY=0MO", "BYDAY ordinal out of range in '0MO'"),
+            ("FREQ=YEARLY;BYDAY=54MO", "BYDAY ordinal out of range in '54MO'"),
+            (
+                "FREQ=WEEKLY;BYDAY=1MO",
+                "BYDAY ordinals require FREQ=MONTHLY or FREQ=YEARLY",
+            ),
+            (
+                "FREQ=MONTHLY;BYDAY=6FR",
+                "BYDAY ordinals must be between -5 and 5 with FREQ=MONTHLY",
+            ),
+            ("FREQ=MONTHLY;BYMONTHDAY=32", "BYMONTHDAY out of range: 32"),
+            ("FREQ=MONTHLY;BYMONTHDAY=0", "BYMONTHDAY out of range: 0"),
+            (
+                "FREQ=WEEKLY;BYMONTHDAY=1",
+                "BYMONTHDAY cannot be used with FREQ=WEEKLY",
+            ),
+            ("FREQ=YEARLY;BYMONTH=13", "BYMONTH out of range: 13"),
+            ("FREQ=WEEKLY;WKST=XX", "invalid weekday 'XX'"),
+            ("FREQ=MONTHLY;BYSETPOS=-1", "BYSETPOS is not supported"),
+            ("FREQ=DAILY;COLOR=RED", "unknown rule part 'COLOR'"),
+        ];
+        for (input, message) in cases {
+            assert_eq!(error(input), message, "{}", input);
+        }
+    }
+
+    #[test]
+    fn dtstart_is_always_the_first_occurrence() {
+        // 2024-01-03 is a Wednesday.
+        assert_eq!(
+            dates("FREQ=WEEKLY;BYDAY=MO", "2024-01-03", 3),
+            ["2024-01-03", "2024-01-08", "2024-01-15"]
+        );
+    }
+
+    #[test]
+    fn expands_byday_ordinals() {
+        // The last Friday of each month.
+        assert_eq!(
+            dates("FREQ=MONTHLY;BYDAY=-1FR", "2024-01-26", 4),
+            ["2024-01-26", "2024-02-23", "2024-03-29", "2024-04-26"]
+        );
+        // The second Tuesday and the first Monday.
+        assert_eq!(
+            dates("FREQ=MONTHLY;BYDAY=2TU,1MO", "2024-01-01", 5),
+            ["2024-01-01", "2024-01-09", "2024-02-05", "2024-02-13", "2024-03-04"]
+        );
+        // A fifth Friday only some months have.
+        assert_eq!(
+            dates("FREQ=MONTHLY;BYDAY=5FR", "2024-03-29", 3),
+            ["2024-03-29", "2024-05-31", "2024-08-30"]
+        );
+        // US Thanksgiving.
+        assert_eq!(
+            dates("FREQ=YEARLY;BYMONTH=11;BYDAY=4TH", "2024-11-28", 3),
+            ["2024-11-28", "2025-11-27", "2026-11-26"]
+        );
+        // The last Monday of the year.
+        assert_eq!(
+            dates("FREQ=YEARLY;BYDAY=-1MO", "2024-12-30", 3),
+            ["2024-12-30", "2025-12-29", "2026-12-28"]
+        );
+    }
+
+    #[test]
+    fn skips_months_without_the_day() {
+        assert_eq!(
+            dates("FREQ=MONTHLY;BYMONTHDAY=31", "2024-01-31", 4),
+            ["2024-01-31", "2024-03-31", "2024-05-31", "2024-07-31"]
+        );
+        // Without BYMONTHDAY the day of dtstart is used, with the same gaps.
+        assert_eq!(
+            dates("FREQ=MONTHLY", "2024-01-31", 3),
+            ["2024-01-31", "2024-03-31", "2024-05-31"]
+        );
+        // Counting from the end always finds the day.
+        assert_eq!(
+            dates("FREQ=MONTHLY;BYMONTHDAY=-1", "2024-01-31", 4),
+            ["2024-01-31", "2024-02-29", "2024-03-31", "2024-04-30"]
+        );
+        assert_eq!(
+            dates("FREQ=MONTHLY;BYMONTHDAY=30,31", "2024-01-30", 4),
+            ["2024-01-30", "2024-01-31", "2024-03-30", "2024-03-31"]
+        );
+    }
+
+    #[test]
+    fn yearly_rules_on_february_29_wait_for_leap_years() {
+        assert_eq!(
+            dates("FREQ=YEARLY", "2024-02-29", 3),
+            ["2024-02-29", "2028-02-29", "2032-02-29"]
+        );
+        assert_eq!(
+            dates("FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=-1", "2024-02-29", 3),
+            ["2024-02-29", "2025-02-28", "2026-02-28"]
+        );
+        assert_eq!(
+            dates("FREQ=MONTHLY;BYMONTHDAY=29", "2023-01-29", 3),
+            ["2023-01-29", "2023-03-29", "2023-04-29"]
+        );
+    }
+
+    #[test]
+    fn rules_that_never_match_again_end() {
+        let rule = rule("FREQ=MONTHLY;BYMONTH=2;BYMONTHDAY=31");
+        let occurrences: Vec<_> = rule.occurrences(local("2024-01-31 09:00"), Tz::UTC).collect();
+        assert_eq!(occurrences.len(), 1);
+    }
+
+    #[test]
+    fn stops_after_count() {
+        assert_eq!(dates("FREQ=DAILY;COUNT=3", "2024-01-01", 10).len(), 3);
+        assert_eq!(dates("FREQ=DAILY;COUNT=1", "2024-01-01", 10), ["2024-01-01"]);
+        assert_eq!(
+            dates("FREQ=WEEKLY;BYDAY=MO,FR;COUNT=4", "2024-01-01", 10),
+            ["2024-01-01", "2024-01-05", "2024-01-08", "2024-01-12"]
+        );
+    }
+
+    #[test]
+    fn stops_at_until() {
+        // A date includes the whole day.
+        assert_eq!(dates("FREQ=DAILY;UNTIL=20240105", "2024-01-01", 10).len(), 5);
+        assert_eq!(
+            dates("FREQ=DAILY;UNTIL=20240105T090000", "2024-01-01", 10).len(),
+            5
+        );
+        assert_eq!(
+            dates("FREQ=DAILY;UNTIL=20240105T085959", "2024-01-01", 10).len(),
+            4
+        );
+
+        // 09:00 in Berlin is 08:00 UTC in winter.
+        let berlin: Tz = "Europe/Berlin".parse().unwrap();
+        let count = |input: &str| {
+            rule(input)
+                .occurrences(local("2024-01-01 09:00"), berlin)
+                .count()
+        };
+        assert_eq!(count("FREQ=DAILY;UNTIL=20240103T080000Z"), 3);
+        assert_eq!(count("FREQ=DAILY;UNTIL=20240103T075959Z"), 2);
+    }
+
+    #[test]
+    fn week_start_decides_which_days_share_a_week() {
+        // RFC 5545, section 3.8.5.3: the same rule with a different WKST.
+        assert_eq!(
+            dates(
+                "FREQ=WEEKLY;INTERVAL=2;COUNT=4;BYDAY=TU,SU;WKST=MO",
+                "1997-08-05",
+                10
+            ),
+            ["1997-08-05", "1997-08-10", "1997-08-19", "1997-08-24"]
+        );
+        assert_eq!(
+            dates(
+                "FREQ=WEEKLY;INTERVAL=2;COUNT=4;BYDAY=TU,SU;WKST=SU",
+                "1997-08-05",
+                10
+            ),
+            ["1997-08-05", "1997-08-17", "1997-08-19", "1997-08-31"]
+        );
+    }
+
+    #[test]
+    fn keeps_the_wall_clock_time_across_dst() {
+        let new_york: Tz = "America/New_York".parse().unwrap();
+        let utc: Vec<String> = rule("FREQ=DAILY;COUNT=3")
+            .occurrences(local("2024-03-09 09:00"), new_york)
+            .map(|o| {
+                assert_eq!(o.format("%H:%M").to_string(), "09:00");
+                o.with_timezone(&Utc).format("%m-%d %H:%M").to_string()
+            })
+            .collect();
+        assert_eq!(utc, ["03-09 14:00", "03-10 13:00", "03-11 13:00"]);
+
+        let utc: Vec<String> = rule("FREQ=WEEKLY;COUNT=2")
+            .occurrences(local("2024-10-28 09:00"), new_york)
+            .map(|o| o.with_timezone(&Utc).format("%m-%d %H:%M").to_string())
+            .collect();
+        assert_eq!(utc, ["10-28 13:00", "11-04 14:00"]);
+    }
+
+    #[test]
+    fn resolves_skipped_and_repeated_local_times() {
+        let new_york: Tz = "America/New_York".parse().unwrap();
+        let at = |raw: &str| {
+            resolve_local(&new_york, local(raw))
+                .with_timezone(&Utc)
+                .format("%m-%d %H:%M")
+                .to_string()
+        };
+
+        // 02:30 does not exist on the day clocks spring forward.
+        assert_eq!(at("2024-03-10 02:30"), "03-10 07:30");
+        // 01:30 happens twice on the day they fall back; the first is used.
+        assert_eq!(at("2024-11-03 01:30"), "11-03 05:30");
+
+        let local_times: Vec<String> = rule("FREQ=DAILY;COUNT=3")
+            .occurrences(local("2024-03-09 02:30"), new_york)
+            .map(|o| o.format("%m-%d %H:%M").to_string())
+            .collect();
+        assert_eq!(local_times, ["03-09 02:30", "03-10 03:30", "03-11 02:30"]);
+    }
+
+    #[test]
+    fn finds_the_next_occurrence_after_an_instant() {
+        let rule = rule("FREQ=WEEKLY;BYDAY=MO,TH;COUNT=4");
+        let start = local("2024-01-01 09:00");
+        let next = |after: &str| rule.next_after(start, Tz::UTC, local(after).and_utc());
+
+        assert_eq!(next("2023-12-31 00:00"), Some(local("2024-01-01 09:00").and_utc()));
+        assert_eq!(next("2024-01-01 09:00"), Some(local("2024-01-04 09:00").and_utc()));
+        assert_eq!(next("2024-01-08 09:00"), Some(local("2024-01-11 09:00").and_utc()));
+        assert_eq!(next("2024-01-11 09:00"), None);
+    }
+
+    #[test]
+    fn ends_at_the_edge_of_the_calendar() {
+        let last = rule("FREQ=YEARLY;INTERVAL=100000")
+            .occurrences(local("2024-01-01 09:00"), Tz::UTC)
+            .last();
+        assert!(last.is_some());
+    }
+}
@@ -0,0 +1,58 @@
+//! CSV cells for files that are likely to be opened in a spreadsheet.
+
+/// Whether a spreadsheet would read the cell as a formula: it starts with
+/// `=`, `+`, `-` or `@`, or with a tab or carriage return.
+fn is_formula(value: &str) -> bool {
+    value.starts_with(['=', '+', '-', '@', '\t', '\r'])
+}
+
+/// Prefixes `'` to a cell that a spreadsheet would otherwise read as a
+/// formula. Cells already starting with `'` get one more, so that
+/// `unescape_cell` gives back exactly what was escaped.
+pub fn escape_cell(mut value: String) -> String {
+    if is_formula(&value) || value.starts_with('\'') {
+        value.insert(0, '\'');
+    }
+    value
+}
+
+/// Undoes `escape_cell` on a cell read back from a file: drops one `'` in
+/// front of a formula or of another `'`. Other cells are left as they are.
+pub fn unescape_cell(value: &str) -> &str {
+    match value.strip_prefix('\'') {
+        Some(rest) if is_formula(rest) || rest.starts_with('\'') => rest,
+        _ => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_formulas() {
+        let cases = [
+            ("=SUM(A1:A2)", "'=SUM(A1:A2)"),
+            ("+1", "'+1"),
+            ("-2+3", "'-2+3"),
+            ("@cmd", "'@cmd"),
+            ("\t=1", "'\t=1"),
+            ("\r=1", "'\r=1"),
+            ("Plain title", "Plain title"),
+            ("'quoted", "''quoted"),
+            ("a=b", "a=b"),
+            ("", ""),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(escape_cell(input.to_string()), expected, "{input:?}");
+            assert_eq!(unescape_cell(expected), input, "{expected:?}");
+        }
+    }
+
+    #[test]
+    fn leaves_cells_that_were_not_escaped() {
+        for cell in ["'quoted", "'", "it's", "- buy milk", "plain"] {
+            assert_eq!(unescape_cell(cell), cell, "{cell:?}");
+        }
+    }
+}
@@ -0,0 +1,175 @@
+// Attachment storage backends
+pub mod local;
+pub mod s3;
+
+use async_trait::async_trait;
+use axum::body::Bytes;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use shared::errors::{AppError, AppResult};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub use local::LocalStorage;
+pub use s3::{S3Config, S3Storage};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Blob store for attachment contents. Keys are generated by the service and
+/// never derived from user input.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn put(&self, key: &str, data: Bytes, content_type: &str) -> AppResult<()>;
+    async fn get(&self, key: &str) -> AppResult<Bytes>;
+    async fn delete(&self, key: &str) -> AppResult<()>;
+}
+
+/// Builds the storage backend selected by `STORAGE_BACKEND` (`local` or `s3`).
+pub fn from_env() -> Arc<dyn Storage> {
+    let backend = std::env::var("STORAGE_BACKEND").unwrap_or_else(|_| "local".to_string());
+
+    match backend.as_str() {
+        "s3" => {
+            let config = S3Config {
+                endpoint: std::env::var("S3_ENDPOINT")
+                    .unwrap_or_else(|_| "http://localhost:9000".to_string()),
+                bucket: std::env::var("S3_BUCKET").expect("S3_BUCKET must be set"),
+                region: std::env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+                access_key: std::env::var("S3_ACCESS_KEY").expect("S3_ACCESS_KEY must be set"),
+                secret_key: std::env::var("S3_SECRET_KEY").expect("S3_SECRET_KEY must be set"),
+            };
+            Arc::new(S3Storage::new(config))
+        }
+        _ => {
+            let root = std::env::var("STORAGE_LOCAL_PATH")
+                .unwrap_or_else(|_| "./data/attachments".to_string());
+            Arc::new(LocalStorage::new(root))
+        }
+    }
+}
+
+/// Signs and verifies expiring download URLs for attachments.
+#[derive(Clone)]
+pub struct UrlSigner {
+    secret: Arc<Vec<u8>>,
+    ttl_seconds: i64,
+}
+
+impl UrlSigner {
+    pub fn new(secret: String, ttl_seconds: i64) -> Self {
+        Self {
+            secret: Arc::new(secret.into_bytes()),
+            ttl_seconds,
+        }
+    }
+
+    fn signature(&self, attachment_id: Uuid, expires: i64) -> String {
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts any key length");
+        mac.update(format!("{}:{}", attachment_id, expires).as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    /// Returns the relative download path and its expiry timestamp.
+    pub fn sign(&self, attachment_id: Uuid) -> (String, i64) {
+        let expires = Utc::now().timestamp() + self.ttl_seconds;
+        let signature = self.signature(attachment_id, expires);
+        (
+            format!(
+                "/attachments/{}/download?expires={}&signature={}",
+                attachment_id, expires, signature
+            ),
+            expires,
+        )
+    }
+
+    pub fn verify(&self, attachment_id: Uuid, expires: i64, signature: &str) -> AppResult<()> {
+        if expires < Utc::now().timestamp() {
+            return Err(AppError::Forbidden("Download link has expired".to_string()));
+        }
+
+        let provided = hex::decode(signature)
+            .map_err(|_| AppError::Forbidden("Invalid download signature".to_string()))?;
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts any key length");
+        mac.update(format!("{}:{}", attachment_id, expires).as_bytes());
+
+        mac.verify_slice(&provided)
+            .map_err(|_| AppError::Forbidden("Invalid download signature".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The expiry and signature from a signed download path.
+    fn query(path: &str) -> (i64, String) {
+        let (_, query) = path.split_once('?').unwrap();
+        let mut expires = None;
+        let mut signature = None;
+        for pair in query.split('&') {
+            match pair.split_once('=').unwrap() {
+                ("expires", value) => expires = Some(value.parse().unwrap()),
+                ("signature", value) => signature = Some(value.to_string()),
+                (key, _) => panic!("unexpected parameter {}", key),
+            }
+        }
+        (expires.unwrap(), signature.unwrap())
+    }
+
+    fn forbidden(result: AppResult<()>) -> String {
+        match result {
+            Err(AppError::Forbidden(message)) => message,
+            other => panic!("expected Forbidden, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn signed_urls_verify() {
+        let signer = UrlSigner::new("secret".to_string(), 300);
+        let id = Uuid::new_v4();
+        let (path, expires) = signer.sign(id);
+
+        assert!(path.starts_with(&format!("/attachments/{}/download?", id)));
+        let (in_path, signature) = query(&path);
+        assert_eq!(in_path, expires);
+        assert!((expires - Utc::now().timestamp() - 300).abs() <= 1);
+        signer.verify(id, expires, &signature).unwrap();
+    }
+
+    #[test]
+    fn rejects_tampered_urls() {
+        let signer = UrlSigner::new("secret".to_string(), 300);
+        let id = Uuid::new_v4();
+        let (path, expires) = signer.sign(id);
+        let (_, signature) = query(&path);
+
+        let invalid = "Invalid download signature";
+        assert_eq!(forbidden(signer.verify(Uuid::new_v4(), expires, &signature)), invalid);
+        assert_eq!(forbidden(signer.verify(id, expires + 3600, &signature)), invalid);
+        assert_eq!(forbidden(signer.verify(id, expires, &signature[2..])), invalid);
+        assert_eq!(forbidden(signer.verify(id, expires, "not hex")), invalid);
+        assert_eq!(forbidden(signer.verify(id, expires, "")), invalid);
+
+        let other = UrlSigner::new("other secret".to_string(), 300);
+        assert_eq!(forbidden(other.verify(id, expires, &signature)), invalid);
+    }
+
+    #[test]
+    fn rejects_expired_urls() {
+        let signer = UrlSigner::new("secret".to_string(), -1);
+        let id = Uuid::new_v4();
+        let (path, expires) = signer.sign(id);
+        let (_, signature) = query(&path);
+
+        assert_eq!(
+            forbidden(signer.verify(id, expires, &signature)),
+            "Download link has expired"
+        );
+        // Expiry is checked before the signature, which cannot rescue it.
+        assert_eq!(
+            forbidden(signer.verify(id, expires, "00")),
+            "Download link has expired"
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use async_trait::async_trait;
+use axum::body::Bytes;
+use shared::errors::{AppError, AppResult};
+use std::{
+    io::ErrorKind,
+    path::{Component, Path, PathBuf},
+};
+
+use super::Storage;
+
+/// Stores attachments as plain files under a root directory.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path_for(&self, key: &str) -> AppResult<PathBuf> {
+        let relative = Path::new(key);
+        let is_safe = relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+
+        if key.is_empty() || !is_safe {
+            return Err(AppError::InternalError(format!("Invalid storage key: {}", key)));
+        }
+
+        Ok(self.root.join(relative))
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, data: Bytes, _content_type: &str) -> AppResult<()> {
+        let path = self.path_for(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| AppError::InternalError(format!("Failed to create directory: {}", e)))?;
+        }
+
+        tokio::fs::write(&path, &data)
+            .await
+            .map_err(|e| AppError::InternalError(format!("Failed to write file: {}", e)))
+    }
+
+    async fn get(&self, key: &str) -> AppResult<Bytes> {
+        let path = self.path_for(key)?;
+        match tokio::fs::read(&path).await {
+            Ok(data) => Ok(Bytes::from(data)),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                Err(AppError::NotFound("Attachment content not found".to_string()))
+            }
+            Err(e) => Err(AppError::InternalError(format!("Failed to read file: {}", e))),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> AppResult<()> {
+        let path = self.path_for(key)?;
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(AppError::InternalError(format!("Failed to delete file: {}", e))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A storage rooted in a fresh directory, removed when dropped.
+    struct TempStorage {
+        storage: LocalStorage,
+    }
+
+    impl TempStorage {
+        fn new() -> Self {
+            let root = std::env::temp_dir().join(format!("local-storage-{}", uuid::Uuid::new_v4()));
+            Self {
+                storage: LocalStorage::new(root),
+            }
+        }
+    }
+
+    impl Drop for TempStorage {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.storage.root);
+        }
+    }
+
+    #[test]
+    fn keeps_keys_inside_the_root() {
+        let storage = LocalStorage::new("/srv/attachments");
+        assert_eq!(
+            storage.path_for("projects/1/file.pdf").unwrap(),
+            Path::new("/srv/attachments/projects/1/file.pdf")
+        );
+        assert_eq!(
+            storage.path_for("..file").unwrap(),
+            Path::new("/srv/attachments/..file")
+        );
+
+        for key in [
+            "",
+            "..",
+            "../secret",
+            "projects/../../secret",
+            "projects/..",
+            "/etc/passwd",
+            "./file",
+        ] {
+            match storage.path_for(key) {
+                Err(AppError::InternalError(message)) => {
+                    assert_eq!(message, format!("Invalid storage key: {}", key))
+                }
+                other => panic!("{:?} gave {:?}", key, other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn stores_reads_and_deletes_files() {
+        let temp = TempStorage::new();
+        let storage = &temp.storage;
+
+        storage
+            .put("projects/1/file.txt", Bytes::from_static(b"hello"), "text/plain")
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.get("projects/1/file.txt").await.unwrap(),
+            Bytes::from_static(b"hello")
+        );
+
+        storage.delete("projects/1/file.txt").await.unwrap();
+        assert!(matches!(
+            storage.get("projects/1/file.txt").await,
+            Err(AppError::NotFound(_))
+        ));
+        // Deleting what is already gone is not an error.
+        storage.delete("projects/1/file.txt").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn refuses_traversal_on_every_operation() {
+        let temp = TempStorage::new();
+        let storage = &temp.storage;
+        let key = "../escaped.txt";
+
+        assert!(storage.put(key, Bytes::from_static(b"x"), "text/plain").await.is_err());
+        assert!(storage.get(key).await.is_err());
+        assert!(storage.delete(key).await.is_err());
+        assert!(!storage.root.parent().unwrap().join("escaped.txt").exists());
+    }
+}
@@ -0,0 +1,419 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde_json::{Map, Value};
+use shared::{
+    errors::{AppError, AppResult},
+    models::{Project, Task},
+};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::{
+    activity::{self, Entity},
+    handlers::{custom_field, label, task::TASK_COLUMNS},
+    models::{TemplateContent, TemplateField, TemplateLabel, TemplateSprint, TemplateTask},
+};
+
+/// What a snapshot keeps beyond the project's structure.
+#[derive(Debug, Clone, Copy)]
+pub struct SnapshotOptions {
+    pub include_tasks: bool,
+    /// Task statuses and remaining estimates.
+    pub keep_progress: bool,
+    /// Assignees and `user` custom field values.
+    pub keep_people: bool,
+}
+
+fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    date.and_time(chrono::NaiveTime::MIN).and_utc()
+}
+
+fn out_of_range() -> AppError {
+    AppError::ValidationError("start_date puts the template's dates out of range".to_string())
+}
+
+/// Places a sprint day offset relative to `start`.
+fn shift_day(start: NaiveDate, offset_days: i64) -> AppResult<NaiveDate> {
+    Duration::try_days(offset_days)
+        .and_then(|offset| start.checked_add_signed(offset))
+        .ok_or_else(out_of_range)
+}
+
+/// Places a deadline offset relative to `origin`, midnight of the start day.
+fn shift_deadline(origin: DateTime<Utc>, offset_minutes: i64) -> AppResult<DateTime<Utc>> {
+    Duration::try_minutes(offset_minutes)
+        .and_then(|offset| origin.checked_add_signed(offset))
+        .ok_or_else(out_of_range)
+}
+
+/// Captures a project's labels, custom fields, open sprints and tasks with
+/// dates relative to `reference`.
+pub async fn snapshot(
+    db: &PgPool,
+    project: &Project,
+    reference: NaiveDate,
+    options: SnapshotOptions,
+) -> AppResult<TemplateContent> {
+    let origin = start_of_day(reference);
+
+    let labels: Vec<(String, String)> =
+        sqlx::query_as("SELECT name, color FROM labels WHERE project_id = $1 ORDER BY LOWER(name)")
+            .bind(project.id)
+            .fetch_all(db)
+            .await?;
+
+    let fields = custom_field::project_fields(db, project.id).await?;
+
+    let sprints: Vec<(Uuid, String, Option<String>, NaiveDate, NaiveDate)> = sqlx::query_as(
+        "SELECT id, name, goal, start_date, end_date FROM sprints
+         WHERE project_id = $1 AND status <> 'completed'
+         ORDER BY start_date, created_at",
+    )
+    .bind(project.id)
+    .fetch_all(db)
+    .await?;
+
+    let mut content = TemplateContent {
+        description: project.description.clone(),
+        labels: labels
+            .into_iter()
+            .map(|(name, color)| TemplateLabel { name, color })
+            .collect(),
+        custom_fields: fields
+            .iter()
+            .map(|f| TemplateField {
+                name: f.name.clone(),
+                field_type: f.field_type.clone(),
+                options: f.options.clone(),
+                required: f.required,
+                position: f.position,
+            })
+            .collect(),
+        sprints: sprints
+            .iter()
+            .map(|(_, name, goal, start, end)| TemplateSprint {
+                name: name.clone(),
+                goal: goal.clone(),
+                start_offset_days: (*start - reference).num_days(),
+                end_offset_days: (*end - reference).num_days(),
+            })
+            .collect(),
+        tasks: Vec::new(),
+    };
+
+    if !options.include_tasks {
+        return Ok(content);
+    }
+
+    let tasks = sqlx::query_as::<_, Task>(&format!(
+        "SELECT {} FROM tasks t
+         WHERE t.project_id = $1 AND t.deleted_at IS NULL
+         ORDER BY t.created_at, t.id",
+        TASK_COLUMNS
+    ))
+    .bind(project.id)
+    .fetch_all(db)
+    .await?;
+    let ids: Vec<Uuid> = tasks.iter().map(|t| t.id).collect();
+    let mut task_labels = label::labels_for_tasks(db, &ids).await?;
+
+    for task in tasks {
+        let mut values = Map::new();
+        if let Some(existing) = task.custom_fields.as_object() {
+            for (key, value) in existing {
+                let Some(field) = fields.iter().find(|f| f.id.to_string() == *key) else {
+                    continue;
+                };
+                if field.field_type == "user" && !options.keep_people {
+                    continue;
+                }
+                values.insert(field.name.clone(), value.clone());
+            }
+        }
+
+        content.tasks.push(TemplateTask {
+            title: task.title,
+            description: task.description,
+            priority: task.priority,
+            status: options.keep_progress.then_some(task.status),
+            assignee_id: task.assignee_id.filter(|_| options.keep_people),
+            deadline_offset_minutes: task.deadline.map(|d| (d - origin).num_minutes()),
+            original_estimate_minutes: task.original_estimate_minutes,
+            remaining_estimate_minutes: task
+                .remaining_estimate_minutes
+                .filter(|_| options.keep_progress),
+            labels: task_labels
+                .remove(&task.id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|l| l.name)
+                .collect(),
+            custom_fields: values,
+            sprint: task
+                .sprint_id
+                .and_then(|id| sprints.iter().position(|s| s.0 == id)),
+        });
+    }
+
+    Ok(content)
+}
+
+/// Creates the snapshot's structure and tasks in `project_id`, placing dates
+/// relative to `start`. Assignees that are not members of the project are
+/// dropped.
+pub async fn instantiate(
+    tx: &mut Transaction<'_, Postgres>,
+    project_id: Uuid,
+    content: &TemplateContent,
+    start: NaiveDate,
+    actor_id: Uuid,
+) -> AppResult<()> {
+    let origin = start_of_day(start);
+    let deadlines = content
+        .tasks
+        .iter()
+        .map(|t| {
+            t.deadline_offset_minutes
+                .map(|m| shift_deadline(origin, m))
+                .transpose()
+        })
+        .collect::<AppResult<Vec<_>>>()?;
+
+    let mut label_ids: HashMap<String, Uuid> = HashMap::new();
+    for l in &content.labels {
+        let id: Uuid = sqlx::query_scalar(
+            "INSERT INTO labels (project_id, name, color) VALUES ($1, $2, $3) RETURNING id",
+        )
+        .bind(project_id)
+        .bind(&l.name)
+        .bind(&l.color)
+        .fetch_one(&mut **tx)
+        .await?;
+        label_ids.insert(l.name.to_lowercase(), id);
+    }
+
+    let mut field_ids: HashMap<String, Uuid> = HashMap::new();
+    for f in &content.custom_fields {
+        let id: Uuid = sqlx::query_scalar(
+            "INSERT INTO custom_fields (project_id, name, field_type, options, required, position)
+             VALUES ($1, $2, $3::custom_field_type, $4, $5, $6)
+             RETURNING id",
+        )
+        .bind(project_id)
+        .bind(&f.name)
+        .bind(&f.field_type)
+        .bind(&f.options)
+        .bind(f.required)
+        .bind(f.position)
+        .fetch_one(&mut **tx)
+        .await?;
+        field_ids.insert(f.name.clone(), id);
+    }
+
+    let mut sprint_ids: Vec<Uuid> = Vec::with_capacity(content.sprints.len());
+    for s in &content.sprints {
+        let start_date = shift_day(start, s.start_offset_days)?;
+        let end_date = shift_day(start, s.end_offset_days)?;
+        let id: Uuid = sqlx::query_scalar(
+            "INSERT INTO sprints (project_id, name, goal, start_date, end_date)
+             VALUES ($1, $2, $3, $4, $5)
+             RETURNING id",
+        )
+        .bind(project_id)
+        .bind(&s.name)
+        .bind(&s.goal)
+        .bind(start_date)
+        .bind(end_date)
+        .fetch_one(&mut **tx)
+        .await?;
+        sprint_ids.push(id);
+    }
+
+    let members: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT owner_id FROM projects WHERE id = $1
+         UNION
+         SELECT user_id FROM project_members WHERE project_id = $1",
+    )
+    .bind(project_id)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    for (t, deadline) in content.tasks.iter().zip(deadlines) {
+        let custom_fields: Map<String, Value> = t
+            .custom_fields
+            .iter()
+            .filter_map(|(name, value)| {
+                field_ids
+                    .get(name)
+                    .map(|id| (id.to_string(), value.clone()))
+            })
+            .collect();
+
+        let task = sqlx::query_as::<_, Task>(&format!(
+            "INSERT INTO tasks AS t (project_id, title, description, status, priority, assignee_id,
+                                     deadline, custom_fields, original_estimate_minutes,
+                                     remaining_estimate_minutes, sprint_id)
+             VALUES ($1, $2, $3, COALESCE($4, 'todo')::task_status, $5::task_priority, $6,
+                     $7, $8, $9, COALESCE($10, $9), $11)
+             RETURNING {}",
+            TASK_COLUMNS
+        ))
+        .bind(project_id)
+        .bind(&t.title)
+        .bind(&t.description)
+        .bind(&t.status)
+        .bind(&t.priority)
+        .bind(t.assignee_id.filter(|id| members.contains(id)))
+        .bind(deadline)
+        .bind(Value::Object(custom_fields))
+        .bind(t.original_estimate_minutes)
+        .bind(t.remaining_estimate_minutes)
+        .bind(t.sprint.and_then(|i| sprint_ids.get(i).copied()))
+        .fetch_one(&mut **tx)
+        .await?;
+
+        let labels: Vec<Uuid> = t
+            .labels
+            .iter()
+            .filter_map(|name| label_ids.get(&name.to_lowercase()).copied())
+            .collect();
+        if !labels.is_empty() {
+            label::set_task_labels(tx, project_id, task.id, &labels).await?;
+        }
+
+        activity::record_created(tx, project_id, Entity::Task, task.id, Some(actor_id), &task)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Copies the members of one project to another, adding the source owner as
+/// an admin when they do not own the new project.
+pub async fn copy_members(
+    tx: &mut Transaction<'_, Postgres>,
+    source: &Project,
+    target_id: Uuid,
+    target_owner: Uuid,
+) -> AppResult<()> {
+    sqlx::query(
+        "INSERT INTO project_members (project_id, user_id, role)
+         SELECT $2, user_id, role FROM project_members
+         WHERE project_id = $1 AND user_id <> $3
+         ON CONFLICT (project_id, user_id) DO NOTHING",
+    )
+    .bind(source.id)
+    .bind(target_id)
+    .bind(target_owner)
+    .execute(&mut **tx)
+    .await?;
+
+    if source.owner_id != target_owner {
+        sqlx::query(
+            "INSERT INTO project_members (project_id, user_id, role)
+             VALUES ($1, $2, 'admin')
+             ON CONFLICT (project_id, user_id) DO NOTHING",
+        )
+        .bind(target_id)
+        .bind(source.owner_id)
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn starts_days_at_midnight_utc() {
+        assert_eq!(
+            start_of_day(day(2024, 2, 29)),
+            Utc.with_ymd_and_hms(2024, 2, 29, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn shifts_sprint_days_from_the_start() {
+        let cases = [
+            (day(2024, 3, 4), 0, day(2024, 3, 4)),
+            (day(2024, 3, 4), 13, day(2024, 3, 17)),
+            (day(2024, 3, 4), -5, day(2024, 2, 28)),
+            (day(2024, 2, 20), 9, day(2024, 2, 29)),
+            (day(2023, 12, 25), 7, day(2024, 1, 1)),
+        ];
+        for (start, offset, expected) in cases {
+            assert_eq!(
+                shift_day(start, offset).unwrap(),
+                expected,
+                "{} {}",
+                start,
+                offset
+            );
+        }
+    }
+
+    #[test]
+    fn shifts_deadlines_from_midnight_of_the_start() {
+        let origin = start_of_day(day(2024, 3, 30));
+        let cases = [
+            (0, Utc.with_ymd_and_hms(2024, 3, 30, 0, 0, 0).unwrap()),
+            (
+                17 * 60 + 30,
+                Utc.with_ymd_and_hms(2024, 3, 30, 17, 30, 0).unwrap(),
+            ),
+            (
+                2 * 24 * 60 + 9 * 60,
+                Utc.with_ymd_and_hms(2024, 4, 1, 9, 0, 0).unwrap(),
+            ),
+            (-90, Utc.with_ymd_and_hms(2024, 3, 29, 22, 30, 0).unwrap()),
+        ];
+        for (offset, expected) in cases {
+            assert_eq!(
+                shift_deadline(origin, offset).unwrap(),
+                expected,
+                "{}",
+                offset
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_dates_when_moved_to_a_new_start() {
+        // How `snapshot` stores dates, then `instantiate` places them again.
+        let reference = day(2024, 1, 15);
+        let sprint_end = day(2024, 1, 28);
+        let deadline = Utc.with_ymd_and_hms(2024, 1, 20, 16, 45, 0).unwrap();
+
+        let end_offset = (sprint_end - reference).num_days();
+        let deadline_offset = (deadline - start_of_day(reference)).num_minutes();
+
+        let start = day(2024, 6, 3);
+        assert_eq!(shift_day(start, end_offset).unwrap(), day(2024, 6, 16));
+        assert_eq!(
+            shift_deadline(start_of_day(start), deadline_offset).unwrap(),
+            Utc.with_ymd_and_hms(2024, 6, 8, 16, 45, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_dates_out_of_range() {
+        let cases = [
+            shift_day(NaiveDate::MAX, 1).map(|_| ()),
+            shift_day(NaiveDate::MIN, -1).map(|_| ()),
+            shift_day(day(2024, 1, 1), i64::MAX).map(|_| ()),
+            shift_deadline(start_of_day(NaiveDate::MAX), 24 * 60).map(|_| ()),
+            shift_deadline(start_of_day(day(2024, 1, 1)), i64::MIN).map(|_| ()),
+        ];
+        for result in cases {
+            assert!(matches!(result, Err(AppError::ValidationError(_))));
+        }
+    }
+}
@@ -0,0 +1,706 @@
+//! TQL, a small JQL-like task query language:
+//!
+//! ```text
+//! status != done AND priority >= medium AND assignee = me AND deadline < +7d
+//! (label IN (bug, "needs review") OR title ~ crash) AND sprint IS NOT EMPTY
+//! project = "Website" ORDER BY deadline ASC, priority DESC
+//! ```
+//!
+//! Queries are parsed into a typed AST, each value checked against the type
+//! of its field, and compiled to a parameterized `WHERE` clause over `tasks t`.
+//! Dates are calendar days in UTC; `today`, `now`, and offsets such as `+7d`,
+//! `-2w` or `4h` are resolved when the query runs.
+
+mod lexer;
+mod parser;
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use shared::errors::{AppError, AppResult};
+use sqlx::{Postgres, QueryBuilder};
+use std::fmt;
+use uuid::Uuid;
+
+pub use parser::parse;
+
+/// Longest accepted query, in characters.
+pub const MAX_QUERY_LENGTH: usize = 2000;
+
+/// Most conditions a query may contain.
+pub const MAX_CONDITIONS: usize = 50;
+
+/// Deepest nesting of parentheses and `NOT`.
+pub const MAX_DEPTH: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    /// Character offset of the offending token; `None` at the end of input.
+    pub position: Option<usize>,
+}
+
+impl ParseError {
+    pub(crate) fn at(position: usize, message: String) -> Self {
+        Self {
+            message,
+            position: Some(position),
+        }
+    }
+
+    pub(crate) fn at_end(message: String) -> Self {
+        Self {
+            message,
+            position: None,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some(position) => write!(f, "{} at column {}", self.message, position + 1),
+            None => write!(f, "{} at end of query", self.message),
+        }
+    }
+}
+
+impl From<ParseError> for AppError {
+    fn from(err: ParseError) -> Self {
+        AppError::BadRequest(format!("Invalid query: {}", err))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Status,
+    Priority,
+    Assignee,
+    Title,
+    Description,
+    /// Title or description.
+    Text,
+    Label,
+    Sprint,
+    Project,
+    Deadline,
+    Created,
+    Updated,
+    Estimate,
+    Remaining,
+}
+
+/// What values a field takes and which operators apply to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Status,
+    Priority,
+    User,
+    Text,
+    Label,
+    Sprint,
+    Project,
+    Time,
+    Minutes,
+}
+
+impl Field {
+    pub const ALL: [(&'static str, Field); 17] = [
+        ("status", Field::Status),
+        ("priority", Field::Priority),
+        ("assignee", Field::Assignee),
+        ("title", Field::Title),
+        ("summary", Field::Title),
+        ("description", Field::Description),
+        ("text", Field::Text),
+        ("label", Field::Label),
+        ("labels", Field::Label),
+        ("sprint", Field::Sprint),
+        ("project", Field::Project),
+        ("deadline", Field::Deadline),
+        ("due", Field::Deadline),
+        ("created", Field::Created),
+        ("updated", Field::Updated),
+        ("estimate", Field::Estimate),
+        ("remaining", Field::Remaining),
+    ];
+
+    pub fn from_name(name: &str) -> Option<Field> {
+        let name = name.to_lowercase();
+        Self::ALL
+            .iter()
+            .find(|(alias, _)| *alias == name)
+            .map(|(_, field)| *field)
+    }
+
+    pub fn name(self) -> &'static str {
+        Self::ALL
+            .iter()
+            .find(|(_, field)| *field == self)
+            .map(|(name, _)| *name)
+            .unwrap_or_default()
+    }
+
+    pub fn kind(self) -> FieldKind {
+        match self {
+            Field::Status => FieldKind::Status,
+            Field::Priority => FieldKind::Priority,
+            Field::Assignee => FieldKind::User,
+            Field::Title | Field::Description | Field::Text => FieldKind::Text,
+            Field::Label => FieldKind::Label,
+            Field::Sprint => FieldKind::Sprint,
+            Field::Project => FieldKind::Project,
+            Field::Deadline | Field::Created | Field::Updated => FieldKind::Time,
+            Field::Estimate | Field::Remaining => FieldKind::Minutes,
+        }
+    }
+
+    /// Whether `<`, `<=`, `>` and `>=` apply.
+    pub fn is_ordered(self) -> bool {
+        matches!(
+            self.kind(),
+            FieldKind::Priority | FieldKind::Time | FieldKind::Minutes
+        )
+    }
+
+    /// Whether `IS EMPTY` applies; the others always have a value.
+    pub fn can_be_empty(self) -> bool {
+        !matches!(
+            self,
+            Field::Status
+                | Field::Priority
+                | Field::Title
+                | Field::Text
+                | Field::Project
+                | Field::Created
+                | Field::Updated
+        )
+    }
+
+    /// Expression to sort by, for fields that can be sorted.
+    fn sort_expr(self) -> Option<&'static str> {
+        match self {
+            Field::Status => Some("t.status"),
+            Field::Priority => Some("t.priority"),
+            Field::Title => Some("LOWER(t.title)"),
+            Field::Deadline => Some("t.deadline"),
+            Field::Created => Some("t.created_at"),
+            Field::Updated => Some("t.updated_at"),
+            Field::Estimate => Some("t.original_estimate_minutes"),
+            Field::Remaining => Some("t.remaining_estimate_minutes"),
+            _ => None,
+        }
+    }
+
+    pub fn is_sortable(self) -> bool {
+        self.sort_expr().is_some()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UserRef {
+    Me,
+    Id(Uuid),
+    Email(String),
+}
+
+/// A sprint or project, by id or by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntityRef {
+    Id(Uuid),
+    Name(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeRef {
+    /// A whole calendar day.
+    Day(NaiveDate),
+    Today,
+    Instant(DateTime<Utc>),
+    Now,
+    /// An offset from now.
+    Relative(Duration),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Status(String),
+    Priority(String),
+    User(UserRef),
+    Text(String),
+    Label(String),
+    Sprint(EntityRef),
+    Project(EntityRef),
+    Time(TimeRef),
+    Minutes(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Predicate {
+    Compare(CompareOp, Value),
+    /// Case-insensitive substring match, `~` or `!~`.
+    Contains {
+        negated: bool,
+        text: String,
+    },
+    In {
+        negated: bool,
+        values: Vec<Value>,
+    },
+    Empty {
+        negated: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    pub field: Field,
+    pub predicate: Predicate,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+    Condition(Condition),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderBy {
+    pub field: Field,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Query {
+    /// `None` matches every task.
+    pub filter: Option<Expr>,
+    pub order_by: Vec<OrderBy>,
+}
+
+/// What a query is resolved against when it runs.
+pub struct Context {
+    pub user_id: Uuid,
+    pub now: DateTime<Utc>,
+}
+
+fn day_start(day: NaiveDate) -> DateTime<Utc> {
+    day.and_time(NaiveTime::MIN).and_utc()
+}
+
+enum Resolved {
+    /// `[start, end)`
+    Day(DateTime<Utc>, DateTime<Utc>),
+    Instant(DateTime<Utc>),
+}
+
+impl TimeRef {
+    /// `None` when the time falls outside what `DateTime` can hold.
+    fn resolve(self, ctx: &Context) -> Option<Resolved> {
+        let day = |d: NaiveDate| Some(Resolved::Day(day_start(d), day_start(d.succ_opt()?)));
+        match self {
+            TimeRef::Day(d) => day(d),
+            TimeRef::Today => day(ctx.now.date_naive()),
+            TimeRef::Instant(at) => Some(Resolved::Instant(at)),
+            TimeRef::Now => Some(Resolved::Instant(ctx.now)),
+            TimeRef::Relative(offset) => ctx.now.checked_add_signed(offset).map(Resolved::Instant),
+        }
+    }
+}
+
+/// Escapes `%`, `_` and `\` for a `LIKE ... ESCAPE '\'` pattern.
+fn like_pattern(text: &str) -> String {
+    let mut pattern = String::with_capacity(text.len() + 2);
+    pattern.push('%');
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+fn text_column(field: Field) -> &'static str {
+    match field {
+        Field::Title => "t.title",
+        Field::Description => "COALESCE(t.description, '')",
+        _ => "(t.title || ' ' || COALESCE(t.description, ''))",
+    }
+}
+
+fn time_column(field: Field) -> &'static str {
+    match field {
+        Field::Deadline => "t.deadline",
+        Field::Created => "t.created_at",
+        _ => "t.updated_at",
+    }
+}
+
+fn minutes_column(field: Field) -> &'static str {
+    match field {
+        Field::Estimate => "t.original_estimate_minutes",
+        _ => "t.remaining_estimate_minutes",
+    }
+}
+
+fn sql_op(op: CompareOp) -> &'static str {
+    match op {
+        CompareOp::Eq => " = ",
+        CompareOp::Ne => " <> ",
+        CompareOp::Lt => " < ",
+        CompareOp::Le => " <= ",
+        CompareOp::Gt => " > ",
+        CompareOp::Ge => " >= ",
+    }
+}
+
+fn push_user(qb: &mut QueryBuilder<'_, Postgres>, user: &UserRef, ctx: &Context) {
+    match user {
+        UserRef::Me => {
+            qb.push_bind(ctx.user_id);
+        }
+        UserRef::Id(id) => {
+            qb.push_bind(*id);
+        }
+        UserRef::Email(email) => {
+            qb.push("(SELECT u.id FROM users u WHERE LOWER(u.email) = LOWER(")
+                .push_bind(email.clone())
+                .push("))");
+        }
+    }
+}
+
+/// Pushes `field = value`. Negation and the other comparisons are built on
+/// top of this where the field is not ordered.
+fn push_equals(
+    qb: &mut QueryBuilder<'_, Postgres>,
+    field: Field,
+    value: &Value,
+    ctx: &Context,
+) -> AppResult<()> {
+    match value {
+        Value::Status(status) => {
+            qb.push("t.status = ")
+                .push_bind(status.clone())
+                .push("::task_status");
+        }
+        Value::Priority(priority) => {
+            qb.push("t.priority = ")
+                .push_bind(priority.clone())
+                .push("::task_priority");
+        }
+        Value::User(user) => {
+            qb.push("t.assignee_id = ");
+            push_user(qb, user, ctx);
+        }
+        Value::Text(text) => {
+            qb.push(format!("LOWER({}) = LOWER(", text_column(field)))
+                .push_bind(text.clone())
+                .push(")");
+        }
+        Value::Label(name) => {
+            qb.push(
+                "EXISTS (SELECT 1 FROM task_labels tl JOIN labels l ON l.id = tl.label_id
+                  WHERE tl.task_id = t.id AND LOWER(l.name) = LOWER(",
+            )
+            .push_bind(name.clone())
+            .push("))");
+        }
+        Value::Sprint(EntityRef::Id(id)) => {
+            qb.push("t.sprint_id = ").push_bind(*id);
+        }
+        Value::Sprint(EntityRef::Name(name)) => {
+            qb.push(
+                "t.sprint_id IN (SELECT s.id FROM sprints s
+                  WHERE s.project_id = t.project_id AND LOWER(s.name) = LOWER(",
+            )
+            .push_bind(name.clone())
+            .push("))");
+        }
+        Value::Project(EntityRef::Id(id)) => {
+            qb.push("t.project_id = ").push_bind(*id);
+        }
+        Value::Project(EntityRef::Name(name)) => {
+            qb.push("t.project_id IN (SELECT p.id FROM projects p WHERE LOWER(p.name) = LOWER(")
+                .push_bind(name.clone())
+                .push("))");
+        }
+        Value::Time(time) => push_time(qb, field, CompareOp::Eq, *time, ctx)?,
+        Value::Minutes(minutes) => {
+            qb.push(minutes_column(field))
+                .push(" = ")
+                .push_bind(*minutes);
+        }
+    }
+    Ok(())
+}
+
+fn push_time(
+    qb: &mut QueryBuilder<'_, Postgres>,
+    field: Field,
+    op: CompareOp,
+    time: TimeRef,
+    ctx: &Context,
+) -> AppResult<()> {
+    let column = time_column(field);
+    let resolved = time.resolve(ctx).ok_or_else(|| {
+        AppError::BadRequest(format!("Invalid query: '{}' is out of range", field.name()))
+    })?;
+    match resolved {
+        Resolved::Instant(at) => {
+            qb.push(column).push(sql_op(op)).push_bind(at);
+        }
+        Resolved::Day(start, end) => match op {
+            CompareOp::Eq | CompareOp::Ne => {
+                if op == CompareOp::Ne {
+                    qb.push("NOT ");
+                }
+                qb.push(format!("({} >= ", column))
+                    .push_bind(start)
+                    .push(format!(" AND {} < ", column))
+                    .push_bind(end)
+                    .push(")");
+            }
+            CompareOp::Lt => {
+                qb.push(column).push(" < ").push_bind(start);
+            }
+            CompareOp::Le => {
+                qb.push(column).push(" < ").push_bind(end);
+            }
+            CompareOp::Gt => {
+                qb.push(column).push(" >= ").push_bind(end);
+            }
+            CompareOp::Ge => {
+                qb.push(column).push(" >= ").push_bind(start);
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Pushes the condition as an expression that is never NULL, so that `NOT`
+/// and `!=` also select tasks where the field has no value.
+fn push_condition(
+    qb: &mut QueryBuilder<'_, Postgres>,
+    condition: &Condition,
+    ctx: &Context,
+) -> AppResult<()> {
+    let field = condition.field;
+    qb.push("COALESCE((");
+    match &condition.predicate {
+        Predicate::Compare(CompareOp::Eq, value) => push_equals(qb, field, value, ctx)?,
+        Predicate::Compare(CompareOp::Ne, value) => {
+            qb.push("NOT COALESCE((");
+            push_equals(qb, field, value, ctx)?;
+            qb.push("), false)");
+        }
+        Predicate::Compare(op, value) => match value {
+            Value::Priority(priority) => {
+                qb.push("t.priority")
+                    .push(sql_op(*op))
+                    .push_bind(priority.clone())
+                    .push("::task_priority");
+            }
+            Value::Time(time) => push_time(qb, field, *op, *time, ctx)?,
+            Value::Minutes(minutes) => {
+                qb.push(minutes_column(field))
+                    .push(sql_op(*op))
+                    .push_bind(*minutes);
+            }
+            // The parser only allows ordering on the types above.
+            _ => {
+                qb.push("false");
+            }
+        },
+        Predicate::Contains { negated, text } => {
+            if *negated {
+                qb.push("NOT ");
+            }
+            qb.push(text_column(field))
+                .push(" ILIKE ")
+                .push_bind(like_pattern(text))
+                .push(" ESCAPE '\\'");
+        }
+        Predicate::In { negated, values } => {
+            if *negated {
+                qb.push("NOT ");
+            }
+            qb.push("(");
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    qb.push(" OR ");
+                }
+                qb.push("COALESCE((");
+                push_equals(qb, field, value, ctx)?;
+                qb.push("), false)");
+            }
+            qb.push(")");
+        }
+        Predicate::Empty { negated } => {
+            if *negated {
+                qb.push("NOT ");
+            }
+            match field {
+                Field::Assignee => qb.push("t.assignee_id IS NULL"),
+                Field::Sprint => qb.push("t.sprint_id IS NULL"),
+                Field::Deadline => qb.push("t.deadline IS NULL"),
+                Field::Description => qb.push("COALESCE(TRIM(t.description), '') = ''"),
+                Field::Estimate | Field::Remaining => {
+                    qb.push(minutes_column(field)).push(" IS NULL")
+                }
+                Field::Label => {
+                    qb.push("NOT EXISTS (SELECT 1 FROM task_labels tl WHERE tl.task_id = t.id)")
+                }
+                // The parser rejects `IS EMPTY` on fields that always have a value.
+                _ => qb.push("false"),
+            };
+        }
+    }
+    qb.push("), false)");
+    Ok(())
+}
+
+fn push_expr(qb: &mut QueryBuilder<'_, Postgres>, expr: &Expr, ctx: &Context) -> AppResult<()> {
+    match expr {
+        Expr::And(parts) | Expr::Or(parts) => {
+            let joiner = if matches!(expr, Expr::And(_)) {
+                " AND "
+            } else {
+                " OR "
+            };
+            qb.push("(");
+            for (i, part) in parts.iter().enumerate() {
+                if i > 0 {
+                    qb.push(joiner);
+                }
+                push_expr(qb, part, ctx)?;
+            }
+            qb.push(")");
+        }
+        Expr::Not(inner) => {
+            qb.push("NOT ");
+            push_expr(qb, inner, ctx)?;
+        }
+        Expr::Condition(condition) => push_condition(qb, condition, ctx)?,
+    }
+    Ok(())
+}
+
+impl Query {
+    /// Appends ` AND <filter>` to a `WHERE` clause over `tasks t`. Fails
+    /// when a time resolves outside the range `DateTime` can hold.
+    pub fn push_filter(
+        &self,
+        qb: &mut QueryBuilder<'_, Postgres>,
+        ctx: &Context,
+    ) -> AppResult<()> {
+        if let Some(filter) = &self.filter {
+            qb.push(" AND ");
+            push_expr(qb, filter, ctx)?;
+        }
+        Ok(())
+    }
+
+    /// Appends the `ORDER BY` clause, newest first when the query has none.
+    pub fn push_order_by(&self, qb: &mut QueryBuilder<'_, Postgres>) {
+        qb.push(" ORDER BY ");
+        if self.order_by.is_empty() {
+            qb.push("t.created_at DESC, ");
+        }
+        for order in &self.order_by {
+            qb.push(order.field.sort_expr().unwrap_or("t.created_at"))
+                .push(if order.descending {
+                    " DESC NULLS LAST, "
+                } else {
+                    " ASC NULLS LAST, "
+                });
+        }
+        qb.push("t.id");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(input: &str) -> String {
+        let ctx = Context {
+            user_id: Uuid::nil(),
+            now: DateTime::parse_from_rfc3339("2024-05-31T12:00:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+        };
+        let mut qb = QueryBuilder::<Postgres>::new("WHERE true");
+        let query = parse(input).unwrap();
+        query.push_filter(&mut qb, &ctx).unwrap();
+        query.push_order_by(&mut qb);
+        qb.sql().split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    #[test]
+    fn compiles_to_parameterized_sql() {
+        assert_eq!(
+            compile("status != done AND assignee = me"),
+            "WHERE true AND (COALESCE((NOT COALESCE((t.status = $1::task_status), false)), false) \
+             AND COALESCE((t.assignee_id = $2), false)) ORDER BY t.created_at DESC, t.id"
+        );
+    }
+
+    #[test]
+    fn never_inlines_values() {
+        let sql = compile("title ~ \"'; DROP TABLE tasks; --\" OR label = x");
+        assert!(!sql.contains("DROP"), "{}", sql);
+        assert!(sql.contains("t.title ILIKE $1 ESCAPE '\\'"), "{}", sql);
+    }
+
+    #[test]
+    fn compiles_day_ranges() {
+        assert!(compile("deadline = 2024-06-01").contains("(t.deadline >= $1 AND t.deadline < $2)"));
+        assert!(compile("deadline <= today").contains("t.deadline < $1"));
+        assert!(compile("deadline > today").contains("t.deadline >= $1"));
+    }
+
+    #[test]
+    fn rejects_days_without_a_following_day() {
+        let ctx = Context {
+            user_id: Uuid::nil(),
+            now: Utc::now(),
+        };
+        let query = Query {
+            filter: Some(Expr::Condition(Condition {
+                field: Field::Deadline,
+                predicate: Predicate::Compare(
+                    CompareOp::Eq,
+                    Value::Time(TimeRef::Day(NaiveDate::MAX)),
+                ),
+            })),
+            order_by: Vec::new(),
+        };
+        let mut qb = QueryBuilder::<Postgres>::new("WHERE true");
+        let err = query.push_filter(&mut qb, &ctx).unwrap_err();
+        assert!(matches!(err, AppError::BadRequest(_)), "{}", err);
+        assert!(compile("deadline = 9999-12-31").contains("t.deadline < $2"));
+    }
+
+    #[test]
+    fn compiles_order_by() {
+        assert!(compile("ORDER BY priority DESC, deadline")
+            .ends_with("ORDER BY t.priority DESC NULLS LAST, t.deadline ASC NULLS LAST, t.id"));
+    }
+
+    #[test]
+    fn escapes_like_patterns() {
+        assert_eq!(like_pattern("50%_a\\b"), "%50\\%\\_a\\\\b%");
+    }
+}
@@ -0,0 +1,163 @@
+use super::ParseError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    /// An unquoted word: a field, keyword, function or bare value.
+    Word(String),
+    /// A single- or double-quoted string, never a keyword.
+    Quoted(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// Character offset of the token in the query.
+    pub position: usize,
+}
+
+impl Token {
+    /// The token as the user wrote it, for error messages.
+    pub fn describe(&self) -> String {
+        match &self.kind {
+            TokenKind::Word(word) => format!("'{}'", word),
+            TokenKind::Quoted(text) => format!("\"{}\"", text),
+            TokenKind::Op(op) => format!("'{}'", op),
+            TokenKind::LParen => "'('".to_string(),
+            TokenKind::RParen => "')'".to_string(),
+            TokenKind::Comma => "','".to_string(),
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | '+' | '@' | ':' | '/')
+}
+
+pub fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let kind = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => {
+                i += 1;
+                TokenKind::LParen
+            }
+            ')' => {
+                i += 1;
+                TokenKind::RParen
+            }
+            ',' => {
+                i += 1;
+                TokenKind::Comma
+            }
+            '=' => {
+                i += 1;
+                TokenKind::Op("=")
+            }
+            '~' => {
+                i += 1;
+                TokenKind::Op("~")
+            }
+            '!' | '<' | '>' => {
+                let next = chars.get(i + 1).copied();
+                i += 1;
+                match (c, next) {
+                    ('!', Some('=')) => {
+                        i += 1;
+                        TokenKind::Op("!=")
+                    }
+                    ('!', Some('~')) => {
+                        i += 1;
+                        TokenKind::Op("!~")
+                    }
+                    ('<', Some('=')) => {
+                        i += 1;
+                        TokenKind::Op("<=")
+                    }
+                    ('>', Some('=')) => {
+                        i += 1;
+                        TokenKind::Op(">=")
+                    }
+                    ('<', Some('>')) => {
+                        i += 1;
+                        TokenKind::Op("!=")
+                    }
+                    ('<', _) => TokenKind::Op("<"),
+                    ('>', _) => TokenKind::Op(">"),
+                    _ => {
+                        return Err(ParseError::at(
+                            start,
+                            "Expected '!=' or '!~' after '!'".to_string(),
+                        ))
+                    }
+                }
+            }
+            '"' | '\'' => {
+                let quote = c;
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => {
+                            return Err(ParseError::at(
+                                start,
+                                format!("Unterminated string starting with {}", quote),
+                            ))
+                        }
+                        Some('\\') => {
+                            match chars.get(i + 1) {
+                                Some(escaped) => text.push(*escaped),
+                                None => {
+                                    return Err(ParseError::at(
+                                        start,
+                                        format!("Unterminated string starting with {}", quote),
+                                    ))
+                                }
+                            }
+                            i += 2;
+                        }
+                        Some(ch) if *ch == quote => {
+                            i += 1;
+                            break;
+                        }
+                        Some(ch) => {
+                            text.push(*ch);
+                            i += 1;
+                        }
+                    }
+                }
+                TokenKind::Quoted(text)
+            }
+            c if is_word_char(c) => {
+                while i < chars.len() && is_word_char(chars[i]) {
+                    i += 1;
+                }
+                TokenKind::Word(chars[start..i].iter().collect())
+            }
+            other => {
+                return Err(ParseError::at(
+                    start,
+                    format!("Unexpected character '{}'", other),
+                ))
+            }
+        };
+        tokens.push(Token {
+            kind,
+            position: start,
+        });
+    }
+
+    Ok(tokens)
+}
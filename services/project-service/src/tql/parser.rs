@@ -0,0 +1,1044 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use uuid::Uuid;
+
+use super::{
+    lexer::{tokenize, Token, TokenKind},
+    CompareOp, Condition, EntityRef, Expr, Field, FieldKind, OrderBy, ParseError, Predicate, Query,
+    TimeRef, UserRef, Value, MAX_CONDITIONS, MAX_DEPTH, MAX_QUERY_LENGTH,
+};
+use crate::handlers::task::{TASK_PRIORITIES, TASK_STATUSES};
+
+const KEYWORDS: [&str; 11] = [
+    "and", "or", "not", "in", "is", "empty", "null", "order", "by", "asc", "desc",
+];
+
+/// Parses a query. An empty query matches every task.
+pub fn parse(input: &str) -> Result<Query, ParseError> {
+    if input.chars().count() > MAX_QUERY_LENGTH {
+        return Err(ParseError::at(
+            MAX_QUERY_LENGTH,
+            format!("Query is longer than {} characters", MAX_QUERY_LENGTH),
+        ));
+    }
+
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        next: 0,
+        conditions: 0,
+    };
+    let filter = if parser.at_end() || parser.peek_keyword("order") {
+        None
+    } else {
+        Some(parser.expr(0)?)
+    };
+    let order_by = parser.order_by()?;
+
+    if let Some(token) = parser.peek() {
+        return Err(ParseError::at(
+            token.position,
+            format!("Expected AND, OR or ORDER BY, found {}", token.describe()),
+        ));
+    }
+
+    Ok(Query { filter, order_by })
+}
+
+/// Levenshtein distance, for "did you mean" hints.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                previous
+            } else {
+                1 + previous.min(row[j]).min(row[j + 1])
+            };
+            previous = current;
+        }
+    }
+    row[b.len()]
+}
+
+fn suggestion<'a>(input: &str, candidates: impl IntoIterator<Item = &'a str>) -> String {
+    let input = input.to_lowercase();
+    candidates
+        .into_iter()
+        .map(|c| (distance(&input, c), c))
+        .filter(|(d, _)| *d <= 2)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| format!("; did you mean '{}'?", c))
+        .unwrap_or_default()
+}
+
+/// `+7d`, `-2w`, `4h` or `30m`. Offsets too large for a `Duration` are
+/// rejected rather than panicking.
+fn parse_offset(raw: &str) -> Option<Duration> {
+    let (sign, rest) = match raw.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, raw.strip_prefix('+').unwrap_or(raw)),
+    };
+    let unit = rest.chars().last()?;
+    let amount: i64 = rest[..rest.len() - unit.len_utf8()].parse().ok()?;
+    let amount = sign * amount;
+    match unit.to_ascii_lowercase() {
+        'm' => Duration::try_minutes(amount),
+        'h' => Duration::try_hours(amount),
+        'd' => Duration::try_days(amount),
+        'w' => Duration::try_weeks(amount),
+        _ => None,
+    }
+}
+
+/// `now`, `today`, a date, an RFC 3339 time or an offset from now. Dates are
+/// limited to the years 1 to 9999 that RFC 3339 times can express.
+fn parse_time(raw: &str) -> Option<TimeRef> {
+    match raw.to_lowercase().as_str() {
+        "now" => return Some(TimeRef::Now),
+        "today" => return Some(TimeRef::Today),
+        _ => {}
+    }
+    if let Ok(day) = NaiveDate::parse_from_str(raw, "%Y-%m-%d") {
+        return (1..=9999)
+            .contains(&day.year())
+            .then_some(TimeRef::Day(day));
+    }
+    if let Ok(at) = DateTime::parse_from_rfc3339(raw) {
+        return Some(TimeRef::Instant(at.with_timezone(&Utc)));
+    }
+    parse_offset(raw)
+        .filter(|offset| offset.num_days().abs() <= 100 * 365)
+        .map(TimeRef::Relative)
+}
+
+/// Minutes, optionally with an `m`, `h` or `d` (8 hours) suffix.
+fn parse_minutes(raw: &str) -> Option<i64> {
+    let raw = raw.to_lowercase();
+    let (number, factor) = match raw.chars().last()? {
+        'm' => (&raw[..raw.len() - 1], 1),
+        'h' => (&raw[..raw.len() - 1], 60),
+        'd' => (&raw[..raw.len() - 1], 8 * 60),
+        _ => (raw.as_str(), 1),
+    };
+    number
+        .parse::<i64>()
+        .ok()
+        .filter(|n| *n >= 0)
+        .and_then(|n| n.checked_mul(factor))
+}
+
+/// Normalizes `In Progress` or `in-progress` to `in_progress`.
+fn normalize_enum(raw: &str) -> String {
+    raw.trim().to_lowercase().replace([' ', '-'], "_")
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+    conditions: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn at_end(&self) -> bool {
+        self.next >= self.tokens.len()
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token { kind: TokenKind::Word(w), .. }) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek_keyword(keyword);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        let found = self.peek().is_some_and(|t| &t.kind == kind);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    /// Error for a missing token, at the next token or at the end.
+    fn expected(&self, what: &str) -> ParseError {
+        match self.peek() {
+            Some(token) => ParseError::at(
+                token.position,
+                format!("Expected {}, found {}", what, token.describe()),
+            ),
+            None => ParseError::at_end(format!("Expected {}", what)),
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.expected(&keyword.to_uppercase()))
+        }
+    }
+
+    fn expr(&mut self, depth: usize) -> Result<Expr, ParseError> {
+        let mut parts = vec![self.and_expr(depth)?];
+        while self.eat_keyword("or") {
+            parts.push(self.and_expr(depth)?);
+        }
+        Ok(if parts.len() == 1 {
+            parts.remove(0)
+        } else {
+            Expr::Or(parts)
+        })
+    }
+
+    fn and_expr(&mut self, depth: usize) -> Result<Expr, ParseError> {
+        let mut parts = vec![self.unary(depth)?];
+        while self.eat_keyword("and") {
+            parts.push(self.unary(depth)?);
+        }
+        Ok(if parts.len() == 1 {
+            parts.remove(0)
+        } else {
+            Expr::And(parts)
+        })
+    }
+
+    fn unary(&mut self, depth: usize) -> Result<Expr, ParseError> {
+        if depth >= MAX_DEPTH {
+            let position = self.peek().map(|t| t.position).unwrap_or_default();
+            return Err(ParseError::at(
+                position,
+                format!("Query is nested more than {} levels deep", MAX_DEPTH),
+            ));
+        }
+
+        if self.eat_keyword("not") {
+            return Ok(Expr::Not(Box::new(self.unary(depth + 1)?)));
+        }
+
+        if let Some(open) = self.peek().filter(|t| t.kind == TokenKind::LParen).cloned() {
+            self.next += 1;
+            let inner = self.expr(depth + 1)?;
+            if !self.eat(&TokenKind::RParen) {
+                return Err(match self.peek() {
+                    Some(_) => self.expected("')'"),
+                    None => ParseError::at(open.position, "Unclosed '('".to_string()),
+                });
+            }
+            return Ok(inner);
+        }
+
+        self.condition().map(Expr::Condition)
+    }
+
+    fn field(&mut self) -> Result<(Field, Token), ParseError> {
+        let token = match self.peek() {
+            Some(
+                token @ Token {
+                    kind: TokenKind::Word(_),
+                    ..
+                },
+            ) => token.clone(),
+            _ => return Err(self.expected("a field name")),
+        };
+        let TokenKind::Word(name) = &token.kind else {
+            unreachable!()
+        };
+        if KEYWORDS.contains(&name.to_lowercase().as_str()) {
+            return Err(self.expected("a field name"));
+        }
+        let field = Field::from_name(name).ok_or_else(|| {
+            ParseError::at(
+                token.position,
+                format!(
+                    "Unknown field '{}'{}",
+                    name,
+                    suggestion(name, Field::ALL.iter().map(|(n, _)| *n))
+                ),
+            )
+        })?;
+        self.next += 1;
+        Ok((field, token))
+    }
+
+    fn condition(&mut self) -> Result<Condition, ParseError> {
+        let (field, field_token) = self.field()?;
+
+        self.conditions += 1;
+        if self.conditions > MAX_CONDITIONS {
+            return Err(ParseError::at(
+                field_token.position,
+                format!("Query has more than {} conditions", MAX_CONDITIONS),
+            ));
+        }
+
+        let predicate = if self.eat_keyword("is") {
+            let negated = self.eat_keyword("not");
+            if !(self.eat_keyword("empty") || self.eat_keyword("null")) {
+                return Err(self.expected("EMPTY"));
+            }
+            if !field.can_be_empty() {
+                return Err(ParseError::at(
+                    field_token.position,
+                    format!(
+                        "'{}' always has a value; IS EMPTY does not apply",
+                        field.name()
+                    ),
+                ));
+            }
+            Predicate::Empty { negated }
+        } else if self.peek_keyword("not") || self.peek_keyword("in") {
+            let negated = self.eat_keyword("not");
+            self.expect_keyword("in")?;
+            if field.kind() == FieldKind::Time {
+                return Err(ParseError::at(
+                    field_token.position,
+                    format!(
+                        "IN does not apply to '{}'; use a range instead",
+                        field.name()
+                    ),
+                ));
+            }
+            if !self.eat(&TokenKind::LParen) {
+                return Err(self.expected("'(' after IN"));
+            }
+            let mut values = vec![self.value(field)?];
+            while self.eat(&TokenKind::Comma) {
+                values.push(self.value(field)?);
+            }
+            if !self.eat(&TokenKind::RParen) {
+                return Err(self.expected("',' or ')'"));
+            }
+            Predicate::In { negated, values }
+        } else {
+            let op_token = match self.peek() {
+                Some(
+                    token @ Token {
+                        kind: TokenKind::Op(_),
+                        ..
+                    },
+                ) => token.clone(),
+                _ => {
+                    return Err(self.expected(&format!(
+                        "an operator after '{}' (=, !=, <, <=, >, >=, ~, !~, IN or IS)",
+                        field.name()
+                    )))
+                }
+            };
+            self.next += 1;
+            let TokenKind::Op(op) = op_token.kind else {
+                unreachable!()
+            };
+
+            match op {
+                "~" | "!~" => {
+                    if field.kind() != FieldKind::Text {
+                        return Err(ParseError::at(
+                            op_token.position,
+                            format!(
+                                "'{}' only applies to title, description and text, not '{}'",
+                                op,
+                                field.name()
+                            ),
+                        ));
+                    }
+                    let Value::Text(text) = self.value(field)? else {
+                        unreachable!()
+                    };
+                    Predicate::Contains {
+                        negated: op == "!~",
+                        text,
+                    }
+                }
+                _ => {
+                    let op = match op {
+                        "=" => CompareOp::Eq,
+                        "!=" => CompareOp::Ne,
+                        "<" => CompareOp::Lt,
+                        "<=" => CompareOp::Le,
+                        ">" => CompareOp::Gt,
+                        _ => CompareOp::Ge,
+                    };
+                    if !matches!(op, CompareOp::Eq | CompareOp::Ne) && !field.is_ordered() {
+                        return Err(ParseError::at(
+                            op_token.position,
+                            format!(
+                                "'{}' cannot be compared with '{}'",
+                                field.name(),
+                                op_token.describe().trim_matches('\'')
+                            ),
+                        ));
+                    }
+                    Predicate::Compare(op, self.value(field)?)
+                }
+            }
+        };
+
+        Ok(Condition { field, predicate })
+    }
+
+    /// Reads a value and checks it against the field's type.
+    fn value(&mut self, field: Field) -> Result<Value, ParseError> {
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => {
+                return Err(ParseError::at_end(format!(
+                    "Expected a value for '{}'",
+                    field.name()
+                )))
+            }
+        };
+        let (raw, quoted) = match &token.kind {
+            TokenKind::Word(word) if KEYWORDS.contains(&word.to_lowercase().as_str()) => {
+                return Err(self.expected(&format!("a value for '{}'", field.name())))
+            }
+            TokenKind::Word(word) => (word.clone(), false),
+            TokenKind::Quoted(text) => (text.clone(), true),
+            _ => return Err(self.expected(&format!("a value for '{}'", field.name()))),
+        };
+        self.next += 1;
+
+        // Functions may be written with parentheses: `me()`, `now()`.
+        if !quoted
+            && self.peek().is_some_and(|t| t.kind == TokenKind::LParen)
+            && self
+                .tokens
+                .get(self.next + 1)
+                .is_some_and(|t| t.kind == TokenKind::RParen)
+        {
+            let name = raw.to_lowercase();
+            if !matches!(name.as_str(), "me" | "now" | "today") {
+                return Err(ParseError::at(
+                    token.position,
+                    format!("Unknown function '{}()'", raw),
+                ));
+            }
+            self.next += 2;
+        }
+
+        let invalid = |expected: &str| {
+            ParseError::at(
+                token.position,
+                format!(
+                    "Invalid value {} for '{}': expected {}",
+                    token.describe(),
+                    field.name(),
+                    expected
+                ),
+            )
+        };
+
+        Ok(match field.kind() {
+            FieldKind::Status => {
+                let status = normalize_enum(&raw);
+                if !TASK_STATUSES.contains(&status.as_str()) {
+                    return Err(ParseError::at(
+                        token.position,
+                        format!(
+                            "Unknown status {}, expected one of: {}{}",
+                            token.describe(),
+                            TASK_STATUSES.join(", "),
+                            suggestion(&status, TASK_STATUSES)
+                        ),
+                    ));
+                }
+                Value::Status(status)
+            }
+            FieldKind::Priority => {
+                let priority = normalize_enum(&raw);
+                if !TASK_PRIORITIES.contains(&priority.as_str()) {
+                    return Err(ParseError::at(
+                        token.position,
+                        format!(
+                            "Unknown priority {}, expected one of: {}{}",
+                            token.describe(),
+                            TASK_PRIORITIES.join(", "),
+                            suggestion(&priority, TASK_PRIORITIES)
+                        ),
+                    ));
+                }
+                Value::Priority(priority)
+            }
+            FieldKind::User => Value::User(if raw.eq_ignore_ascii_case("me") && !quoted {
+                UserRef::Me
+            } else if let Ok(id) = Uuid::parse_str(&raw) {
+                UserRef::Id(id)
+            } else if raw.contains('@') {
+                UserRef::Email(raw)
+            } else {
+                return Err(invalid("me, an email address or a user id"));
+            }),
+            FieldKind::Text => Value::Text(raw),
+            FieldKind::Label => Value::Label(raw),
+            FieldKind::Sprint | FieldKind::Project => {
+                let entity = match Uuid::parse_str(&raw) {
+                    Ok(id) => EntityRef::Id(id),
+                    Err(_) => EntityRef::Name(raw),
+                };
+                if field.kind() == FieldKind::Sprint {
+                    Value::Sprint(entity)
+                } else {
+                    Value::Project(entity)
+                }
+            }
+            FieldKind::Time => Value::Time(parse_time(&raw).ok_or_else(|| {
+                invalid("a date (2024-05-31), a timestamp, today, now or an offset such as +7d")
+            })?),
+            FieldKind::Minutes => Value::Minutes(
+                parse_minutes(&raw)
+                    .ok_or_else(|| invalid("minutes, or a duration such as 90m, 2h or 1d"))?,
+            ),
+        })
+    }
+
+    fn order_by(&mut self) -> Result<Vec<OrderBy>, ParseError> {
+        if !self.eat_keyword("order") {
+            return Ok(Vec::new());
+        }
+        self.expect_keyword("by")?;
+
+        let mut order = Vec::new();
+        loop {
+            let (field, token) = self.field()?;
+            if !field.is_sortable() {
+                return Err(ParseError::at(
+                    token.position,
+                    format!("Cannot order by '{}'", field.name()),
+                ));
+            }
+            let descending = if self.eat_keyword("desc") {
+                true
+            } else {
+                self.eat_keyword("asc");
+                false
+            };
+            order.push(OrderBy { field, descending });
+            if !self.eat(&TokenKind::Comma) {
+                break;
+            }
+        }
+        Ok(order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cond(field: Field, predicate: Predicate) -> Expr {
+        Expr::Condition(Condition { field, predicate })
+    }
+
+    fn eq(field: Field, value: Value) -> Expr {
+        cond(field, Predicate::Compare(CompareOp::Eq, value))
+    }
+
+    fn filter(input: &str) -> Expr {
+        parse(input)
+            .unwrap_or_else(|e| panic!("{:?} failed: {}", input, e))
+            .filter
+            .expect("query has a filter")
+    }
+
+    fn error(input: &str) -> ParseError {
+        match parse(input) {
+            Ok(query) => panic!("{:?} parsed as {:?}", input, query),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        assert_eq!(parse("").unwrap(), Query::default());
+        assert_eq!(parse("   ").unwrap(), Query::default());
+    }
+
+    #[test]
+    fn parses_the_example_query() {
+        assert_eq!(
+            filter("status != done AND priority = high AND assignee = me AND deadline < +7d"),
+            Expr::And(vec![
+                cond(
+                    Field::Status,
+                    Predicate::Compare(CompareOp::Ne, Value::Status("done".into()))
+                ),
+                eq(Field::Priority, Value::Priority("high".into())),
+                eq(Field::Assignee, Value::User(UserRef::Me)),
+                cond(
+                    Field::Deadline,
+                    Predicate::Compare(
+                        CompareOp::Lt,
+                        Value::Time(TimeRef::Relative(Duration::days(7)))
+                    )
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let a = eq(Field::Status, Value::Status("todo".into()));
+        let b = eq(Field::Priority, Value::Priority("high".into()));
+        let c = eq(Field::Label, Value::Label("bug".into()));
+
+        assert_eq!(
+            filter("status = todo OR priority = high AND label = bug"),
+            Expr::Or(vec![a.clone(), Expr::And(vec![b.clone(), c.clone()])])
+        );
+        assert_eq!(
+            filter("(status = todo OR priority = high) AND label = bug"),
+            Expr::And(vec![Expr::Or(vec![a, b]), c])
+        );
+    }
+
+    #[test]
+    fn not_applies_to_the_next_term() {
+        let a = eq(Field::Status, Value::Status("done".into()));
+        let b = eq(Field::Label, Value::Label("bug".into()));
+
+        assert_eq!(
+            filter("NOT status = done AND label = bug"),
+            Expr::And(vec![Expr::Not(Box::new(a.clone())), b.clone()])
+        );
+        assert_eq!(
+            filter("NOT (status = done AND label = bug)"),
+            Expr::Not(Box::new(Expr::And(vec![a.clone(), b])))
+        );
+        assert_eq!(
+            filter("not not status = done"),
+            Expr::Not(Box::new(Expr::Not(Box::new(a))))
+        );
+    }
+
+    #[test]
+    fn keywords_and_fields_are_case_insensitive() {
+        assert_eq!(
+            filter("STATUS = Done and PRIORITY in (HIGH, Medium)"),
+            Expr::And(vec![
+                eq(Field::Status, Value::Status("done".into())),
+                cond(
+                    Field::Priority,
+                    Predicate::In {
+                        negated: false,
+                        values: vec![
+                            Value::Priority("high".into()),
+                            Value::Priority("medium".into())
+                        ],
+                    }
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn accepts_operators_without_spaces() {
+        assert_eq!(
+            filter("priority>=medium"),
+            cond(
+                Field::Priority,
+                Predicate::Compare(CompareOp::Ge, Value::Priority("medium".into()))
+            )
+        );
+        assert_eq!(
+            filter("status!=done"),
+            cond(
+                Field::Status,
+                Predicate::Compare(CompareOp::Ne, Value::Status("done".into()))
+            )
+        );
+        assert_eq!(
+            filter("status<>done"),
+            cond(
+                Field::Status,
+                Predicate::Compare(CompareOp::Ne, Value::Status("done".into()))
+            )
+        );
+        assert_eq!(
+            filter("title~crash"),
+            cond(
+                Field::Title,
+                Predicate::Contains {
+                    negated: false,
+                    text: "crash".into()
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn normalizes_status_spellings() {
+        for input in [
+            "status = in_progress",
+            "status = \"In Progress\"",
+            "status = in-progress",
+        ] {
+            assert_eq!(
+                filter(input),
+                eq(Field::Status, Value::Status("in_progress".into())),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn parses_user_references() {
+        let id = Uuid::new_v4();
+        assert_eq!(
+            filter("assignee = me()"),
+            eq(Field::Assignee, Value::User(UserRef::Me))
+        );
+        assert_eq!(
+            filter(&format!("assignee = {}", id)),
+            eq(Field::Assignee, Value::User(UserRef::Id(id)))
+        );
+        assert_eq!(
+            filter("assignee = ana@example.com"),
+            eq(
+                Field::Assignee,
+                Value::User(UserRef::Email("ana@example.com".into()))
+            )
+        );
+        assert_eq!(
+            filter("assignee NOT IN (me, \"bo@example.com\")"),
+            cond(
+                Field::Assignee,
+                Predicate::In {
+                    negated: true,
+                    values: vec![
+                        Value::User(UserRef::Me),
+                        Value::User(UserRef::Email("bo@example.com".into()))
+                    ],
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn parses_times() {
+        let day = NaiveDate::from_ymd_opt(2024, 5, 31).unwrap();
+        let at = DateTime::parse_from_rfc3339("2024-05-31T09:30:00+02:00")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let time = |input: &str| match filter(input) {
+            Expr::Condition(Condition {
+                predicate: Predicate::Compare(_, Value::Time(time)),
+                ..
+            }) => time,
+            other => panic!("unexpected {:?}", other),
+        };
+
+        assert_eq!(time("deadline = 2024-05-31"), TimeRef::Day(day));
+        assert_eq!(
+            time("due <= \"2024-05-31T09:30:00+02:00\""),
+            TimeRef::Instant(at)
+        );
+        assert_eq!(time("created > today"), TimeRef::Today);
+        assert_eq!(time("updated >= now()"), TimeRef::Now);
+        assert_eq!(time("deadline < 7d"), TimeRef::Relative(Duration::days(7)));
+        assert_eq!(
+            time("deadline < -2w"),
+            TimeRef::Relative(Duration::weeks(-2))
+        );
+        assert_eq!(
+            time("updated > -4h"),
+            TimeRef::Relative(Duration::hours(-4))
+        );
+        assert_eq!(
+            time("updated > -30m"),
+            TimeRef::Relative(Duration::minutes(-30))
+        );
+    }
+
+    #[test]
+    fn parses_durations() {
+        let minutes = |input: &str| match filter(input) {
+            Expr::Condition(Condition {
+                predicate: Predicate::Compare(_, Value::Minutes(m)),
+                ..
+            }) => m,
+            other => panic!("unexpected {:?}", other),
+        };
+
+        assert_eq!(minutes("estimate > 90"), 90);
+        assert_eq!(minutes("estimate > 90m"), 90);
+        assert_eq!(minutes("remaining <= 2h"), 120);
+        assert_eq!(minutes("estimate >= 1d"), 480);
+    }
+
+    #[test]
+    fn parses_empty_checks() {
+        assert_eq!(
+            filter("assignee IS EMPTY"),
+            cond(Field::Assignee, Predicate::Empty { negated: false })
+        );
+        assert_eq!(
+            filter("sprint is not null"),
+            cond(Field::Sprint, Predicate::Empty { negated: true })
+        );
+        assert_eq!(
+            filter("labels IS NOT EMPTY"),
+            cond(Field::Label, Predicate::Empty { negated: true })
+        );
+    }
+
+    #[test]
+    fn parses_quoted_strings() {
+        assert_eq!(
+            filter(r#"title ~ "can't \"save\"""#),
+            cond(
+                Field::Title,
+                Predicate::Contains {
+                    negated: false,
+                    text: "can't \"save\"".into()
+                }
+            )
+        );
+        assert_eq!(
+            filter("label = 'and'"),
+            eq(Field::Label, Value::Label("and".into()))
+        );
+        assert_eq!(
+            filter("project = 'Website Redesign'"),
+            eq(
+                Field::Project,
+                Value::Project(EntityRef::Name("Website Redesign".into()))
+            )
+        );
+        let id = Uuid::new_v4();
+        assert_eq!(
+            filter(&format!("sprint = '{}'", id)),
+            eq(Field::Sprint, Value::Sprint(EntityRef::Id(id)))
+        );
+    }
+
+    #[test]
+    fn parses_order_by() {
+        let query = parse("status != done ORDER BY deadline, priority DESC, created asc").unwrap();
+        assert_eq!(
+            query.order_by,
+            vec![
+                OrderBy {
+                    field: Field::Deadline,
+                    descending: false
+                },
+                OrderBy {
+                    field: Field::Priority,
+                    descending: true
+                },
+                OrderBy {
+                    field: Field::Created,
+                    descending: false
+                },
+            ]
+        );
+
+        let query = parse("order by updated desc").unwrap();
+        assert_eq!(query.filter, None);
+        assert_eq!(query.order_by.len(), 1);
+    }
+
+    #[test]
+    fn reports_unknown_fields_with_a_hint() {
+        let err = error("stauts = done");
+        assert_eq!(err.position, Some(0));
+        assert_eq!(
+            err.message,
+            "Unknown field 'stauts'; did you mean 'status'?"
+        );
+
+        let err = error("status = done AND colour = red");
+        assert_eq!(err.position, Some(18));
+        assert_eq!(err.message, "Unknown field 'colour'");
+    }
+
+    #[test]
+    fn reports_invalid_values() {
+        let err = error("status = finished");
+        assert_eq!(err.position, Some(9));
+        assert!(err.message.starts_with("Unknown status 'finished'"));
+
+        let err = error("priority = hihg");
+        assert!(
+            err.message.ends_with("did you mean 'high'?"),
+            "{}",
+            err.message
+        );
+
+        let err = error("assignee = bob");
+        assert!(err
+            .message
+            .contains("expected me, an email address or a user id"));
+
+        let err = error("deadline < tomorrow");
+        assert_eq!(err.position, Some(11));
+        assert!(err
+            .message
+            .starts_with("Invalid value 'tomorrow' for 'deadline'"));
+
+        for input in [
+            "deadline < +999999999999999d",
+            "deadline < -999999999999999w",
+            "deadline < 9223372036854775807m",
+            "deadline < 9999999h",
+            "deadline = +262142-12-31",
+            "deadline > -0001-01-01",
+            "deadline < +10000-01-01",
+        ] {
+            let err = error(input);
+            assert_eq!(err.position, Some(11), "{}", input);
+            assert!(
+                err.message.starts_with("Invalid value"),
+                "{}: {}",
+                input,
+                err.message
+            );
+        }
+
+        let err = error("estimate > lots");
+        assert!(err
+            .message
+            .starts_with("Invalid value 'lots' for 'estimate'"));
+
+        let err = error("assignee = whoami()");
+        assert_eq!(err.message, "Unknown function 'whoami()'");
+    }
+
+    #[test]
+    fn reports_operators_that_do_not_apply() {
+        let err = error("status < done");
+        assert_eq!(err.position, Some(7));
+        assert_eq!(err.message, "'status' cannot be compared with '<'");
+
+        let err = error("priority ~ high");
+        assert!(err.message.starts_with("'~' only applies to title"));
+
+        let err = error("title IS EMPTY");
+        assert_eq!(
+            err.message,
+            "'title' always has a value; IS EMPTY does not apply"
+        );
+
+        let err = error("deadline IN (today)");
+        assert!(err.message.starts_with("IN does not apply to 'deadline'"));
+
+        let err = error("ORDER BY label");
+        assert_eq!(err.message, "Cannot order by 'label'");
+    }
+
+    #[test]
+    fn reports_structural_errors() {
+        let err = error("status =");
+        assert_eq!(err.position, None);
+        assert_eq!(
+            err.to_string(),
+            "Expected a value for 'status' at end of query"
+        );
+
+        let err = error("status done");
+        assert_eq!(err.position, Some(7));
+        assert!(err
+            .message
+            .starts_with("Expected an operator after 'status'"));
+
+        let err = error("status = done AND");
+        assert_eq!(err.to_string(), "Expected a field name at end of query");
+
+        let err = error("status = done priority = high");
+        assert_eq!(err.position, Some(14));
+        assert_eq!(
+            err.message,
+            "Expected AND, OR or ORDER BY, found 'priority'"
+        );
+
+        let err = error("(status = done");
+        assert_eq!(err.position, Some(0));
+        assert_eq!(err.message, "Unclosed '('");
+
+        let err = error("status = done)");
+        assert_eq!(err.position, Some(13));
+
+        let err = error("status = AND");
+        assert_eq!(err.message, "Expected a value for 'status', found 'AND'");
+
+        let err = error("label IN (bug");
+        assert_eq!(err.to_string(), "Expected ',' or ')' at end of query");
+
+        let err = error("label IN bug");
+        assert!(err.message.starts_with("Expected '(' after IN"));
+
+        let err = error("assignee IS me");
+        assert_eq!(err.message, "Expected EMPTY, found 'me'");
+
+        let err = error("ORDER deadline");
+        assert_eq!(err.message, "Expected BY, found 'deadline'");
+    }
+
+    #[test]
+    fn reports_lexical_errors() {
+        let err = error("title ~ 'unterminated");
+        assert_eq!(err.position, Some(8));
+        assert!(err.message.starts_with("Unterminated string"));
+
+        let err = error("status = done; DROP TABLE tasks");
+        assert_eq!(err.position, Some(13));
+        assert_eq!(err.message, "Unexpected character ';'");
+
+        let err = error("status ! done");
+        assert_eq!(err.message, "Expected '!=' or '!~' after '!'");
+    }
+
+    #[test]
+    fn limits_query_size() {
+        let long = format!("title ~ '{}'", "x".repeat(MAX_QUERY_LENGTH));
+        assert!(error(&long).message.starts_with("Query is longer than"));
+
+        let many = vec!["label = bug"; MAX_CONDITIONS + 1].join(" OR ");
+        assert!(error(&many).message.starts_with("Query has more than"));
+        assert!(parse(&vec!["label = bug"; MAX_CONDITIONS].join(" OR ")).is_ok());
+
+        let deep = format!(
+            "{}status = done{}",
+            "(".repeat(MAX_DEPTH + 1),
+            ")".repeat(MAX_DEPTH + 1)
+        );
+        assert!(error(&deep).message.contains("nested more than"));
+        let nots = format!("{}status = done", "NOT ".repeat(MAX_DEPTH + 1));
+        assert!(error(&nots).message.contains("nested more than"));
+    }
+
+    #[test]
+    fn display_uses_one_based_columns() {
+        let err = error("status = finished");
+        assert!(err.to_string().ends_with("at column 10"), "{}", err);
+    }
+
+    #[test]
+    fn field_helpers_cover_every_alias() {
+        for (name, field) in Field::ALL {
+            assert_eq!(Field::from_name(name), Some(field));
+            assert_eq!(Field::from_name(&name.to_uppercase()), Some(field));
+            assert_eq!(Field::from_name(field.name()), Some(field));
+        }
+        assert_eq!(Field::from_name("nope"), None);
+    }
+
+    #[test]
+    fn computes_edit_distance() {
+        assert_eq!(distance("status", "status"), 0);
+        assert_eq!(distance("stauts", "status"), 2);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("kitten", "sitting"), 3);
+    }
+}
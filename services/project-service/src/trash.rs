@@ -0,0 +1,70 @@
+use shared::errors::AppResult;
+use sqlx::PgPool;
+use std::{sync::Arc, time::Duration};
+
+use crate::storage::Storage;
+
+/// Permanently deletes projects and tasks that have been in the trash for
+/// longer than `retention_days`, along with their stored attachments.
+/// Returns how many projects and tasks were purged.
+pub async fn purge_expired(
+    db: &PgPool,
+    storage: &dyn Storage,
+    retention_days: i32,
+) -> AppResult<(u64, u64)> {
+    let mut tx = db.begin().await?;
+
+    let keys: Vec<String> = sqlx::query_scalar(
+        "SELECT storage_key FROM attachments
+         WHERE project_id IN (SELECT id FROM projects
+                              WHERE deleted_at < NOW() - make_interval(days => $1))
+            OR task_id IN (SELECT id FROM tasks
+                           WHERE deleted_at < NOW() - make_interval(days => $1))",
+    )
+    .bind(retention_days)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let tasks =
+        sqlx::query("DELETE FROM tasks WHERE deleted_at < NOW() - make_interval(days => $1)")
+            .bind(retention_days)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+    let projects =
+        sqlx::query("DELETE FROM projects WHERE deleted_at < NOW() - make_interval(days => $1)")
+            .bind(retention_days)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+    tx.commit().await?;
+
+    for key in keys {
+        if let Err(err) = storage.delete(&key).await {
+            tracing::warn!(key = %key, error = %err, "Failed to delete purged attachment content");
+        }
+    }
+
+    Ok((projects, tasks))
+}
+
+/// Runs `purge_expired` every `every` in the background.
+pub fn spawn_purger(db: PgPool, storage: Arc<dyn Storage>, retention_days: i32, every: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(every);
+        loop {
+            ticker.tick().await;
+            match purge_expired(&db, storage.as_ref(), retention_days).await {
+                Ok((0, 0)) => {}
+                Ok((projects, tasks)) => tracing::info!(
+                    "Purged {} project(s) and {} task(s) from the trash",
+                    projects,
+                    tasks
+                ),
+                Err(e) => tracing::warn!("Trash purge failed: {}", e),
+            }
+        }
+    });
+}
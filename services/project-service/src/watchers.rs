@@ -0,0 +1,143 @@
+//! Task and project watchers. Watchers of a task, and watchers of its project
+//! who have not unfollowed the task, are notified when it changes. Creating,
+//! being assigned or commenting on a task watches it automatically, unless
+//! the user unfollowed it before.
+
+use serde_json::{json, Value};
+use shared::{errors::AppResult, models::NotificationEvent};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::AppState;
+
+/// Watches the task for each user who has not unfollowed it.
+pub async fn auto_watch(
+    tx: &mut Transaction<'_, Postgres>,
+    task_id: Uuid,
+    user_ids: &[Uuid],
+) -> AppResult<()> {
+    sqlx::query(
+        "INSERT INTO task_watchers (task_id, user_id)
+         SELECT $1, UNNEST($2::uuid[])
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(task_id)
+    .bind(user_ids)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Follows or unfollows a task explicitly.
+pub async fn set_task_watch(
+    db: &PgPool,
+    task_id: Uuid,
+    user_id: Uuid,
+    watching: bool,
+) -> AppResult<()> {
+    sqlx::query(
+        "INSERT INTO task_watchers (task_id, user_id, watching) VALUES ($1, $2, $3)
+         ON CONFLICT (task_id, user_id)
+         DO UPDATE SET watching = EXCLUDED.watching, updated_at = NOW()",
+    )
+    .bind(task_id)
+    .bind(user_id)
+    .bind(watching)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn set_project_watch(
+    db: &PgPool,
+    project_id: Uuid,
+    user_id: Uuid,
+    watching: bool,
+) -> AppResult<()> {
+    let query = if watching {
+        "INSERT INTO project_watchers (project_id, user_id) VALUES ($1, $2)
+         ON CONFLICT DO NOTHING"
+    } else {
+        "DELETE FROM project_watchers WHERE project_id = $1 AND user_id = $2"
+    };
+    sqlx::query(query)
+        .bind(project_id)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Watchers of a task who can still see it, as `(user_id, source, since)`
+/// rows in a subquery over `$1`. Users that watch both the task and its
+/// project are listed once, as task watchers.
+pub(crate) const TASK_WATCHERS: &str = "SELECT DISTINCT ON (w.user_id) w.user_id, w.source, w.since
+     FROM (
+         SELECT tw.user_id, 'task' AS source, tw.created_at AS since, 0 AS rank
+         FROM task_watchers tw WHERE tw.task_id = $1 AND tw.watching
+         UNION ALL
+         SELECT pw.user_id, 'project', pw.created_at, 1
+         FROM project_watchers pw
+         JOIN tasks t ON t.project_id = pw.project_id
+         WHERE t.id = $1
+           AND NOT EXISTS (SELECT 1 FROM task_watchers tw
+                           WHERE tw.task_id = $1 AND tw.user_id = pw.user_id AND NOT tw.watching)
+     ) w
+     JOIN tasks t ON t.id = $1
+     JOIN projects p ON p.id = t.project_id
+     JOIN users u ON u.id = w.user_id
+     WHERE u.is_active
+       AND (p.owner_id = w.user_id OR EXISTS (SELECT 1 FROM project_members pm
+                                              WHERE pm.project_id = p.id
+                                                AND pm.user_id = w.user_id))
+     ORDER BY w.user_id, w.rank";
+
+async fn recipients(db: &PgPool, task_id: Uuid, exclude: &[Uuid]) -> AppResult<Vec<Uuid>> {
+    Ok(sqlx::query_scalar(&format!(
+        "SELECT w.user_id FROM ({}) w WHERE NOT (w.user_id = ANY($2))",
+        TASK_WATCHERS
+    ))
+    .bind(task_id)
+    .bind(exclude)
+    .fetch_all(db)
+    .await?)
+}
+
+/// Notifies the watchers of a task, except the actor and `exclude`. The
+/// payload gets the task's ids and title added. Like the notifier itself,
+/// this never fails the request; errors are logged.
+pub async fn notify_task(
+    state: &AppState,
+    kind: &str,
+    task: &shared::models::Task,
+    actor_id: Uuid,
+    exclude: &[Uuid],
+    mut payload: Value,
+) {
+    let mut exclude = exclude.to_vec();
+    exclude.push(actor_id);
+
+    let recipient_ids = match recipients(&state.db, task.id, &exclude).await {
+        Ok(ids) => ids,
+        Err(err) => {
+            tracing::warn!(kind, error = %err, "Failed to look up task watchers");
+            return;
+        }
+    };
+
+    if let Value::Object(fields) = &mut payload {
+        fields.insert("project_id".to_string(), json!(task.project_id));
+        fields.insert("task_id".to_string(), json!(task.id));
+        fields.insert("title".to_string(), json!(task.title));
+    }
+
+    state.notifier.send(NotificationEvent {
+        kind: kind.to_string(),
+        recipient_ids,
+        actor_id: Some(actor_id),
+        payload,
+    });
+}
@@ -0,0 +1,835 @@
+//! Outbound webhooks. Events are queued as deliveries in the transaction
+//! that records them in the activity log, then sent by a background
+//! dispatcher that retries with exponential backoff and disables webhooks
+//! that keep failing.
+//!
+//! Each request carries `X-Webhook-Signature: sha256=<hex>`, the HMAC-SHA256
+//! of `<X-Webhook-Timestamp>.<body>` keyed with the webhook's secret.
+//!
+//! Webhooks only reach public addresses. The host is resolved when a webhook
+//! is saved and again for every attempt, and the request connects only to
+//! the addresses that were checked, so a name cannot be repointed at an
+//! internal service in between.
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use shared::errors::{AppError, AppResult};
+use sqlx::{FromRow, PgPool, Postgres, Transaction};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::{Duration, Instant},
+};
+use tokio::task::JoinSet;
+use uuid::Uuid;
+
+use crate::activity::Entity;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Events a webhook can subscribe to.
+pub const EVENTS: [&str; 9] = [
+    "project.updated",
+    "project.deleted",
+    "project.restored",
+    "project.member_added",
+    "project.member_removed",
+    "task.created",
+    "task.updated",
+    "task.deleted",
+    "task.restored",
+];
+
+/// Attempts per delivery before it is marked failed.
+pub const MAX_ATTEMPTS: i32 = 8;
+
+/// Failed attempts in a row, across deliveries, that disable a webhook.
+pub const DISABLE_AFTER: i32 = 20;
+
+/// Deliveries sent per dispatcher pass.
+const BATCH_SIZE: i64 = 20;
+
+/// How long a claimed delivery is hidden from other dispatchers.
+const CLAIM_SECS: f64 = 120.0;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Response bodies are kept up to this many characters.
+const MAX_RESPONSE_BODY: usize = 2048;
+
+pub fn validate_events(events: &[String]) -> AppResult<Vec<String>> {
+    if events.is_empty() {
+        return Err(AppError::ValidationError(
+            "At least one event is required".to_string(),
+        ));
+    }
+    let mut valid: Vec<String> = Vec::new();
+    for event in events {
+        if !EVENTS.contains(&event.as_str()) {
+            return Err(AppError::ValidationError(format!(
+                "Unknown event '{}', expected one of: {}",
+                event,
+                EVENTS.join(", ")
+            )));
+        }
+        if !valid.contains(event) {
+            valid.push(event.clone());
+        }
+    }
+    Ok(valid)
+}
+
+/// Which addresses webhooks may be sent to. Private, loopback, link-local
+/// and other non-public addresses are refused so that webhooks cannot reach
+/// internal services or cloud metadata endpoints. Loopback can be allowed
+/// for receivers running on the same machine, as in development and tests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AddressPolicy {
+    pub allow_loopback: bool,
+}
+
+impl AddressPolicy {
+    pub fn allows(self, ip: IpAddr) -> bool {
+        is_public(ip) || (self.allow_loopback && is_loopback(ip))
+    }
+}
+
+/// The IPv4 address an IPv6 address stands for: mapped (`::ffff:a.b.c.d`),
+/// compatible (`::a.b.c.d`) or NAT64 (`64:ff9b::a.b.c.d`).
+fn embedded_v4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let s = ip.segments();
+    let v4 = || Ipv4Addr::new((s[6] >> 8) as u8, s[6] as u8, (s[7] >> 8) as u8, s[7] as u8);
+    match s {
+        [0, 0, 0, 0, 0, 0xffff, ..] | [0x64, 0xff9b, 0, 0, 0, 0, ..] => Some(v4()),
+        [0, 0, 0, 0, 0, 0, ..] if !ip.is_loopback() && !ip.is_unspecified() => Some(v4()),
+        _ => None,
+    }
+}
+
+fn is_loopback(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_loopback(),
+        IpAddr::V6(ip) => ip.is_loopback() || embedded_v4(ip).is_some_and(|v4| v4.is_loopback()),
+    }
+}
+
+/// Whether `ip` is a globally reachable unicast address.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match embedded_v4(ip) {
+            Some(v4) => is_public_v4(v4),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(a == 0
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || (a == 100 && (64..128).contains(&b)) // shared address space
+        || (a == 192 && b == 0 && c == 0) // IETF protocol assignments
+        || ip.is_documentation()
+        || (a == 198 && (b == 18 || b == 19)) // benchmarking
+        || ip.is_multicast()
+        || a >= 240) // reserved and broadcast
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let s = ip.segments();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        || (s[0] & 0xfe00) == 0xfc00 // unique local
+        || (s[0] & 0xffc0) == 0xfe80 // link-local
+        || (s[0] & 0xffc0) == 0xfec0 // site-local
+        || (s[0] == 0x2001 && s[1] == 0x0db8)) // documentation
+}
+
+/// Resolves the URL's host and checks every address it resolves to, so a
+/// name with one internal address among public ones is refused as well.
+/// Returns the host and the addresses to connect to.
+async fn resolve(
+    url: &reqwest::Url,
+    policy: AddressPolicy,
+) -> Result<(String, Vec<SocketAddr>), String> {
+    let host = url.host_str().ok_or("Webhook URL has no host")?;
+    let port = url
+        .port_or_known_default()
+        .ok_or("Webhook URL has no port")?;
+
+    let literal = host.trim_start_matches('[').trim_end_matches(']');
+    let addrs: Vec<SocketAddr> = match literal.parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|_| format!("Could not resolve webhook host '{}'", host))?
+            .collect(),
+    };
+    if addrs.is_empty() {
+        return Err(format!("Could not resolve webhook host '{}'", host));
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !policy.allows(addr.ip())) {
+        return Err(format!(
+            "Webhook host '{}' resolves to {}, which is not a public address",
+            host,
+            addr.ip()
+        ));
+    }
+    Ok((host.to_string(), addrs))
+}
+
+/// Checks that `url` is an http(s) URL whose host resolves to addresses
+/// `policy` allows.
+pub async fn validate_url(url: &str, policy: AddressPolicy) -> AppResult<String> {
+    let url = url.trim();
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|_| AppError::ValidationError(format!("Invalid webhook URL '{}'", url)))?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err(AppError::ValidationError(
+            "Webhook URL must be an http or https URL".to_string(),
+        ));
+    }
+    resolve(&parsed, policy)
+        .await
+        .map_err(AppError::ValidationError)?;
+    Ok(url.to_string())
+}
+
+pub fn validate_secret(secret: &str) -> AppResult<String> {
+    let secret = secret.trim();
+    if secret.len() < 16 || secret.len() > 128 {
+        return Err(AppError::ValidationError(
+            "Webhook secret must be between 16 and 128 characters".to_string(),
+        ));
+    }
+    Ok(secret.to_string())
+}
+
+pub fn new_secret() -> String {
+    format!("whsec_{}", Uuid::new_v4().simple())
+}
+
+/// Signature header value for a body sent at `timestamp` (Unix seconds).
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Wait before retrying after `attempts` failed attempts: 30 seconds,
+/// doubling each time, at most 6 hours.
+pub fn retry_delay(attempts: i32) -> Duration {
+    let exponent = attempts.clamp(1, 16) as u32 - 1;
+    Duration::from_secs((30u64 << exponent).min(6 * 60 * 60))
+}
+
+/// Queues an event for every active webhook of the project subscribed to it.
+pub async fn enqueue(
+    tx: &mut Transaction<'_, Postgres>,
+    project_id: Uuid,
+    entity: Entity,
+    entity_id: Uuid,
+    actor_id: Option<Uuid>,
+    action: &str,
+    data: Value,
+) -> AppResult<()> {
+    let event = format!("{}.{}", entity.as_str(), action);
+    if !EVENTS.contains(&event.as_str()) {
+        return Ok(());
+    }
+
+    let payload = json!({
+        "id": Uuid::new_v4(),
+        "event": event,
+        "created_at": Utc::now(),
+        "project_id": project_id,
+        "entity_id": entity_id,
+        "actor_id": actor_id,
+        "data": data,
+    });
+
+    sqlx::query(
+        "INSERT INTO webhook_deliveries (webhook_id, event, payload, next_attempt_at)
+         SELECT id, $2, $3, NOW() FROM webhooks
+         WHERE project_id = $1 AND active AND $2 = ANY(events)",
+    )
+    .bind(project_id)
+    .bind(&event)
+    .bind(&payload)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Outcome of one HTTP attempt.
+#[derive(Debug)]
+pub struct Attempt {
+    pub status: Option<u16>,
+    pub body: Option<String>,
+    pub error: Option<String>,
+    pub duration_ms: i32,
+}
+
+impl Attempt {
+    pub fn succeeded(&self) -> bool {
+        self.status.is_some_and(|s| (200..300).contains(&s))
+    }
+}
+
+/// A client that connects to `host` only at `addrs`, without proxies, which
+/// would connect to addresses of their own choosing.
+fn client(host: &str, addrs: &[SocketAddr]) -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .no_proxy()
+        .resolve_to_addrs(host, addrs)
+        .user_agent("mini-saas-webhooks/1")
+        .build()
+}
+
+/// Sends one delivery. Never fails; errors are part of the attempt, including
+/// a host that no longer resolves to addresses `policy` allows.
+/// Reads the start of a response body, stopping once it holds enough octets
+/// for `MAX_RESPONSE_BODY` characters so endpoints cannot make us buffer the
+/// rest.
+async fn read_body(mut response: reqwest::Response) -> Option<String> {
+    let limit = MAX_RESPONSE_BODY * 4;
+    let mut bytes = Vec::new();
+    while bytes.len() < limit {
+        match response.chunk().await {
+            Ok(Some(chunk)) => {
+                let take = chunk.len().min(limit - bytes.len());
+                bytes.extend_from_slice(&chunk[..take]);
+            }
+            Ok(None) => break,
+            Err(_) => return None,
+        }
+    }
+    Some(
+        String::from_utf8_lossy(&bytes)
+            .chars()
+            .take(MAX_RESPONSE_BODY)
+            .collect(),
+    )
+}
+
+pub async fn send(
+    policy: AddressPolicy,
+    url: &str,
+    secret: &str,
+    delivery_id: Uuid,
+    event: &str,
+    payload: &Value,
+) -> Attempt {
+    let body = payload.to_string().into_bytes();
+    let timestamp = Utc::now().timestamp();
+    let started = Instant::now();
+
+    let refused = |error: String| Attempt {
+        status: None,
+        body: None,
+        error: Some(error),
+        duration_ms: started.elapsed().as_millis().min(i32::MAX as u128) as i32,
+    };
+    let parsed = match reqwest::Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(_) => return refused(format!("Invalid webhook URL '{}'", url)),
+    };
+    let client = match resolve(&parsed, policy).await {
+        Ok((host, addrs)) => match client(&host, &addrs) {
+            Ok(client) => client,
+            Err(e) => return refused(e.to_string()),
+        },
+        Err(e) => return refused(e),
+    };
+
+    let result = client
+        .post(parsed)
+        .header("Content-Type", "application/json")
+        .header("X-Webhook-Event", event)
+        .header("X-Webhook-Delivery", delivery_id.to_string())
+        .header("X-Webhook-Timestamp", timestamp.to_string())
+        .header("X-Webhook-Signature", sign(secret, timestamp, &body))
+        .body(body)
+        .send()
+        .await;
+
+    let (status, body, error) = match result {
+        Ok(response) => {
+            let status = response.status().as_u16();
+            (Some(status), read_body(response).await, None)
+        }
+        Err(e) => (None, None, Some(e.to_string())),
+    };
+
+    Attempt {
+        status,
+        body,
+        error,
+        duration_ms: started.elapsed().as_millis().min(i32::MAX as u128) as i32,
+    }
+}
+
+#[derive(FromRow)]
+struct DueDelivery {
+    id: Uuid,
+    webhook_id: Uuid,
+    event: String,
+    payload: Value,
+    attempts: i32,
+    url: String,
+    secret: String,
+}
+
+/// Claims due deliveries so that concurrent dispatchers skip them.
+async fn claim_due(db: &PgPool) -> AppResult<Vec<DueDelivery>> {
+    Ok(sqlx::query_as::<_, DueDelivery>(
+        "WITH due AS (
+             SELECT d.id FROM webhook_deliveries d
+             JOIN webhooks w ON w.id = d.webhook_id
+             WHERE d.status = 'pending' AND d.next_attempt_at <= NOW() AND w.active
+             ORDER BY d.next_attempt_at
+             LIMIT $1
+             FOR UPDATE OF d SKIP LOCKED
+         )
+         UPDATE webhook_deliveries d SET next_attempt_at = NOW() + make_interval(secs => $2)
+         FROM due, webhooks w
+         WHERE d.id = due.id AND w.id = d.webhook_id
+         RETURNING d.id, d.webhook_id, d.event, d.payload, d.attempts, w.url, w.secret",
+    )
+    .bind(BATCH_SIZE)
+    .bind(CLAIM_SECS)
+    .fetch_all(db)
+    .await?)
+}
+
+async fn record_attempt(db: &PgPool, delivery: &DueDelivery, attempt: &Attempt) -> AppResult<()> {
+    let mut tx = db.begin().await?;
+    let attempts = delivery.attempts + 1;
+    let succeeded = attempt.succeeded();
+
+    let (status, next_attempt_at) = if succeeded {
+        ("succeeded", None)
+    } else if attempts >= MAX_ATTEMPTS {
+        ("failed", None)
+    } else {
+        let delay = chrono::Duration::from_std(retry_delay(attempts))
+            .unwrap_or_else(|_| chrono::Duration::hours(6));
+        ("pending", Some(Utc::now() + delay))
+    };
+
+    sqlx::query(
+        "UPDATE webhook_deliveries
+         SET status = $2, attempts = $3, next_attempt_at = $4, response_status = $5,
+             response_body = $6, error = $7, duration_ms = $8, last_attempt_at = NOW(),
+             delivered_at = CASE WHEN $2 = 'succeeded' THEN NOW() END
+         WHERE id = $1",
+    )
+    .bind(delivery.id)
+    .bind(status)
+    .bind(attempts)
+    .bind(next_attempt_at)
+    .bind(attempt.status.map(i32::from))
+    .bind(&attempt.body)
+    .bind(&attempt.error)
+    .bind(attempt.duration_ms)
+    .execute(&mut *tx)
+    .await?;
+
+    if succeeded {
+        sqlx::query("UPDATE webhooks SET consecutive_failures = 0 WHERE id = $1")
+            .bind(delivery.webhook_id)
+            .execute(&mut *tx)
+            .await?;
+    } else {
+        let disabled: bool = sqlx::query_scalar(
+            "UPDATE webhooks
+             SET consecutive_failures = consecutive_failures + 1,
+                 active = active AND consecutive_failures + 1 < $2,
+                 disabled_at = CASE WHEN active AND consecutive_failures + 1 >= $2
+                                    THEN NOW() ELSE disabled_at END
+             WHERE id = $1
+             RETURNING NOT active",
+        )
+        .bind(delivery.webhook_id)
+        .bind(DISABLE_AFTER)
+        .fetch_one(&mut *tx)
+        .await?;
+        if disabled {
+            tracing::warn!(
+                "Disabled webhook {} after {} failed deliveries in a row",
+                delivery.webhook_id,
+                DISABLE_AFTER
+            );
+        }
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Sends the deliveries that are due. Returns how many were attempted.
+pub async fn dispatch_due(db: &PgPool, policy: AddressPolicy) -> AppResult<usize> {
+    let due = claim_due(db).await?;
+    let count = due.len();
+
+    let mut sends = JoinSet::new();
+    for delivery in due {
+        let db = db.clone();
+        sends.spawn(async move {
+            let attempt = send(
+                policy,
+                &delivery.url,
+                &delivery.secret,
+                delivery.id,
+                &delivery.event,
+                &delivery.payload,
+            )
+            .await;
+            if let Err(e) = record_attempt(&db, &delivery, &attempt).await {
+                tracing::warn!("Failed to record webhook delivery {}: {}", delivery.id, e);
+            }
+        });
+    }
+    while sends.join_next().await.is_some() {}
+
+    Ok(count)
+}
+
+/// Runs `dispatch_due` every `every` in the background.
+pub fn spawn_dispatcher(db: PgPool, every: Duration, policy: AddressPolicy) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(every);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = dispatch_due(&db, policy).await {
+                tracing::warn!("Webhook dispatcher failed: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Bytes,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+    use std::sync::{Arc, Mutex};
+
+    /// The receivers below listen on loopback, which must be opted into.
+    const LOCAL: AddressPolicy = AddressPolicy {
+        allow_loopback: true,
+    };
+
+    #[derive(Debug, Clone)]
+    struct Received {
+        headers: HeaderMap,
+        body: Bytes,
+    }
+
+    /// Starts an HTTP receiver on a free local port that answers with
+    /// `status` and records what it gets.
+    async fn receiver(status: StatusCode) -> (String, Arc<Mutex<Vec<Received>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = received.clone();
+        let app = Router::new().route(
+            "/hook",
+            post(move |headers: HeaderMap, body: Bytes| {
+                let log = log.clone();
+                async move {
+                    log.lock().unwrap().push(Received { headers, body });
+                    (status, "receiver says hi")
+                }
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        (format!("http://{}/hook", addr), received)
+    }
+
+    fn header<'a>(received: &'a Received, name: &str) -> &'a str {
+        received.headers.get(name).unwrap().to_str().unwrap()
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_payload() {
+        let (url, received) = receiver(StatusCode::OK).await;
+        let secret = "a-very-secret-key-123";
+        let delivery_id = Uuid::new_v4();
+        let payload = json!({"event": "task.created", "data": {"title": "Write tests"}});
+
+        let attempt = send(LOCAL, &url, secret, delivery_id, "task.created", &payload).await;
+
+        assert!(attempt.succeeded());
+        assert_eq!(attempt.status, Some(200));
+        assert_eq!(attempt.body.as_deref(), Some("receiver says hi"));
+        assert!(attempt.error.is_none());
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        let request = &received[0];
+        assert_eq!(header(request, "x-webhook-event"), "task.created");
+        assert_eq!(
+            header(request, "x-webhook-delivery"),
+            delivery_id.to_string()
+        );
+        assert_eq!(header(request, "content-type"), "application/json");
+
+        let timestamp: i64 = header(request, "x-webhook-timestamp").parse().unwrap();
+        assert_eq!(
+            header(request, "x-webhook-signature"),
+            sign(secret, timestamp, &request.body)
+        );
+        let body: Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body, payload);
+    }
+
+    #[tokio::test]
+    async fn records_error_responses() {
+        let (url, received) = receiver(StatusCode::SERVICE_UNAVAILABLE).await;
+
+        let attempt = send(
+            LOCAL,
+            &url,
+            "a-very-secret-key-123",
+            Uuid::new_v4(),
+            "task.updated",
+            &json!({}),
+        )
+        .await;
+
+        assert!(!attempt.succeeded());
+        assert_eq!(attempt.status, Some(503));
+        assert_eq!(attempt.body.as_deref(), Some("receiver says hi"));
+        assert_eq!(received.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn stops_reading_long_responses() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Announces a huge body and keeps sending it until the client hangs up.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 4096];
+            let _ = socket.read(&mut request).await;
+            let head = "HTTP/1.1 200 OK\r\nContent-Length: 1000000000000\r\n\r\n";
+            if socket.write_all(head.as_bytes()).await.is_err() {
+                return;
+            }
+            let chunk = "é".repeat(4096);
+            while socket.write_all(chunk.as_bytes()).await.is_ok() {}
+        });
+
+        let attempt = tokio::time::timeout(
+            REQUEST_TIMEOUT / 2,
+            send(
+                LOCAL,
+                &format!("http://{}/hook", addr),
+                "a-very-secret-key-123",
+                Uuid::new_v4(),
+                "task.updated",
+                &json!({}),
+            ),
+        )
+        .await
+        .expect("the response body should not be read to the end");
+
+        assert_eq!(attempt.status, Some(200));
+        assert_eq!(attempt.body, Some("é".repeat(MAX_RESPONSE_BODY)));
+    }
+
+    #[tokio::test]
+    async fn does_not_follow_redirects() {
+        let (url, _) = receiver(StatusCode::PERMANENT_REDIRECT).await;
+
+        let attempt = send(
+            LOCAL,
+            &url,
+            "a-very-secret-key-123",
+            Uuid::new_v4(),
+            "task.updated",
+            &json!({}),
+        )
+        .await;
+
+        assert!(!attempt.succeeded());
+        assert_eq!(attempt.status, Some(308));
+    }
+
+    #[tokio::test]
+    async fn records_connection_errors() {
+        // Bind and drop a listener to get a port nothing is listening on.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        drop(listener);
+
+        let attempt = send(
+            LOCAL,
+            &url,
+            "a-very-secret-key-123",
+            Uuid::new_v4(),
+            "task.updated",
+            &json!({}),
+        )
+        .await;
+
+        assert!(!attempt.succeeded());
+        assert_eq!(attempt.status, None);
+        assert!(attempt.error.is_some());
+    }
+
+    #[tokio::test]
+    async fn refuses_loopback_unless_allowed() {
+        let (url, received) = receiver(StatusCode::OK).await;
+
+        let attempt = send(
+            AddressPolicy::default(),
+            &url,
+            "a-very-secret-key-123",
+            Uuid::new_v4(),
+            "task.updated",
+            &json!({}),
+        )
+        .await;
+
+        assert!(!attempt.succeeded());
+        assert_eq!(attempt.status, None);
+        assert!(attempt.error.unwrap().contains("not a public address"));
+        assert!(received.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn tells_public_addresses_from_internal_ones() {
+        for ip in [
+            "93.184.216.34",
+            "8.8.8.8",
+            "2606:4700:4700::1111",
+            "::ffff:93.184.216.34",
+        ] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "127.0.0.1",
+            "127.1.2.3",
+            "10.0.0.1",
+            "172.16.0.1",
+            "172.31.255.255",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "192.0.0.1",
+            "192.0.2.1",
+            "198.18.0.1",
+            "224.0.0.1",
+            "255.255.255.255",
+            "::",
+            "::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "::10.0.0.1",
+            "64:ff9b::a00:1",
+            "fc00::1",
+            "fd12:3456::1",
+            "fe80::1",
+            "ff02::1",
+            "2001:db8::1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        assert!(!AddressPolicy::default().allows(ip("127.0.0.1")));
+        assert!(LOCAL.allows(ip("127.0.0.1")));
+        assert!(LOCAL.allows(ip("::1")));
+        assert!(LOCAL.allows(ip("::ffff:127.0.0.1")));
+        assert!(!LOCAL.allows(ip("10.0.0.1")));
+        assert!(!LOCAL.allows(ip("169.254.169.254")));
+    }
+
+    #[tokio::test]
+    async fn validates_urls() {
+        let public = AddressPolicy::default();
+        assert!(validate_url("https://93.184.216.34/hooks", public)
+            .await
+            .is_ok());
+        assert!(
+            validate_url("http://[2606:4700:4700::1111]:8080/hook", public)
+                .await
+                .is_ok()
+        );
+        for url in [
+            "http://127.0.0.1:8080/hook",
+            "http://localhost:3004/events",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://10.0.0.5/internal",
+            "http://[::1]/hook",
+            "http://[::ffff:7f00:1]/hook",
+            "http://0x7f.1/hook",
+            "http://0/hook",
+        ] {
+            assert!(validate_url(url, public).await.is_err(), "{}", url);
+        }
+        assert!(validate_url("http://127.0.0.1:8080/hook", LOCAL)
+            .await
+            .is_ok());
+        assert!(validate_url("http://10.0.0.5/internal", LOCAL)
+            .await
+            .is_err());
+        assert!(validate_url("ftp://93.184.216.34", public).await.is_err());
+        assert!(validate_url("not a url", public).await.is_err());
+    }
+
+    #[test]
+    fn signature_covers_timestamp_and_body() {
+        let signature = sign("secret", 1_700_000_000, b"{}");
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+        assert_eq!(signature, sign("secret", 1_700_000_000, b"{}"));
+        assert_ne!(signature, sign("secret", 1_700_000_001, b"{}"));
+        assert_ne!(signature, sign("secret", 1_700_000_000, b"{ }"));
+        assert_ne!(signature, sign("other", 1_700_000_000, b"{}"));
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_a_cap() {
+        assert_eq!(retry_delay(1), Duration::from_secs(30));
+        assert_eq!(retry_delay(2), Duration::from_secs(60));
+        assert_eq!(retry_delay(3), Duration::from_secs(120));
+        assert_eq!(retry_delay(MAX_ATTEMPTS - 1), Duration::from_secs(30 * 64));
+        assert_eq!(retry_delay(20), Duration::from_secs(6 * 60 * 60));
+        assert_eq!(retry_delay(0), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn validates_subscriptions() {
+        let events =
+            validate_events(&["task.created".to_string(), "task.created".to_string()]).unwrap();
+        assert_eq!(events, vec!["task.created".to_string()]);
+        assert!(validate_events(&[]).is_err());
+        assert!(validate_events(&["task.exploded".to_string()]).is_err());
+
+        assert!(validate_secret("short").is_err());
+        assert!(validate_secret(&new_secret()).is_ok());
+    }
+}
@@ -2,6 +2,7 @@ use crate::models::Claims;
 use crate::errors::{AppError, AppResult};
 use chrono::Utc;
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
 pub struct AuthService {
@@ -55,3 +56,24 @@ pub fn verify_password(password: &str, hash: &str) -> AppResult<bool> {
     bcrypt::verify(password, hash)
         .map_err(|_| AppError::InternalError("Failed to verify password".to_string()))
 }
+
+/// Header carrying the shared `SERVICE_SECRET` on calls between services.
+pub const SERVICE_SECRET_HEADER: &str = "X-Service-Secret";
+
+/// Checks the secret presented on a service-to-service call. Both sides are
+/// hashed first so the comparison takes the same time whatever their length
+/// and wherever they differ.
+pub fn verify_service_secret(expected: &str, presented: Option<&str>) -> AppResult<()> {
+    let presented = presented
+        .ok_or_else(|| AppError::Unauthorized("Missing service secret".to_string()))?;
+    let expected_digest = Sha256::digest(expected.as_bytes());
+    let presented_digest = Sha256::digest(presented.as_bytes());
+    let difference = expected_digest
+        .iter()
+        .zip(presented_digest.iter())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+    if difference != 0 || expected.is_empty() {
+        return Err(AppError::Unauthorized("Invalid service secret".to_string()));
+    }
+    Ok(())
+}
@@ -10,7 +10,7 @@ pub async fn create_pool(database_url: &str) -> Result<PgPool, sqlx::Error> {
 
 pub async fn init_pool(database_url: &str, pool_size: u32) -> Result<PgPool, sqlx::Error> {
     let pool = PgPoolOptions::new()
-        .max_connections(pool_size as u32)
+        .max_connections(pool_size)
         .acquire_timeout(std::time::Duration::from_secs(30))
         .connect(database_url)
         .await?;
@@ -13,6 +13,23 @@ pub enum AppError {
     Unauthorized(String),
     Forbidden(String),
     Conflict(String),
+    /// The client's `If-Match` does not match the current version.
+    PreconditionFailed(String),
+    /// A conditional request was required but no `If-Match` was sent.
+    PreconditionRequired(String),
+    /// Creating something would exceed a plan limit. Answered with 402 when
+    /// the caller can upgrade the plan, 403 when it belongs to someone else.
+    LimitExceeded {
+        /// `max_projects` or `max_tasks`.
+        limit: String,
+        plan: String,
+        max: i64,
+        current: i64,
+        message: String,
+        upgradable: bool,
+    },
+    /// A service this one depends on could not be reached.
+    ServiceUnavailable(String),
     InternalError(String),
     DatabaseError(String),
     ValidationError(String),
@@ -27,6 +44,10 @@ impl fmt::Display for AppError {
             AppError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
             AppError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
             AppError::Conflict(msg) => write!(f, "Conflict: {}", msg),
+            AppError::PreconditionFailed(msg) => write!(f, "Precondition failed: {}", msg),
+            AppError::PreconditionRequired(msg) => write!(f, "Precondition required: {}", msg),
+            AppError::LimitExceeded { message, .. } => write!(f, "Limit exceeded: {}", message),
+            AppError::ServiceUnavailable(msg) => write!(f, "Service unavailable: {}", msg),
             AppError::InternalError(msg) => write!(f, "Internal error: {}", msg),
             AppError::DatabaseError(msg) => write!(f, "Database error: {}", msg),
             AppError::ValidationError(msg) => write!(f, "Validation error: {}", msg),
@@ -43,11 +64,37 @@ impl IntoResponse for AppError {
             AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
             AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
             AppError::Conflict(msg) => (StatusCode::CONFLICT, msg),
+            AppError::PreconditionFailed(msg) => (StatusCode::PRECONDITION_FAILED, msg),
+            AppError::PreconditionRequired(msg) => (StatusCode::PRECONDITION_REQUIRED, msg),
             AppError::ValidationError(msg) => (StatusCode::BAD_REQUEST, msg),
             AppError::InvalidToken(msg) => (StatusCode::UNAUTHORIZED, msg),
+            AppError::ServiceUnavailable(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg),
             AppError::InternalError(msg) | AppError::DatabaseError(msg) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, msg)
             }
+            AppError::LimitExceeded {
+                limit,
+                plan,
+                max,
+                current,
+                message,
+                upgradable,
+            } => {
+                let status = if upgradable {
+                    StatusCode::PAYMENT_REQUIRED
+                } else {
+                    StatusCode::FORBIDDEN
+                };
+                let body = Json(json!({
+                    "error": message,
+                    "status": status.as_u16(),
+                    "limit": limit,
+                    "plan": plan,
+                    "max": max,
+                    "current": current,
+                }));
+                return (status, body).into_response();
+            }
         };
 
         let body = Json(json!({
@@ -69,6 +69,9 @@ pub struct Project {
     pub name: String,
     pub description: Option<String>,
     pub status: String,
+    /// Incremented on every write; exposed as the `ETag`.
+    #[serde(default)]
+    pub version: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -115,6 +118,16 @@ pub struct Task {
     pub status: String,
     pub priority: String,
     pub deadline: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub custom_fields: serde_json::Value,
+    pub original_estimate_minutes: Option<i32>,
+    pub remaining_estimate_minutes: Option<i32>,
+    pub recurrence_id: Option<Uuid>,
+    pub occurrence_at: Option<DateTime<Utc>>,
+    pub sprint_id: Option<Uuid>,
+    /// Incremented on every write; exposed as the `ETag`.
+    #[serde(default)]
+    pub version: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -125,16 +138,48 @@ pub struct CreateTaskRequest {
     pub description: Option<String>,
     pub priority: Option<String>,
     pub deadline: Option<DateTime<Utc>>,
+    pub label_ids: Option<Vec<Uuid>>,
+    pub custom_fields: Option<serde_json::Map<String, serde_json::Value>>,
+    pub original_estimate_minutes: Option<i32>,
+    pub recurrence: Option<RecurrenceRule>,
 }
 
+/// Fields left out are unchanged. `description`, `assignee_id` and
+/// `deadline` are cleared by sending `null`, which arrives as `Some(None)`.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateTaskRequest {
     pub title: Option<String>,
-    pub description: Option<String>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub description: Option<Option<String>>,
     pub status: Option<String>,
     pub priority: Option<String>,
-    pub assignee_id: Option<Uuid>,
-    pub deadline: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub assignee_id: Option<Option<Uuid>>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<Option<DateTime<Utc>>>,
+    pub label_ids: Option<Vec<Uuid>>,
+    pub custom_fields: Option<serde_json::Map<String, serde_json::Value>>,
+    pub original_estimate_minutes: Option<i32>,
+    pub remaining_estimate_minutes: Option<i32>,
+    pub recurrence: Option<RecurrenceRule>,
+}
+
+/// Tells a field sent as `null` (`Some(None)`) from one left out (`None`,
+/// through `#[serde(default)]`).
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// An RFC 5545 `RRULE` (e.g. `FREQ=WEEKLY;BYDAY=MO`) evaluated in an IANA
+/// timezone, `UTC` when omitted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecurrenceRule {
+    pub rrule: String,
+    pub timezone: Option<String>,
 }
 
 // ============= PAGINATION =============
@@ -151,7 +196,7 @@ impl PaginationParams {
     }
 
     pub fn limit(&self) -> i64 {
-        self.limit.unwrap_or(10).min(100).max(1)
+        self.limit.unwrap_or(10).clamp(1, 100)
     }
 
     pub fn offset(&self) -> i64 {
@@ -167,6 +212,17 @@ pub struct PaginatedResponse<T> {
     pub total: i64,
 }
 
+// ============= NOTIFICATION EVENT =============
+
+/// Event handed from another service to notification-service.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationEvent {
+    pub kind: String,
+    pub recipient_ids: Vec<Uuid>,
+    pub actor_id: Option<Uuid>,
+    pub payload: serde_json::Value,
+}
+
 // ============= CLAIMS (JWT) =============
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -176,4 +232,43 @@ pub struct Claims {
     pub role: String,
     pub exp: i64,
     pub iat: i64,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_task_tells_null_from_missing() {
+        let req: UpdateTaskRequest = serde_json::from_str(
+            r#"{"description": null, "assignee_id": null, "title": "Renamed"}"#,
+        )
+        .unwrap();
+        assert_eq!(req.description, Some(None));
+        assert_eq!(req.assignee_id, Some(None));
+        assert_eq!(req.deadline, None);
+        assert_eq!(req.title.as_deref(), Some("Renamed"));
+
+        let id = Uuid::new_v4();
+        let req: UpdateTaskRequest = serde_json::from_value(serde_json::json!({
+            "description": "Details",
+            "assignee_id": id,
+            "deadline": "2024-05-01T12:00:00Z",
+        }))
+        .unwrap();
+        assert_eq!(req.description, Some(Some("Details".to_string())));
+        assert_eq!(req.assignee_id, Some(Some(id)));
+        assert_eq!(
+            req.deadline,
+            Some(Some("2024-05-01T12:00:00Z".parse().unwrap()))
+        );
+
+        let json = serde_json::to_value(UpdateTaskRequest {
+            deadline: Some(None),
+            ..serde_json::from_str("{}").unwrap()
+        })
+        .unwrap();
+        assert_eq!(json["deadline"], serde_json::Value::Null);
+        assert!(json.get("description").is_none());
+    }
+}